@@ -0,0 +1,111 @@
+use anyhow::Result;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::{Keypair, Signer};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    cex::{self, OrderTracker},
+    config::{Config, TradingParams},
+    dex, oracles,
+    secrets::SecretStore,
+    tokens::TokenRegistry,
+    transactions::{PendingTransactions, SwapPrograms, TransactionSender},
+};
+
+// Everything a command needs once configuration is loaded: unlocked secrets,
+// the wallet and the enabled venue clients.
+pub struct App {
+    pub config: Config,
+    // Live trading parameters, hot-reloaded while the bot runs
+    pub trading_params: TradingParams,
+    pub rpc_client: Arc<RpcClient>,
+    pub wallet: Arc<Keypair>,
+    pub tokens: Arc<TokenRegistry>,
+    pub dex_clients: dex::DexClients,
+    // Pool state the DEX clients have loaded, for quoting without RPC calls
+    pub pool_states: dex::PoolCache,
+    pub price_feeds: oracles::PriceFeeds,
+    pub cex_clients: cex::CexClients,
+    pub orders: OrderTracker,
+    pub pending_transactions: PendingTransactions,
+    // The sender every DEX client uses, for anything else that transacts
+    pub sender: Arc<TransactionSender>,
+}
+
+impl App {
+    pub async fn init(config: Config, trading_params: TradingParams) -> Result<Self> {
+        // Unlock secrets and the wallet keypair
+        let secrets = SecretStore::unlock(&config)?;
+        let wallet = Arc::new(secrets.keypair(&config.solana)?);
+        info!("Wallet {} loaded", wallet.pubkey());
+
+        // Reads at the commitment transactions are confirmed at
+        let rpc_client = Arc::new(RpcClient::new_with_commitment(
+            config.solana.rpc_url.clone(),
+            CommitmentConfig {
+                commitment: config.transactions.commitment,
+            },
+        ));
+
+        // One vocabulary of symbols, mints and venue symbols for everything below
+        let tokens = Arc::new(TokenRegistry::load(&config.tokens).await?);
+        info!("Token registry loaded with {} tokens", tokens.len());
+
+        // Initialize DEX clients, all sending through one transaction sender
+        // that tracks in-flight transactions for shutdown
+        let pending_transactions = PendingTransactions::new();
+        let sender = Arc::new(TransactionSender::new(
+            rpc_client.clone(),
+            &config.transactions,
+            SwapPrograms::from_config(&config.dex)?,
+            pending_transactions.clone(),
+        ));
+        let pool_states = dex::PoolCache::new();
+        let dex_clients = dex::init_clients(
+            &config,
+            rpc_client.clone(),
+            sender.clone(),
+            wallet.clone(),
+            &trading_params,
+            &pool_states,
+        )
+        .await?;
+        info!(
+            "DEX clients initialized: {:?}",
+            dex_clients.names().collect::<Vec<_>>()
+        );
+
+        // Initialize price feeds
+        let price_feeds = oracles::init_price_feeds(&config).await?;
+        info!(
+            "Price feeds initialized: {:?}",
+            price_feeds.names().collect::<Vec<_>>()
+        );
+
+        // Initialize CEX clients, recording every order placed so it can be
+        // cancelled on shutdown
+        let orders = OrderTracker::new();
+        let cex_clients = cex::track_orders(&cex::init_clients(&config, &secrets).await?, &orders)?;
+        info!(
+            "CEX clients initialized: {:?}",
+            cex_clients.names().collect::<Vec<_>>()
+        );
+
+        Ok(Self {
+            config,
+            trading_params,
+            rpc_client,
+            wallet,
+            tokens,
+            dex_clients,
+            pool_states,
+            price_feeds,
+            cex_clients,
+            orders,
+            pending_transactions,
+            sender,
+        })
+    }
+}
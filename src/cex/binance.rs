@@ -6,17 +6,22 @@ use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::{CexClient, OrderBook, PriceLevel, Trade};
+use super::{CexClient, OrderBook, OrderStatus, PriceLevel, Trade};
+use crate::secrets::Secret;
+use crate::units::{Price, Rounding, TokenAmount};
+
+// Binance reports every balance and quantity with 8 decimal places
+const QUANTITY_DECIMALS: u8 = 8;
 
 pub struct BinanceClient {
     client: Client,
     api_key: String,
-    api_secret: String,
+    api_secret: Secret<String>,
     base_url: String,
 }
 
 impl BinanceClient {
-    pub fn new(api_key: String, api_secret: String) -> Result<Self> {
+    pub fn new(api_key: String, api_secret: Secret<String>) -> Result<Self> {
         Ok(Self {
             client: Client::new(),
             api_key,
@@ -26,7 +31,7 @@ impl BinanceClient {
     }
 
     fn generate_signature(&self, query_string: &str) -> String {
-        let mut mac = Hmac::<Sha256>::new_from_slice(self.api_secret.as_bytes())
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.api_secret.expose().as_bytes())
             .expect("HMAC can take key of any size");
         mac.update(query_string.as_bytes());
         hex::encode(mac.finalize().into_bytes())
@@ -46,14 +51,14 @@ impl BinanceClient {
         signed: bool,
     ) -> Result<T> {
         let mut url = format!("{}{}", self.base_url, endpoint);
-        
+
         if let Some(params) = params {
             let query_string = params
                 .iter()
                 .map(|(k, v)| format!("{}={}", k, v))
                 .collect::<Vec<_>>()
                 .join("&");
-            
+
             if signed {
                 let signature = self.generate_signature(&query_string);
                 url = format!("{}?{}&signature={}", url, query_string, signature);
@@ -88,21 +93,15 @@ impl CexClient for BinanceClient {
 
         let bids = binance_ob
             .bids
-            .into_iter()
-            .map(|[price, qty]| PriceLevel {
-                price: price.parse().unwrap(),
-                quantity: qty.parse().unwrap(),
-            })
-            .collect();
+            .iter()
+            .map(|[price, qty]| price_level(price, qty))
+            .collect::<Result<_>>()?;
 
         let asks = binance_ob
             .asks
-            .into_iter()
-            .map(|[price, qty]| PriceLevel {
-                price: price.parse().unwrap(),
-                quantity: qty.parse().unwrap(),
-            })
-            .collect();
+            .iter()
+            .map(|[price, qty]| price_level(price, qty))
+            .collect::<Result<_>>()?;
 
         Ok(OrderBook {
             bids,
@@ -111,7 +110,7 @@ impl CexClient for BinanceClient {
         })
     }
 
-    async fn get_ticker(&self, symbol: &str) -> Result<f64> {
+    async fn get_ticker(&self, symbol: &str) -> Result<Price> {
         #[derive(Deserialize)]
         struct BinanceTicker {
             price: String,
@@ -119,15 +118,15 @@ impl CexClient for BinanceClient {
 
         let endpoint = format!("/api/v3/ticker/price?symbol={}", symbol);
         let ticker: BinanceTicker = self.make_request(&endpoint, None, false).await?;
-        Ok(ticker.price.parse()?)
+        Price::parse(&ticker.price, Rounding::Nearest)
     }
 
     async fn place_order(
         &self,
         symbol: &str,
         side: &str,
-        price: f64,
-        quantity: f64,
+        price: Price,
+        quantity: TokenAmount,
     ) -> Result<String> {
         let timestamp = Self::get_timestamp();
         let params = &[
@@ -159,11 +158,12 @@ impl CexClient for BinanceClient {
         ];
 
         let endpoint = "/api/v3/order";
-        self.make_request::<()>(endpoint, Some(params), true).await?;
+        self.make_request::<()>(endpoint, Some(params), true)
+            .await?;
         Ok(())
     }
 
-    async fn get_balance(&self, asset: &str) -> Result<f64> {
+    async fn get_balance(&self, asset: &str) -> Result<TokenAmount> {
         let timestamp = Self::get_timestamp();
         let params = &[("timestamp", &timestamp.to_string())];
 
@@ -188,7 +188,7 @@ impl CexClient for BinanceClient {
             .find(|b| b.asset == asset)
             .ok_or_else(|| anyhow::anyhow!("Asset not found"))?;
 
-        Ok(balance.free.parse()?)
+        TokenAmount::parse(&balance.free, QUANTITY_DECIMALS, Rounding::Down)
     }
 
     async fn get_recent_trades(&self, symbol: &str) -> Result<Vec<Trade>> {
@@ -204,16 +204,61 @@ impl CexClient for BinanceClient {
         let endpoint = format!("/api/v3/trades?symbol={}&limit=100", symbol);
         let trades: Vec<BinanceTrade> = self.make_request(&endpoint, None, false).await?;
 
-        Ok(trades
+        trades
             .into_iter()
-            .map(|t| Trade {
-                id: t.id.to_string(),
-                symbol: symbol.to_string(),
-                side: if t.isBuyerMaker { "SELL" } else { "BUY" }.to_string(),
-                price: t.price.parse().unwrap(),
-                quantity: t.qty.parse().unwrap(),
-                timestamp: t.time,
+            .map(|t| {
+                Ok(Trade {
+                    id: t.id.to_string(),
+                    symbol: symbol.to_string(),
+                    side: if t.isBuyerMaker { "SELL" } else { "BUY" }.to_string(),
+                    price: Price::parse(&t.price, Rounding::Nearest)?,
+                    quantity: TokenAmount::parse(&t.qty, QUANTITY_DECIMALS, Rounding::Down)?,
+                    timestamp: t.time,
+                })
             })
-            .collect())
+            .collect()
     }
-} 
\ No newline at end of file
+
+    async fn get_order(&self, symbol: &str, order_id: &str) -> Result<OrderStatus> {
+        let timestamp = Self::get_timestamp();
+        let params = &[
+            ("symbol", symbol),
+            ("orderId", order_id),
+            ("timestamp", &timestamp.to_string()),
+        ];
+
+        #[derive(Deserialize)]
+        struct BinanceOrder {
+            status: String,
+            executedQty: String,
+            cummulativeQuoteQty: String,
+        }
+
+        let endpoint = "/api/v3/order";
+        let order: BinanceOrder = self.make_request(endpoint, Some(params), true).await?;
+
+        let filled = TokenAmount::parse(&order.executedQty, QUANTITY_DECIMALS, Rounding::Down)?;
+        let quote = TokenAmount::parse(
+            &order.cummulativeQuoteQty,
+            QUANTITY_DECIMALS,
+            Rounding::Down,
+        )?;
+        let average_price = if filled.is_zero() {
+            None
+        } else {
+            Some(Price::from_amounts(filled, quote, Rounding::Nearest)?)
+        };
+        Ok(OrderStatus {
+            filled,
+            average_price,
+            open: matches!(order.status.as_str(), "NEW" | "PARTIALLY_FILLED"),
+        })
+    }
+}
+
+fn price_level(price: &str, quantity: &str) -> Result<PriceLevel> {
+    Ok(PriceLevel {
+        price: Price::parse(price, Rounding::Nearest)?,
+        quantity: TokenAmount::parse(quantity, QUANTITY_DECIMALS, Rounding::Down)?,
+    })
+}
@@ -1,22 +1,31 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use futures::future::join_all;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tracing::warn;
+
+use crate::registry::VenueRegistry;
+use crate::secrets::SecretStore;
+use crate::units::{Price, TokenAmount};
 
 mod binance;
 mod bybit;
 mod okx;
+mod tracking;
 
 pub use binance::BinanceClient;
 pub use bybit::BybitClient;
 pub use okx::OkxClient;
+pub use tracking::{
+    attribute_orders, track_orders, OpenOrder, OrderFill, OrderTracker, TrackedCexClient,
+};
 
-#[derive(Clone)]
-pub struct CexClients {
-    pub binance: Arc<BinanceClient>,
-    pub bybit: Arc<BybitClient>,
-    pub okx: Arc<OkxClient>,
-}
+pub const BINANCE: &str = "binance";
+pub const BYBIT: &str = "bybit";
+pub const OKX: &str = "okx";
+
+pub type CexClients = VenueRegistry<dyn CexClient>;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OrderBook {
@@ -27,8 +36,8 @@ pub struct OrderBook {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PriceLevel {
-    pub price: f64,
-    pub quantity: f64,
+    pub price: Price,
+    pub quantity: TokenAmount,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,69 +45,96 @@ pub struct Trade {
     pub id: String,
     pub symbol: String,
     pub side: String,
-    pub price: f64,
-    pub quantity: f64,
+    pub price: Price,
+    pub quantity: TokenAmount,
     pub timestamp: u64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderStatus {
+    // Total filled so far
+    pub filled: TokenAmount,
+    // Average price of what has filled, None before the first fill
+    pub average_price: Option<Price>,
+    // False once the order is filled, cancelled or expired
+    pub open: bool,
+}
+
 #[async_trait]
-pub trait CexClient {
+pub trait CexClient: Send + Sync {
     async fn get_order_book(&self, symbol: &str) -> Result<OrderBook>;
-    async fn get_ticker(&self, symbol: &str) -> Result<f64>;
+    async fn get_ticker(&self, symbol: &str) -> Result<Price>;
     async fn place_order(
         &self,
         symbol: &str,
         side: &str,
-        price: f64,
-        quantity: f64,
+        price: Price,
+        quantity: TokenAmount,
     ) -> Result<String>;
     async fn cancel_order(&self, symbol: &str, order_id: &str) -> Result<()>;
-    async fn get_balance(&self, asset: &str) -> Result<f64>;
+    async fn get_balance(&self, asset: &str) -> Result<TokenAmount>;
     async fn get_recent_trades(&self, symbol: &str) -> Result<Vec<Trade>>;
+
+    // Fills of orders on venues that cannot report them are never delivered
+    async fn get_order(&self, _symbol: &str, order_id: &str) -> Result<OrderStatus> {
+        Err(anyhow!("cannot check order {} on this venue", order_id))
+    }
 }
 
-pub async fn init_clients(config: &crate::config::Config) -> Result<CexClients> {
-    let binance_client = Arc::new(BinanceClient::new(
-        config.binance_api_key.clone(),
-        config.binance_api_secret.clone(),
-    )?);
-
-    let bybit_client = Arc::new(BybitClient::new(
-        config.bybit_api_key.clone(),
-        config.bybit_api_secret.clone(),
-    )?);
-
-    let okx_client = Arc::new(OkxClient::new(
-        config.okx_api_key.clone(),
-        config.okx_api_secret.clone(),
-    )?);
-
-    Ok(CexClients {
-        binance: binance_client,
-        bybit: bybit_client,
-        okx: okx_client,
-    })
+pub async fn init_clients(
+    config: &crate::config::Config,
+    secrets: &SecretStore,
+) -> Result<CexClients> {
+    let mut clients = CexClients::new();
+
+    if let Some(binance) = config.cex.binance.as_ref().filter(|c| c.enabled) {
+        clients.register(
+            BINANCE,
+            Arc::new(BinanceClient::new(
+                binance.api_key.clone(),
+                secrets.cex_secret(BINANCE, binance)?,
+            )?),
+        )?;
+    }
+
+    if let Some(bybit) = config.cex.bybit.as_ref().filter(|c| c.enabled) {
+        clients.register(
+            BYBIT,
+            Arc::new(BybitClient::new(
+                bybit.api_key.clone(),
+                secrets.cex_secret(BYBIT, bybit)?,
+            )?),
+        )?;
+    }
+
+    if let Some(okx) = config.cex.okx.as_ref().filter(|c| c.enabled) {
+        clients.register(
+            OKX,
+            Arc::new(OkxClient::new(
+                okx.api_key.clone(),
+                secrets.cex_secret(OKX, okx)?,
+            )?),
+        )?;
+    }
+
+    if clients.is_empty() {
+        warn!("No CEX venues enabled");
+    }
+
+    Ok(clients)
 }
 
 // Helper functions for CEX operations
 pub async fn get_best_price_across_exchanges(
     clients: &CexClients,
     symbol: &str,
-) -> Result<(f64, String)> {
-    let binance_price = clients.binance.get_ticker(symbol).await;
-    let bybit_price = clients.bybit.get_ticker(symbol).await;
-    let okx_price = clients.okx.get_ticker(symbol).await;
-
-    let prices = vec![
-        (binance_price, "Binance"),
-        (bybit_price, "Bybit"),
-        (okx_price, "OKX"),
-    ];
+) -> Result<(Price, String)> {
+    let prices = get_tickers(clients, symbol).await;
 
     let mut best_price = None;
     let mut best_exchange = None;
 
-    for (price, exchange) in prices {
+    for (exchange, price) in prices {
         if let Ok(p) = price {
             if best_price.is_none() || p > best_price.unwrap() {
                 best_price = Some(p);
@@ -108,20 +144,35 @@ pub async fn get_best_price_across_exchanges(
     }
 
     match (best_price, best_exchange) {
-        (Some(price), Some(exchange)) => Ok((price, exchange.to_string())),
+        (Some(price), Some(exchange)) => Ok((price, exchange)),
         _ => Err(anyhow::anyhow!("No valid prices available")),
     }
 }
 
+// Queries the ticker on every registered exchange concurrently
+pub async fn get_tickers(clients: &CexClients, symbol: &str) -> Vec<(String, Result<Price>)> {
+    join_all(
+        clients.iter().map(|(name, client)| async move {
+            (name.to_string(), client.get_ticker(symbol).await)
+        }),
+    )
+    .await
+}
+
 pub async fn execute_arbitrage(
     clients: &CexClients,
     symbol: &str,
     min_profit_threshold: f64,
 ) -> Result<()> {
     // Get order books from all exchanges
-    let binance_ob = clients.binance.get_order_book(symbol).await?;
-    let bybit_ob = clients.bybit.get_order_book(symbol).await?;
-    let okx_ob = clients.okx.get_order_book(symbol).await?;
+    let order_books = join_all(
+        clients
+            .iter()
+            .map(|(_, client)| async move { client.get_order_book(symbol).await }),
+    )
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
 
     // Find arbitrage opportunities
     // This is a simplified implementation - in production, you'd want to:
@@ -131,27 +182,19 @@ pub async fn execute_arbitrage(
     // 4. Handle order execution failures
     // 5. Consider market impact
 
-    let best_bid = vec![
-        binance_ob.bids.first(),
-        bybit_ob.bids.first(),
-        okx_ob.bids.first(),
-    ]
-    .into_iter()
-    .flatten()
-    .max_by(|a, b| a.price.partial_cmp(&b.price).unwrap())
-    .ok_or_else(|| anyhow::anyhow!("No bids available"))?;
-
-    let best_ask = vec![
-        binance_ob.asks.first(),
-        bybit_ob.asks.first(),
-        okx_ob.asks.first(),
-    ]
-    .into_iter()
-    .flatten()
-    .min_by(|a, b| a.price.partial_cmp(&b.price).unwrap())
-    .ok_or_else(|| anyhow::anyhow!("No asks available"))?;
+    let best_bid = order_books
+        .iter()
+        .filter_map(|ob| ob.bids.first())
+        .max_by_key(|level| level.price)
+        .ok_or_else(|| anyhow::anyhow!("No bids available"))?;
 
-    let profit = best_bid.price - best_ask.price;
+    let best_ask = order_books
+        .iter()
+        .filter_map(|ob| ob.asks.first())
+        .min_by_key(|level| level.price)
+        .ok_or_else(|| anyhow::anyhow!("No asks available"))?;
+
+    let profit = best_bid.price.to_f64() - best_ask.price.to_f64();
     if profit > min_profit_threshold {
         // Execute arbitrage trades
         // This would involve:
@@ -174,26 +217,24 @@ pub async fn monitor_price_differences(
 
     loop {
         interval.tick().await;
-        let (price, exchange) = get_best_price_across_exchanges(clients, symbol).await?;
-        
+        let (price, _exchange) = get_best_price_across_exchanges(clients, symbol).await?;
+
         // Compare with other exchanges
-        let other_prices = vec![
-            clients.binance.get_ticker(symbol).await,
-            clients.bybit.get_ticker(symbol).await,
-            clients.okx.get_ticker(symbol).await,
-        ];
+        let other_prices = get_tickers(clients, symbol).await;
 
-        for other_price in other_prices {
+        for (_, other_price) in other_prices {
             if let Ok(p) = other_price {
-                let diff = (price - p).abs() / p;
+                let diff = (price.to_f64() - p.to_f64()).abs() / p.to_f64();
                 if diff > threshold {
                     // Log or alert about significant price difference
-                    println!(
+                    warn!(
                         "Significant price difference detected: {} vs {} ({}%)",
-                        price, p, diff * 100.0
+                        price,
+                        p,
+                        diff * 100.0
                     );
                 }
             }
         }
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,310 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use super::{CexClient, CexClients, OrderBook, OrderStatus, Trade};
+use crate::units::{Price, Rounding, TokenAmount};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenOrder {
+    pub venue: String,
+    pub symbol: String,
+    pub order_id: String,
+    pub side: String,
+    pub price: Price,
+    pub quantity: TokenAmount,
+    // The strategy that placed the order, which its fills are delivered to
+    pub strategy: Option<String>,
+    // Filled quantity already delivered
+    pub filled: TokenAmount,
+}
+
+// Part of a strategy's order that filled since the last check
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderFill {
+    pub strategy: String,
+    pub venue: String,
+    pub order_id: String,
+    pub side: String,
+    pub price: Price,
+    pub quantity: TokenAmount,
+}
+
+// Orders placed through the registry that have not been cancelled yet, so
+// they can be cancelled on shutdown.
+#[derive(Clone, Default)]
+pub struct OrderTracker {
+    orders: Arc<Mutex<HashMap<(String, String), OpenOrder>>>,
+}
+
+impl OrderTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, order: OpenOrder) {
+        let key = (order.venue.clone(), order.order_id.clone());
+        self.orders.lock().unwrap().insert(key, order);
+    }
+
+    // Called when an order is cancelled or reported as filled
+    pub fn remove(&self, venue: &str, order_id: &str) -> Option<OpenOrder> {
+        self.orders
+            .lock()
+            .unwrap()
+            .remove(&(venue.to_string(), order_id.to_string()))
+    }
+
+    pub fn open_orders(&self) -> Vec<OpenOrder> {
+        self.orders.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn assign(&self, venue: &str, order_id: &str, strategy: &str) {
+        if let Some(order) = self
+            .orders
+            .lock()
+            .unwrap()
+            .get_mut(&(venue.to_string(), order_id.to_string()))
+        {
+            order.strategy = Some(strategy.to_string());
+        }
+    }
+
+    // Checks every open order a strategy placed and returns what filled since
+    // the last check. Orders the venue no longer has open stop being tracked.
+    pub async fn poll_fills(&self, clients: &CexClients) -> Vec<OrderFill> {
+        let mut fills = Vec::new();
+        for order in self.open_orders() {
+            let (Some(strategy), Some(client)) = (&order.strategy, clients.get(&order.venue))
+            else {
+                continue;
+            };
+            let status = match client.get_order(&order.symbol, &order.order_id).await {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!(
+                        "Failed to check {} order {} on {}: {:#}",
+                        order.symbol, order.order_id, order.venue, e
+                    );
+                    continue;
+                }
+            };
+            match self.record_fill(&order, strategy, &status) {
+                Ok(Some(fill)) => fills.push(fill),
+                Ok(None) => {}
+                Err(e) => warn!(
+                    "Cannot read the fill of {} order {} on {}: {:#}",
+                    order.symbol, order.order_id, order.venue, e
+                ),
+            }
+        }
+        fills
+    }
+
+    fn record_fill(
+        &self,
+        order: &OpenOrder,
+        strategy: &str,
+        status: &OrderStatus,
+    ) -> Result<Option<OrderFill>> {
+        let key = (order.venue.clone(), order.order_id.clone());
+        let previous = order
+            .filled
+            .base_units_at(status.filled.decimals, Rounding::Down)?;
+        let quantity = status.filled.base_units.saturating_sub(previous);
+
+        let mut orders = self.orders.lock().unwrap();
+        if !status.open {
+            orders.remove(&key);
+        } else if let Some(open) = orders.get_mut(&key) {
+            open.filled = status.filled;
+        }
+        if quantity == 0 {
+            return Ok(None);
+        }
+        Ok(Some(OrderFill {
+            strategy: strategy.to_string(),
+            venue: order.venue.clone(),
+            order_id: order.order_id.clone(),
+            side: order.side.clone(),
+            price: status.average_price.unwrap_or(order.price),
+            quantity: TokenAmount::new(quantity, status.filled.decimals),
+        }))
+    }
+
+    pub async fn cancel_all(&self, clients: &CexClients) -> usize {
+        let mut cancelled = 0;
+        for order in self.open_orders() {
+            let Some(client) = clients.get(&order.venue) else {
+                warn!(
+                    "Cannot cancel order {}: venue {} is not registered",
+                    order.order_id, order.venue
+                );
+                continue;
+            };
+            match client.cancel_order(&order.symbol, &order.order_id).await {
+                Ok(()) => {
+                    cancelled += 1;
+                    info!(
+                        "Cancelled {} order {} on {}",
+                        order.symbol, order.order_id, order.venue
+                    );
+                }
+                Err(e) => warn!(
+                    "Failed to cancel {} order {} on {}: {:#}",
+                    order.symbol, order.order_id, order.venue, e
+                ),
+            }
+        }
+        cancelled
+    }
+}
+
+// Wraps a venue client and records every order it places in the tracker
+pub struct TrackedCexClient {
+    venue: String,
+    inner: Arc<dyn CexClient>,
+    orders: OrderTracker,
+}
+
+#[async_trait]
+impl CexClient for TrackedCexClient {
+    async fn get_order_book(&self, symbol: &str) -> Result<OrderBook> {
+        self.inner.get_order_book(symbol).await
+    }
+
+    async fn get_ticker(&self, symbol: &str) -> Result<Price> {
+        self.inner.get_ticker(symbol).await
+    }
+
+    async fn place_order(
+        &self,
+        symbol: &str,
+        side: &str,
+        price: Price,
+        quantity: TokenAmount,
+    ) -> Result<String> {
+        let order_id = self
+            .inner
+            .place_order(symbol, side, price, quantity)
+            .await?;
+        self.orders.insert(OpenOrder {
+            venue: self.venue.clone(),
+            symbol: symbol.to_string(),
+            order_id: order_id.clone(),
+            side: side.to_string(),
+            price,
+            quantity,
+            strategy: None,
+            filled: TokenAmount::zero(quantity.decimals),
+        });
+        Ok(order_id)
+    }
+
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> Result<()> {
+        self.inner.cancel_order(symbol, order_id).await?;
+        self.orders.remove(&self.venue, order_id);
+        Ok(())
+    }
+
+    async fn get_balance(&self, asset: &str) -> Result<TokenAmount> {
+        self.inner.get_balance(asset).await
+    }
+
+    async fn get_recent_trades(&self, symbol: &str) -> Result<Vec<Trade>> {
+        self.inner.get_recent_trades(symbol).await
+    }
+
+    async fn get_order(&self, symbol: &str, order_id: &str) -> Result<OrderStatus> {
+        self.inner.get_order(symbol, order_id).await
+    }
+}
+
+// Labels the orders one strategy places so their fills are routed back to it.
+// Wraps clients that are already tracked.
+struct StrategyCexClient {
+    venue: String,
+    strategy: String,
+    inner: Arc<dyn CexClient>,
+    orders: OrderTracker,
+}
+
+#[async_trait]
+impl CexClient for StrategyCexClient {
+    async fn get_order_book(&self, symbol: &str) -> Result<OrderBook> {
+        self.inner.get_order_book(symbol).await
+    }
+
+    async fn get_ticker(&self, symbol: &str) -> Result<Price> {
+        self.inner.get_ticker(symbol).await
+    }
+
+    async fn place_order(
+        &self,
+        symbol: &str,
+        side: &str,
+        price: Price,
+        quantity: TokenAmount,
+    ) -> Result<String> {
+        let order_id = self
+            .inner
+            .place_order(symbol, side, price, quantity)
+            .await?;
+        self.orders.assign(&self.venue, &order_id, &self.strategy);
+        Ok(order_id)
+    }
+
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> Result<()> {
+        self.inner.cancel_order(symbol, order_id).await
+    }
+
+    async fn get_balance(&self, asset: &str) -> Result<TokenAmount> {
+        self.inner.get_balance(asset).await
+    }
+
+    async fn get_recent_trades(&self, symbol: &str) -> Result<Vec<Trade>> {
+        self.inner.get_recent_trades(symbol).await
+    }
+
+    async fn get_order(&self, symbol: &str, order_id: &str) -> Result<OrderStatus> {
+        self.inner.get_order(symbol, order_id).await
+    }
+}
+
+pub fn attribute_orders(
+    clients: &CexClients,
+    orders: &OrderTracker,
+    strategy: &str,
+) -> Result<CexClients> {
+    let mut attributed = CexClients::new();
+    for (venue, client) in clients.iter() {
+        attributed.register(
+            venue,
+            Arc::new(StrategyCexClient {
+                venue: venue.to_string(),
+                strategy: strategy.to_string(),
+                inner: client.clone(),
+                orders: orders.clone(),
+            }),
+        )?;
+    }
+    Ok(attributed)
+}
+
+pub fn track_orders(clients: &CexClients, orders: &OrderTracker) -> Result<CexClients> {
+    let mut tracked = CexClients::new();
+    for (venue, client) in clients.iter() {
+        tracked.register(
+            venue,
+            Arc::new(TrackedCexClient {
+                venue: venue.to_string(),
+                inner: client.clone(),
+                orders: orders.clone(),
+            }),
+        )?;
+    }
+    Ok(tracked)
+}
@@ -0,0 +1,280 @@
+use anyhow::{anyhow, Result};
+use solana_account_decoder::UiAccountData;
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{info, Instrument};
+
+use super::Command;
+use crate::app::App;
+use crate::config::{Config, ConfigSources};
+use crate::dex::layout::mint_decimals;
+use crate::dex::programs::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use crate::dex::{check_trade_size, DexClient};
+use crate::logging;
+use crate::units::{Rounding, TokenAmount};
+
+// Runs one of the one-off operator commands against initialized clients
+pub async fn execute(app: &App, command: Command) -> Result<()> {
+    match command {
+        Command::Quote {
+            token_a,
+            token_b,
+            venue,
+        } => quote(app, &token_a, &token_b, venue.as_deref()).await,
+        Command::Swap {
+            token_in,
+            token_out,
+            amount_in,
+            min_amount_out,
+            venue,
+            yes,
+        } => {
+            swap(
+                app,
+                &token_in,
+                &token_out,
+                &amount_in,
+                &min_amount_out,
+                &venue,
+                yes,
+            )
+            .await
+        }
+        Command::Positions { venue } => positions(app, venue.as_deref()).await,
+        Command::Balances { assets } => balances(app, &assets).await,
+        Command::Run | Command::Config { .. } | Command::Keystore { .. } => {
+            Err(anyhow!("command does not run against live clients"))
+        }
+    }
+}
+
+pub fn config_check(sources: &ConfigSources) -> Result<()> {
+    let config = Config::build(sources)?;
+    match config.validate() {
+        Ok(()) => {
+            println!("Configuration OK");
+            println!(
+                "  DEX venues: {}",
+                enabled_names(&[
+                    ("raydium", config.dex.raydium.as_ref().map(|c| c.enabled)),
+                    ("jupiter", config.dex.jupiter.as_ref().map(|c| c.enabled)),
+                    ("orca", config.dex.orca.as_ref().map(|c| c.enabled)),
+                    ("meteora", config.dex.meteora.as_ref().map(|c| c.enabled)),
+                    ("router", config.dex.router.as_ref().map(|c| c.enabled)),
+                ])
+            );
+            println!(
+                "  Price feeds: {}",
+                enabled_names(&[
+                    ("pyth", config.oracles.pyth.as_ref().map(|c| c.enabled)),
+                    (
+                        "switchboard",
+                        config.oracles.switchboard.as_ref().map(|c| c.enabled)
+                    ),
+                ])
+            );
+            println!(
+                "  CEX venues: {}",
+                enabled_names(&[
+                    ("binance", config.cex.binance.as_ref().map(|c| c.enabled)),
+                    ("bybit", config.cex.bybit.as_ref().map(|c| c.enabled)),
+                    ("okx", config.cex.okx.as_ref().map(|c| c.enabled)),
+                ])
+            );
+            Ok(())
+        }
+        Err(errors) => Err(errors.into()),
+    }
+}
+
+fn enabled_names(venues: &[(&str, Option<bool>)]) -> String {
+    let names: Vec<&str> = venues
+        .iter()
+        .filter(|(_, enabled)| *enabled == Some(true))
+        .map(|(name, _)| *name)
+        .collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+pub async fn quote(app: &App, token_a: &str, token_b: &str, venue: Option<&str>) -> Result<()> {
+    let token_a = app.tokens.resolve_mint(token_a)?;
+    let token_b = app.tokens.resolve_mint(token_b)?;
+    for (name, client) in select_venues(app, venue)? {
+        match client.get_price(&token_a, &token_b).await {
+            Ok(price) => println!("{:<12} {}", name, price),
+            Err(e) => println!("{:<12} error: {:#}", name, e),
+        }
+    }
+    Ok(())
+}
+
+pub async fn swap(
+    app: &App,
+    token_in: &str,
+    token_out: &str,
+    amount_in: &str,
+    min_amount_out: &str,
+    venue: &str,
+    yes: bool,
+) -> Result<()> {
+    let client = app.dex_clients.require(venue)?;
+    let token_in = app.tokens.resolve_mint(token_in)?;
+    let token_out = app.tokens.resolve_mint(token_out)?;
+    let mints = app
+        .rpc_client
+        .get_multiple_accounts(&[token_in, token_out])
+        .await?;
+    let decimals = mints
+        .iter()
+        .zip([token_in, token_out])
+        .map(|(account, mint)| {
+            let account = account
+                .as_ref()
+                .ok_or_else(|| anyhow!("mint {} not found", mint))?;
+            mint_decimals(&account.data)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Never sell more than asked or accept less than the stated minimum
+    let amount_in = TokenAmount::parse(amount_in, decimals[0], Rounding::Down)?;
+    let min_amount_out = TokenAmount::parse(min_amount_out, decimals[1], Rounding::Up)?;
+    check_trade_size(&app.trading_params.borrow(), amount_in)?;
+
+    println!("Swap on {}", venue);
+    println!("  wallet:         {}", app.wallet.pubkey());
+    println!(
+        "  sell:           {} {} ({})",
+        amount_in,
+        app.tokens.label(&token_in),
+        token_in
+    );
+    println!(
+        "  buy (minimum):  {} {} ({})",
+        min_amount_out,
+        app.tokens.label(&token_out),
+        token_out
+    );
+    if !yes && !confirm("Send this transaction?")? {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let pair = format!(
+        "{}/{}",
+        app.tokens.label(&token_in),
+        app.tokens.label(&token_out)
+    );
+    let span = logging::trade_span("swap", venue, &pair);
+    async {
+        info!(%amount_in, %min_amount_out, "Sending swap");
+        let signature = client
+            .execute_swap(&token_in, &token_out, amount_in, min_amount_out)
+            .await?;
+        logging::record_signature(&signature);
+        info!("Swap submitted");
+        println!("Submitted: {}", signature);
+        Ok(())
+    }
+    .instrument(span)
+    .await
+}
+
+pub async fn positions(app: &App, venue: Option<&str>) -> Result<()> {
+    for (name, client) in select_venues(app, venue)? {
+        let positions = match client.get_positions().await {
+            Ok(positions) => positions,
+            Err(e) => {
+                println!("{}: error: {:#}", name, e);
+                continue;
+            }
+        };
+        println!("{}: {} position(s)", name, positions.len());
+        for position in positions {
+            println!(
+                "  {}  {}/{}  range {}..{}  amounts {} / {}",
+                position.id,
+                app.tokens.label(&position.token_a),
+                app.tokens.label(&position.token_b),
+                position.min_price,
+                position.max_price,
+                position.amount_a,
+                position.amount_b,
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn balances(app: &App, assets: &[String]) -> Result<()> {
+    let owner = app.wallet.pubkey();
+    let lamports = app.rpc_client.get_balance(&owner).await?;
+    println!("wallet {}", owner);
+    println!("  SOL {}", lamports_to_sol(lamports));
+
+    for program in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+        let token_accounts = app
+            .rpc_client
+            .get_token_accounts_by_owner(&owner, TokenAccountsFilter::ProgramId(program))
+            .await?;
+        for keyed in token_accounts {
+            if let UiAccountData::Json(parsed) = keyed.account.data {
+                let info = &parsed.parsed["info"];
+                let amount = info["tokenAmount"]["uiAmountString"]
+                    .as_str()
+                    .unwrap_or("?");
+                let mint = info["mint"].as_str().unwrap_or("?");
+                let label = Pubkey::from_str(mint)
+                    .map(|mint| app.tokens.label(&mint))
+                    .unwrap_or_else(|_| mint.to_string());
+                if amount != "0" {
+                    println!("  {} {}", label, amount);
+                }
+            }
+        }
+    }
+
+    for (name, client) in app.cex_clients.iter() {
+        println!("{}", name);
+        for asset in assets {
+            // Assets are canonical symbols, listed under the venue's own code
+            let code = app
+                .tokens
+                .get(asset)
+                .and_then(|token| token.cex_symbol(name))
+                .unwrap_or(asset);
+            match client.get_balance(code).await {
+                Ok(balance) => println!("  {} {}", asset, balance),
+                Err(e) => println!("  {} error: {:#}", asset, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn select_venues(app: &App, venue: Option<&str>) -> Result<Vec<(String, Arc<dyn DexClient>)>> {
+    match venue {
+        Some(name) => Ok(vec![(name.to_string(), app.dex_clients.require(name)?)]),
+        None if app.dex_clients.is_empty() => Err(anyhow!("no DEX venues are enabled")),
+        None => Ok(app
+            .dex_clients
+            .iter()
+            .map(|(name, client)| (name.to_string(), client.clone()))
+            .collect()),
+    }
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,66 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::signature::{read_keypair_file, Signer};
+
+use super::KeystoreCommand;
+use crate::config::Config;
+use crate::secrets::{self, Keystore, Secret};
+
+pub fn run(config: &Config, action: &KeystoreCommand) -> Result<()> {
+    let keystore_path = config
+        .secrets
+        .keystore_path
+        .as_ref()
+        .ok_or_else(|| anyhow!("secrets.keystore_path is not configured"))?;
+    let open = || Keystore::open(keystore_path, &secrets::read_passphrase()?);
+
+    match action {
+        KeystoreCommand::Init => {
+            let passphrase = secrets::read_passphrase()?;
+            let confirmation = rpassword::prompt_password("Repeat passphrase: ")?;
+            if passphrase.expose() != &confirmation {
+                return Err(anyhow!("passphrases do not match"));
+            }
+            Keystore::create(keystore_path, &passphrase)?;
+            println!("Created keystore {}", keystore_path.display());
+        }
+        KeystoreCommand::SetSecret { name } => {
+            let mut keystore = open()?;
+            let value = rpassword::prompt_password(format!("Value for {}: ", name))?;
+            keystore.set_secret(name, Secret::new(value));
+            keystore.save()?;
+            println!("Stored {}", name);
+        }
+        KeystoreCommand::RemoveSecret { name } => {
+            let mut keystore = open()?;
+            if !keystore.remove_secret(name) {
+                return Err(anyhow!("no secret named {}", name));
+            }
+            keystore.save()?;
+            println!("Removed {}", name);
+        }
+        KeystoreCommand::ImportKeypair { path } => {
+            let mut keystore = open()?;
+            let path = path.as_ref().unwrap_or(&config.solana.keypair_path);
+            let keypair = read_keypair_file(path)
+                .map_err(|e| anyhow!("failed to read keypair {}: {}", path.display(), e))?;
+            keystore.set_keypair(&keypair);
+            keystore.save()?;
+            println!(
+                "Imported keypair {}; {} can now be deleted",
+                keypair.pubkey(),
+                path.display()
+            );
+        }
+        KeystoreCommand::List => {
+            let keystore = open()?;
+            for name in keystore.secret_names() {
+                println!("{}", name);
+            }
+            if let Some(keypair) = keystore.keypair()? {
+                println!("keypair: {}", keypair.pubkey());
+            }
+        }
+    }
+
+    Ok(())
+}
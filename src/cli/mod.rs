@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::ConfigSources;
+
+pub mod commands;
+pub mod keystore;
+
+#[derive(Debug, Parser)]
+#[command(name = "solana_dex_bot", version, about = "Solana DEX trading bot")]
+pub struct Cli {
+    /// Path to the TOML configuration file
+    #[arg(short, long, env = "BOT_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Override a configuration value, e.g. `--set trading.slippage_tolerance=0.005`
+    #[arg(
+        long = "set",
+        value_name = "KEY=VALUE",
+        value_parser = parse_override,
+        global = true
+    )]
+    pub overrides: Vec<(String, String)>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Start the trading daemon (default)
+    Run,
+    /// Get a DEX price for a token pair
+    Quote {
+        /// Mint address or registry symbol, e.g. SOL
+        token_a: String,
+        token_b: String,
+        /// Only query this venue; all enabled DEX venues are queried by default
+        #[arg(long)]
+        venue: Option<String>,
+    },
+    /// Execute a one-off swap after confirmation
+    Swap {
+        /// Mint address or registry symbol, e.g. SOL
+        token_in: String,
+        token_out: String,
+        /// Amount to sell in UI units of `token_in`, e.g. 1.5
+        #[arg(long)]
+        amount_in: String,
+        /// Least to accept in UI units of `token_out`
+        #[arg(long)]
+        min_amount_out: String,
+        #[arg(long)]
+        venue: String,
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List LP positions
+    Positions {
+        #[arg(long)]
+        venue: Option<String>,
+    },
+    /// Show balances across the wallet and CEX accounts
+    Balances {
+        /// CEX assets to query
+        #[arg(long = "asset", default_values_t = ["SOL".to_string(), "USDC".to_string(), "USDT".to_string()])]
+        assets: Vec<String>,
+    },
+    /// Configuration utilities
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Manage the encrypted keystore
+    Keystore {
+        #[command(subcommand)]
+        action: KeystoreCommand,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigCommand {
+    /// Validate the configuration without connecting to anything
+    Check,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum KeystoreCommand {
+    /// Create an empty keystore at `secrets.keystore_path`
+    Init,
+    /// Store a secret, e.g. `cex.binance.api_secret`; the value is prompted for
+    SetSecret { name: String },
+    /// Remove a secret
+    RemoveSecret { name: String },
+    /// Import a Solana keypair file (defaults to `solana.keypair_path`)
+    ImportKeypair { path: Option<PathBuf> },
+    /// List the names of stored secrets
+    List,
+}
+
+impl Cli {
+    pub fn config_sources(&self) -> ConfigSources {
+        ConfigSources {
+            file: self.config.clone(),
+            overrides: self.overrides.clone(),
+        }
+    }
+}
+
+fn parse_override(value: &str) -> Result<(String, String)> {
+    let (key, value) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected KEY=VALUE, got {:?}", value))?;
+    if key.trim().is_empty() {
+        return Err(anyhow!("override key must not be empty"));
+    }
+    Ok((key.trim().to_string(), value.to_string()))
+}
@@ -0,0 +1,1196 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::CommitmentLevel;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::secrets::Secret;
+
+pub mod reload;
+
+pub use reload::{fixed_trading_params, watch_trading_config, TradingParams};
+
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+pub const ENV_PREFIX: &str = "BOT";
+
+// Close instructions that fit in one transaction next to the compute budget ones
+const MAX_SWEEP_ACCOUNTS_PER_TRANSACTION: usize = 20;
+// Every venue is quoted once per part, so this bounds the quotes per swap
+const MAX_ROUTER_SPLIT_PARTS: u32 = 10;
+const ROUTABLE_VENUES: &[&str] = &["raydium", "jupiter", "orca", "meteora"];
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    pub solana: SolanaConfig,
+    #[serde(default)]
+    pub dex: DexConfig,
+    #[serde(default)]
+    pub oracles: OracleConfig,
+    #[serde(default)]
+    pub cex: CexConfig,
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    pub trading: TradingConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    #[serde(default)]
+    pub strategies: Vec<StrategyConfig>,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub monitoring: MonitoringConfig,
+    #[serde(default)]
+    pub harvest: HarvestConfig,
+    #[serde(default)]
+    pub transactions: TransactionConfig,
+    #[serde(default)]
+    pub tokens: TokensConfig,
+    #[serde(default)]
+    pub sweep: SweepConfig,
+}
+
+// Solana Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SolanaConfig {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub keypair_path: PathBuf,
+}
+
+// DEX Configuration
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct DexConfig {
+    pub raydium: Option<RaydiumConfig>,
+    pub jupiter: Option<JupiterConfig>,
+    pub orca: Option<OrcaConfig>,
+    pub meteora: Option<MeteoraConfig>,
+    pub router: Option<RouterConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RaydiumConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // AMM v4 program, used for prices and swaps
+    pub program_id: String,
+    // Concentrated liquidity program holding ranged LP positions
+    #[serde(default = "default_raydium_clmm_program_id")]
+    pub clmm_program_id: String,
+    // Progress of unfinished position rebalances, resumed on the next attempt
+    #[serde(default = "default_rebalance_journal_path")]
+    pub rebalance_journal_path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OrcaConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_orca_program_id")]
+    pub program_id: String,
+    // Pools are derived from this config account, the mints and the tick spacing
+    #[serde(default = "default_orca_whirlpools_config")]
+    pub whirlpools_config: String,
+    // Fee tiers searched for the deepest pool of a pair
+    #[serde(default = "default_orca_tick_spacings")]
+    pub tick_spacings: Vec<u16>,
+    #[serde(default = "default_orca_rebalance_journal_path")]
+    pub rebalance_journal_path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MeteoraConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // DLMM (bin based liquidity) program
+    #[serde(default = "default_meteora_program_id")]
+    pub program_id: String,
+    // How new positions spread their liquidity over the bins of the range
+    #[serde(default)]
+    pub liquidity_shape: LiquidityShape,
+    #[serde(default = "default_meteora_rebalance_journal_path")]
+    pub rebalance_journal_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiquidityShape {
+    // The same amount in every bin
+    #[default]
+    Spot,
+    // Concentrated around the active bin
+    Curve,
+    // Growing towards the edges of the range
+    BidAsk,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct JupiterConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub api_url: String,
+}
+
+// Swaps through whichever enabled venues pay the most, registered as the
+// "router" venue
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RouterConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // Venues to route across; all enabled DEX venues when empty
+    #[serde(default)]
+    pub venues: Vec<String>,
+    // Orders are split across venues in steps of 1/split_parts; 1 never splits
+    #[serde(default = "default_router_split_parts")]
+    pub split_parts: u32,
+}
+
+// Oracle Configuration
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct OracleConfig {
+    pub pyth: Option<OracleProgramConfig>,
+    pub switchboard: Option<OracleProgramConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OracleProgramConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub program_id: String,
+}
+
+// CEX Configuration
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct CexConfig {
+    pub binance: Option<CexVenueConfig>,
+    pub bybit: Option<CexVenueConfig>,
+    pub okx: Option<CexVenueConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CexVenueConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub api_key: String,
+    // Usually left out and read from the keystore instead
+    #[serde(default)]
+    pub api_secret: Option<Secret<String>>,
+}
+
+// Database Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DatabaseConfig {
+    pub url: String,
+}
+
+// Metrics Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_prometheus_port")]
+    pub prometheus_port: u16,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            prometheus_port: default_prometheus_port(),
+        }
+    }
+}
+
+// Trading Configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradingConfig {
+    pub min_trade_size: f64,
+    pub max_trade_size: f64,
+    pub price_impact_threshold: f64,
+    pub slippage_tolerance: f64,
+    pub rebalance_threshold: f64,
+}
+
+// Logging Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    // Per-module overrides in `target=level` form, e.g. "solana_dex_bot::dex=debug"
+    #[serde(default)]
+    pub filters: Vec<String>,
+    #[serde(default = "default_log_file_path")]
+    pub file_path: PathBuf,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    // Only used with size based rotation
+    #[serde(default = "default_log_max_file_size_mb")]
+    pub max_file_size_mb: u64,
+    // Number of rotated files kept next to the active one
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            filters: Vec::new(),
+            file_path: default_log_file_path(),
+            rotation: LogRotation::default(),
+            max_file_size_mb: default_log_max_file_size_mb(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    #[default]
+    Daily,
+    Size,
+}
+
+// Secrets Configuration
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SecretsConfig {
+    pub keystore_path: Option<PathBuf>,
+}
+
+// Strategy Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StrategyConfig {
+    pub name: String,
+    pub kind: String,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub price_symbol: String,
+    // Price feed venue to drive the strategy from; the first enabled one by default
+    #[serde(default)]
+    pub price_feed: Option<String>,
+    #[serde(default = "default_timer_interval_secs")]
+    pub timer_interval_secs: u64,
+    #[serde(default = "default_price_poll_interval_ms")]
+    pub price_poll_interval_ms: u64,
+    // Strategy specific settings, interpreted by the strategy kind
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+// Shutdown Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ShutdownConfig {
+    #[serde(default = "default_snapshot_path")]
+    pub snapshot_path: PathBuf,
+    // How long to wait for in-flight transactions before giving up
+    #[serde(default = "default_transaction_timeout_secs")]
+    pub transaction_timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_path: default_snapshot_path(),
+            transaction_timeout_secs: default_transaction_timeout_secs(),
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub fn transaction_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.transaction_timeout_secs)
+    }
+}
+
+// Transaction Submission Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TransactionConfig {
+    // Commitment a transaction must reach to count as landed
+    #[serde(default = "default_commitment")]
+    pub commitment: CommitmentLevel,
+    #[serde(default)]
+    pub priority_fee: PriorityFeeConfig,
+    // Compute unit limit requested when the instructions don't set their own
+    // and simulation is off
+    #[serde(default)]
+    pub compute_unit_limit: Option<u32>,
+    // Simulate before sending, rejecting transactions that would fail and
+    // sizing the compute unit limit from what the simulation used
+    #[serde(default = "default_enabled")]
+    pub simulate: bool,
+    // Headroom added to the simulated compute units, as a fraction
+    #[serde(default = "default_compute_unit_margin")]
+    pub compute_unit_margin: f64,
+    #[serde(default = "default_resend_interval_ms")]
+    pub resend_interval_ms: u64,
+    // How long a fetched blockhash is reused before asking for a newer one
+    #[serde(default = "default_blockhash_ttl_ms")]
+    pub blockhash_ttl_ms: u64,
+}
+
+impl Default for TransactionConfig {
+    fn default() -> Self {
+        Self {
+            commitment: default_commitment(),
+            priority_fee: PriorityFeeConfig::default(),
+            compute_unit_limit: None,
+            simulate: true,
+            compute_unit_margin: default_compute_unit_margin(),
+            resend_interval_ms: default_resend_interval_ms(),
+            blockhash_ttl_ms: default_blockhash_ttl_ms(),
+        }
+    }
+}
+
+impl TransactionConfig {
+    pub fn resend_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.resend_interval_ms)
+    }
+
+    pub fn blockhash_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.blockhash_ttl_ms)
+    }
+}
+
+// Priority fees, in micro-lamports per compute unit
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PriorityFeeConfig {
+    #[serde(default)]
+    pub policy: PriorityFeePolicy,
+    // The fixed fee, and the fallback before any recent fees are known
+    #[serde(default)]
+    pub micro_lamports: u64,
+    // Percentile of recent prioritization fees to pay
+    #[serde(default = "default_priority_fee_percentile")]
+    pub percentile: u8,
+    #[serde(default)]
+    pub urgency: Urgency,
+    // Upper bound for the percentile and urgency policies
+    #[serde(default = "default_max_priority_fee_micro_lamports")]
+    pub max_micro_lamports: u64,
+    // How many recent slots the fee model keeps
+    #[serde(default = "default_priority_fee_window_slots")]
+    pub window_slots: u64,
+}
+
+impl Default for PriorityFeeConfig {
+    fn default() -> Self {
+        Self {
+            policy: PriorityFeePolicy::default(),
+            micro_lamports: 0,
+            percentile: default_priority_fee_percentile(),
+            urgency: Urgency::default(),
+            max_micro_lamports: default_max_priority_fee_micro_lamports(),
+            window_slots: default_priority_fee_window_slots(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityFeePolicy {
+    // Always `micro_lamports`
+    #[default]
+    Fixed,
+    // The configured percentile of recent fees for the accounts written
+    Percentile,
+    // The percentile fee scaled by the urgency
+    Urgency,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+impl Urgency {
+    pub fn multiplier(self) -> f64 {
+        match self {
+            Urgency::Low => 0.5,
+            Urgency::Normal => 1.0,
+            Urgency::High => 2.0,
+            Urgency::Critical => 4.0,
+        }
+    }
+}
+
+// Fee Harvesting Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HarvestConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_harvest_interval_secs")]
+    pub interval_secs: u64,
+    // Harvest only once unclaimed fees are worth this many times the transaction cost
+    #[serde(default = "default_min_fee_to_cost_ratio")]
+    pub min_fee_to_cost_ratio: f64,
+    // Estimated cost of one transaction, including the priority fee
+    #[serde(default = "default_transaction_cost_lamports")]
+    pub transaction_cost_lamports: u64,
+    // Redeposit harvested fees into the same position
+    #[serde(default)]
+    pub auto_compound: bool,
+    // DEX venues to harvest on; empty means all enabled venues
+    #[serde(default)]
+    pub venues: Vec<String>,
+}
+
+impl Default for HarvestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_harvest_interval_secs(),
+            min_fee_to_cost_ratio: default_min_fee_to_cost_ratio(),
+            transaction_cost_lamports: default_transaction_cost_lamports(),
+            auto_compound: false,
+            venues: Vec::new(),
+        }
+    }
+}
+
+// Token Registry Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokensConfig {
+    // Extra tokens in the format of src/tokens/tokens.json, replacing bundled
+    // entries with the same mint or symbol
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    // Fill in mints not covered above from the Jupiter token list
+    #[serde(default = "default_enabled")]
+    pub jupiter_token_list: bool,
+    #[serde(default = "default_jupiter_token_list_url")]
+    pub jupiter_token_list_url: String,
+}
+
+impl Default for TokensConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            jupiter_token_list: true,
+            jupiter_token_list_url: default_jupiter_token_list_url(),
+        }
+    }
+}
+
+// Empty Token Account Sweep Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SweepConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_sweep_interval_secs")]
+    pub interval_secs: u64,
+    // Mints or registry symbols whose empty accounts are kept because they are
+    // refilled often; tokens of open LP positions are always kept
+    #[serde(default = "default_sweep_keep")]
+    pub keep: Vec<String>,
+    #[serde(default = "default_sweep_max_accounts_per_transaction")]
+    pub max_accounts_per_transaction: usize,
+}
+
+impl Default for SweepConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_sweep_interval_secs(),
+            keep: default_sweep_keep(),
+            max_accounts_per_transaction: default_sweep_max_accounts_per_transaction(),
+        }
+    }
+}
+
+// Monitoring Configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MonitoringConfig {
+    #[serde(default = "default_health_log_interval_secs")]
+    pub health_log_interval_secs: u64,
+    #[serde(default)]
+    pub restart: RestartConfig,
+    #[serde(default)]
+    pub price_changes: Vec<PriceChangeMonitorConfig>,
+    #[serde(default)]
+    pub cex_spreads: Vec<CexSpreadMonitorConfig>,
+}
+
+impl Default for MonitoringConfig {
+    fn default() -> Self {
+        Self {
+            health_log_interval_secs: default_health_log_interval_secs(),
+            restart: RestartConfig::default(),
+            price_changes: Vec::new(),
+            cex_spreads: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RestartConfig {
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    // Leave unset to restart forever
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: Option<u32>,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_secs: default_max_backoff_secs(),
+            max_restarts: default_max_restarts(),
+        }
+    }
+}
+
+impl RestartConfig {
+    pub fn policy(&self) -> crate::supervisor::RestartPolicy {
+        crate::supervisor::RestartPolicy {
+            initial_backoff: std::time::Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: std::time::Duration::from_secs(self.max_backoff_secs),
+            max_restarts: self.max_restarts,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PriceChangeMonitorConfig {
+    pub feed: String,
+    pub symbol: String,
+    pub threshold: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CexSpreadMonitorConfig {
+    pub symbol: String,
+    pub threshold: f64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_raydium_clmm_program_id() -> String {
+    "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK".to_string()
+}
+
+fn default_rebalance_journal_path() -> PathBuf {
+    PathBuf::from("state/raydium_rebalances.json")
+}
+
+fn default_orca_program_id() -> String {
+    "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc".to_string()
+}
+
+fn default_orca_whirlpools_config() -> String {
+    "2LecshUwdy9xi7meFgHtFJQNSKk4KdTrcpvaB56dP2NQ".to_string()
+}
+
+fn default_orca_tick_spacings() -> Vec<u16> {
+    vec![1, 8, 64, 128]
+}
+
+fn default_orca_rebalance_journal_path() -> PathBuf {
+    PathBuf::from("state/orca_rebalances.json")
+}
+
+fn default_meteora_program_id() -> String {
+    "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo".to_string()
+}
+
+fn default_meteora_rebalance_journal_path() -> PathBuf {
+    PathBuf::from("state/meteora_rebalances.json")
+}
+
+fn default_router_split_parts() -> u32 {
+    4
+}
+
+fn default_prometheus_port() -> u16 {
+    9090
+}
+
+fn default_timer_interval_secs() -> u64 {
+    60
+}
+
+fn default_price_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_snapshot_path() -> PathBuf {
+    PathBuf::from("state/snapshot.json")
+}
+
+fn default_transaction_timeout_secs() -> u64 {
+    90
+}
+
+fn default_commitment() -> CommitmentLevel {
+    CommitmentLevel::Confirmed
+}
+
+fn default_priority_fee_percentile() -> u8 {
+    75
+}
+
+fn default_max_priority_fee_micro_lamports() -> u64 {
+    1_000_000
+}
+
+fn default_priority_fee_window_slots() -> u64 {
+    150
+}
+
+fn default_compute_unit_margin() -> f64 {
+    0.1
+}
+
+fn default_resend_interval_ms() -> u64 {
+    2000
+}
+
+fn default_blockhash_ttl_ms() -> u64 {
+    10_000
+}
+
+fn default_harvest_interval_secs() -> u64 {
+    900
+}
+
+fn default_min_fee_to_cost_ratio() -> f64 {
+    5.0
+}
+
+fn default_transaction_cost_lamports() -> u64 {
+    10_000
+}
+
+fn default_jupiter_token_list_url() -> String {
+    "https://lite-api.jup.ag/tokens/v2/tag?query=verified".to_string()
+}
+
+fn default_sweep_interval_secs() -> u64 {
+    3600
+}
+
+fn default_sweep_keep() -> Vec<String> {
+    vec!["USDC".to_string(), "USDT".to_string()]
+}
+
+fn default_sweep_max_accounts_per_transaction() -> usize {
+    MAX_SWEEP_ACCOUNTS_PER_TRANSACTION
+}
+
+fn default_health_log_interval_secs() -> u64 {
+    60
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_max_backoff_secs() -> u64 {
+    60
+}
+
+fn default_max_restarts() -> Option<u32> {
+    Some(10)
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_max_file_size_mb() -> u64 {
+    100
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+fn default_log_file_path() -> PathBuf {
+    PathBuf::from("logs/bot.log")
+}
+
+// Where configuration values come from, in increasing order of precedence:
+// the TOML file, `BOT_*` environment variables, then command line overrides.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    pub file: Option<PathBuf>,
+    pub overrides: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid configuration:\n{}", format_field_errors(.0))]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+fn format_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("  - {}", e))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        Self::load_from(&ConfigSources::default())
+    }
+
+    pub fn load_from(sources: &ConfigSources) -> Result<Self> {
+        // Load environment variables from .env file
+        dotenv::dotenv().ok();
+
+        let config = Self::build(sources)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Merges all sources without validating, so callers such as `config check`
+    // can report every problem at once.
+    pub fn build(sources: &ConfigSources) -> Result<Self> {
+        let path = sources
+            .file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+
+        let mut builder = ::config::Config::builder()
+            .add_source(::config::File::from(path.as_path()).required(sources.file.is_some()))
+            .add_source(
+                ::config::Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .try_parsing(true),
+            );
+
+        for (key, value) in &sources.overrides {
+            builder = builder.set_override(key.as_str(), value.as_str())?;
+        }
+
+        builder
+            .build()?
+            .try_deserialize()
+            .with_context(|| format!("failed to parse configuration from {}", path.display()))
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = Vec::new();
+
+        validate_url(
+            &mut errors,
+            "solana.rpc_url",
+            &self.solana.rpc_url,
+            &["http", "https"],
+        );
+        validate_url(
+            &mut errors,
+            "solana.ws_url",
+            &self.solana.ws_url,
+            &["ws", "wss"],
+        );
+        if self.solana.keypair_path.as_os_str().is_empty() {
+            errors.push(FieldError::new("solana.keypair_path", "must not be empty"));
+        }
+
+        if let Some(raydium) = self.dex.raydium.as_ref().filter(|c| c.enabled) {
+            validate_pubkey(&mut errors, "dex.raydium.program_id", &raydium.program_id);
+            validate_pubkey(
+                &mut errors,
+                "dex.raydium.clmm_program_id",
+                &raydium.clmm_program_id,
+            );
+        }
+        if let Some(orca) = self.dex.orca.as_ref().filter(|c| c.enabled) {
+            validate_pubkey(&mut errors, "dex.orca.program_id", &orca.program_id);
+            validate_pubkey(
+                &mut errors,
+                "dex.orca.whirlpools_config",
+                &orca.whirlpools_config,
+            );
+            if orca.tick_spacings.is_empty() || orca.tick_spacings.contains(&0) {
+                errors.push(FieldError::new(
+                    "dex.orca.tick_spacings",
+                    "must list at least one tick spacing, all greater than 0",
+                ));
+            }
+        }
+        if let Some(meteora) = self.dex.meteora.as_ref().filter(|c| c.enabled) {
+            validate_pubkey(&mut errors, "dex.meteora.program_id", &meteora.program_id);
+        }
+        if let Some(jupiter) = self.dex.jupiter.as_ref().filter(|c| c.enabled) {
+            validate_url(
+                &mut errors,
+                "dex.jupiter.api_url",
+                &jupiter.api_url,
+                &["http", "https"],
+            );
+        }
+
+        if let Some(router) = self.dex.router.as_ref().filter(|c| c.enabled) {
+            for venue in &router.venues {
+                if !ROUTABLE_VENUES.contains(&venue.as_str()) {
+                    errors.push(FieldError::new(
+                        "dex.router.venues",
+                        format!(
+                            "unknown venue {:?}, expected one of {}",
+                            venue,
+                            ROUTABLE_VENUES.join(", ")
+                        ),
+                    ));
+                }
+            }
+            if !(1..=MAX_ROUTER_SPLIT_PARTS).contains(&router.split_parts) {
+                errors.push(FieldError::new(
+                    "dex.router.split_parts",
+                    format!("must be between 1 and {}", MAX_ROUTER_SPLIT_PARTS),
+                ));
+            }
+        }
+
+        if let Some(pyth) = self.oracles.pyth.as_ref().filter(|c| c.enabled) {
+            validate_pubkey(&mut errors, "oracles.pyth.program_id", &pyth.program_id);
+        }
+        if let Some(switchboard) = self.oracles.switchboard.as_ref().filter(|c| c.enabled) {
+            validate_pubkey(
+                &mut errors,
+                "oracles.switchboard.program_id",
+                &switchboard.program_id,
+            );
+        }
+
+        for (venue, credentials) in [
+            ("binance", &self.cex.binance),
+            ("bybit", &self.cex.bybit),
+            ("okx", &self.cex.okx),
+        ] {
+            if let Some(credentials) = credentials.as_ref().filter(|c| c.enabled) {
+                credentials.validate(
+                    &mut errors,
+                    &format!("cex.{}", venue),
+                    self.secrets.keystore_path.is_some(),
+                );
+            }
+        }
+
+        if self.database.url.trim().is_empty() {
+            errors.push(FieldError::new("database.url", "must not be empty"));
+        }
+
+        if self.metrics.enabled && self.metrics.prometheus_port == 0 {
+            errors.push(FieldError::new(
+                "metrics.prometheus_port",
+                "must be non-zero when metrics are enabled",
+            ));
+        }
+
+        self.trading.validate(&mut errors);
+
+        if self.harvest.interval_secs == 0 {
+            errors.push(FieldError::new(
+                "harvest.interval_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.harvest.min_fee_to_cost_ratio.is_nan() || self.harvest.min_fee_to_cost_ratio < 0.0 {
+            errors.push(FieldError::new(
+                "harvest.min_fee_to_cost_ratio",
+                "must not be negative",
+            ));
+        }
+
+        if self.transactions.resend_interval_ms == 0 {
+            errors.push(FieldError::new(
+                "transactions.resend_interval_ms",
+                "must be greater than 0",
+            ));
+        }
+        if self.transactions.compute_unit_margin.is_nan()
+            || self.transactions.compute_unit_margin < 0.0
+        {
+            errors.push(FieldError::new(
+                "transactions.compute_unit_margin",
+                "must not be negative",
+            ));
+        }
+        let priority_fee = &self.transactions.priority_fee;
+        if priority_fee.percentile > 100 {
+            errors.push(FieldError::new(
+                "transactions.priority_fee.percentile",
+                "must be at most 100",
+            ));
+        }
+        if priority_fee.max_micro_lamports < priority_fee.micro_lamports {
+            errors.push(FieldError::new(
+                "transactions.priority_fee.max_micro_lamports",
+                "must not be below transactions.priority_fee.micro_lamports",
+            ));
+        }
+        if priority_fee.window_slots == 0 {
+            errors.push(FieldError::new(
+                "transactions.priority_fee.window_slots",
+                "must be greater than 0",
+            ));
+        }
+
+        if self.tokens.jupiter_token_list {
+            validate_url(
+                &mut errors,
+                "tokens.jupiter_token_list_url",
+                &self.tokens.jupiter_token_list_url,
+                &["http", "https"],
+            );
+        }
+
+        if self.sweep.interval_secs == 0 {
+            errors.push(FieldError::new(
+                "sweep.interval_secs",
+                "must be greater than 0",
+            ));
+        }
+        if !(1..=MAX_SWEEP_ACCOUNTS_PER_TRANSACTION)
+            .contains(&self.sweep.max_accounts_per_transaction)
+        {
+            errors.push(FieldError::new(
+                "sweep.max_accounts_per_transaction",
+                format!(
+                    "must be between 1 and {}",
+                    MAX_SWEEP_ACCOUNTS_PER_TRANSACTION
+                ),
+            ));
+        }
+
+        if self.monitoring.health_log_interval_secs == 0 {
+            errors.push(FieldError::new(
+                "monitoring.health_log_interval_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.monitoring.restart.initial_backoff_ms == 0 {
+            errors.push(FieldError::new(
+                "monitoring.restart.initial_backoff_ms",
+                "must be greater than 0",
+            ));
+        }
+        for (i, monitor) in self.monitoring.price_changes.iter().enumerate() {
+            if monitor.threshold.is_nan() || monitor.threshold <= 0.0 {
+                errors.push(FieldError::new(
+                    format!("monitoring.price_changes[{}].threshold", i),
+                    "must be greater than 0",
+                ));
+            }
+        }
+        for (i, monitor) in self.monitoring.cex_spreads.iter().enumerate() {
+            if monitor.threshold.is_nan() || monitor.threshold <= 0.0 {
+                errors.push(FieldError::new(
+                    format!("monitoring.cex_spreads[{}].threshold", i),
+                    "must be greater than 0",
+                ));
+            }
+        }
+
+        let mut strategy_names = std::collections::HashSet::new();
+        for (i, strategy) in self.strategies.iter().enumerate() {
+            strategy.validate(&mut errors, &format!("strategies[{}]", i));
+            if !strategy_names.insert(strategy.name.as_str()) {
+                errors.push(FieldError::new(
+                    format!("strategies[{}].name", i),
+                    format!("duplicate strategy name {:?}", strategy.name),
+                ));
+            }
+        }
+
+        self.logging.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(errors))
+        }
+    }
+}
+
+impl LoggingConfig {
+    fn validate(&self, errors: &mut Vec<FieldError>) {
+        if tracing::Level::from_str(&self.level).is_err() {
+            errors.push(FieldError::new(
+                "logging.level",
+                format!("unknown level {:?}", self.level),
+            ));
+        }
+        for (i, filter) in self.filters.iter().enumerate() {
+            if let Err(e) = tracing_subscriber::filter::Directive::from_str(filter) {
+                errors.push(FieldError::new(
+                    format!("logging.filters[{}]", i),
+                    format!("invalid filter {:?}: {}", filter, e),
+                ));
+            }
+        }
+        if self.rotation == LogRotation::Size && self.max_file_size_mb == 0 {
+            errors.push(FieldError::new(
+                "logging.max_file_size_mb",
+                "must be greater than 0",
+            ));
+        }
+        if self.max_files == 0 {
+            errors.push(FieldError::new(
+                "logging.max_files",
+                "must be greater than 0",
+            ));
+        }
+    }
+}
+
+impl TradingConfig {
+    pub fn validate(&self, errors: &mut Vec<FieldError>) {
+        if self.min_trade_size.is_nan() || self.min_trade_size <= 0.0 {
+            errors.push(FieldError::new(
+                "trading.min_trade_size",
+                "must be greater than 0",
+            ));
+        }
+        if !self.max_trade_size.is_finite() {
+            errors.push(FieldError::new(
+                "trading.max_trade_size",
+                format!("must be a finite number, got {}", self.max_trade_size),
+            ));
+        } else if self.min_trade_size > self.max_trade_size {
+            errors.push(FieldError::new(
+                "trading.max_trade_size",
+                format!(
+                    "must be at least min_trade_size ({} > {})",
+                    self.min_trade_size, self.max_trade_size
+                ),
+            ));
+        }
+        for (field, value) in [
+            ("trading.slippage_tolerance", self.slippage_tolerance),
+            (
+                "trading.price_impact_threshold",
+                self.price_impact_threshold,
+            ),
+            ("trading.rebalance_threshold", self.rebalance_threshold),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                errors.push(FieldError::new(
+                    field,
+                    format!("must be between 0 and 1, got {}", value),
+                ));
+            }
+        }
+    }
+}
+
+impl StrategyConfig {
+    fn validate(&self, errors: &mut Vec<FieldError>, section: &str) {
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new(
+                format!("{}.name", section),
+                "must not be empty",
+            ));
+        }
+        if self.kind.trim().is_empty() {
+            errors.push(FieldError::new(
+                format!("{}.kind", section),
+                "must not be empty",
+            ));
+        }
+        validate_pubkey(errors, &format!("{}.base_mint", section), &self.base_mint);
+        validate_pubkey(errors, &format!("{}.quote_mint", section), &self.quote_mint);
+        if self.timer_interval_secs == 0 {
+            errors.push(FieldError::new(
+                format!("{}.timer_interval_secs", section),
+                "must be greater than 0",
+            ));
+        }
+        if self.price_poll_interval_ms == 0 {
+            errors.push(FieldError::new(
+                format!("{}.price_poll_interval_ms", section),
+                "must be greater than 0",
+            ));
+        }
+    }
+}
+
+impl CexVenueConfig {
+    fn validate(&self, errors: &mut Vec<FieldError>, section: &str, has_keystore: bool) {
+        if self.api_key.trim().is_empty() {
+            errors.push(FieldError::new(
+                format!("{}.api_key", section),
+                "must not be empty",
+            ));
+        }
+        match &self.api_secret {
+            Some(secret) if secret.expose().trim().is_empty() => {
+                errors.push(FieldError::new(
+                    format!("{}.api_secret", section),
+                    "must not be empty",
+                ));
+            }
+            None if !has_keystore => {
+                errors.push(FieldError::new(
+                    format!("{}.api_secret", section),
+                    "must be set when no secrets.keystore_path is configured",
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn validate_url(errors: &mut Vec<FieldError>, field: &str, url: &str, schemes: &[&str]) {
+    let valid = url
+        .split_once("://")
+        .map(|(scheme, rest)| schemes.contains(&scheme) && !rest.is_empty())
+        .unwrap_or(false);
+
+    if !valid {
+        errors.push(FieldError::new(
+            field,
+            format!("expected a {} URL, got {:?}", schemes.join("/"), url),
+        ));
+    }
+}
+
+fn validate_pubkey(errors: &mut Vec<FieldError>, field: &str, value: &str) {
+    if Pubkey::from_str(value).is_err() {
+        errors.push(FieldError::new(
+            field,
+            format!("invalid public key {:?}", value),
+        ));
+    }
+}
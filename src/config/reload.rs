@@ -0,0 +1,256 @@
+use anyhow::Result;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use super::{Config, ConfigSources, TradingConfig, DEFAULT_CONFIG_PATH};
+
+// Editors usually write a file in several steps; wait for the burst to settle
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Live trading parameters. Components hold a receiver and read the latest
+// value with `borrow()` or wait for updates with `changed()`.
+pub type TradingParams = watch::Receiver<TradingConfig>;
+
+// Keeps the file watcher alive; dropping it stops hot-reloading.
+pub struct ConfigWatcher {
+    _watcher: Option<RecommendedWatcher>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+pub fn watch_trading_config(
+    sources: ConfigSources,
+    initial: &Config,
+    shutdown: CancellationToken,
+) -> Result<(TradingParams, ConfigWatcher)> {
+    let (params_tx, params_rx) = watch::channel(initial.trading.clone());
+
+    let path = sources
+        .file
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+    if !path.exists() {
+        warn!(
+            "Config file {} not found, trading parameters will not be hot-reloaded",
+            path.display()
+        );
+        return Ok((
+            params_rx,
+            ConfigWatcher {
+                _watcher: None,
+                task: None,
+            },
+        ));
+    }
+
+    let (event_tx, event_rx) = mpsc::channel(16);
+    let watched = path.canonicalize()?;
+    let file_name = watched.file_name().map(|name| name.to_owned());
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let relevant = match res {
+            Ok(event) => {
+                matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name().map(|name| name.to_owned()) == file_name)
+            }
+            Err(e) => {
+                warn!("Config watcher error: {}", e);
+                false
+            }
+        };
+        if relevant {
+            let _ = event_tx.try_send(());
+        }
+    })?;
+
+    // Watch the directory rather than the file so atomic renames are picked up
+    let dir = watched.parent().unwrap_or_else(|| Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    info!("Watching {} for trading parameter changes", path.display());
+
+    let task = tokio::spawn(reload_loop(
+        sources,
+        initial.clone(),
+        event_rx,
+        params_tx,
+        shutdown,
+    ));
+
+    Ok((
+        params_rx,
+        ConfigWatcher {
+            _watcher: Some(watcher),
+            task: Some(task),
+        },
+    ))
+}
+
+// Trading parameters fixed at their configured values, for one-off commands
+// that do not watch the config file
+pub fn fixed_trading_params(config: &Config) -> TradingParams {
+    watch::channel(config.trading.clone()).1
+}
+
+async fn reload_loop(
+    sources: ConfigSources,
+    mut current: Config,
+    mut events: mpsc::Receiver<()>,
+    params_tx: watch::Sender<TradingConfig>,
+    shutdown: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            event = events.recv() => {
+                if event.is_none() {
+                    break;
+                }
+            }
+        }
+
+        tokio::time::sleep(DEBOUNCE).await;
+        while events.try_recv().is_ok() {}
+
+        let candidate = match Config::build(&sources) {
+            Ok(candidate) => candidate,
+            Err(e) => {
+                error!("Config reload failed, keeping current parameters: {:#}", e);
+                continue;
+            }
+        };
+
+        match check_reload(&current, &candidate) {
+            Ok(None) => {}
+            Ok(Some(trading)) => {
+                info!(
+                    "Trading parameters reloaded: {}",
+                    describe_changes(&current.trading, &trading).join(", ")
+                );
+                current.trading = trading.clone();
+                params_tx.send_replace(trading);
+            }
+            Err(problems) => {
+                error!(
+                    "Config reload rejected, keeping current parameters: {}",
+                    problems.join("; ")
+                );
+            }
+        }
+    }
+}
+
+// Returns the new trading section if it changed and may be applied, or the
+// reasons the reload has to be rejected.
+pub fn check_reload(
+    current: &Config,
+    candidate: &Config,
+) -> Result<Option<TradingConfig>, Vec<String>> {
+    let mut problems: Vec<String> = non_reloadable_changes(current, candidate)
+        .into_iter()
+        .map(|section| format!("[{}] changed but requires a restart", section))
+        .collect();
+
+    let mut errors = Vec::new();
+    candidate.trading.validate(&mut errors);
+    problems.extend(errors.iter().map(|e| e.to_string()));
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    if candidate.trading == current.trading {
+        Ok(None)
+    } else {
+        Ok(Some(candidate.trading.clone()))
+    }
+}
+
+pub fn non_reloadable_changes(current: &Config, candidate: &Config) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    if current.solana != candidate.solana {
+        changed.push("solana");
+    }
+    if current.dex != candidate.dex {
+        changed.push("dex");
+    }
+    if current.oracles != candidate.oracles {
+        changed.push("oracles");
+    }
+    if current.cex != candidate.cex {
+        changed.push("cex");
+    }
+    if current.database != candidate.database {
+        changed.push("database");
+    }
+    if current.metrics != candidate.metrics {
+        changed.push("metrics");
+    }
+    if current.logging != candidate.logging {
+        changed.push("logging");
+    }
+    if current.secrets != candidate.secrets {
+        changed.push("secrets");
+    }
+    if current.strategies != candidate.strategies {
+        changed.push("strategies");
+    }
+    if current.shutdown != candidate.shutdown {
+        changed.push("shutdown");
+    }
+    if current.monitoring != candidate.monitoring {
+        changed.push("monitoring");
+    }
+    if current.harvest != candidate.harvest {
+        changed.push("harvest");
+    }
+    if current.transactions != candidate.transactions {
+        changed.push("transactions");
+    }
+    if current.tokens != candidate.tokens {
+        changed.push("tokens");
+    }
+    if current.sweep != candidate.sweep {
+        changed.push("sweep");
+    }
+    changed
+}
+
+fn describe_changes(old: &TradingConfig, new: &TradingConfig) -> Vec<String> {
+    [
+        ("min_trade_size", old.min_trade_size, new.min_trade_size),
+        ("max_trade_size", old.max_trade_size, new.max_trade_size),
+        (
+            "price_impact_threshold",
+            old.price_impact_threshold,
+            new.price_impact_threshold,
+        ),
+        (
+            "slippage_tolerance",
+            old.slippage_tolerance,
+            new.slippage_tolerance,
+        ),
+        (
+            "rebalance_threshold",
+            old.rebalance_threshold,
+            new.rebalance_threshold,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, old, new)| old != new)
+    .map(|(field, old, new)| format!("{} {} -> {}", field, old, new))
+    .collect()
+}
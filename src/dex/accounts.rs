@@ -0,0 +1,253 @@
+use anyhow::Result;
+use solana_account_decoder::UiAccountData;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use super::programs::{
+    associated_token_address, close_account, create_associated_token_account_idempotent,
+    sync_native, NATIVE_MINT, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+use super::{send_transaction, DexClients};
+use crate::config::SweepConfig;
+use crate::transactions::TransactionSender;
+
+// Where the SOL leg of a swap or deposit comes from and goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolBalance {
+    // The wallet's native balance: SOL is wrapped just before it is spent and
+    // the wrapped SOL account is closed again at the end of the transaction
+    Native,
+    // Whatever the wrapped SOL account holds, as between the steps of a
+    // rebalance that measure token balances
+    Wrapped,
+}
+
+// Instructions around a swap or deposit that get the wallet's token accounts
+// ready: every account used is created unless it exists, SOL to spend is
+// wrapped first and whatever wrapped SOL is left is unwrapped last
+pub struct WalletAccounts {
+    owner: Pubkey,
+    sol: SolBalance,
+    setup: Vec<Instruction>,
+    created: HashSet<Pubkey>,
+    unwrap: bool,
+}
+
+impl WalletAccounts {
+    pub fn new(owner: Pubkey, sol: SolBalance) -> Self {
+        Self {
+            owner,
+            sol,
+            setup: Vec::new(),
+            created: HashSet::new(),
+            unwrap: false,
+        }
+    }
+
+    // The owner's associated account for `mint`
+    pub fn account(&mut self, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+        let address = associated_token_address(&self.owner, mint, token_program);
+        if self.created.insert(address) {
+            self.setup.push(create_associated_token_account_idempotent(
+                &self.owner,
+                &self.owner,
+                mint,
+                token_program,
+            ));
+        }
+        address
+    }
+
+    // Account to spend up to `amount` base units of `mint` from
+    pub fn spend(&mut self, mint: &Pubkey, token_program: &Pubkey, amount: u64) -> Pubkey {
+        let address = self.account(mint, token_program);
+        if self.is_native(mint) {
+            if amount > 0 {
+                self.setup
+                    .push(system_instruction::transfer(&self.owner, &address, amount));
+                self.setup.push(sync_native(&address));
+            }
+            self.unwrap = true;
+        }
+        address
+    }
+
+    // Account to receive `mint` into
+    pub fn receive(&mut self, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+        let address = self.account(mint, token_program);
+        if self.is_native(mint) {
+            self.unwrap = true;
+        }
+        address
+    }
+
+    fn is_native(&self, mint: &Pubkey) -> bool {
+        *mint == NATIVE_MINT && self.sol == SolBalance::Native
+    }
+
+    // The setup instructions, then `instructions`, then the unwrap
+    pub fn build(self, instructions: impl IntoIterator<Item = Instruction>) -> Vec<Instruction> {
+        let mut all = self.setup;
+        all.extend(instructions);
+        if self.unwrap {
+            let account = associated_token_address(&self.owner, &NATIVE_MINT, &TOKEN_PROGRAM_ID);
+            all.push(close_account(
+                &account,
+                &self.owner,
+                &self.owner,
+                &TOKEN_PROGRAM_ID,
+            ));
+        }
+        all
+    }
+}
+
+// Periodically closes the wallet's empty token accounts, returning their rent
+// to the wallet. Accounts of configured mints and of tokens in open LP
+// positions are kept, as they are about to be paid into again.
+pub struct AccountSweeper {
+    rpc_client: Arc<RpcClient>,
+    sender: Arc<TransactionSender>,
+    wallet: Arc<Keypair>,
+    dex_clients: DexClients,
+    config: SweepConfig,
+    keep: HashSet<Pubkey>,
+}
+
+impl AccountSweeper {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        sender: Arc<TransactionSender>,
+        wallet: Arc<Keypair>,
+        dex_clients: DexClients,
+        config: SweepConfig,
+        keep: HashSet<Pubkey>,
+    ) -> Self {
+        Self {
+            rpc_client,
+            sender,
+            wallet,
+            dex_clients,
+            config,
+            keep,
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.sweep().await {
+                warn!("Sweep: failed to close empty token accounts: {:#}", e);
+            }
+        }
+    }
+
+    // Closes every empty account that is not kept and returns how many were
+    // closed. A batch that fails is logged and left for the next sweep.
+    pub async fn sweep(&self) -> Result<usize> {
+        let owner = self.wallet.pubkey();
+        let keep = self.kept_mints().await?;
+
+        let mut empty = Vec::new();
+        for program in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+            let accounts = self
+                .rpc_client
+                .get_token_accounts_by_owner(&owner, TokenAccountsFilter::ProgramId(program))
+                .await?;
+            for keyed in accounts {
+                let info = match &keyed.account.data {
+                    UiAccountData::Json(parsed) => &parsed.parsed["info"],
+                    _ => continue,
+                };
+                let mint = match info["mint"].as_str().and_then(|m| Pubkey::from_str(m).ok()) {
+                    Some(mint) => mint,
+                    None => continue,
+                };
+                if keep.contains(&mint) || !is_closable(info, &owner) {
+                    continue;
+                }
+                empty.push((
+                    Pubkey::from_str(&keyed.pubkey)?,
+                    program,
+                    keyed.account.lamports,
+                ));
+            }
+        }
+
+        let mut closed = 0;
+        let mut lamports = 0;
+        for batch in empty.chunks(self.config.max_accounts_per_transaction) {
+            let instructions: Vec<Instruction> = batch
+                .iter()
+                .map(|(account, program, _)| close_account(account, &owner, &owner, program))
+                .collect();
+            match send_transaction(&self.sender, &self.wallet, &instructions, &[], "sweep").await {
+                Ok(signature) => {
+                    debug!("Sweep: closed {} accounts: {}", batch.len(), signature);
+                    closed += batch.len();
+                    lamports += batch.iter().map(|(_, _, rent)| rent).sum::<u64>();
+                }
+                Err(e) => warn!("Sweep: failed to close {} accounts: {:#}", batch.len(), e),
+            }
+        }
+        if closed > 0 {
+            info!(
+                "Sweep: closed {} empty token accounts, reclaiming {} SOL",
+                closed,
+                lamports_to_sol(lamports)
+            );
+        }
+        Ok(closed)
+    }
+
+    // The configured mints plus both tokens of every open position. A venue
+    // that cannot list its positions stops the sweep rather than risk closing
+    // an account a withdrawal pays into.
+    async fn kept_mints(&self) -> Result<HashSet<Pubkey>> {
+        let mut keep = self.keep.clone();
+        for (venue, client) in self.dex_clients.iter() {
+            let positions = client
+                .get_positions()
+                .await
+                .map_err(|e| e.context(format!("failed to list {} positions", venue)))?;
+            for position in positions {
+                keep.insert(position.token_a);
+                keep.insert(position.token_b);
+            }
+        }
+        Ok(keep)
+    }
+}
+
+// Whether the owner can close a parsed token account right now: it is empty,
+// not frozen, has no other close authority and, under Token-2022, holds no
+// withheld transfer fees
+pub fn is_closable(info: &serde_json::Value, owner: &Pubkey) -> bool {
+    if info["tokenAmount"]["amount"].as_str() != Some("0")
+        || info["state"].as_str() != Some("initialized")
+    {
+        return false;
+    }
+    if let Some(authority) = info["closeAuthority"].as_str() {
+        if authority != owner.to_string() {
+            return false;
+        }
+    }
+    !info["extensions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|extension| extension["extension"] == "transferFeeAmount")
+        .any(|extension| extension["state"]["withheldAmount"].as_u64().unwrap_or(0) > 0)
+}
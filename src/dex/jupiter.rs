@@ -1,154 +1,423 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
+use base64::Engine;
+use reqwest::{Client, Response};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::address_lookup_table::state::AddressLookupTable;
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
 use std::str::FromStr;
+use std::sync::Arc;
 
-use super::DexClient;
+use super::mints::MintCache;
+use super::{
+    check_price_impact, check_trade_size, submit_transaction, to_base_units, DexClient, FeeAmounts,
+    HarvestReceipt, LpPosition, RebalanceReceipt, SwapEstimate, JUPITER,
+};
+use crate::config::{JupiterConfig, TradingParams};
+use crate::transactions::{PreparedTransaction, TransactionSender};
+use crate::units::{Price, Rounding, TokenAmount};
 
+// Swaps routed by the Jupiter v6 aggregator API. Jupiter returns the route's
+// instructions; the transaction is assembled, signed and sent here.
 pub struct JupiterClient {
-    rpc_client: RpcClient,
+    rpc_client: Arc<RpcClient>,
+    sender: Arc<TransactionSender>,
     http_client: Client,
     api_url: String,
+    wallet: Arc<Keypair>,
+    // Quotes are requested in base units, and unsafe Token-2022 mints refused
+    mints: MintCache,
+    trading: TradingParams,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct QuoteRequest {
-    input_mint: String,
-    output_mint: String,
-    amount: String,
-    slippage_bps: u32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct QuoteResponse {
-    input_amount: String,
-    output_amount: String,
-    price_impact_pct: f64,
+    in_amount: String,
+    out_amount: String,
+    other_amount_threshold: String,
+    slippage_bps: u16,
+    price_impact_pct: String,
     route_plan: Vec<RouteStep>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct RouteStep {
-    swap_info: SwapInfo,
-    percent: u32,
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteStep {
+    pub swap_info: SwapInfo,
+    pub percent: u8,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SwapInfo {
-    amm_key: String,
-    label: String,
-    input_mint: String,
-    output_mint: String,
-    in_amount: String,
-    out_amount: String,
-    fee_amount: String,
-    fee_mint: String,
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapInfo {
+    pub amm_key: String,
+    pub label: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    pub in_amount: String,
+    pub out_amount: String,
+    pub fee_amount: String,
+    pub fee_mint: String,
+}
+
+// An exact-input quote in base units. The response is kept as returned
+// because /swap-instructions takes it back verbatim.
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    // Least the swap may return once slippage is applied
+    pub other_amount_threshold: u64,
+    pub slippage_bps: u16,
+    pub price_impact_pct: f64,
+    pub route_plan: Vec<RouteStep>,
+    raw: serde_json::Value,
+}
+
+impl Quote {
+    pub fn from_response(raw: serde_json::Value) -> Result<Self> {
+        let response: QuoteResponse = serde_json::from_value(raw.clone())?;
+        Ok(Self {
+            in_amount: response.in_amount.parse()?,
+            out_amount: response.out_amount.parse()?,
+            other_amount_threshold: response.other_amount_threshold.parse()?,
+            slippage_bps: response.slippage_bps,
+            price_impact_pct: response.price_impact_pct.parse()?,
+            route_plan: response.route_plan,
+            raw,
+        })
+    }
+
+    // Tightens the quote's slippage so the program rejects any fill below
+    // `min_amount_out`. Fails if the quote itself already falls short.
+    pub fn with_min_amount_out(mut self, min_amount_out: u64) -> Result<Self> {
+        if self.out_amount < min_amount_out {
+            return Err(anyhow!(
+                "Jupiter quote of {} is below the minimum output {}",
+                self.out_amount,
+                min_amount_out
+            ));
+        }
+        if self.other_amount_threshold >= min_amount_out {
+            return Ok(self);
+        }
+
+        // The program derives the threshold from the quoted output and the slippage
+        let slippage_bps =
+            ((self.out_amount - min_amount_out) as u128 * 10_000 / self.out_amount as u128) as u16;
+        let threshold = (self.out_amount as u128 * (10_000 - slippage_bps) as u128 / 10_000) as u64;
+        self.slippage_bps = slippage_bps;
+        self.other_amount_threshold = threshold;
+        self.raw["slippageBps"] = json!(slippage_bps);
+        self.raw["otherAmountThreshold"] = json!(threshold.to_string());
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwapInstructionsResponse {
+    #[serde(default)]
+    setup_instructions: Vec<ApiInstruction>,
+    swap_instruction: ApiInstruction,
+    #[serde(default)]
+    cleanup_instruction: Option<ApiInstruction>,
+    #[serde(default)]
+    other_instructions: Vec<ApiInstruction>,
+    #[serde(default)]
+    address_lookup_table_addresses: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiInstruction {
+    program_id: String,
+    accounts: Vec<ApiAccountMeta>,
+    // Base64
+    data: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiAccountMeta {
+    pubkey: String,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+impl TryFrom<ApiInstruction> for Instruction {
+    type Error = anyhow::Error;
+
+    fn try_from(instruction: ApiInstruction) -> Result<Self> {
+        let accounts = instruction
+            .accounts
+            .into_iter()
+            .map(|meta| {
+                Ok(AccountMeta {
+                    pubkey: Pubkey::from_str(&meta.pubkey)?,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Instruction {
+            program_id: Pubkey::from_str(&instruction.program_id)?,
+            accounts,
+            data: base64::engine::general_purpose::STANDARD.decode(&instruction.data)?,
+        })
+    }
+}
+
+// Jupiter reports failures as JSON with an `error` field and a non-2xx status
+async fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Jupiter API returned {}: {}", status, body));
+    }
+    Ok(response.json::<T>().await?)
 }
 
 impl JupiterClient {
-    pub fn new(rpc_client: RpcClient, api_url: String) -> Result<Self> {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        sender: Arc<TransactionSender>,
+        config: &JupiterConfig,
+        trading: TradingParams,
+        wallet: Arc<Keypair>,
+    ) -> Result<Self> {
         Ok(Self {
             rpc_client,
+            sender,
             http_client: Client::new(),
-            api_url,
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            wallet,
+            mints: MintCache::new(),
+            trading,
         })
     }
 
-    async fn get_quote(
+    // Read for each quote, so reloaded values apply to the next one
+    fn slippage_bps(&self) -> u16 {
+        (self.trading.borrow().slippage_tolerance * 10_000.0).round() as u16
+    }
+
+    // Best route for swapping exactly `amount_in` base units of `token_in`
+    pub async fn get_quote(
         &self,
         token_in: &Pubkey,
         token_out: &Pubkey,
-        amount_in: f64,
-        slippage_bps: u32,
-    ) -> Result<QuoteResponse> {
-        let request = QuoteRequest {
-            input_mint: token_in.to_string(),
-            output_mint: token_out.to_string(),
-            amount: amount_in.to_string(),
-            slippage_bps,
-        };
+        amount_in: u64,
+        slippage_bps: u16,
+    ) -> Result<Quote> {
+        let response = self
+            .http_client
+            .get(format!("{}/quote", self.api_url))
+            .query(&[
+                ("inputMint", token_in.to_string()),
+                ("outputMint", token_out.to_string()),
+                ("amount", amount_in.to_string()),
+                ("slippageBps", slippage_bps.to_string()),
+                ("swapMode", "ExactIn".to_string()),
+            ])
+            .send()
+            .await?;
+        Quote::from_response(parse_response(response).await?)
+    }
 
+    // Builds and signs a v0 transaction for the quote from the instructions
+    // Jupiter returns, resolving the route's address lookup tables
+    pub async fn swap_transaction(&self, quote: &Quote) -> Result<PreparedTransaction> {
         let response = self
             .http_client
-            .post(format!("{}/quote", self.api_url))
-            .json(&request)
+            .post(format!("{}/swap-instructions", self.api_url))
+            .json(&json!({
+                "quoteResponse": quote.raw,
+                "userPublicKey": self.wallet.pubkey().to_string(),
+                "wrapAndUnwrapSol": true,
+            }))
             .send()
-            .await?
-            .json::<QuoteResponse>()
             .await?;
+        let response: SwapInstructionsResponse = parse_response(response).await?;
+
+        // Jupiter's compute budget instructions are left out, so the shared
+        // sender sets the price and limit as it does for every other venue
+        let mut instructions = Vec::new();
+        for instruction in response
+            .setup_instructions
+            .into_iter()
+            .chain(std::iter::once(response.swap_instruction))
+            .chain(response.cleanup_instruction)
+            .chain(response.other_instructions)
+        {
+            instructions.push(Instruction::try_from(instruction)?);
+        }
 
-        Ok(response)
+        let lookup_tables = self
+            .lookup_tables(&response.address_lookup_table_addresses)
+            .await?;
+        self.sender
+            .prepare(&self.wallet, &instructions, &lookup_tables, &[])
+            .await
     }
 
-    async fn get_swap_transaction(
-        &self,
-        quote_response: &QuoteResponse,
-        user_public_key: &Pubkey,
-    ) -> Result<solana_sdk::transaction::Transaction> {
-        // Implement transaction creation from quote
-        // This would involve:
-        // 1. Converting the quote response into a Solana transaction
-        // 2. Adding necessary instructions for the swap
-        todo!("Implement swap transaction creation")
+    // Jupiter builds the route's Token-2022 accounts itself, but cannot make
+    // a non-transferable or interest-bearing token safe to trade
+    async fn tradable_decimals(&self, token_in: &Pubkey, token_out: &Pubkey) -> Result<Vec<u8>> {
+        let mints = self
+            .mints
+            .info(&self.rpc_client, &[*token_in, *token_out])
+            .await?;
+        for mint in &mints {
+            mint.check_tradable()?;
+        }
+        Ok(mints.iter().map(|mint| mint.decimals).collect())
+    }
+
+    async fn lookup_tables(&self, addresses: &[String]) -> Result<Vec<AddressLookupTableAccount>> {
+        let keys = addresses
+            .iter()
+            .map(|address| Pubkey::from_str(address))
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let accounts = self.rpc_client.get_multiple_accounts(&keys).await?;
+        keys.into_iter()
+            .zip(accounts)
+            .map(|(key, account)| {
+                let account =
+                    account.ok_or_else(|| anyhow!("address lookup table {} not found", key))?;
+                let table = AddressLookupTable::deserialize(&account.data)
+                    .map_err(|e| anyhow!("invalid address lookup table {}: {}", key, e))?;
+                Ok(AddressLookupTableAccount {
+                    key,
+                    addresses: table.addresses.to_vec(),
+                })
+            })
+            .collect()
     }
 }
 
 #[async_trait]
 impl DexClient for JupiterClient {
-    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<f64> {
-        let quote = self.get_quote(token_a, token_b, 1.0, 100).await?;
-        Ok(quote.output_amount.parse::<f64>()?)
+    // Output of a one token quote, so the price includes route fees
+    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Price> {
+        let decimals = self.tradable_decimals(token_a, token_b).await?;
+        let quote = self
+            .get_quote(
+                token_a,
+                token_b,
+                10u64.pow(decimals[0] as u32),
+                self.slippage_bps(),
+            )
+            .await?;
+        Price::from_amounts(
+            TokenAmount::new(quote.in_amount, decimals[0]),
+            TokenAmount::new(quote.out_amount, decimals[1]),
+            Rounding::Down,
+        )
     }
 
     async fn create_lp_position(
         &self,
-        token_a: &Pubkey,
-        token_b: &Pubkey,
-        amount_a: f64,
-        amount_b: f64,
-        min_price: f64,
-        max_price: f64,
+        _token_a: &Pubkey,
+        _token_b: &Pubkey,
+        _amount_a: TokenAmount,
+        _amount_b: TokenAmount,
+        _min_price: Price,
+        _max_price: Price,
     ) -> Result<String> {
-        // Jupiter doesn't support LP positions directly
-        // This would need to be implemented using other DEXs
-        todo!("LP positions not supported by Jupiter")
+        Err(anyhow!("Jupiter only swaps; open positions on a venue"))
     }
 
     async fn rebalance_position(
         &self,
-        position_id: &str,
-        new_min_price: f64,
-        new_max_price: f64,
-    ) -> Result<()> {
-        // Jupiter doesn't support LP positions directly
-        todo!("LP positions not supported by Jupiter")
+        _position_id: &str,
+        _new_min_price: Price,
+        _new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        Err(anyhow!("Jupiter does not hold LP positions"))
     }
 
-    async fn harvest_fees(&self, position_id: &str) -> Result<()> {
-        // Jupiter doesn't support LP positions directly
-        todo!("LP positions not supported by Jupiter")
+    async fn harvest_fees(&self, _position_id: &str) -> Result<HarvestReceipt> {
+        Err(anyhow!("Jupiter does not hold LP positions"))
     }
 
-    async fn execute_swap(
+    async fn get_unclaimed_fees(&self, _position_id: &str) -> Result<FeeAmounts> {
+        Err(anyhow!("Jupiter does not hold LP positions"))
+    }
+
+    async fn increase_liquidity(
+        &self,
+        _position_id: &str,
+        _amount_a: TokenAmount,
+        _amount_b: TokenAmount,
+    ) -> Result<String> {
+        Err(anyhow!("Jupiter does not hold LP positions"))
+    }
+
+    async fn get_positions(&self) -> Result<Vec<LpPosition>> {
+        // Jupiter doesn't support LP positions, so there is never anything to list
+        Ok(Vec::new())
+    }
+
+    async fn quote_swap(
         &self,
         token_in: &Pubkey,
         token_out: &Pubkey,
-        amount_in: f64,
-        min_amount_out: f64,
-    ) -> Result<String> {
-        let quote = self.get_quote(token_in, token_out, amount_in, 100).await?;
-        
-        // Create and execute swap transaction
-        let transaction = self
-            .get_swap_transaction(&quote, &self.rpc_client.payer()?)
+        amount_in: TokenAmount,
+    ) -> Result<SwapEstimate> {
+        let decimals = self.tradable_decimals(token_in, token_out).await?;
+        let quote = self
+            .get_quote(
+                token_in,
+                token_out,
+                to_base_units(amount_in, decimals[0])?,
+                self.slippage_bps(),
+            )
             .await?;
+        Ok(SwapEstimate {
+            amount_in: TokenAmount::new(quote.in_amount, decimals[0]),
+            amount_out: TokenAmount::new(quote.out_amount, decimals[1]),
+            price_impact: quote.price_impact_pct,
+        })
+    }
 
-        // Sign and send transaction
-        // Return transaction signature
+    async fn execute_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+        min_amount_out: TokenAmount,
+    ) -> Result<String> {
+        check_trade_size(&self.trading.borrow(), amount_in)?;
+        let decimals = self.tradable_decimals(token_in, token_out).await?;
+        let quote = self
+            .get_quote(
+                token_in,
+                token_out,
+                to_base_units(amount_in, decimals[0])?,
+                self.slippage_bps(),
+            )
+            .await?
+            .with_min_amount_out(min_amount_out.base_units_at(decimals[1], Rounding::Up)?)?;
+        // Routes span pools outside the pool cache, so the route's own
+        // impact is checked
+        check_price_impact(
+            quote.price_impact_pct,
+            self.trading.borrow().price_impact_threshold,
+        )?;
 
-        todo!("Implement swap execution")
+        let transaction = self.swap_transaction(&quote).await?;
+        submit_transaction(&self.sender, &transaction, JUPITER).await
     }
-} 
\ No newline at end of file
+}
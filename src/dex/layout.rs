@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+
+// Little-endian readers for fixed-offset on-chain account layouts
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "account data too short: need {} bytes at offset {}, have {}",
+                N,
+                offset,
+                data.len()
+            )
+        })
+}
+
+pub fn read_u8(data: &[u8], offset: usize) -> Result<u8> {
+    Ok(read_bytes::<1>(data, offset)?[0])
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+pub fn read_i32(data: &[u8], offset: usize) -> Result<i32> {
+    read_bytes(data, offset).map(i32::from_le_bytes)
+}
+
+pub fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+pub fn read_i64(data: &[u8], offset: usize) -> Result<i64> {
+    read_bytes(data, offset).map(i64::from_le_bytes)
+}
+
+pub fn read_u128(data: &[u8], offset: usize) -> Result<u128> {
+    read_bytes(data, offset).map(u128::from_le_bytes)
+}
+
+pub fn read_i128(data: &[u8], offset: usize) -> Result<i128> {
+    read_bytes(data, offset).map(i128::from_le_bytes)
+}
+
+pub fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    read_bytes(data, offset).map(Pubkey::new_from_array)
+}
+
+// SPL token (and Token-2022) accounts share the base layout: mint, owner, amount
+pub fn token_account_amount(data: &[u8]) -> Result<u64> {
+    read_u64(data, 64)
+}
+
+pub fn mint_decimals(data: &[u8]) -> Result<u8> {
+    read_u8(data, 44)
+}
+
+// First 8 bytes of sha256 over an Anchor preimage such as "account:PoolState"
+// or "global:open_position_v2"
+pub fn anchor_discriminator(preimage: &str) -> [u8; 8] {
+    let hash = solana_sdk::hash::hash(preimage.as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+pub fn check_anchor_account(data: &[u8], name: &str) -> Result<()> {
+    if data.get(..8) != Some(&anchor_discriminator(&format!("account:{}", name))[..]) {
+        return Err(anyhow!("account is not a {}", name));
+    }
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use anyhow::{anyhow, Result};
+
+use super::state::{Bin, BinArray, LbPair, Position, MAX_BINS_PER_POSITION};
+use crate::config::LiquidityShape;
+
+// Bin math for DLMM pairs. Every bin holds liquidity at a single price,
+// (1 + bin_step / 10000) ^ bin_id in raw (base unit) terms; bins above the
+// active one hold only token x and bins below it only token y.
+
+pub const MIN_BIN_ID: i32 = -443636;
+pub const MAX_BIN_ID: i32 = 443636;
+
+const BASIS_POINT_MAX: u32 = 10_000;
+const Q64: f64 = 18_446_744_073_709_551_616.0;
+
+// Token y base units per token x base unit in the bin
+pub fn bin_price(bin_id: i32, bin_step: u16) -> f64 {
+    (1.0 + bin_step as f64 / BASIS_POINT_MAX as f64).powi(bin_id)
+}
+
+// UI price (token y per token x) of the bin
+pub fn bin_id_to_price(bin_id: i32, bin_step: u16, decimals_x: u8, decimals_y: u8) -> f64 {
+    bin_price(bin_id, bin_step) * 10f64.powi(decimals_x as i32 - decimals_y as i32)
+}
+
+// The bin whose price is at or just below the UI price
+pub fn price_to_bin_id(price: f64, bin_step: u16, decimals_x: u8, decimals_y: u8) -> Result<i32> {
+    if !price.is_finite() || price <= 0.0 {
+        return Err(anyhow!("price must be positive, got {}", price));
+    }
+    let raw = price * 10f64.powi(decimals_y as i32 - decimals_x as i32);
+    let base = 1.0 + bin_step as f64 / BASIS_POINT_MAX as f64;
+    // Nudge up so prices that sit exactly on a bin are not floored to the one below
+    let bin_id = (raw.ln() / base.ln() + 1e-9).floor();
+    if bin_id < MIN_BIN_ID as f64 || bin_id > MAX_BIN_ID as f64 {
+        return Err(anyhow!(
+            "price {} is outside the supported bin range",
+            price
+        ));
+    }
+    Ok(bin_id as i32)
+}
+
+// Bins covering `min_price..max_price`, limited to what one position can hold
+pub fn bin_range(
+    min_price: f64,
+    max_price: f64,
+    bin_step: u16,
+    decimals_x: u8,
+    decimals_y: u8,
+) -> Result<(i32, i32)> {
+    if min_price >= max_price {
+        return Err(anyhow!("min_price must be below max_price"));
+    }
+    let lower = price_to_bin_id(min_price, bin_step, decimals_x, decimals_y)?;
+    let upper = price_to_bin_id(max_price, bin_step, decimals_x, decimals_y)?;
+    let width = upper - lower + 1;
+    if width > MAX_BINS_PER_POSITION {
+        return Err(anyhow!(
+            "range {}..{} spans {} bins; a DLMM position holds at most {}",
+            min_price,
+            max_price,
+            width,
+            MAX_BINS_PER_POSITION
+        ));
+    }
+    Ok((lower, upper))
+}
+
+// Share of each token a bin receives, in basis points of that token's deposit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinDistribution {
+    pub bin_id: i32,
+    pub x_bps: u16,
+    pub y_bps: u16,
+}
+
+// Relative liquidity value the shape puts in each bin of the range, by
+// distance from the active bin
+fn shape_weights(
+    shape: LiquidityShape,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+    active_id: i32,
+) -> Vec<u64> {
+    let max_distance = (active_id - lower_bin_id)
+        .unsigned_abs()
+        .max((upper_bin_id - active_id).unsigned_abs()) as u64;
+    (lower_bin_id..=upper_bin_id)
+        .map(|bin_id| {
+            let distance = (bin_id - active_id).unsigned_abs() as u64;
+            match shape {
+                LiquidityShape::Spot => 1,
+                LiquidityShape::Curve => max_distance + 1 - distance,
+                LiquidityShape::BidAsk => distance + 1,
+            }
+        })
+        .collect()
+}
+
+// Spreads token x over the bins at and above the active bin and token y over
+// the bins at and below it, following the shape's weights
+pub fn distribution(
+    shape: LiquidityShape,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+    active_id: i32,
+) -> Vec<BinDistribution> {
+    let weights = shape_weights(shape, lower_bin_id, upper_bin_id, active_id);
+    let bins: Vec<i32> = (lower_bin_id..=upper_bin_id).collect();
+    let x_bps = side_bps(&bins, &weights, |bin_id| bin_id >= active_id);
+    let y_bps = side_bps(&bins, &weights, |bin_id| bin_id <= active_id);
+    bins.into_iter()
+        .zip(x_bps.into_iter().zip(y_bps))
+        .map(|(bin_id, (x_bps, y_bps))| BinDistribution {
+            bin_id,
+            x_bps,
+            y_bps,
+        })
+        .collect()
+}
+
+// Share of a deposit's value that should be token x for `distribution` to
+// give every bin the value its weight asks for
+pub fn value_share_x(
+    shape: LiquidityShape,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+    active_id: i32,
+) -> f64 {
+    let weights = shape_weights(shape, lower_bin_id, upper_bin_id, active_id);
+    let (mut x, mut y) = (0, 0);
+    for (bin_id, weight) in (lower_bin_id..=upper_bin_id).zip(weights) {
+        if bin_id >= active_id {
+            x += weight;
+        }
+        if bin_id <= active_id {
+            y += weight;
+        }
+    }
+    x as f64 / (x + y) as f64
+}
+
+// Basis points per bin for the bins of one token, summing to exactly 10000
+fn side_bps(bins: &[i32], weights: &[u64], takes_token: impl Fn(i32) -> bool) -> Vec<u16> {
+    let weights: Vec<u64> = bins
+        .iter()
+        .zip(weights)
+        .map(|(bin_id, weight)| if takes_token(*bin_id) { *weight } else { 0 })
+        .collect();
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return vec![0; bins.len()];
+    }
+
+    let mut bps: Vec<u16> = weights
+        .iter()
+        .map(|w| (w * BASIS_POINT_MAX as u64 / total) as u16)
+        .collect();
+    // Rounding leftovers go to the heaviest bin so the side sums to 100%
+    let assigned: u32 = bps.iter().map(|b| *b as u32).sum();
+    let heaviest = (0..weights.len())
+        .max_by_key(|i| (weights[*i], std::cmp::Reverse(*i)))
+        .unwrap_or(0);
+    bps[heaviest] += (BASIS_POINT_MAX - assigned) as u16;
+    bps
+}
+
+// Expected result of an exact-input swap, in base units
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapQuote {
+    pub swap_for_y: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+    pub end_bin_id: i32,
+    // Shortfall against the active bin price, fees included
+    pub price_impact: f64,
+}
+
+// Walks bins away from the active one, emptying each in turn, until the input
+// is used up. Fails if the input would go past the last loaded bin.
+pub fn quote_exact_in(
+    pair: &LbPair,
+    bin_arrays: &[BinArray],
+    amount_in: u64,
+    swap_for_y: bool,
+) -> Result<SwapQuote> {
+    let fee_rate = pair.fee_rate();
+    let mut bin_id = pair.active_id;
+    let mut remaining = amount_in as f64;
+    let mut amount_out = 0.0;
+    let mut fees = 0.0;
+
+    // Less than a base unit left over is rounding
+    while remaining >= 1.0 {
+        let bin = bin_arrays
+            .iter()
+            .find_map(|array| array.bin(bin_id))
+            .ok_or_else(|| {
+                anyhow!(
+                    "swap of {} exceeds the liquidity in the loaded bin arrays",
+                    amount_in
+                )
+            })?;
+        let price = bin_price(bin_id, pair.bin_step);
+        let (available, max_in) = if swap_for_y {
+            (bin.amount_y as f64, bin.amount_y as f64 / price)
+        } else {
+            (bin.amount_x as f64, bin.amount_x as f64 * price)
+        };
+
+        let net = remaining * (1.0 - fee_rate);
+        if net <= max_in {
+            amount_out += if swap_for_y { net * price } else { net / price };
+            fees += remaining - net;
+            break;
+        }
+        if available > 0.0 {
+            let gross = max_in / (1.0 - fee_rate);
+            amount_out += available;
+            fees += gross - max_in;
+            remaining -= gross;
+        }
+        bin_id += if swap_for_y { -1 } else { 1 };
+    }
+
+    let spot = bin_price(pair.active_id, pair.bin_step);
+    let spot_out = if swap_for_y {
+        amount_in as f64 * spot
+    } else {
+        amount_in as f64 / spot
+    };
+    Ok(SwapQuote {
+        swap_for_y,
+        amount_in,
+        amount_out: amount_out as u64,
+        fee_amount: fees.ceil() as u64,
+        end_bin_id: bin_id,
+        price_impact: if spot_out > 0.0 {
+            1.0 - amount_out / spot_out
+        } else {
+            0.0
+        },
+    })
+}
+
+// Base unit amounts of token x and token y the position's shares are worth
+pub fn position_amounts(position: &Position, bin_arrays: &[BinArray]) -> Result<(u64, u64)> {
+    let mut amount_x = 0.0;
+    let mut amount_y = 0.0;
+    for (bin_id, share) in position.bin_ids().zip(&position.liquidity_shares) {
+        if *share == 0 {
+            continue;
+        }
+        let bin = find_bin(bin_arrays, bin_id)?;
+        if bin.liquidity_supply == 0 {
+            continue;
+        }
+        let fraction = *share as f64 / bin.liquidity_supply as f64;
+        amount_x += bin.amount_x as f64 * fraction;
+        amount_y += bin.amount_y as f64 * fraction;
+    }
+    Ok((amount_x as u64, amount_y as u64))
+}
+
+// Fees owed to the position: what was already settled per bin plus what the
+// bins have accumulated per share since
+pub fn position_fees(position: &Position, bin_arrays: &[BinArray]) -> Result<(u64, u64)> {
+    let mut fee_x = 0.0;
+    let mut fee_y = 0.0;
+    for ((bin_id, share), fee) in position
+        .bin_ids()
+        .zip(&position.liquidity_shares)
+        .zip(&position.fees)
+    {
+        fee_x += fee.fee_x_pending as f64;
+        fee_y += fee.fee_y_pending as f64;
+        if *share == 0 {
+            continue;
+        }
+        let bin = find_bin(bin_arrays, bin_id)?;
+        // Shares and per token accumulators are both scaled by 2^64
+        let share = *share as f64 / Q64;
+        fee_x += share
+            * bin
+                .fee_amount_x_per_token_stored
+                .wrapping_sub(fee.fee_x_per_token_complete) as f64
+            / Q64;
+        fee_y += share
+            * bin
+                .fee_amount_y_per_token_stored
+                .wrapping_sub(fee.fee_y_per_token_complete) as f64
+            / Q64;
+    }
+    Ok((fee_x as u64, fee_y as u64))
+}
+
+fn find_bin(bin_arrays: &[BinArray], bin_id: i32) -> Result<&Bin> {
+    bin_arrays
+        .iter()
+        .find_map(|array| array.bin(bin_id))
+        .ok_or_else(|| anyhow!("bin array holding bin {} not loaded", bin_id))
+}
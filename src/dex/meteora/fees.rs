@@ -0,0 +1,60 @@
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use std::str::FromStr;
+
+use super::{bins, instructions, MeteoraClient};
+use crate::dex::programs::TOKEN_PROGRAM_ID;
+use crate::dex::{FeeAmounts, HarvestReceipt, SolBalance, WalletAccounts};
+use crate::units::TokenAmount;
+
+impl MeteoraClient {
+    // Fees settled on the position plus what its bins have accrued since
+    pub(super) async fn dlmm_unclaimed_fees(&self, position_id: &str) -> Result<FeeAmounts> {
+        let (position, pair) = self.load_position(position_id).await?;
+        let bin_arrays = self.position_bin_arrays(&pair, &position).await?;
+        let (fee_x, fee_y) = bins::position_fees(&position, &bin_arrays)?;
+
+        Ok(FeeAmounts {
+            token_a: pair.lb_pair.token_x_mint,
+            token_b: pair.lb_pair.token_y_mint,
+            amount_a: TokenAmount::new(fee_x, pair.decimals_x),
+            amount_b: TokenAmount::new(fee_y, pair.decimals_y),
+        })
+    }
+
+    // Claims the position's swap fees, leaving its liquidity untouched
+    pub(super) async fn harvest_dlmm_fees(&self, position_id: &str) -> Result<HarvestReceipt> {
+        let (position, pair) = self.load_position(position_id).await?;
+        let mints = [pair.lb_pair.token_x_mint, pair.lb_pair.token_y_mint];
+        // Balances are compared before and after, so SOL stays wrapped
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), SolBalance::Wrapped);
+        accounts.receive(&mints[0], &TOKEN_PROGRAM_ID);
+        accounts.receive(&mints[1], &TOKEN_PROGRAM_ID);
+        let ixs = accounts.build(vec![instructions::claim_fee(
+            &self.program_id,
+            &self.wallet.pubkey(),
+            &pair.address,
+            &pair.lb_pair,
+            &Pubkey::from_str(position_id)?,
+            position.lower_bin_id,
+            position.upper_bin_id,
+        )]);
+
+        let before = self.token_balances(&mints).await?;
+        let signature = self.send_transaction(&ixs, &[]).await?;
+        let after = self.token_balances(&mints).await?;
+
+        Ok(HarvestReceipt {
+            position_id: position_id.to_string(),
+            collected: FeeAmounts {
+                token_a: mints[0],
+                token_b: mints[1],
+                amount_a: TokenAmount::new(after[0].saturating_sub(before[0]), pair.decimals_x),
+                amount_b: TokenAmount::new(after[1].saturating_sub(before[1]), pair.decimals_y),
+            },
+            rewards: Vec::new(),
+            signature,
+        })
+    }
+}
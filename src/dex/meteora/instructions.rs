@@ -0,0 +1,424 @@
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_program, sysvar};
+
+use super::state::{bin_array_index, LbPair};
+use crate::dex::layout::anchor_discriminator;
+use crate::dex::programs::{associated_token_address, MEMO_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+// Liquidity for one bin, in basis points of the deposited amount of each token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinLiquidityDistribution {
+    pub bin_id: i32,
+    pub distribution_x: u16,
+    pub distribution_y: u16,
+}
+
+// Basis points of a bin's liquidity to withdraw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinLiquidityReduction {
+    pub bin_id: i32,
+    pub bps_to_remove: u16,
+}
+
+pub fn bin_array_address(program_id: &Pubkey, lb_pair: &Pubkey, index: i64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"bin_array", lb_pair.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+    .0
+}
+
+// Anchor event CPI signer every instruction below passes along
+pub fn event_authority(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], program_id).0
+}
+
+// Position instructions take two consecutive bin arrays, even when the
+// position fits inside the lower one
+pub fn position_bin_arrays(lower_bin_id: i32, upper_bin_id: i32) -> (i64, i64) {
+    let lower = bin_array_index(lower_bin_id);
+    (lower, bin_array_index(upper_bin_id).max(lower + 1))
+}
+
+fn event_accounts(program_id: &Pubkey) -> [AccountMeta; 2] {
+    [
+        AccountMeta::new_readonly(event_authority(program_id), false),
+        AccountMeta::new_readonly(*program_id, false),
+    ]
+}
+
+pub fn initialize_bin_array(
+    program_id: &Pubkey,
+    funder: &Pubkey,
+    lb_pair_id: &Pubkey,
+    index: i64,
+) -> Instruction {
+    let mut data = anchor_discriminator("global:initialize_bin_array").to_vec();
+    data.extend_from_slice(&index.to_le_bytes());
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*lb_pair_id, false),
+            AccountMeta::new(bin_array_address(program_id, lb_pair_id, index), false),
+            AccountMeta::new(*funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+// Creates an empty position over `width` bins starting at `lower_bin_id`. The
+// position account is a fresh keypair that must sign.
+pub fn initialize_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    position: &Pubkey,
+    lower_bin_id: i32,
+    width: i32,
+) -> Instruction {
+    let mut data = anchor_discriminator("global:initialize_position").to_vec();
+    data.extend_from_slice(&lower_bin_id.to_le_bytes());
+    data.extend_from_slice(&width.to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::new(*owner, true),
+        AccountMeta::new(*position, true),
+        AccountMeta::new_readonly(*lb_pair_id, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    accounts.extend(event_accounts(program_id));
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+// Add and remove share their accounts
+fn modify_liquidity_accounts(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    lb_pair: &LbPair,
+    position: &Pubkey,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+) -> Vec<AccountMeta> {
+    let (lower_index, upper_index) = position_bin_arrays(lower_bin_id, upper_bin_id);
+    let mut accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(*lb_pair_id, false),
+        // No bitmap extension; Anchor reads the program id as an absent optional account
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new(
+            associated_token_address(owner, &lb_pair.token_x_mint, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(
+            associated_token_address(owner, &lb_pair.token_y_mint, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(lb_pair.reserve_x, false),
+        AccountMeta::new(lb_pair.reserve_y, false),
+        AccountMeta::new_readonly(lb_pair.token_x_mint, false),
+        AccountMeta::new_readonly(lb_pair.token_y_mint, false),
+        AccountMeta::new(
+            bin_array_address(program_id, lb_pair_id, lower_index),
+            false,
+        ),
+        AccountMeta::new(
+            bin_array_address(program_id, lb_pair_id, upper_index),
+            false,
+        ),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    accounts.extend(event_accounts(program_id));
+    accounts
+}
+
+// Deposits up to `amount_x`/`amount_y`, spread over the bins by `distribution`
+#[allow(clippy::too_many_arguments)]
+pub fn add_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    lb_pair: &LbPair,
+    position: &Pubkey,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+    amount_x: u64,
+    amount_y: u64,
+    distribution: &[BinLiquidityDistribution],
+) -> Instruction {
+    let mut data = anchor_discriminator("global:add_liquidity").to_vec();
+    data.extend_from_slice(&amount_x.to_le_bytes());
+    data.extend_from_slice(&amount_y.to_le_bytes());
+    data.extend_from_slice(&(distribution.len() as u32).to_le_bytes());
+    for bin in distribution {
+        data.extend_from_slice(&bin.bin_id.to_le_bytes());
+        data.extend_from_slice(&bin.distribution_x.to_le_bytes());
+        data.extend_from_slice(&bin.distribution_y.to_le_bytes());
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts: modify_liquidity_accounts(
+            program_id,
+            owner,
+            lb_pair_id,
+            lb_pair,
+            position,
+            lower_bin_id,
+            upper_bin_id,
+        ),
+        data,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn remove_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    lb_pair: &LbPair,
+    position: &Pubkey,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+    reductions: &[BinLiquidityReduction],
+) -> Instruction {
+    let mut data = anchor_discriminator("global:remove_liquidity").to_vec();
+    data.extend_from_slice(&(reductions.len() as u32).to_le_bytes());
+    for bin in reductions {
+        data.extend_from_slice(&bin.bin_id.to_le_bytes());
+        data.extend_from_slice(&bin.bps_to_remove.to_le_bytes());
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts: modify_liquidity_accounts(
+            program_id,
+            owner,
+            lb_pair_id,
+            lb_pair,
+            position,
+            lower_bin_id,
+            upper_bin_id,
+        ),
+        data,
+    }
+}
+
+pub fn claim_fee(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    lb_pair: &LbPair,
+    position: &Pubkey,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+) -> Instruction {
+    let (lower_index, upper_index) = position_bin_arrays(lower_bin_id, upper_bin_id);
+    let mut accounts = vec![
+        AccountMeta::new(*lb_pair_id, false),
+        AccountMeta::new(*position, false),
+        AccountMeta::new(
+            bin_array_address(program_id, lb_pair_id, lower_index),
+            false,
+        ),
+        AccountMeta::new(
+            bin_array_address(program_id, lb_pair_id, upper_index),
+            false,
+        ),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(lb_pair.reserve_x, false),
+        AccountMeta::new(lb_pair.reserve_y, false),
+        AccountMeta::new(
+            associated_token_address(owner, &lb_pair.token_x_mint, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(
+            associated_token_address(owner, &lb_pair.token_y_mint, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new_readonly(lb_pair.token_x_mint, false),
+        AccountMeta::new_readonly(lb_pair.token_y_mint, false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    accounts.extend(event_accounts(program_id));
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: anchor_discriminator("global:claim_fee").to_vec(),
+    }
+}
+
+// Closes an emptied position and returns its rent to the owner
+pub fn close_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    position: &Pubkey,
+    lower_bin_id: i32,
+    upper_bin_id: i32,
+) -> Instruction {
+    let (lower_index, upper_index) = position_bin_arrays(lower_bin_id, upper_bin_id);
+    let mut accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(*lb_pair_id, false),
+        AccountMeta::new(
+            bin_array_address(program_id, lb_pair_id, lower_index),
+            false,
+        ),
+        AccountMeta::new(
+            bin_array_address(program_id, lb_pair_id, upper_index),
+            false,
+        ),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*owner, false),
+    ];
+    accounts.extend(event_accounts(program_id));
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: anchor_discriminator("global:close_position").to_vec(),
+    }
+}
+
+// Exact-input swap. The bin arrays the swap may cross go last, nearest first.
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    lb_pair: &LbPair,
+    swap_for_y: bool,
+    amount_in: u64,
+    min_amount_out: u64,
+    bin_arrays: &[Pubkey],
+) -> Instruction {
+    let (mint_in, mint_out) = if swap_for_y {
+        (lb_pair.token_x_mint, lb_pair.token_y_mint)
+    } else {
+        (lb_pair.token_y_mint, lb_pair.token_x_mint)
+    };
+
+    let mut data = anchor_discriminator("global:swap").to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::new(*lb_pair_id, false),
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new(lb_pair.reserve_x, false),
+        AccountMeta::new(lb_pair.reserve_y, false),
+        AccountMeta::new(
+            associated_token_address(owner, &mint_in, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(
+            associated_token_address(owner, &mint_out, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new_readonly(lb_pair.token_x_mint, false),
+        AccountMeta::new_readonly(lb_pair.token_y_mint, false),
+        AccountMeta::new(lb_pair.oracle, false),
+        // No host fee account
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    accounts.extend(event_accounts(program_id));
+    accounts.extend(
+        bin_arrays
+            .iter()
+            .map(|address| AccountMeta::new(*address, false)),
+    );
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+// Exact-input swap for pairs with a Token-2022 mint: each side names its own
+// token program, and the transfer hook accounts of token x, then token y, go
+// before the bin arrays
+#[allow(clippy::too_many_arguments)]
+pub fn swap2(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lb_pair_id: &Pubkey,
+    lb_pair: &LbPair,
+    token_programs: [Pubkey; 2],
+    swap_for_y: bool,
+    amount_in: u64,
+    min_amount_out: u64,
+    bin_arrays: &[Pubkey],
+    hook_accounts: [Vec<AccountMeta>; 2],
+) -> Instruction {
+    let [token_x_program, token_y_program] = token_programs;
+    let (account_in, account_out) = {
+        let account_x = associated_token_address(owner, &lb_pair.token_x_mint, &token_x_program);
+        let account_y = associated_token_address(owner, &lb_pair.token_y_mint, &token_y_program);
+        if swap_for_y {
+            (account_x, account_y)
+        } else {
+            (account_y, account_x)
+        }
+    };
+
+    let mut data = anchor_discriminator("global:swap2").to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    // RemainingAccountsInfo: slices of (TransferHookX = 0 or TransferHookY =
+    // 1, number of accounts)
+    let slices: Vec<(u8, u8)> = hook_accounts
+        .iter()
+        .enumerate()
+        .filter(|(_, accounts)| !accounts.is_empty())
+        .map(|(kind, accounts)| (kind as u8, accounts.len() as u8))
+        .collect();
+    data.extend_from_slice(&(slices.len() as u32).to_le_bytes());
+    for (kind, length) in slices {
+        data.push(kind);
+        data.push(length);
+    }
+
+    let mut accounts = vec![
+        AccountMeta::new(*lb_pair_id, false),
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new(lb_pair.reserve_x, false),
+        AccountMeta::new(lb_pair.reserve_y, false),
+        AccountMeta::new(account_in, false),
+        AccountMeta::new(account_out, false),
+        AccountMeta::new_readonly(lb_pair.token_x_mint, false),
+        AccountMeta::new_readonly(lb_pair.token_y_mint, false),
+        AccountMeta::new(lb_pair.oracle, false),
+        // No host fee account
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(token_x_program, false),
+        AccountMeta::new_readonly(token_y_program, false),
+        AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+    ];
+    accounts.extend(event_accounts(program_id));
+    accounts.extend(hook_accounts.into_iter().flatten());
+    accounts.extend(
+        bin_arrays
+            .iter()
+            .map(|address| AccountMeta::new(*address, false)),
+    );
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
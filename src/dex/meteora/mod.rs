@@ -0,0 +1,561 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use super::layout::token_account_amount;
+use super::mints::MintCache;
+use super::programs::{associated_token_address, TOKEN_PROGRAM_ID};
+use super::token_2022::{transfer_hook_accounts, MintInfo, SwapTransferFees};
+use super::{
+    check_price_impact, check_trade_size, pair_key, send_transaction, to_base_units, DexClient,
+    FeeAmounts, HarvestReceipt, LpPosition, RebalanceJournal, RebalanceReceipt, SolBalance,
+    SwapEstimate, WalletAccounts, METEORA,
+};
+use crate::config::{LiquidityShape, MeteoraConfig, TradingParams};
+use crate::tokens::TokenProgram;
+use crate::transactions::TransactionSender;
+use crate::units::{Price, Rounding, TokenAmount};
+
+pub mod bins;
+mod fees;
+pub mod instructions;
+mod position;
+mod rebalance;
+mod state;
+
+pub use bins::{distribution, quote_exact_in, BinDistribution, SwapQuote};
+pub use position::to_lp_position;
+pub use state::{
+    bin_array_index, Bin, BinArray, LbPair, Position, PositionFee, BINS_PER_ARRAY, BIN_ARRAY_LEN,
+    LB_PAIR_LEN, POSITION_LEN,
+};
+
+// Bin arrays loaded ahead of the active bin when quoting or swapping
+const SWAP_BIN_ARRAYS: i64 = 3;
+
+// Bin based liquidity pairs of the Meteora DLMM program. Every bin trades at
+// a fixed price, so swaps walk bins instead of a curve and positions choose
+// how to spread their deposit over the bins of their range.
+pub struct MeteoraClient {
+    rpc_client: Arc<RpcClient>,
+    sender: Arc<TransactionSender>,
+    program_id: Pubkey,
+    shape: LiquidityShape,
+    wallet: Arc<Keypair>,
+    // Deepest pair per mint pair, keyed with the smaller mint first
+    pairs: Mutex<HashMap<(Pubkey, Pubkey), Pubkey>>,
+    // Pairs do not store mint decimals or token programs
+    mints: MintCache,
+    trading: TradingParams,
+    journal: RebalanceJournal,
+}
+
+// A DLMM pair together with the decimals of its mints
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pair {
+    pub address: Pubkey,
+    pub lb_pair: LbPair,
+    pub decimals_x: u8,
+    pub decimals_y: u8,
+}
+
+impl Pair {
+    // Token y per token x in UI units, at the active bin
+    pub fn price(&self) -> f64 {
+        bins::bin_id_to_price(
+            self.lb_pair.active_id,
+            self.lb_pair.bin_step,
+            self.decimals_x,
+            self.decimals_y,
+        )
+    }
+
+    // Token y base units per token x base unit, at the active bin
+    fn raw_price(&self) -> f64 {
+        bins::bin_price(self.lb_pair.active_id, self.lb_pair.bin_step)
+    }
+}
+
+impl MeteoraClient {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        sender: Arc<TransactionSender>,
+        config: &MeteoraConfig,
+        trading: TradingParams,
+        wallet: Arc<Keypair>,
+    ) -> Result<Self> {
+        let journal = RebalanceJournal::new(&config.rebalance_journal_path);
+        let pending = journal.pending()?;
+        if !pending.is_empty() {
+            warn!(
+                "Unfinished Meteora rebalances for positions {}; they resume on the next rebalance of each",
+                pending.join(", ")
+            );
+        }
+
+        Ok(Self {
+            rpc_client,
+            sender,
+            program_id: Pubkey::from_str(&config.program_id)?,
+            shape: config.liquidity_shape,
+            wallet,
+            pairs: Mutex::new(HashMap::new()),
+            mints: MintCache::new(),
+            trading,
+            journal,
+        })
+    }
+
+    // Read as each operation starts, so reloaded values apply to the next one
+    fn slippage_tolerance(&self) -> f64 {
+        self.trading.borrow().slippage_tolerance
+    }
+
+    async fn send_transaction(
+        &self,
+        instructions: &[Instruction],
+        extra_signers: &[&Keypair],
+    ) -> Result<String> {
+        send_transaction(
+            &self.sender,
+            &self.wallet,
+            instructions,
+            extra_signers,
+            METEORA,
+        )
+        .await
+    }
+
+    async fn pair_from_lb_pair(&self, address: Pubkey, lb_pair: LbPair) -> Result<Pair> {
+        let decimals = self
+            .mints
+            .decimals(
+                &self.rpc_client,
+                &[lb_pair.token_x_mint, lb_pair.token_y_mint],
+            )
+            .await?;
+        Ok(Pair {
+            address,
+            lb_pair,
+            decimals_x: decimals[0],
+            decimals_y: decimals[1],
+        })
+    }
+
+    // Position instructions only move classic SPL tokens
+    async fn check_lp_supported(&self, pair: &Pair) -> Result<()> {
+        self.mints
+            .check_lp_supported(
+                &self.rpc_client,
+                &[pair.lb_pair.token_x_mint, pair.lb_pair.token_y_mint],
+                METEORA,
+            )
+            .await
+    }
+
+    async fn load_pair(&self, address: &Pubkey) -> Result<Pair> {
+        let data = self.rpc_client.get_account_data(address).await?;
+        self.pair_from_lb_pair(*address, LbPair::decode(&data)?)
+            .await
+    }
+
+    // The enabled pair for the mints holding the most `token_a`. Pairs keep
+    // their mints in creation order, so both orders are searched.
+    pub async fn find_pair(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Pair> {
+        let key = pair_key(token_a, token_b);
+        let cached = self.pairs.lock().unwrap().get(&key).copied();
+        if let Some(address) = cached {
+            return self.load_pair(&address).await;
+        }
+
+        let mut candidates = self.pairs_for_mints(token_a, token_b).await?;
+        candidates.extend(self.pairs_for_mints(token_b, token_a).await?);
+        candidates.retain(|(_, lb_pair)| lb_pair.is_enabled());
+
+        let reserves: Vec<Pubkey> = candidates
+            .iter()
+            .map(|(_, lb_pair)| {
+                if lb_pair.token_x_mint == *token_a {
+                    lb_pair.reserve_x
+                } else {
+                    lb_pair.reserve_y
+                }
+            })
+            .collect();
+        let mut depths = Vec::with_capacity(reserves.len());
+        for chunk in reserves.chunks(100) {
+            for account in self.rpc_client.get_multiple_accounts(chunk).await? {
+                depths.push(account.map_or(Ok(0), |account| token_account_amount(&account.data))?);
+            }
+        }
+        let (address, lb_pair) = candidates
+            .into_iter()
+            .zip(depths)
+            .max_by_key(|(_, depth)| *depth)
+            .map(|(candidate, _)| candidate)
+            .ok_or_else(|| anyhow!("no Meteora DLMM pair found for {}/{}", token_a, token_b))?;
+
+        self.pairs.lock().unwrap().insert(key, address);
+        self.pair_from_lb_pair(address, lb_pair).await
+    }
+
+    async fn pairs_for_mints(
+        &self,
+        mint_x: &Pubkey,
+        mint_y: &Pubkey,
+    ) -> Result<Vec<(Pubkey, LbPair)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(LB_PAIR_LEN as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    state::LB_PAIR_MINT_X,
+                    mint_x.as_ref(),
+                )),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    state::LB_PAIR_MINT_Y,
+                    mint_y.as_ref(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Ok(self
+            .rpc_client
+            .get_program_accounts_with_config(&self.program_id, config)
+            .await?
+            .into_iter()
+            .filter_map(|(address, account)| {
+                LbPair::decode(&account.data)
+                    .ok()
+                    .map(|lb_pair| (address, lb_pair))
+            })
+            .collect())
+    }
+
+    // The initialized bin arrays from the active one outwards in the swap
+    // direction, nearest first, with their addresses
+    async fn swap_bin_arrays(
+        &self,
+        pair: &Pair,
+        swap_for_y: bool,
+    ) -> Result<(Vec<Pubkey>, Vec<BinArray>)> {
+        let start = bin_array_index(pair.lb_pair.active_id);
+        let step = if swap_for_y { -1 } else { 1 };
+        let candidates: Vec<Pubkey> = (0..SWAP_BIN_ARRAYS)
+            .map(|i| {
+                instructions::bin_array_address(&self.program_id, &pair.address, start + i * step)
+            })
+            .collect();
+
+        let accounts = self.rpc_client.get_multiple_accounts(&candidates).await?;
+        let mut addresses = Vec::new();
+        let mut arrays = Vec::new();
+        for (address, account) in candidates.into_iter().zip(accounts) {
+            let account = match account {
+                Some(account) => account,
+                None => break,
+            };
+            arrays.push(BinArray::decode(&account.data)?);
+            addresses.push(address);
+        }
+        if arrays.is_empty() {
+            return Err(anyhow!(
+                "DLMM pair {} has no initialized bin array at the active bin",
+                pair.address
+            ));
+        }
+        Ok((addresses, arrays))
+    }
+
+    // Input and output mints of a swap through the pair, and the transfer
+    // fees they charge. Fails for tokens that are unsafe to trade.
+    async fn swap_mints(
+        &self,
+        pair: &Pair,
+        swap_for_y: bool,
+    ) -> Result<(MintInfo, MintInfo, SwapTransferFees)> {
+        let mut mints = self
+            .mints
+            .info(
+                &self.rpc_client,
+                &[pair.lb_pair.token_x_mint, pair.lb_pair.token_y_mint],
+            )
+            .await?;
+        let (mint_y, mint_x) = (mints.pop().unwrap(), mints.pop().unwrap());
+        let (mint_in, mint_out) = if swap_for_y {
+            (mint_x, mint_y)
+        } else {
+            (mint_y, mint_x)
+        };
+        let fees = SwapTransferFees::load(&self.rpc_client, &mint_in, &mint_out).await?;
+        Ok((mint_in, mint_out, fees))
+    }
+
+    // Simulates an exact-input swap of `amount_in` base units sent from the
+    // wallet. Amounts are what leaves and reaches the wallet, net of any
+    // Token-2022 transfer fees.
+    async fn quote_in_pair(
+        &self,
+        pair: &Pair,
+        swap_for_y: bool,
+        amount_in: u64,
+        fees: &SwapTransferFees,
+    ) -> Result<(Vec<Pubkey>, SwapQuote)> {
+        let (bin_array_keys, bin_arrays) = self.swap_bin_arrays(pair, swap_for_y).await?;
+        let mut quote = quote_exact_in(
+            &pair.lb_pair,
+            &bin_arrays,
+            fees.pool_input(amount_in),
+            swap_for_y,
+        )?;
+        quote.amount_in = amount_in;
+        quote.amount_out = fees.received(quote.amount_out);
+        Ok((bin_array_keys, quote))
+    }
+
+    // Swaps `amount_in` base units through the pair and returns the quote it
+    // was checked against along with the signature. Bins are not kept in the
+    // pool cache, so the price impact threshold, when given, is checked
+    // against this quote.
+    async fn swap_in_pair(
+        &self,
+        pair: &Pair,
+        swap_for_y: bool,
+        amount_in: u64,
+        min_amount_out: u64,
+        price_impact_threshold: Option<f64>,
+        sol: SolBalance,
+    ) -> Result<(SwapQuote, String)> {
+        let (mint_in, mint_out, fees) = self.swap_mints(pair, swap_for_y).await?;
+        let (bin_array_keys, quote) = self
+            .quote_in_pair(pair, swap_for_y, amount_in, &fees)
+            .await?;
+        if let Some(threshold) = price_impact_threshold {
+            check_price_impact(quote.price_impact, threshold)?;
+        }
+        if quote.amount_out < min_amount_out {
+            return Err(anyhow!(
+                "Meteora quote of {} is below the minimum output {}",
+                quote.amount_out,
+                min_amount_out
+            ));
+        }
+        // The program checks its minimum before the output fee is withheld
+        let pool_min_amount_out = fees.pool_min_output(min_amount_out)?;
+
+        let owner = self.wallet.pubkey();
+        let mut accounts = WalletAccounts::new(owner, sol);
+        let account_in = accounts.spend(&mint_in.mint, &mint_in.program.id(), amount_in);
+        let account_out = accounts.receive(&mint_out.mint, &mint_out.program.id());
+        let lb_pair = &pair.lb_pair;
+        let swap = if mint_in.program == TokenProgram::Spl && mint_out.program == TokenProgram::Spl
+        {
+            instructions::swap(
+                &self.program_id,
+                &owner,
+                &pair.address,
+                lb_pair,
+                swap_for_y,
+                amount_in,
+                pool_min_amount_out,
+                &bin_array_keys,
+            )
+        } else {
+            let (reserve_in, reserve_out) = if swap_for_y {
+                (lb_pair.reserve_x, lb_pair.reserve_y)
+            } else {
+                (lb_pair.reserve_y, lb_pair.reserve_x)
+            };
+            // The output leaves the reserve on the pair's authority
+            let hook_in = transfer_hook_accounts(
+                &self.rpc_client,
+                &mint_in,
+                &account_in,
+                &reserve_in,
+                &owner,
+                amount_in,
+            )
+            .await?;
+            let hook_out = transfer_hook_accounts(
+                &self.rpc_client,
+                &mint_out,
+                &reserve_out,
+                &account_out,
+                &pair.address,
+                fees.pool_min_output(quote.amount_out)?,
+            )
+            .await?;
+            let (token_programs, hook_accounts) = if swap_for_y {
+                (
+                    [mint_in.program.id(), mint_out.program.id()],
+                    [hook_in, hook_out],
+                )
+            } else {
+                (
+                    [mint_out.program.id(), mint_in.program.id()],
+                    [hook_out, hook_in],
+                )
+            };
+            instructions::swap2(
+                &self.program_id,
+                &owner,
+                &pair.address,
+                lb_pair,
+                token_programs,
+                swap_for_y,
+                amount_in,
+                pool_min_amount_out,
+                &bin_array_keys,
+                hook_accounts,
+            )
+        };
+        let signature = self.send_transaction(&accounts.build([swap]), &[]).await?;
+        Ok((quote, signature))
+    }
+
+    // Wallet balances of `mints`, in base units; missing accounts count as 0
+    async fn token_balances(&self, mints: &[Pubkey]) -> Result<Vec<u64>> {
+        let owner = self.wallet.pubkey();
+        let addresses: Vec<Pubkey> = mints
+            .iter()
+            .map(|mint| associated_token_address(&owner, mint, &TOKEN_PROGRAM_ID))
+            .collect();
+        self.rpc_client
+            .get_multiple_accounts(&addresses)
+            .await?
+            .into_iter()
+            .map(|account| account.map_or(Ok(0), |account| token_account_amount(&account.data)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DexClient for MeteoraClient {
+    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Price> {
+        let pair = self.find_pair(token_a, token_b).await?;
+        for info in self
+            .mints
+            .info(&self.rpc_client, &[*token_a, *token_b])
+            .await?
+        {
+            info.check_tradable()?;
+        }
+        if pair.lb_pair.token_x_mint == *token_a {
+            Price::from_f64(pair.price(), Rounding::Nearest)
+        } else {
+            Price::from_f64(pair.price(), Rounding::Nearest)?.invert(Rounding::Nearest)
+        }
+    }
+
+    async fn create_lp_position(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+        min_price: Price,
+        max_price: Price,
+    ) -> Result<String> {
+        self.open_dlmm_position(token_a, token_b, amount_a, amount_b, min_price, max_price)
+            .await
+    }
+
+    async fn rebalance_position(
+        &self,
+        position_id: &str,
+        new_min_price: Price,
+        new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        self.rebalance_dlmm_position(position_id, new_min_price, new_max_price)
+            .await
+    }
+
+    async fn harvest_fees(&self, position_id: &str) -> Result<HarvestReceipt> {
+        self.harvest_dlmm_fees(position_id).await
+    }
+
+    async fn get_unclaimed_fees(&self, position_id: &str) -> Result<FeeAmounts> {
+        self.dlmm_unclaimed_fees(position_id).await
+    }
+
+    async fn increase_liquidity(
+        &self,
+        position_id: &str,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+    ) -> Result<String> {
+        self.increase_dlmm_liquidity(position_id, amount_a, amount_b)
+            .await
+    }
+
+    async fn get_positions(&self) -> Result<Vec<LpPosition>> {
+        self.dlmm_positions().await
+    }
+
+    async fn quote_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+    ) -> Result<SwapEstimate> {
+        let pair = self.find_pair(token_in, token_out).await?;
+        let swap_for_y = *token_in == pair.lb_pair.token_x_mint;
+        let (mint_in, mint_out, fees) = self.swap_mints(&pair, swap_for_y).await?;
+        let (_, quote) = self
+            .quote_in_pair(
+                &pair,
+                swap_for_y,
+                to_base_units(amount_in, mint_in.decimals)?,
+                &fees,
+            )
+            .await?;
+        Ok(SwapEstimate {
+            amount_in: TokenAmount::new(quote.amount_in, mint_in.decimals),
+            amount_out: TokenAmount::new(quote.amount_out, mint_out.decimals),
+            price_impact: quote.price_impact,
+        })
+    }
+
+    async fn execute_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+        min_amount_out: TokenAmount,
+    ) -> Result<String> {
+        check_trade_size(&self.trading.borrow(), amount_in)?;
+        let pair = self.find_pair(token_in, token_out).await?;
+        let swap_for_y = *token_in == pair.lb_pair.token_x_mint;
+        let (decimals_in, decimals_out) = if swap_for_y {
+            (pair.decimals_x, pair.decimals_y)
+        } else {
+            (pair.decimals_y, pair.decimals_x)
+        };
+        let price_impact_threshold = self.trading.borrow().price_impact_threshold;
+
+        let (_, signature) = self
+            .swap_in_pair(
+                &pair,
+                swap_for_y,
+                to_base_units(amount_in, decimals_in)?,
+                min_amount_out.base_units_at(decimals_out, Rounding::Up)?,
+                Some(price_impact_threshold),
+                SolBalance::Native,
+            )
+            .await?;
+        Ok(signature)
+    }
+}
@@ -0,0 +1,403 @@
+use anyhow::{anyhow, Result};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::str::FromStr;
+use tracing::info;
+
+use super::bins::{self, BinDistribution};
+use super::instructions::{self, BinLiquidityDistribution, BinLiquidityReduction};
+use super::state::POSITION_OWNER;
+use super::{BinArray, MeteoraClient, Pair, Position, POSITION_LEN};
+use crate::dex::programs::TOKEN_PROGRAM_ID;
+use crate::dex::{invert_range, to_base_units, LpPosition, SolBalance, WalletAccounts};
+use crate::units::{Price, Rounding, TokenAmount};
+
+// Adding liquidity touches every bin of the range
+const ADD_LIQUIDITY_COMPUTE_UNITS: u32 = 1_000_000;
+// Removing all of a bin's liquidity
+const FULL_BPS: u16 = 10_000;
+
+impl MeteoraClient {
+    pub(super) async fn load_position(&self, position_id: &str) -> Result<(Position, Pair)> {
+        let address = Pubkey::from_str(position_id)
+            .map_err(|_| anyhow!("invalid Meteora position id {}", position_id))?;
+        let data = self
+            .rpc_client
+            .get_account_data(&address)
+            .await
+            .map_err(|e| anyhow!("Meteora position {} not found: {}", position_id, e))?;
+        let position = Position::decode(&data)?;
+        let pair = self.load_pair(&position.lb_pair).await?;
+        Ok((position, pair))
+    }
+
+    // The two bin arrays covering the position, as its instructions pass them
+    pub(super) async fn position_bin_arrays(
+        &self,
+        pair: &Pair,
+        position: &Position,
+    ) -> Result<Vec<BinArray>> {
+        let (lower, upper) =
+            instructions::position_bin_arrays(position.lower_bin_id, position.upper_bin_id);
+        let addresses: Vec<Pubkey> = (lower..=upper)
+            .map(|index| instructions::bin_array_address(&self.program_id, &pair.address, index))
+            .collect();
+        self.rpc_client
+            .get_multiple_accounts(&addresses)
+            .await?
+            .into_iter()
+            .flatten()
+            .map(|account| BinArray::decode(&account.data))
+            .collect()
+    }
+
+    // Opens a position over the bins covering `min_price..max_price` funded
+    // with up to `amount_a`/`amount_b`, spread by the configured liquidity
+    // shape. Prices are token_b per token_a. Returns the position account
+    // address.
+    pub async fn open_dlmm_position(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+        min_price: Price,
+        max_price: Price,
+    ) -> Result<String> {
+        let pair = self.find_pair(token_a, token_b).await?;
+        self.check_lp_supported(&pair).await?;
+
+        // Express everything in the pair's token x / token y orientation
+        let (amount_x, amount_y, (min_price, max_price)) = if *token_a == pair.lb_pair.token_x_mint
+        {
+            (amount_a, amount_b, (min_price, max_price))
+        } else {
+            (amount_b, amount_a, invert_range(min_price, max_price)?)
+        };
+        let (lower_bin_id, upper_bin_id) = bins::bin_range(
+            min_price.to_f64(),
+            max_price.to_f64(),
+            pair.lb_pair.bin_step,
+            pair.decimals_x,
+            pair.decimals_y,
+        )?;
+
+        let (position, _) = self
+            .open_position_in_pair(
+                &pair,
+                lower_bin_id,
+                upper_bin_id,
+                to_base_units(amount_x, pair.decimals_x)?,
+                to_base_units(amount_y, pair.decimals_y)?,
+                SolBalance::Native,
+            )
+            .await?;
+        Ok(position.to_string())
+    }
+
+    // Creates the position and deposits into it in one transaction, creating
+    // the bin arrays it needs if nobody has yet. Returns the position address
+    // and the signature.
+    pub(super) async fn open_position_in_pair(
+        &self,
+        pair: &Pair,
+        lower_bin_id: i32,
+        upper_bin_id: i32,
+        amount_x: u64,
+        amount_y: u64,
+        sol: SolBalance,
+    ) -> Result<(Pubkey, String)> {
+        let owner = self.wallet.pubkey();
+        let mut accounts = WalletAccounts::new(owner, sol);
+        accounts.spend(&pair.lb_pair.token_x_mint, &TOKEN_PROGRAM_ID, amount_x);
+        accounts.spend(&pair.lb_pair.token_y_mint, &TOKEN_PROGRAM_ID, amount_y);
+        let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            ADD_LIQUIDITY_COMPUTE_UNITS,
+        )];
+        ixs.extend(
+            self.missing_bin_arrays(pair, lower_bin_id, upper_bin_id)
+                .await?,
+        );
+
+        let position = Keypair::new();
+        ixs.push(instructions::initialize_position(
+            &self.program_id,
+            &owner,
+            &pair.address,
+            &position.pubkey(),
+            lower_bin_id,
+            upper_bin_id - lower_bin_id + 1,
+        ));
+        ixs.push(self.add_liquidity_instruction(
+            pair,
+            &position.pubkey(),
+            lower_bin_id,
+            upper_bin_id,
+            amount_x,
+            amount_y,
+        )?);
+        let signature = self
+            .send_transaction(&accounts.build(ixs), &[&position])
+            .await?;
+
+        info!(
+            "Opened Meteora position {} in pair {} (bins {}..={}, {:?}): {}",
+            position.pubkey(),
+            pair.address,
+            lower_bin_id,
+            upper_bin_id,
+            self.shape,
+            signature
+        );
+        Ok((position.pubkey(), signature))
+    }
+
+    async fn missing_bin_arrays(
+        &self,
+        pair: &Pair,
+        lower_bin_id: i32,
+        upper_bin_id: i32,
+    ) -> Result<Vec<Instruction>> {
+        let (lower, upper) = instructions::position_bin_arrays(lower_bin_id, upper_bin_id);
+        let indexes: Vec<i64> = (lower..=upper).collect();
+        let addresses: Vec<Pubkey> = indexes
+            .iter()
+            .map(|index| instructions::bin_array_address(&self.program_id, &pair.address, *index))
+            .collect();
+        let accounts = self.rpc_client.get_multiple_accounts(&addresses).await?;
+        Ok(indexes
+            .into_iter()
+            .zip(accounts)
+            .filter(|(_, account)| account.is_none())
+            .map(|(index, _)| {
+                instructions::initialize_bin_array(
+                    &self.program_id,
+                    &self.wallet.pubkey(),
+                    &pair.address,
+                    index,
+                )
+            })
+            .collect())
+    }
+
+    // Spreads the deposit over the range with the configured shape around the
+    // pair's current active bin
+    fn add_liquidity_instruction(
+        &self,
+        pair: &Pair,
+        position: &Pubkey,
+        lower_bin_id: i32,
+        upper_bin_id: i32,
+        amount_x: u64,
+        amount_y: u64,
+    ) -> Result<Instruction> {
+        let distribution = bins::distribution(
+            self.shape,
+            lower_bin_id,
+            upper_bin_id,
+            pair.lb_pair.active_id,
+        );
+        // A range entirely on one side of the price only takes one token
+        let takes_x = distribution.iter().any(|bin| bin.x_bps > 0);
+        let takes_y = distribution.iter().any(|bin| bin.y_bps > 0);
+        let amount_x = if takes_x { amount_x } else { 0 };
+        let amount_y = if takes_y { amount_y } else { 0 };
+        if amount_x == 0 && amount_y == 0 {
+            return Err(anyhow!(
+                "nothing to deposit in bins {}..={} at active bin {}",
+                lower_bin_id,
+                upper_bin_id,
+                pair.lb_pair.active_id
+            ));
+        }
+
+        let distribution: Vec<BinLiquidityDistribution> = distribution
+            .iter()
+            .map(
+                |BinDistribution {
+                     bin_id,
+                     x_bps,
+                     y_bps,
+                 }| BinLiquidityDistribution {
+                    bin_id: *bin_id,
+                    distribution_x: *x_bps,
+                    distribution_y: *y_bps,
+                },
+            )
+            .collect();
+        Ok(instructions::add_liquidity(
+            &self.program_id,
+            &self.wallet.pubkey(),
+            &pair.address,
+            &pair.lb_pair,
+            position,
+            lower_bin_id,
+            upper_bin_id,
+            amount_x,
+            amount_y,
+            &distribution,
+        ))
+    }
+
+    // Adds up to `amount_a`/`amount_b` of the pair's tokens to a position,
+    // shaped around the current active bin
+    pub async fn increase_dlmm_liquidity(
+        &self,
+        position_id: &str,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+    ) -> Result<String> {
+        let (position, pair) = self.load_position(position_id).await?;
+        self.check_lp_supported(&pair).await?;
+        let amount_x = to_base_units(amount_a, pair.decimals_x)?;
+        let amount_y = to_base_units(amount_b, pair.decimals_y)?;
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), SolBalance::Native);
+        accounts.spend(&pair.lb_pair.token_x_mint, &TOKEN_PROGRAM_ID, amount_x);
+        accounts.spend(&pair.lb_pair.token_y_mint, &TOKEN_PROGRAM_ID, amount_y);
+        let instruction = self.add_liquidity_instruction(
+            &pair,
+            &Pubkey::from_str(position_id)?,
+            position.lower_bin_id,
+            position.upper_bin_id,
+            amount_x,
+            amount_y,
+        )?;
+        let ixs = accounts.build([
+            ComputeBudgetInstruction::set_compute_unit_limit(ADD_LIQUIDITY_COMPUTE_UNITS),
+            instruction,
+        ]);
+        self.send_transaction(&ixs, &[]).await
+    }
+
+    // Withdraws all liquidity, claims fees and closes the position
+    pub async fn close_dlmm_position(&self, position_id: &str) -> Result<String> {
+        let (position, pair) = self.load_position(position_id).await?;
+        let ixs = self.withdraw_instructions(
+            &pair,
+            &Pubkey::from_str(position_id)?,
+            &position,
+            SolBalance::Native,
+        );
+        self.send_transaction(&ixs, &[]).await
+    }
+
+    // Everything needed to empty and close a position in one transaction,
+    // including the wallet accounts the tokens are paid into.
+    // DLMM withdrawals take no minimum amounts: a bin's composition can only
+    // change by swaps, which leave its value at the bin price.
+    pub(super) fn withdraw_instructions(
+        &self,
+        pair: &Pair,
+        address: &Pubkey,
+        position: &Position,
+        sol: SolBalance,
+    ) -> Vec<Instruction> {
+        let owner = self.wallet.pubkey();
+        let mut accounts = WalletAccounts::new(owner, sol);
+        accounts.receive(&pair.lb_pair.token_x_mint, &TOKEN_PROGRAM_ID);
+        accounts.receive(&pair.lb_pair.token_y_mint, &TOKEN_PROGRAM_ID);
+        let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            ADD_LIQUIDITY_COMPUTE_UNITS,
+        )];
+        if position.has_liquidity() {
+            let reductions: Vec<BinLiquidityReduction> = position
+                .bin_ids()
+                .zip(&position.liquidity_shares)
+                .filter(|(_, share)| **share > 0)
+                .map(|(bin_id, _)| BinLiquidityReduction {
+                    bin_id,
+                    bps_to_remove: FULL_BPS,
+                })
+                .collect();
+            ixs.push(instructions::remove_liquidity(
+                &self.program_id,
+                &owner,
+                &pair.address,
+                &pair.lb_pair,
+                address,
+                position.lower_bin_id,
+                position.upper_bin_id,
+                &reductions,
+            ));
+        }
+        ixs.push(instructions::claim_fee(
+            &self.program_id,
+            &owner,
+            &pair.address,
+            &pair.lb_pair,
+            address,
+            position.lower_bin_id,
+            position.upper_bin_id,
+        ));
+        ixs.push(instructions::close_position(
+            &self.program_id,
+            &owner,
+            &pair.address,
+            address,
+            position.lower_bin_id,
+            position.upper_bin_id,
+        ));
+        accounts.build(ixs)
+    }
+
+    // Position accounts record their owner, so they are found by filtering on it
+    pub(super) async fn dlmm_positions(&self) -> Result<Vec<LpPosition>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(POSITION_LEN as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    POSITION_OWNER,
+                    self.wallet.pubkey().as_ref(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let accounts = self
+            .rpc_client
+            .get_program_accounts_with_config(&self.program_id, config)
+            .await?;
+
+        let mut positions = Vec::new();
+        for (address, account) in accounts {
+            let position = Position::decode(&account.data)?;
+            let pair = self.load_pair(&position.lb_pair).await?;
+            let bin_arrays = self.position_bin_arrays(&pair, &position).await?;
+            positions.push(to_lp_position(&address, &position, &pair, &bin_arrays)?);
+        }
+        Ok(positions)
+    }
+}
+
+pub fn to_lp_position(
+    address: &Pubkey,
+    position: &Position,
+    pair: &Pair,
+    bin_arrays: &[BinArray],
+) -> Result<LpPosition> {
+    let (amount_x, amount_y) = bins::position_amounts(position, bin_arrays)?;
+    let lb_pair = &pair.lb_pair;
+    let bin_price = |bin_id: i32| {
+        Price::from_f64(
+            bins::bin_id_to_price(bin_id, lb_pair.bin_step, pair.decimals_x, pair.decimals_y),
+            Rounding::Nearest,
+        )
+    };
+    Ok(LpPosition {
+        id: address.to_string(),
+        token_a: lb_pair.token_x_mint,
+        token_b: lb_pair.token_y_mint,
+        min_price: bin_price(position.lower_bin_id)?,
+        max_price: bin_price(position.upper_bin_id)?,
+        amount_a: TokenAmount::new(amount_x, pair.decimals_x),
+        amount_b: TokenAmount::new(amount_y, pair.decimals_y),
+    })
+}
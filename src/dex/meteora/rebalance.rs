@@ -0,0 +1,204 @@
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use tracing::info;
+
+use super::{bins, MeteoraClient, Pair};
+use crate::dex::rebalance::{swap_cost, swap_to_value_share, RebalanceProgress, Stage};
+use crate::dex::{RebalanceReceipt, SolBalance, SwapLeg};
+use crate::units::{Price, Rounding, TokenAmount};
+
+impl MeteoraClient {
+    // Withdraws the old position and claims its fees in one transaction,
+    // swaps the proceeds to the token mix the new range's shape needs, then
+    // opens the new position. Progress is journaled like the other venues.
+    pub(super) async fn rebalance_dlmm_position(
+        &self,
+        position_id: &str,
+        new_min_price: Price,
+        new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        let mut progress = match self.journal.get(position_id)? {
+            Some(progress) => {
+                info!(
+                    "Resuming rebalance of Meteora position {} after {:?}",
+                    position_id, progress.stage
+                );
+                progress
+            }
+            None => self.withdraw_for_rebalance(position_id).await?,
+        };
+        let pair_id = Pubkey::from_str(&progress.pool_id)?;
+
+        let pair = self.load_pair(&pair_id).await?;
+        let (lower_bin_id, upper_bin_id) = bins::bin_range(
+            new_min_price.to_f64(),
+            new_max_price.to_f64(),
+            pair.lb_pair.bin_step,
+            pair.decimals_x,
+            pair.decimals_y,
+        )?;
+
+        if progress.stage == Stage::Withdrawn {
+            self.swap_to_range(&pair, lower_bin_id, upper_bin_id, &mut progress)
+                .await?;
+            progress.stage = Stage::Swapped;
+            self.journal.put(position_id, &progress)?;
+        }
+
+        let pair = self.load_pair(&pair_id).await?;
+        let mints = [pair.lb_pair.token_x_mint, pair.lb_pair.token_y_mint];
+        let before = self.token_balances(&mints).await?;
+        let (position, signature) = self
+            .open_position_in_pair(
+                &pair,
+                lower_bin_id,
+                upper_bin_id,
+                progress.available_0,
+                progress.available_1,
+                SolBalance::Wrapped,
+            )
+            .await?;
+        let after = self.token_balances(&mints).await?;
+
+        let mut receipt = progress.receipt;
+        receipt.new_position_id = position.to_string();
+        receipt.deposited_a = TokenAmount::new(before[0].saturating_sub(after[0]), pair.decimals_x);
+        receipt.deposited_b = TokenAmount::new(before[1].saturating_sub(after[1]), pair.decimals_y);
+        receipt.signatures.push(signature);
+        self.journal.remove(position_id)?;
+
+        info!(
+            "Rebalanced Meteora position {} into {} (fees {} / {}, swap cost {})",
+            receipt.old_position_id,
+            receipt.new_position_id,
+            receipt.fees_a,
+            receipt.fees_b,
+            receipt.swap_cost
+        );
+        Ok(receipt)
+    }
+
+    async fn withdraw_for_rebalance(&self, position_id: &str) -> Result<RebalanceProgress> {
+        let (position, pair) = self.load_position(position_id).await?;
+        let bin_arrays = self.position_bin_arrays(&pair, &position).await?;
+        let (principal_x, principal_y) = bins::position_amounts(&position, &bin_arrays)?;
+
+        let mints = [pair.lb_pair.token_x_mint, pair.lb_pair.token_y_mint];
+        let before = self.token_balances(&mints).await?;
+        let ixs = self.withdraw_instructions(
+            &pair,
+            &Pubkey::from_str(position_id)?,
+            &position,
+            SolBalance::Wrapped,
+        );
+        let signature = self.send_transaction(&ixs, &[]).await?;
+        let after = self.token_balances(&mints).await?;
+
+        // Whatever arrived beyond the principal is fees claimed with it
+        let received_x = after[0].saturating_sub(before[0]);
+        let received_y = after[1].saturating_sub(before[1]);
+        let progress = RebalanceProgress {
+            pool_id: position.lb_pair.to_string(),
+            stage: Stage::Withdrawn,
+            available_0: received_x,
+            available_1: received_y,
+            sent_swap: None,
+            receipt: RebalanceReceipt {
+                old_position_id: position_id.to_string(),
+                withdrawn_a: TokenAmount::new(received_x.min(principal_x), pair.decimals_x),
+                withdrawn_b: TokenAmount::new(received_y.min(principal_y), pair.decimals_y),
+                fees_a: TokenAmount::new(received_x.saturating_sub(principal_x), pair.decimals_x),
+                fees_b: TokenAmount::new(received_y.saturating_sub(principal_y), pair.decimals_y),
+                swap: None,
+                swap_cost: TokenAmount::zero(pair.decimals_y),
+                deposited_a: TokenAmount::zero(pair.decimals_x),
+                deposited_b: TokenAmount::zero(pair.decimals_y),
+                signatures: vec![signature],
+            },
+        };
+        self.journal.put(position_id, &progress)?;
+        Ok(progress)
+    }
+
+    async fn swap_to_range(
+        &self,
+        pair: &Pair,
+        lower_bin_id: i32,
+        upper_bin_id: i32,
+        progress: &mut RebalanceProgress,
+    ) -> Result<()> {
+        let raw_price = pair.raw_price();
+        let share_x = bins::value_share_x(
+            self.shape,
+            lower_bin_id,
+            upper_bin_id,
+            pair.lb_pair.active_id,
+        );
+        let (swap_for_y, amount_in) = match swap_to_value_share(
+            progress.available_0,
+            progress.available_1,
+            raw_price,
+            share_x,
+        ) {
+            Some(swap) => swap,
+            None => return Ok(()),
+        };
+
+        let expected_out = if swap_for_y {
+            amount_in as f64 * raw_price
+        } else {
+            amount_in as f64 / raw_price
+        };
+        let min_amount_out = (expected_out * (1.0 - self.slippage_tolerance())).max(0.0) as u64;
+
+        let mints = [pair.lb_pair.token_x_mint, pair.lb_pair.token_y_mint];
+        let before = self.token_balances(&mints).await?;
+        let (_, signature) = self
+            .swap_in_pair(
+                pair,
+                swap_for_y,
+                amount_in,
+                min_amount_out,
+                None,
+                SolBalance::Wrapped,
+            )
+            .await?;
+        let after = self.token_balances(&mints).await?;
+
+        let (spent, received) = if swap_for_y {
+            let spent = before[0].saturating_sub(after[0]);
+            let received = after[1].saturating_sub(before[1]);
+            progress.available_0 = progress.available_0.saturating_sub(spent);
+            progress.available_1 += received;
+            (
+                TokenAmount::new(spent, pair.decimals_x),
+                TokenAmount::new(received, pair.decimals_y),
+            )
+        } else {
+            let spent = before[1].saturating_sub(after[1]);
+            let received = after[0].saturating_sub(before[0]);
+            progress.available_1 = progress.available_1.saturating_sub(spent);
+            progress.available_0 += received;
+            (
+                TokenAmount::new(spent, pair.decimals_y),
+                TokenAmount::new(received, pair.decimals_x),
+            )
+        };
+
+        let receipt = &mut progress.receipt;
+        receipt.swap_cost = swap_cost(
+            Price::from_f64(pair.price(), Rounding::Nearest)?,
+            swap_for_y,
+            spent,
+            received,
+        )?;
+        receipt.swap = Some(SwapLeg {
+            a_to_b: swap_for_y,
+            amount_in: spent,
+            amount_out: received,
+        });
+        receipt.signatures.push(signature);
+        Ok(())
+    }
+}
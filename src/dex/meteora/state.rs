@@ -0,0 +1,228 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::dex::layout::{
+    check_anchor_account, read_i32, read_i64, read_pubkey, read_u128, read_u16, read_u32, read_u64,
+    read_u8,
+};
+
+pub const LB_PAIR_LEN: usize = 904;
+pub const BIN_ARRAY_LEN: usize = 10136;
+pub const POSITION_LEN: usize = 8120;
+pub const BINS_PER_ARRAY: i32 = 70;
+// Bins a single position can hold
+pub const MAX_BINS_PER_POSITION: i32 = 70;
+
+const BIN_ARRAY_BINS: usize = 56;
+const BIN_LEN: usize = 144;
+const POSITION_SHARES: usize = 72;
+const POSITION_FEE_INFOS: usize = 4552;
+const POSITION_FEE_INFO_LEN: usize = 48;
+
+// Offsets include the 8 byte Anchor discriminator
+pub const LB_PAIR_MINT_X: usize = 88;
+pub const LB_PAIR_MINT_Y: usize = 120;
+pub const POSITION_OWNER: usize = 40;
+
+// Fee rates are stored with 9 decimals and capped at 10%
+const FEE_PRECISION: f64 = 1_000_000_000.0;
+const MAX_FEE_RATE: f64 = 100_000_000.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LbPair {
+    pub base_factor: u16,
+    pub variable_fee_control: u32,
+    pub base_fee_power_factor: u8,
+    pub volatility_accumulator: u32,
+    pub active_id: i32,
+    pub bin_step: u16,
+    pub status: u8,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub reserve_x: Pubkey,
+    pub reserve_y: Pubkey,
+    pub oracle: Pubkey,
+}
+
+impl LbPair {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != LB_PAIR_LEN {
+            return Err(anyhow!(
+                "not a DLMM pair account: expected {} bytes, got {}",
+                LB_PAIR_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "LbPair")?;
+
+        let bin_step = read_u16(data, 80)?;
+        if bin_step == 0 {
+            return Err(anyhow!("DLMM pair has a bin step of 0"));
+        }
+        Ok(Self {
+            base_factor: read_u16(data, 8)?,
+            variable_fee_control: read_u32(data, 16)?,
+            base_fee_power_factor: read_u8(data, 34)?,
+            volatility_accumulator: read_u32(data, 40)?,
+            active_id: read_i32(data, 76)?,
+            bin_step,
+            status: read_u8(data, 82)?,
+            token_x_mint: read_pubkey(data, LB_PAIR_MINT_X)?,
+            token_y_mint: read_pubkey(data, LB_PAIR_MINT_Y)?,
+            reserve_x: read_pubkey(data, 152)?,
+            reserve_y: read_pubkey(data, 184)?,
+            oracle: read_pubkey(data, 552)?,
+        })
+    }
+
+    // Base plus variable fee at the current volatility. The variable part
+    // grows as a swap crosses bins, which quotes do not model.
+    pub fn fee_rate(&self) -> f64 {
+        let base = self.base_factor as f64
+            * self.bin_step as f64
+            * 10.0
+            * 10f64.powi(self.base_fee_power_factor as i32);
+        let volatility = self.volatility_accumulator as f64 * self.bin_step as f64;
+        let variable = self.variable_fee_control as f64 * volatility * volatility / 1e11;
+        (base + variable).min(MAX_FEE_RATE) / FEE_PRECISION
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.status == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub liquidity_supply: u128,
+    pub fee_amount_x_per_token_stored: u128,
+    pub fee_amount_y_per_token_stored: u128,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinArray {
+    pub index: i64,
+    pub bins: Vec<Bin>,
+}
+
+impl BinArray {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != BIN_ARRAY_LEN {
+            return Err(anyhow!(
+                "not a DLMM bin array: expected {} bytes, got {}",
+                BIN_ARRAY_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "BinArray")?;
+
+        let mut bins = Vec::with_capacity(BINS_PER_ARRAY as usize);
+        for i in 0..BINS_PER_ARRAY as usize {
+            let offset = BIN_ARRAY_BINS + i * BIN_LEN;
+            // amount_x, amount_y, price, liquidity_supply, reward_per_token_stored[2], fees
+            bins.push(Bin {
+                amount_x: read_u64(data, offset)?,
+                amount_y: read_u64(data, offset + 8)?,
+                liquidity_supply: read_u128(data, offset + 32)?,
+                fee_amount_x_per_token_stored: read_u128(data, offset + 80)?,
+                fee_amount_y_per_token_stored: read_u128(data, offset + 96)?,
+            });
+        }
+        Ok(Self {
+            index: read_i64(data, 8)?,
+            bins,
+        })
+    }
+
+    pub fn lower_bin_id(&self) -> i32 {
+        self.index as i32 * BINS_PER_ARRAY
+    }
+
+    pub fn bin(&self, bin_id: i32) -> Option<&Bin> {
+        let offset = bin_id - self.lower_bin_id();
+        if (0..BINS_PER_ARRAY).contains(&offset) {
+            self.bins.get(offset as usize)
+        } else {
+            None
+        }
+    }
+}
+
+// Index of the bin array holding `bin_id`
+pub fn bin_array_index(bin_id: i32) -> i64 {
+    bin_id.div_euclid(BINS_PER_ARRAY) as i64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionFee {
+    pub fee_x_per_token_complete: u128,
+    pub fee_y_per_token_complete: u128,
+    pub fee_x_pending: u64,
+    pub fee_y_pending: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub lb_pair: Pubkey,
+    pub owner: Pubkey,
+    pub lower_bin_id: i32,
+    pub upper_bin_id: i32,
+    // Per bin from `lower_bin_id` to `upper_bin_id`
+    pub liquidity_shares: Vec<u128>,
+    pub fees: Vec<PositionFee>,
+}
+
+impl Position {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != POSITION_LEN {
+            return Err(anyhow!(
+                "not a DLMM position account: expected {} bytes, got {}",
+                POSITION_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "PositionV2")?;
+
+        let lower_bin_id = read_i32(data, 7912)?;
+        let upper_bin_id = read_i32(data, 7916)?;
+        let width = upper_bin_id - lower_bin_id + 1;
+        if !(1..=MAX_BINS_PER_POSITION).contains(&width) {
+            return Err(anyhow!(
+                "DLMM position has an invalid bin range {}..={}",
+                lower_bin_id,
+                upper_bin_id
+            ));
+        }
+
+        let mut liquidity_shares = Vec::with_capacity(width as usize);
+        let mut fees = Vec::with_capacity(width as usize);
+        for i in 0..width as usize {
+            liquidity_shares.push(read_u128(data, POSITION_SHARES + i * 16)?);
+            let offset = POSITION_FEE_INFOS + i * POSITION_FEE_INFO_LEN;
+            fees.push(PositionFee {
+                fee_x_per_token_complete: read_u128(data, offset)?,
+                fee_y_per_token_complete: read_u128(data, offset + 16)?,
+                fee_x_pending: read_u64(data, offset + 32)?,
+                fee_y_pending: read_u64(data, offset + 40)?,
+            });
+        }
+        Ok(Self {
+            lb_pair: read_pubkey(data, 8)?,
+            owner: read_pubkey(data, POSITION_OWNER)?,
+            lower_bin_id,
+            upper_bin_id,
+            liquidity_shares,
+            fees,
+        })
+    }
+
+    pub fn bin_ids(&self) -> impl Iterator<Item = i32> {
+        self.lower_bin_id..=self.upper_bin_id
+    }
+
+    pub fn has_liquidity(&self) -> bool {
+        self.liquidity_shares.iter().any(|share| *share > 0)
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{anyhow, Result};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::token_2022::MintInfo;
+use crate::tokens::TokenProgram;
+
+// Mint authorities can change a Token-2022 transfer fee or transfer hook
+// program at any time, so cached mints are read again once this old
+const MINT_INFO_TTL: Duration = Duration::from_secs(300);
+
+// Decimals, token program and Token-2022 extensions per mint, for venues whose
+// pool accounts do not store them. Decimals and the token program are fixed
+// once a mint exists, but its extensions are not, so entries expire.
+#[derive(Default)]
+pub struct MintCache {
+    cache: Mutex<HashMap<Pubkey, (MintInfo, Instant)>>,
+}
+
+impl MintCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn info(&self, rpc_client: &RpcClient, mints: &[Pubkey]) -> Result<Vec<MintInfo>> {
+        let missing: Vec<Pubkey> = {
+            let cache = self.cache.lock().unwrap();
+            mints
+                .iter()
+                .filter(|mint| match cache.get(mint) {
+                    Some((_, read_at)) => read_at.elapsed() > MINT_INFO_TTL,
+                    None => true,
+                })
+                .copied()
+                .collect()
+        };
+        if !missing.is_empty() {
+            let accounts = rpc_client.get_multiple_accounts(&missing).await?;
+            let mut cache = self.cache.lock().unwrap();
+            for (mint, account) in missing.iter().zip(accounts) {
+                let account = account.ok_or_else(|| anyhow!("mint {} not found", mint))?;
+                let info = MintInfo::parse(mint, &account.owner, &account.data)?;
+                // Logged when first read or changed, as every quote involving
+                // the mint is affected
+                let extensions = info.extensions();
+                let changed = cache.get(mint).map(|(cached, _)| cached) != Some(&info);
+                if changed && !extensions.is_empty() {
+                    warn!(
+                        "Token-2022 mint {} has extensions: {}",
+                        mint,
+                        extensions.join(", ")
+                    );
+                }
+                cache.insert(*mint, (info, Instant::now()));
+            }
+        }
+
+        let cache = self.cache.lock().unwrap();
+        Ok(mints.iter().map(|mint| cache[mint].0.clone()).collect())
+    }
+
+    pub async fn decimals(&self, rpc_client: &RpcClient, mints: &[Pubkey]) -> Result<Vec<u8>> {
+        Ok(self
+            .info(rpc_client, mints)
+            .await?
+            .iter()
+            .map(|info| info.decimals)
+            .collect())
+    }
+
+    // For LP instructions that only move classic SPL tokens: fails for tokens
+    // unsafe to trade and for any Token-2022 mint
+    pub async fn check_lp_supported(
+        &self,
+        rpc_client: &RpcClient,
+        mints: &[Pubkey],
+        venue: &str,
+    ) -> Result<()> {
+        for info in self.info(rpc_client, mints).await? {
+            info.check_tradable()?;
+            if info.program == TokenProgram::Token2022 {
+                return Err(anyhow!(
+                    "{} positions in Token-2022 mint {} are not supported",
+                    venue,
+                    info.mint
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,66 +1,328 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use solana_client::rpc_client::RpcClient;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
 use std::sync::Arc;
+use tracing::warn;
 
+use crate::config::{TradingConfig, TradingParams};
+use crate::logging;
+use crate::registry::VenueRegistry;
+use crate::transactions::{PreparedTransaction, TransactionSender};
+use crate::units::{Price, Rounding, TokenAmount};
+
+pub mod accounts;
+pub(crate) mod layout;
+mod mints;
+pub(crate) mod programs;
+pub mod quote;
+mod router;
+pub mod tick_math;
+pub mod token_2022;
+mod rebalance;
 mod raydium;
-mod jupiter;
+pub mod jupiter;
+pub mod orca;
+pub mod meteora;
 
-pub use raydium::RaydiumClient;
+pub use accounts::{AccountSweeper, SolBalance, WalletAccounts};
 pub use jupiter::JupiterClient;
+pub use meteora::MeteoraClient;
+pub use orca::OrcaClient;
+pub use quote::{check_price_impact, PoolCache, PoolQuote, Quote, SwapCurve};
+pub use raydium::{clmm, AmmInfo, PoolInfo, RaydiumClient};
+pub use rebalance::{rebalance_swap, swap_cost, RebalanceJournal};
+pub use router::{plan_route, split_amount, RouteLeg, RoutePlan, SmartRouter};
+
+pub const RAYDIUM: &str = "raydium";
+pub const JUPITER: &str = "jupiter";
+pub const ORCA: &str = "orca";
+pub const METEORA: &str = "meteora";
+pub const ROUTER: &str = "router";
+
+pub type DexClients = VenueRegistry<dyn DexClient>;
+
+#[derive(Debug, Clone)]
+pub struct LpPosition {
+    pub id: String,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub min_price: Price,
+    pub max_price: Price,
+    pub amount_a: TokenAmount,
+    pub amount_b: TokenAmount,
+}
 
-#[derive(Clone)]
-pub struct DexClients {
-    pub raydium: Arc<RaydiumClient>,
-    pub jupiter: Arc<JupiterClient>,
+// Outcome of moving a position to a new range. Amounts are of the position's
+// token_a / token_b.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RebalanceReceipt {
+    pub old_position_id: String,
+    pub new_position_id: String,
+    pub withdrawn_a: TokenAmount,
+    pub withdrawn_b: TokenAmount,
+    pub fees_a: TokenAmount,
+    pub fees_b: TokenAmount,
+    pub swap: Option<SwapLeg>,
+    // Value lost to the swap (fees and price impact), in token_b
+    pub swap_cost: TokenAmount,
+    pub deposited_a: TokenAmount,
+    pub deposited_b: TokenAmount,
+    pub signatures: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapLeg {
+    pub a_to_b: bool,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+}
+
+// Fees of one position in its token_a / token_b
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeAmounts {
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub amount_a: TokenAmount,
+    pub amount_b: TokenAmount,
+}
+
+// Liquidity mining reward paid in an arbitrary token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardAmount {
+    pub mint: Pubkey,
+    pub amount: TokenAmount,
+}
+
+// What a venue expects an exact-input swap to pay out, after fees and price
+// impact
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapEstimate {
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    // Shortfall against the spot price, fees included
+    pub price_impact: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HarvestReceipt {
+    pub position_id: String,
+    pub collected: FeeAmounts,
+    #[serde(default)]
+    pub rewards: Vec<RewardAmount>,
+    pub signature: String,
 }
 
 #[async_trait]
-pub trait DexClient {
-    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<f64>;
+pub trait DexClient: Send + Sync {
+    // Units of token_b per whole token_a
+    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Price>;
     async fn create_lp_position(
         &self,
         token_a: &Pubkey,
         token_b: &Pubkey,
-        amount_a: f64,
-        amount_b: f64,
-        min_price: f64,
-        max_price: f64,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+        min_price: Price,
+        max_price: Price,
     ) -> Result<String>;
     async fn rebalance_position(
         &self,
         position_id: &str,
-        new_min_price: f64,
-        new_max_price: f64,
-    ) -> Result<()>;
-    async fn harvest_fees(&self, position_id: &str) -> Result<()>;
+        new_min_price: Price,
+        new_max_price: Price,
+    ) -> Result<RebalanceReceipt>;
+    async fn harvest_fees(&self, position_id: &str) -> Result<HarvestReceipt>;
+    // Fees earned so far that a harvest would collect
+    async fn get_unclaimed_fees(&self, position_id: &str) -> Result<FeeAmounts>;
+    // Deposits up to the given amounts into an existing position
+    async fn increase_liquidity(
+        &self,
+        position_id: &str,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+    ) -> Result<String>;
+    async fn get_positions(&self) -> Result<Vec<LpPosition>>;
+    // Quotes swapping exactly `amount_in` of `token_in` without sending anything
+    async fn quote_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+    ) -> Result<SwapEstimate>;
     async fn execute_swap(
         &self,
         token_in: &Pubkey,
         token_out: &Pubkey,
-        amount_in: f64,
-        min_amount_out: f64,
+        amount_in: TokenAmount,
+        min_amount_out: TokenAmount,
     ) -> Result<String>;
 }
 
-pub async fn init_clients(config: &crate::config::Config) -> Result<DexClients> {
-    let rpc_client = RpcClient::new(config.solana_rpc_url.clone());
+// Every client sends through the one `sender` the app shares, which records
+// what is in flight so shutdown can wait for it, and reads slippage and trade
+// limits from `trading` as each swap starts so reloads apply at once. Venues
+// that quote from pool state keep the pools they load in `pool_states`.
+pub async fn init_clients(
+    config: &crate::config::Config,
+    rpc_client: Arc<RpcClient>,
+    sender: Arc<TransactionSender>,
+    wallet: Arc<Keypair>,
+    trading: &TradingParams,
+    pool_states: &PoolCache,
+) -> Result<DexClients> {
+    let mut clients = DexClients::new();
+
+    if let Some(raydium) = config.dex.raydium.as_ref().filter(|c| c.enabled) {
+        clients.register(
+            RAYDIUM,
+            Arc::new(RaydiumClient::new(
+                rpc_client.clone(),
+                sender.clone(),
+                raydium,
+                trading.clone(),
+                wallet.clone(),
+                pool_states.clone(),
+            )?),
+        )?;
+    }
+
+    if let Some(jupiter) = config.dex.jupiter.as_ref().filter(|c| c.enabled) {
+        clients.register(
+            JUPITER,
+            Arc::new(JupiterClient::new(
+                rpc_client.clone(),
+                sender.clone(),
+                jupiter,
+                trading.clone(),
+                wallet.clone(),
+            )?),
+        )?;
+    }
+
+    if let Some(orca) = config.dex.orca.as_ref().filter(|c| c.enabled) {
+        clients.register(
+            ORCA,
+            Arc::new(OrcaClient::new(
+                rpc_client.clone(),
+                sender.clone(),
+                orca,
+                trading.clone(),
+                wallet.clone(),
+                pool_states.clone(),
+            )?),
+        )?;
+    }
 
-    let raydium_client = Arc::new(RaydiumClient::new(
-        rpc_client.clone(),
-        config.raydium_program_id.clone(),
-    )?);
+    if let Some(meteora) = config.dex.meteora.as_ref().filter(|c| c.enabled) {
+        clients.register(
+            METEORA,
+            Arc::new(MeteoraClient::new(
+                rpc_client.clone(),
+                sender.clone(),
+                meteora,
+                trading.clone(),
+                wallet.clone(),
+            )?),
+        )?;
+    }
 
-    let jupiter_client = Arc::new(JupiterClient::new(
-        rpc_client,
-        config.jupiter_api_url.clone(),
-    )?);
+    if clients.is_empty() {
+        warn!("No DEX venues enabled");
+    }
+
+    // The router swaps through the venues above, so it is registered last
+    if let Some(router) = config.dex.router.as_ref().filter(|c| c.enabled) {
+        let venues = if router.venues.is_empty() {
+            clients.clone()
+        } else {
+            let mut venues = DexClients::new();
+            for name in &router.venues {
+                venues.register(name.as_str(), clients.require(name)?)?;
+            }
+            venues
+        };
+        clients.register(
+            ROUTER,
+            Arc::new(SmartRouter::new(venues, router, trading.clone())),
+        )?;
+    }
+
+    Ok(clients)
+}
+
+// Signs with the wallet (plus any new account keypairs) and sends through the
+// shared sender, failing unless the transaction lands. The signature is
+// recorded on the current trade span.
+pub(crate) async fn send_transaction(
+    sender: &TransactionSender,
+    wallet: &Keypair,
+    instructions: &[Instruction],
+    extra_signers: &[&Keypair],
+    venue: &str,
+) -> Result<String> {
+    let prepared = sender
+        .prepare(wallet, instructions, &[], extra_signers)
+        .await?;
+    submit_transaction(sender, &prepared, venue).await
+}
 
-    Ok(DexClients {
-        raydium: raydium_client,
-        jupiter: jupiter_client,
-    })
+// Sends an already signed transaction, such as a v0 transaction using address
+// lookup tables, failing unless it lands
+pub(crate) async fn submit_transaction(
+    sender: &TransactionSender,
+    prepared: &PreparedTransaction,
+    venue: &str,
+) -> Result<String> {
+    let signature = prepared.signature().to_string();
+    logging::record_signature(&signature);
+    sender
+        .submit(prepared, &format!("{} transaction", venue))
+        .await?
+        .into_result()?;
+    Ok(signature)
+}
+
+// Pools index a pair with the smaller mint first
+pub(crate) fn pair_key(token_a: &Pubkey, token_b: &Pubkey) -> (Pubkey, Pubkey) {
+    if token_a <= token_b {
+        (*token_a, *token_b)
+    } else {
+        (*token_b, *token_a)
+    }
+}
+
+// Base units of a mint with `decimals` decimals to spend or deposit, rounded
+// down so a caller's amount is never exceeded
+pub(crate) fn to_base_units(amount: TokenAmount, decimals: u8) -> Result<u64> {
+    amount.base_units_at(decimals, Rounding::Down)
+}
+
+// A token_b per token_a range as token_a per token_b, rounded outwards so the
+// flipped range still covers the requested one
+pub(crate) fn invert_range(min_price: Price, max_price: Price) -> Result<(Price, Price)> {
+    Ok((
+        max_price.invert(Rounding::Down)?,
+        min_price.invert(Rounding::Up)?,
+    ))
+}
+
+// Fails unless `amount_in` is within the configured trade size bounds, which
+// are in whole units of the token sold
+pub fn check_trade_size(trading: &TradingConfig, amount_in: TokenAmount) -> Result<()> {
+    let size = amount_in.to_ui();
+    if size < trading.min_trade_size || size > trading.max_trade_size {
+        return Err(anyhow!(
+            "trade size {} is outside the configured range {} to {}",
+            amount_in,
+            trading.min_trade_size,
+            trading.max_trade_size
+        ));
+    }
+    Ok(())
 }
 
 // Helper functions for LP management
@@ -91,4 +353,4 @@ pub async fn calculate_optimal_position_size(
 ) -> f64 {
     // Calculate position size based on risk management rules
     total_capital * risk_per_trade / current_price
-} 
\ No newline at end of file
+}
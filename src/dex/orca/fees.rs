@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+use super::{instructions, OrcaClient, Pool, Position, Tick, TickArray};
+use crate::dex::{tick_math, FeeAmounts, HarvestReceipt, RewardAmount, SolBalance, WalletAccounts};
+use crate::units::TokenAmount;
+
+impl OrcaClient {
+    // Fees owed on the position account plus what has accrued since it was
+    // last updated, read from the pool's fee growth accumulators
+    pub(super) async fn whirlpool_unclaimed_fees(&self, position_id: &str) -> Result<FeeAmounts> {
+        let (position, pool) = self.load_position(position_id).await?;
+        let (lower, upper) = self.position_ticks(&position, &pool).await?;
+        let whirlpool = &pool.whirlpool;
+
+        let growth_a = tick_math::fee_growth_inside(
+            whirlpool.tick_current,
+            position.tick_lower,
+            position.tick_upper,
+            whirlpool.fee_growth_global_a_x64,
+            lower.fee_growth_outside_a_x64,
+            upper.fee_growth_outside_a_x64,
+        );
+        let growth_b = tick_math::fee_growth_inside(
+            whirlpool.tick_current,
+            position.tick_lower,
+            position.tick_upper,
+            whirlpool.fee_growth_global_b_x64,
+            lower.fee_growth_outside_b_x64,
+            upper.fee_growth_outside_b_x64,
+        );
+        let owed_a = position.fee_owed_a.saturating_add(tick_math::fees_earned(
+            position.liquidity,
+            growth_a,
+            position.fee_growth_checkpoint_a_x64,
+        ));
+        let owed_b = position.fee_owed_b.saturating_add(tick_math::fees_earned(
+            position.liquidity,
+            growth_b,
+            position.fee_growth_checkpoint_b_x64,
+        ));
+
+        Ok(FeeAmounts {
+            token_a: whirlpool.token_mint_a,
+            token_b: whirlpool.token_mint_b,
+            amount_a: TokenAmount::new(owed_a, pool.decimals_a),
+            amount_b: TokenAmount::new(owed_b, pool.decimals_b),
+        })
+    }
+
+    // Brings the position's fees and rewards up to date and collects them,
+    // leaving its liquidity untouched
+    pub(super) async fn harvest_whirlpool_fees(&self, position_id: &str) -> Result<HarvestReceipt> {
+        let (position, pool) = self.load_position(position_id).await?;
+        let whirlpool = &pool.whirlpool;
+
+        // Balances are compared before and after, so SOL stays wrapped
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), SolBalance::Wrapped);
+        let mut ixs = Vec::new();
+        if position.liquidity > 0 {
+            ixs.push(instructions::update_fees_and_rewards(
+                &self.program_id,
+                whirlpool,
+                &position,
+            ));
+        }
+        ixs.extend(self.collect_instructions(&mut accounts, &pool, &position));
+        let ixs = accounts.build(ixs);
+
+        let mut mints = vec![whirlpool.token_mint_a, whirlpool.token_mint_b];
+        mints.extend(whirlpool.rewards.iter().map(|reward| reward.mint));
+        let before = self.token_balances(&mints).await?;
+        let signature = self.send_transaction(&ixs, &[]).await?;
+        let after = self.token_balances(&mints).await?;
+        let received: Vec<u64> = after
+            .iter()
+            .zip(&before)
+            .map(|(after, before)| after.saturating_sub(*before))
+            .collect();
+
+        Ok(HarvestReceipt {
+            position_id: position_id.to_string(),
+            collected: FeeAmounts {
+                token_a: whirlpool.token_mint_a,
+                token_b: whirlpool.token_mint_b,
+                amount_a: TokenAmount::new(received[0], pool.decimals_a),
+                amount_b: TokenAmount::new(received[1], pool.decimals_b),
+            },
+            rewards: self.reward_amounts(&mints[2..], &received[2..]).await?,
+            signature,
+        })
+    }
+
+    async fn reward_amounts(&self, mints: &[Pubkey], amounts: &[u64]) -> Result<Vec<RewardAmount>> {
+        let decimals = self.mint_decimals(mints).await?;
+        Ok(mints
+            .iter()
+            .zip(amounts)
+            .zip(decimals)
+            .map(|((mint, amount), decimals)| RewardAmount {
+                mint: *mint,
+                amount: TokenAmount::new(*amount, decimals),
+            })
+            .collect())
+    }
+
+    async fn position_ticks(&self, position: &Position, pool: &Pool) -> Result<(Tick, Tick)> {
+        let whirlpool = &pool.whirlpool;
+        let addresses = [
+            instructions::tick_array_address(
+                &self.program_id,
+                &position.whirlpool,
+                whirlpool.tick_array_start_index(position.tick_lower),
+            ),
+            instructions::tick_array_address(
+                &self.program_id,
+                &position.whirlpool,
+                whirlpool.tick_array_start_index(position.tick_upper),
+            ),
+        ];
+        let accounts = self.rpc_client.get_multiple_accounts(&addresses).await?;
+        match accounts.as_slice() {
+            [Some(lower), Some(upper)] => Ok((
+                *TickArray::decode(&lower.data, whirlpool.tick_spacing)?
+                    .tick(position.tick_lower)?,
+                *TickArray::decode(&upper.data, whirlpool.tick_spacing)?
+                    .tick(position.tick_upper)?,
+            )),
+            _ => Err(anyhow!(
+                "tick arrays for position {} not found",
+                position.position_mint
+            )),
+        }
+    }
+}
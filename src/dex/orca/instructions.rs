@@ -0,0 +1,495 @@
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_program, sysvar};
+
+use super::state::{Position, Whirlpool};
+use crate::dex::layout::anchor_discriminator;
+use crate::dex::programs::{
+    associated_token_address, ASSOCIATED_TOKEN_PROGRAM_ID, MEMO_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+
+// Swap price limits at the edges of the supported tick range
+pub const MIN_SQRT_PRICE_X64: u128 = 4295048016;
+pub const MAX_SQRT_PRICE_X64: u128 = 79226673515401279992447579055;
+
+pub fn whirlpool_address(
+    program_id: &Pubkey,
+    whirlpools_config: &Pubkey,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    tick_spacing: u16,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"whirlpool",
+            whirlpools_config.as_ref(),
+            mint_a.as_ref(),
+            mint_b.as_ref(),
+            &tick_spacing.to_le_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn position_address(program_id: &Pubkey, position_mint: &Pubkey) -> Pubkey {
+    position_pda(program_id, position_mint).0
+}
+
+fn position_pda(program_id: &Pubkey, position_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"position", position_mint.as_ref()], program_id)
+}
+
+// Whirlpools seed tick arrays with the start index as a decimal string
+pub fn tick_array_address(program_id: &Pubkey, whirlpool: &Pubkey, start_index: i32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"tick_array",
+            whirlpool.as_ref(),
+            start_index.to_string().as_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn oracle_address(program_id: &Pubkey, whirlpool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"oracle", whirlpool.as_ref()], program_id).0
+}
+
+pub fn initialize_tick_array(
+    program_id: &Pubkey,
+    funder: &Pubkey,
+    whirlpool_id: &Pubkey,
+    start_index: i32,
+) -> Instruction {
+    let mut data = anchor_discriminator("global:initialize_tick_array").to_vec();
+    data.extend_from_slice(&start_index.to_le_bytes());
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*whirlpool_id, false),
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(
+                tick_array_address(program_id, whirlpool_id, start_index),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+// Creates an empty position; liquidity is added with `increase_liquidity`
+pub fn open_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool_id: &Pubkey,
+    position_mint: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> Instruction {
+    let (position, bump) = position_pda(program_id, position_mint);
+
+    let mut data = anchor_discriminator("global:open_position").to_vec();
+    data.push(bump);
+    data.extend_from_slice(&tick_lower.to_le_bytes());
+    data.extend_from_slice(&tick_upper.to_le_bytes());
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new(position, false),
+            AccountMeta::new(*position_mint, true),
+            AccountMeta::new(
+                associated_token_address(owner, position_mint, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new_readonly(*whirlpool_id, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+        ],
+        data,
+    }
+}
+
+// Increase and decrease share their accounts and argument layout
+#[allow(clippy::too_many_arguments)]
+fn modify_liquidity(
+    name: &str,
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool_id: &Pubkey,
+    whirlpool: &Whirlpool,
+    position_mint: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u128,
+    amount_a: u64,
+    amount_b: u64,
+) -> Instruction {
+    let mut data = anchor_discriminator(&format!("global:{}", name)).to_vec();
+    data.extend_from_slice(&liquidity.to_le_bytes());
+    data.extend_from_slice(&amount_a.to_le_bytes());
+    data.extend_from_slice(&amount_b.to_le_bytes());
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*whirlpool_id, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(position_address(program_id, position_mint), false),
+            AccountMeta::new_readonly(
+                associated_token_address(owner, position_mint, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(
+                associated_token_address(owner, &whirlpool.token_mint_a, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(
+                associated_token_address(owner, &whirlpool.token_mint_b, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(whirlpool.token_vault_a, false),
+            AccountMeta::new(whirlpool.token_vault_b, false),
+            AccountMeta::new(
+                tick_array_address(
+                    program_id,
+                    whirlpool_id,
+                    whirlpool.tick_array_start_index(tick_lower),
+                ),
+                false,
+            ),
+            AccountMeta::new(
+                tick_array_address(
+                    program_id,
+                    whirlpool_id,
+                    whirlpool.tick_array_start_index(tick_upper),
+                ),
+                false,
+            ),
+        ],
+        data,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn increase_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool_id: &Pubkey,
+    whirlpool: &Whirlpool,
+    position_mint: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u128,
+    amount_a_max: u64,
+    amount_b_max: u64,
+) -> Instruction {
+    modify_liquidity(
+        "increase_liquidity",
+        program_id,
+        owner,
+        whirlpool_id,
+        whirlpool,
+        position_mint,
+        tick_lower,
+        tick_upper,
+        liquidity,
+        amount_a_max,
+        amount_b_max,
+    )
+}
+
+pub fn decrease_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool: &Whirlpool,
+    position: &Position,
+    liquidity: u128,
+    amount_a_min: u64,
+    amount_b_min: u64,
+) -> Instruction {
+    modify_liquidity(
+        "decrease_liquidity",
+        program_id,
+        owner,
+        &position.whirlpool,
+        whirlpool,
+        &position.position_mint,
+        position.tick_lower,
+        position.tick_upper,
+        liquidity,
+        amount_a_min,
+        amount_b_min,
+    )
+}
+
+// Brings the fees and rewards owed on the position up to date; only needed
+// while the position still has liquidity
+pub fn update_fees_and_rewards(
+    program_id: &Pubkey,
+    whirlpool: &Whirlpool,
+    position: &Position,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(position.whirlpool, false),
+            AccountMeta::new(position_address(program_id, &position.position_mint), false),
+            AccountMeta::new_readonly(
+                tick_array_address(
+                    program_id,
+                    &position.whirlpool,
+                    whirlpool.tick_array_start_index(position.tick_lower),
+                ),
+                false,
+            ),
+            AccountMeta::new_readonly(
+                tick_array_address(
+                    program_id,
+                    &position.whirlpool,
+                    whirlpool.tick_array_start_index(position.tick_upper),
+                ),
+                false,
+            ),
+        ],
+        data: anchor_discriminator("global:update_fees_and_rewards").to_vec(),
+    }
+}
+
+pub fn collect_fees(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool: &Whirlpool,
+    position: &Position,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(position.whirlpool, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(position_address(program_id, &position.position_mint), false),
+            AccountMeta::new_readonly(
+                associated_token_address(owner, &position.position_mint, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(
+                associated_token_address(owner, &whirlpool.token_mint_a, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(whirlpool.token_vault_a, false),
+            AccountMeta::new(
+                associated_token_address(owner, &whirlpool.token_mint_b, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(whirlpool.token_vault_b, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ],
+        data: anchor_discriminator("global:collect_fees").to_vec(),
+    }
+}
+
+pub fn collect_reward(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool: &Whirlpool,
+    position: &Position,
+    reward_index: u8,
+) -> Instruction {
+    let reward = &whirlpool.rewards[reward_index as usize];
+    let mut data = anchor_discriminator("global:collect_reward").to_vec();
+    data.push(reward_index);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(position.whirlpool, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(position_address(program_id, &position.position_mint), false),
+            AccountMeta::new_readonly(
+                associated_token_address(owner, &position.position_mint, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(
+                associated_token_address(owner, &reward.mint, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new(reward.vault, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ],
+        data,
+    }
+}
+
+// Burns the position NFT and closes the position account; liquidity, fees and
+// rewards must already have been withdrawn
+pub fn close_position(program_id: &Pubkey, owner: &Pubkey, position_mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*owner, false),
+            AccountMeta::new(position_address(program_id, position_mint), false),
+            AccountMeta::new(*position_mint, false),
+            AccountMeta::new(
+                associated_token_address(owner, position_mint, &TOKEN_PROGRAM_ID),
+                false,
+            ),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ],
+        data: anchor_discriminator("global:close_position").to_vec(),
+    }
+}
+
+// Exact-input swap. The program always takes three tick arrays in the
+// direction of travel; pass the same array again when fewer are initialized.
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool_id: &Pubkey,
+    whirlpool: &Whirlpool,
+    a_to_b: bool,
+    amount_in: u64,
+    min_amount_out: u64,
+    tick_arrays: [Pubkey; 3],
+) -> Instruction {
+    let sqrt_price_limit = if a_to_b {
+        MIN_SQRT_PRICE_X64
+    } else {
+        MAX_SQRT_PRICE_X64
+    };
+
+    let mut data = anchor_discriminator("global:swap").to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
+    // amount_specified_is_input
+    data.push(1);
+    data.push(a_to_b as u8);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*whirlpool_id, false),
+        AccountMeta::new(
+            associated_token_address(owner, &whirlpool.token_mint_a, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(whirlpool.token_vault_a, false),
+        AccountMeta::new(
+            associated_token_address(owner, &whirlpool.token_mint_b, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(whirlpool.token_vault_b, false),
+    ];
+    accounts.extend(
+        tick_arrays
+            .iter()
+            .map(|address| AccountMeta::new(*address, false)),
+    );
+    accounts.push(AccountMeta::new(
+        oracle_address(program_id, whirlpool_id),
+        false,
+    ));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+// Swap for pools with a Token-2022 mint: each side names its own token
+// program, and the transfer hook accounts of token a, then token b, follow
+// the oracle
+#[allow(clippy::too_many_arguments)]
+pub fn swap_v2(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    whirlpool_id: &Pubkey,
+    whirlpool: &Whirlpool,
+    token_programs: [Pubkey; 2],
+    a_to_b: bool,
+    amount_in: u64,
+    min_amount_out: u64,
+    tick_arrays: [Pubkey; 3],
+    hook_accounts: [Vec<AccountMeta>; 2],
+) -> Instruction {
+    let sqrt_price_limit = if a_to_b {
+        MIN_SQRT_PRICE_X64
+    } else {
+        MAX_SQRT_PRICE_X64
+    };
+
+    let mut data = anchor_discriminator("global:swap_v2").to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
+    // amount_specified_is_input
+    data.push(1);
+    data.push(a_to_b as u8);
+    // Option<RemainingAccountsInfo>: slices of (TransferHookA = 0 or
+    // TransferHookB = 1, number of accounts)
+    let slices: Vec<(u8, u8)> = hook_accounts
+        .iter()
+        .enumerate()
+        .filter(|(_, accounts)| !accounts.is_empty())
+        .map(|(kind, accounts)| (kind as u8, accounts.len() as u8))
+        .collect();
+    if slices.is_empty() {
+        data.push(0);
+    } else {
+        data.push(1);
+        data.extend_from_slice(&(slices.len() as u32).to_le_bytes());
+        for (kind, length) in slices {
+            data.push(kind);
+            data.push(length);
+        }
+    }
+
+    let [token_program_a, token_program_b] = token_programs;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(token_program_a, false),
+        AccountMeta::new_readonly(token_program_b, false),
+        AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*whirlpool_id, false),
+        AccountMeta::new_readonly(whirlpool.token_mint_a, false),
+        AccountMeta::new_readonly(whirlpool.token_mint_b, false),
+        AccountMeta::new(
+            associated_token_address(owner, &whirlpool.token_mint_a, &token_program_a),
+            false,
+        ),
+        AccountMeta::new(whirlpool.token_vault_a, false),
+        AccountMeta::new(
+            associated_token_address(owner, &whirlpool.token_mint_b, &token_program_b),
+            false,
+        ),
+        AccountMeta::new(whirlpool.token_vault_b, false),
+    ];
+    accounts.extend(
+        tick_arrays
+            .iter()
+            .map(|address| AccountMeta::new(*address, false)),
+    );
+    accounts.push(AccountMeta::new(
+        oracle_address(program_id, whirlpool_id),
+        false,
+    ));
+    accounts.extend(hook_accounts.into_iter().flatten());
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
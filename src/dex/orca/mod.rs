@@ -0,0 +1,579 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use super::layout::token_account_amount;
+use super::mints::MintCache;
+use super::programs::{associated_token_address, TOKEN_PROGRAM_ID};
+use super::quote::ConcentratedLiquidity;
+use super::token_2022::{transfer_hook_accounts, MintInfo, SwapTransferFees};
+use super::{
+    check_price_impact, check_trade_size, pair_key, send_transaction, tick_math, to_base_units,
+    DexClient, FeeAmounts, HarvestReceipt, LpPosition, PoolCache, RebalanceJournal,
+    RebalanceReceipt, SolBalance, SwapCurve, SwapEstimate, WalletAccounts, ORCA,
+};
+use crate::config::{OrcaConfig, TradingParams};
+use crate::tokens::TokenProgram;
+use crate::transactions::TransactionSender;
+use crate::units::{Price, Rounding, TokenAmount};
+
+mod fees;
+pub mod instructions;
+mod position;
+mod quote;
+mod rebalance;
+mod state;
+
+pub use position::to_lp_position;
+pub use quote::{quote_exact_in, SwapQuote};
+pub use state::{
+    Position, Tick, TickArray, Whirlpool, WhirlpoolReward, POSITION_LEN, TICK_ARRAY_LEN,
+    TICK_ARRAY_SIZE, WHIRLPOOL_LEN,
+};
+
+// Concentrated liquidity pools of the Orca Whirlpool program. Prices, swaps
+// and positions all go through the same pools.
+pub struct OrcaClient {
+    rpc_client: Arc<RpcClient>,
+    sender: Arc<TransactionSender>,
+    program_id: Pubkey,
+    whirlpools_config: Pubkey,
+    // Fee tiers searched when no tick spacing is given
+    tick_spacings: Vec<u16>,
+    wallet: Arc<Keypair>,
+    // Deepest whirlpool per mint pair, keyed with the smaller mint first
+    pools: Mutex<HashMap<(Pubkey, Pubkey), Pubkey>>,
+    // Whirlpools do not store mint decimals or token programs
+    mints: MintCache,
+    // Whirlpools and tick arrays last loaded for a swap, for local quotes
+    pool_states: PoolCache,
+    trading: TradingParams,
+    journal: RebalanceJournal,
+}
+
+// A whirlpool together with the decimals of its mints
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pool {
+    pub address: Pubkey,
+    pub whirlpool: Whirlpool,
+    pub decimals_a: u8,
+    pub decimals_b: u8,
+}
+
+impl Pool {
+    // Token b per token a in UI units
+    pub fn price(&self) -> f64 {
+        tick_math::sqrt_price_x64_to_price(
+            self.whirlpool.sqrt_price_x64,
+            self.decimals_a,
+            self.decimals_b,
+        )
+    }
+}
+
+impl OrcaClient {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        sender: Arc<TransactionSender>,
+        config: &OrcaConfig,
+        trading: TradingParams,
+        wallet: Arc<Keypair>,
+        pool_states: PoolCache,
+    ) -> Result<Self> {
+        let journal = RebalanceJournal::new(&config.rebalance_journal_path);
+        let pending = journal.pending()?;
+        if !pending.is_empty() {
+            warn!(
+                "Unfinished Orca rebalances for positions {}; they resume on the next rebalance of each",
+                pending.join(", ")
+            );
+        }
+
+        Ok(Self {
+            rpc_client,
+            sender,
+            program_id: Pubkey::from_str(&config.program_id)?,
+            whirlpools_config: Pubkey::from_str(&config.whirlpools_config)?,
+            tick_spacings: config.tick_spacings.clone(),
+            wallet,
+            pools: Mutex::new(HashMap::new()),
+            mints: MintCache::new(),
+            pool_states,
+            trading,
+            journal,
+        })
+    }
+
+    // Read as each operation starts, so reloaded values apply to the next one
+    fn slippage_tolerance(&self) -> f64 {
+        self.trading.borrow().slippage_tolerance
+    }
+
+    async fn send_transaction(
+        &self,
+        instructions: &[Instruction],
+        extra_signers: &[&Keypair],
+    ) -> Result<String> {
+        send_transaction(
+            &self.sender,
+            &self.wallet,
+            instructions,
+            extra_signers,
+            ORCA,
+        )
+        .await
+    }
+
+    async fn mint_decimals(&self, mints: &[Pubkey]) -> Result<Vec<u8>> {
+        self.mints.decimals(&self.rpc_client, mints).await
+    }
+
+    // Position instructions only move classic SPL tokens
+    async fn check_lp_supported(&self, pool: &Pool) -> Result<()> {
+        self.mints
+            .check_lp_supported(
+                &self.rpc_client,
+                &[pool.whirlpool.token_mint_a, pool.whirlpool.token_mint_b],
+                ORCA,
+            )
+            .await
+    }
+
+    async fn pool_from_whirlpool(&self, address: Pubkey, whirlpool: Whirlpool) -> Result<Pool> {
+        let decimals = self
+            .mint_decimals(&[whirlpool.token_mint_a, whirlpool.token_mint_b])
+            .await?;
+        Ok(Pool {
+            address,
+            whirlpool,
+            decimals_a: decimals[0],
+            decimals_b: decimals[1],
+        })
+    }
+
+    async fn load_pool(&self, address: &Pubkey) -> Result<Pool> {
+        let data = self.rpc_client.get_account_data(address).await?;
+        self.pool_from_whirlpool(*address, Whirlpool::decode(&data)?)
+            .await
+    }
+
+    // The whirlpool for the pair in the given fee tier, or the one with the
+    // most active liquidity across the configured tiers
+    pub async fn find_whirlpool(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        tick_spacing: Option<u16>,
+    ) -> Result<Pool> {
+        // Whirlpools require mint a to sort before mint b
+        let key = pair_key(token_a, token_b);
+        let (mint_a, mint_b) = key;
+        if let Some(tick_spacing) = tick_spacing {
+            let address = instructions::whirlpool_address(
+                &self.program_id,
+                &self.whirlpools_config,
+                &mint_a,
+                &mint_b,
+                tick_spacing,
+            );
+            return self.load_pool(&address).await.map_err(|e| {
+                anyhow!(
+                    "no Orca whirlpool for {}/{} with tick spacing {}: {}",
+                    token_a,
+                    token_b,
+                    tick_spacing,
+                    e
+                )
+            });
+        }
+
+        let cached = self.pools.lock().unwrap().get(&key).copied();
+        if let Some(address) = cached {
+            return self.load_pool(&address).await;
+        }
+
+        // Pool addresses are derived from the fee tier, so every configured
+        // tier can be checked in a single request
+        let addresses: Vec<Pubkey> = self
+            .tick_spacings
+            .iter()
+            .map(|tick_spacing| {
+                instructions::whirlpool_address(
+                    &self.program_id,
+                    &self.whirlpools_config,
+                    &mint_a,
+                    &mint_b,
+                    *tick_spacing,
+                )
+            })
+            .collect();
+        let accounts = self.rpc_client.get_multiple_accounts(&addresses).await?;
+        let (address, whirlpool) = addresses
+            .into_iter()
+            .zip(accounts)
+            .filter_map(|(address, account)| {
+                Whirlpool::decode(&account?.data)
+                    .ok()
+                    .map(|whirlpool| (address, whirlpool))
+            })
+            .max_by_key(|(_, whirlpool)| whirlpool.liquidity)
+            .ok_or_else(|| anyhow!("no Orca whirlpool found for {}/{}", token_a, token_b))?;
+
+        self.pools.lock().unwrap().insert(key, address);
+        self.pool_from_whirlpool(address, whirlpool).await
+    }
+
+    // The three tick array addresses a swap passes to the program, and the
+    // initialized ones among them, nearest first. The pool's curve over them
+    // is kept in the pool cache.
+    async fn swap_tick_arrays(
+        &self,
+        pool: &Pool,
+        a_to_b: bool,
+    ) -> Result<([Pubkey; 3], Vec<TickArray>)> {
+        let whirlpool = &pool.whirlpool;
+        let span = whirlpool.tick_spacing as i32 * TICK_ARRAY_SIZE;
+        // Moving up, the next tick may already be in the following array
+        let shift = if a_to_b {
+            0
+        } else {
+            whirlpool.tick_spacing as i32
+        };
+        let start = whirlpool.tick_array_start_index(whirlpool.tick_current + shift);
+        let step = if a_to_b { -span } else { span };
+        let candidates: Vec<Pubkey> = (0..3)
+            .map(|i| {
+                instructions::tick_array_address(&self.program_id, &pool.address, start + i * step)
+            })
+            .collect();
+
+        let accounts = self.rpc_client.get_multiple_accounts(&candidates).await?;
+        let mut addresses = Vec::new();
+        let mut arrays = Vec::new();
+        for (address, account) in candidates.into_iter().zip(accounts) {
+            let account = match account {
+                Some(account) => account,
+                None => break,
+            };
+            arrays.push(TickArray::decode(&account.data, whirlpool.tick_spacing)?);
+            addresses.push(address);
+        }
+        let last = *addresses.last().ok_or_else(|| {
+            anyhow!(
+                "whirlpool {} has no initialized tick array at the current price",
+                pool.address
+            )
+        })?;
+        addresses.resize(3, last);
+        self.pool_states.update(
+            ORCA,
+            pool.address,
+            whirlpool.token_mint_a,
+            whirlpool.token_mint_b,
+            SwapCurve::Concentrated(ConcentratedLiquidity::from_whirlpool(whirlpool, &arrays)),
+        );
+        Ok(([addresses[0], addresses[1], addresses[2]], arrays))
+    }
+
+    // Input and output mints of a swap through the pool, and the transfer
+    // fees they charge. Fails for tokens that are unsafe to trade.
+    async fn swap_mints(
+        &self,
+        pool: &Pool,
+        a_to_b: bool,
+    ) -> Result<(MintInfo, MintInfo, SwapTransferFees)> {
+        let mut mints = self
+            .mints
+            .info(
+                &self.rpc_client,
+                &[pool.whirlpool.token_mint_a, pool.whirlpool.token_mint_b],
+            )
+            .await?;
+        let (mint_b, mint_a) = (mints.pop().unwrap(), mints.pop().unwrap());
+        let (mint_in, mint_out) = if a_to_b {
+            (mint_a, mint_b)
+        } else {
+            (mint_b, mint_a)
+        };
+        let fees = SwapTransferFees::load(&self.rpc_client, &mint_in, &mint_out).await?;
+        Ok((mint_in, mint_out, fees))
+    }
+
+    // Simulates an exact-input swap of `amount_in` base units sent from the
+    // wallet. Amounts are what leaves and reaches the wallet, net of any
+    // Token-2022 transfer fees.
+    async fn quote_in_pool(
+        &self,
+        pool: &Pool,
+        a_to_b: bool,
+        amount_in: u64,
+        fees: &SwapTransferFees,
+    ) -> Result<([Pubkey; 3], SwapQuote)> {
+        let (tick_array_keys, tick_arrays) = self.swap_tick_arrays(pool, a_to_b).await?;
+        let mut quote = quote_exact_in(
+            &pool.whirlpool,
+            &tick_arrays,
+            fees.pool_input(amount_in),
+            a_to_b,
+        )?;
+        quote.amount_in = amount_in;
+        quote.amount_out = fees.received(quote.amount_out);
+        Ok((tick_array_keys, quote))
+    }
+
+    // Swaps `amount_in` base units through the pool and returns the quote it
+    // was checked against along with the signature
+    async fn swap_in_pool(
+        &self,
+        pool: &Pool,
+        a_to_b: bool,
+        amount_in: u64,
+        min_amount_out: u64,
+        sol: SolBalance,
+    ) -> Result<(SwapQuote, String)> {
+        let (mint_in, mint_out, fees) = self.swap_mints(pool, a_to_b).await?;
+        let (tick_array_keys, quote) = self.quote_in_pool(pool, a_to_b, amount_in, &fees).await?;
+        if quote.amount_out < min_amount_out {
+            return Err(anyhow!(
+                "Orca quote of {} is below the minimum output {}",
+                quote.amount_out,
+                min_amount_out
+            ));
+        }
+        // The program checks its minimum before the output fee is withheld
+        let pool_min_amount_out = fees.pool_min_output(min_amount_out)?;
+
+        let owner = self.wallet.pubkey();
+        let mut accounts = WalletAccounts::new(owner, sol);
+        let account_in = accounts.spend(&mint_in.mint, &mint_in.program.id(), amount_in);
+        let account_out = accounts.receive(&mint_out.mint, &mint_out.program.id());
+        let whirlpool = &pool.whirlpool;
+        let swap = if mint_in.program == TokenProgram::Spl && mint_out.program == TokenProgram::Spl
+        {
+            instructions::swap(
+                &self.program_id,
+                &owner,
+                &pool.address,
+                whirlpool,
+                a_to_b,
+                amount_in,
+                pool_min_amount_out,
+                tick_array_keys,
+            )
+        } else {
+            let (vault_in, vault_out) = if a_to_b {
+                (whirlpool.token_vault_a, whirlpool.token_vault_b)
+            } else {
+                (whirlpool.token_vault_b, whirlpool.token_vault_a)
+            };
+            // The output leaves the vault on the whirlpool's authority
+            let hook_in = transfer_hook_accounts(
+                &self.rpc_client,
+                &mint_in,
+                &account_in,
+                &vault_in,
+                &owner,
+                amount_in,
+            )
+            .await?;
+            let hook_out = transfer_hook_accounts(
+                &self.rpc_client,
+                &mint_out,
+                &vault_out,
+                &account_out,
+                &pool.address,
+                fees.pool_min_output(quote.amount_out)?,
+            )
+            .await?;
+            let (token_programs, hook_accounts) = if a_to_b {
+                (
+                    [mint_in.program.id(), mint_out.program.id()],
+                    [hook_in, hook_out],
+                )
+            } else {
+                (
+                    [mint_out.program.id(), mint_in.program.id()],
+                    [hook_out, hook_in],
+                )
+            };
+            instructions::swap_v2(
+                &self.program_id,
+                &owner,
+                &pool.address,
+                whirlpool,
+                token_programs,
+                a_to_b,
+                amount_in,
+                pool_min_amount_out,
+                tick_array_keys,
+                hook_accounts,
+            )
+        };
+        let signature = self.send_transaction(&accounts.build([swap]), &[]).await?;
+        Ok((quote, signature))
+    }
+
+    // Wallet balances of `mints`, in base units; missing accounts count as 0
+    async fn token_balances(&self, mints: &[Pubkey]) -> Result<Vec<u64>> {
+        let owner = self.wallet.pubkey();
+        let addresses: Vec<Pubkey> = mints
+            .iter()
+            .map(|mint| associated_token_address(&owner, mint, &TOKEN_PROGRAM_ID))
+            .collect();
+        self.rpc_client
+            .get_multiple_accounts(&addresses)
+            .await?
+            .into_iter()
+            .map(|account| account.map_or(Ok(0), |account| token_account_amount(&account.data)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DexClient for OrcaClient {
+    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Price> {
+        let pool = self.find_whirlpool(token_a, token_b, None).await?;
+        for info in self
+            .mints
+            .info(&self.rpc_client, &[*token_a, *token_b])
+            .await?
+        {
+            info.check_tradable()?;
+        }
+        if pool.whirlpool.token_mint_a == *token_a {
+            Price::from_f64(pool.price(), Rounding::Nearest)
+        } else {
+            Price::from_f64(pool.price(), Rounding::Nearest)?.invert(Rounding::Nearest)
+        }
+    }
+
+    async fn create_lp_position(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+        min_price: Price,
+        max_price: Price,
+    ) -> Result<String> {
+        self.open_whirlpool_position(
+            token_a, token_b, None, amount_a, amount_b, min_price, max_price,
+        )
+        .await
+    }
+
+    async fn rebalance_position(
+        &self,
+        position_id: &str,
+        new_min_price: Price,
+        new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        self.rebalance_whirlpool_position(position_id, new_min_price, new_max_price)
+            .await
+    }
+
+    async fn harvest_fees(&self, position_id: &str) -> Result<HarvestReceipt> {
+        self.harvest_whirlpool_fees(position_id).await
+    }
+
+    async fn get_unclaimed_fees(&self, position_id: &str) -> Result<FeeAmounts> {
+        self.whirlpool_unclaimed_fees(position_id).await
+    }
+
+    async fn increase_liquidity(
+        &self,
+        position_id: &str,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+    ) -> Result<String> {
+        self.increase_whirlpool_liquidity(position_id, amount_a, amount_b)
+            .await
+    }
+
+    async fn get_positions(&self) -> Result<Vec<LpPosition>> {
+        self.whirlpool_positions().await
+    }
+
+    async fn quote_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+    ) -> Result<SwapEstimate> {
+        let pool = self.find_whirlpool(token_in, token_out, None).await?;
+        let a_to_b = *token_in == pool.whirlpool.token_mint_a;
+        let (mint_in, mint_out, fees) = self.swap_mints(&pool, a_to_b).await?;
+        let (_, quote) = self
+            .quote_in_pool(
+                &pool,
+                a_to_b,
+                to_base_units(amount_in, mint_in.decimals)?,
+                &fees,
+            )
+            .await?;
+        Ok(SwapEstimate {
+            amount_in: TokenAmount::new(quote.amount_in, mint_in.decimals),
+            amount_out: TokenAmount::new(quote.amount_out, mint_out.decimals),
+            price_impact: quote.price_impact,
+        })
+    }
+
+    async fn execute_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+        min_amount_out: TokenAmount,
+    ) -> Result<String> {
+        check_trade_size(&self.trading.borrow(), amount_in)?;
+        let pool = self.find_whirlpool(token_in, token_out, None).await?;
+        let a_to_b = *token_in == pool.whirlpool.token_mint_a;
+        let (decimals_in, decimals_out) = if a_to_b {
+            (pool.decimals_a, pool.decimals_b)
+        } else {
+            (pool.decimals_b, pool.decimals_a)
+        };
+        let amount_in = to_base_units(amount_in, decimals_in)?;
+
+        // Price impact comes from the cached curve, read again once stale
+        if self
+            .pool_states
+            .quote_exact_in(&pool.address, token_in, token_out, amount_in)
+            .is_none()
+        {
+            self.swap_tick_arrays(&pool, a_to_b).await?;
+        }
+        let quote = self
+            .pool_states
+            .quote_exact_in(&pool.address, token_in, token_out, amount_in)
+            .ok_or_else(|| {
+                anyhow!(
+                    "swap of {} exceeds the liquidity of whirlpool {}",
+                    amount_in,
+                    pool.address
+                )
+            })?;
+        check_price_impact(
+            quote.price_impact,
+            self.trading.borrow().price_impact_threshold,
+        )?;
+
+        let (_, signature) = self
+            .swap_in_pool(
+                &pool,
+                a_to_b,
+                amount_in,
+                min_amount_out.base_units_at(decimals_out, Rounding::Up)?,
+                SolBalance::Native,
+            )
+            .await?;
+        Ok(signature)
+    }
+}
@@ -0,0 +1,435 @@
+use anyhow::{anyhow, Result};
+use solana_account_decoder::UiAccountData;
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::str::FromStr;
+use tracing::info;
+
+use super::{instructions, OrcaClient, Pool, Position};
+use crate::dex::programs::TOKEN_PROGRAM_ID;
+use crate::dex::{invert_range, tick_math, to_base_units, LpPosition, SolBalance, WalletAccounts};
+use crate::units::{Price, Rounding, TokenAmount};
+
+// Opening a position mints the NFT and may initialize tick arrays
+const OPEN_POSITION_COMPUTE_UNITS: u32 = 400_000;
+// Float liquidity math can overshoot by a few ulps; shave it so the amounts the
+// program pulls never exceed the maxima we pass
+const LIQUIDITY_SAFETY_FACTOR: f64 = 1.0 - 1e-9;
+
+impl OrcaClient {
+    pub(super) async fn load_position(&self, position_id: &str) -> Result<(Position, Pool)> {
+        let position_mint = Pubkey::from_str(position_id)
+            .map_err(|_| anyhow!("invalid Orca position id {}", position_id))?;
+        let address = instructions::position_address(&self.program_id, &position_mint);
+        let data = self
+            .rpc_client
+            .get_account_data(&address)
+            .await
+            .map_err(|e| anyhow!("Orca position {} not found: {}", position_id, e))?;
+        let position = Position::decode(&data)?;
+        let pool = self.load_pool(&position.whirlpool).await?;
+        Ok((position, pool))
+    }
+
+    // Opens a ranged position funded with up to `amount_a`/`amount_b` in the
+    // given fee tier, or the deepest one. Prices are token_b per token_a.
+    // Returns the position NFT mint.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn open_whirlpool_position(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        tick_spacing: Option<u16>,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+        min_price: Price,
+        max_price: Price,
+    ) -> Result<String> {
+        let pool = self.find_whirlpool(token_a, token_b, tick_spacing).await?;
+        self.check_lp_supported(&pool).await?;
+
+        // Express everything in the pool's token a / token b orientation
+        let (amount_a, amount_b, (min_price, max_price)) =
+            if *token_a == pool.whirlpool.token_mint_a {
+                (amount_a, amount_b, (min_price, max_price))
+            } else {
+                (amount_b, amount_a, invert_range(min_price, max_price)?)
+            };
+        let (tick_lower, tick_upper) = tick_math::tick_range(
+            min_price.to_f64(),
+            max_price.to_f64(),
+            pool.decimals_a,
+            pool.decimals_b,
+            pool.whirlpool.tick_spacing,
+        )?;
+
+        let (position_mint, _) = self
+            .open_position_in_pool(
+                &pool,
+                tick_lower,
+                tick_upper,
+                to_base_units(amount_a, pool.decimals_a)?,
+                to_base_units(amount_b, pool.decimals_b)?,
+                SolBalance::Native,
+            )
+            .await?;
+        Ok(position_mint.to_string())
+    }
+
+    // Opens the position and deposits into it in one transaction, creating
+    // the tick arrays at either bound if nobody has yet. Returns the position
+    // NFT mint and the signature.
+    pub(super) async fn open_position_in_pool(
+        &self,
+        pool: &Pool,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount_a_max: u64,
+        amount_b_max: u64,
+        sol: SolBalance,
+    ) -> Result<(Pubkey, String)> {
+        let liquidity = liquidity_for(pool, tick_lower, tick_upper, amount_a_max, amount_b_max)?;
+        let owner = self.wallet.pubkey();
+        let mut accounts = WalletAccounts::new(owner, sol);
+        accounts.spend(
+            &pool.whirlpool.token_mint_a,
+            &TOKEN_PROGRAM_ID,
+            amount_a_max,
+        );
+        accounts.spend(
+            &pool.whirlpool.token_mint_b,
+            &TOKEN_PROGRAM_ID,
+            amount_b_max,
+        );
+
+        let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            OPEN_POSITION_COMPUTE_UNITS,
+        )];
+        ixs.extend(
+            self.missing_tick_arrays(pool, tick_lower, tick_upper)
+                .await?,
+        );
+
+        let position_mint = Keypair::new();
+        ixs.push(instructions::open_position(
+            &self.program_id,
+            &owner,
+            &pool.address,
+            &position_mint.pubkey(),
+            tick_lower,
+            tick_upper,
+        ));
+        ixs.push(instructions::increase_liquidity(
+            &self.program_id,
+            &owner,
+            &pool.address,
+            &pool.whirlpool,
+            &position_mint.pubkey(),
+            tick_lower,
+            tick_upper,
+            liquidity,
+            amount_a_max,
+            amount_b_max,
+        ));
+        let signature = self
+            .send_transaction(&accounts.build(ixs), &[&position_mint])
+            .await?;
+
+        info!(
+            "Opened Orca position {} in whirlpool {} (ticks {}..{}, liquidity {}): {}",
+            position_mint.pubkey(),
+            pool.address,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            signature
+        );
+        Ok((position_mint.pubkey(), signature))
+    }
+
+    async fn missing_tick_arrays(
+        &self,
+        pool: &Pool,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<Vec<Instruction>> {
+        let mut starts = vec![pool.whirlpool.tick_array_start_index(tick_lower)];
+        let upper_start = pool.whirlpool.tick_array_start_index(tick_upper);
+        if upper_start != starts[0] {
+            starts.push(upper_start);
+        }
+
+        let addresses: Vec<Pubkey> = starts
+            .iter()
+            .map(|start| instructions::tick_array_address(&self.program_id, &pool.address, *start))
+            .collect();
+        let accounts = self.rpc_client.get_multiple_accounts(&addresses).await?;
+        Ok(starts
+            .into_iter()
+            .zip(accounts)
+            .filter(|(_, account)| account.is_none())
+            .map(|(start, _)| {
+                instructions::initialize_tick_array(
+                    &self.program_id,
+                    &self.wallet.pubkey(),
+                    &pool.address,
+                    start,
+                )
+            })
+            .collect())
+    }
+
+    // Adds up to `amount_a`/`amount_b` of the pool's tokens to a position
+    pub async fn increase_whirlpool_liquidity(
+        &self,
+        position_id: &str,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+    ) -> Result<String> {
+        let (position, pool) = self.load_position(position_id).await?;
+        self.check_lp_supported(&pool).await?;
+        let amount_a_max = to_base_units(amount_a, pool.decimals_a)?;
+        let amount_b_max = to_base_units(amount_b, pool.decimals_b)?;
+        let liquidity = liquidity_for(
+            &pool,
+            position.tick_lower,
+            position.tick_upper,
+            amount_a_max,
+            amount_b_max,
+        )?;
+
+        let owner = self.wallet.pubkey();
+        let mut accounts = WalletAccounts::new(owner, SolBalance::Native);
+        accounts.spend(
+            &pool.whirlpool.token_mint_a,
+            &TOKEN_PROGRAM_ID,
+            amount_a_max,
+        );
+        accounts.spend(
+            &pool.whirlpool.token_mint_b,
+            &TOKEN_PROGRAM_ID,
+            amount_b_max,
+        );
+        let instruction = instructions::increase_liquidity(
+            &self.program_id,
+            &owner,
+            &pool.address,
+            &pool.whirlpool,
+            &position.position_mint,
+            position.tick_lower,
+            position.tick_upper,
+            liquidity,
+            amount_a_max,
+            amount_b_max,
+        );
+        self.send_transaction(&accounts.build([instruction]), &[])
+            .await
+    }
+
+    // Withdraws `fraction` (0..=1) of the position's liquidity, failing if the
+    // amounts received fall more than `slippage_tolerance` below the current quote
+    pub async fn decrease_whirlpool_liquidity(
+        &self,
+        position_id: &str,
+        fraction: f64,
+        slippage_tolerance: f64,
+    ) -> Result<String> {
+        if fraction.is_nan() || fraction <= 0.0 || fraction > 1.0 {
+            return Err(anyhow!("fraction must be in (0, 1], got {}", fraction));
+        }
+        let (position, pool) = self.load_position(position_id).await?;
+        let liquidity = if fraction == 1.0 {
+            position.liquidity
+        } else {
+            (position.liquidity as f64 * fraction) as u128
+        };
+
+        let instruction =
+            self.decrease_instruction(&pool, &position, liquidity, slippage_tolerance);
+        self.send_transaction(&[instruction], &[]).await
+    }
+
+    // Withdraws any remaining liquidity, collects fees and rewards, then burns
+    // the position NFT
+    pub async fn close_whirlpool_position(
+        &self,
+        position_id: &str,
+        slippage_tolerance: f64,
+    ) -> Result<String> {
+        let (position, pool) = self.load_position(position_id).await?;
+        let ixs =
+            self.withdraw_instructions(&pool, &position, slippage_tolerance, SolBalance::Native);
+        self.send_transaction(&ixs, &[]).await
+    }
+
+    // Everything needed to empty and close a position in one transaction,
+    // including the wallet accounts the tokens and rewards are paid into
+    pub(crate) fn withdraw_instructions(
+        &self,
+        pool: &Pool,
+        position: &Position,
+        slippage_tolerance: f64,
+        sol: SolBalance,
+    ) -> Vec<Instruction> {
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), sol);
+        let mut ixs = Vec::new();
+        if position.liquidity > 0 {
+            ixs.push(self.decrease_instruction(
+                pool,
+                position,
+                position.liquidity,
+                slippage_tolerance,
+            ));
+        }
+        ixs.extend(self.collect_instructions(&mut accounts, pool, position));
+        ixs.push(instructions::close_position(
+            &self.program_id,
+            &self.wallet.pubkey(),
+            &position.position_mint,
+        ));
+        accounts.build(ixs)
+    }
+
+    // Collects the fees and every reward owed to the position. The pool's
+    // tokens and the rewards are received through `accounts`, as the wallet
+    // may not hold them, or may have unwrapped its SOL, when this runs.
+    pub(super) fn collect_instructions(
+        &self,
+        accounts: &mut WalletAccounts,
+        pool: &Pool,
+        position: &Position,
+    ) -> Vec<Instruction> {
+        let owner = self.wallet.pubkey();
+        accounts.receive(&pool.whirlpool.token_mint_a, &TOKEN_PROGRAM_ID);
+        accounts.receive(&pool.whirlpool.token_mint_b, &TOKEN_PROGRAM_ID);
+        let mut ixs = vec![instructions::collect_fees(
+            &self.program_id,
+            &owner,
+            &pool.whirlpool,
+            position,
+        )];
+        for (index, reward) in pool.whirlpool.rewards.iter().enumerate() {
+            accounts.receive(&reward.mint, &TOKEN_PROGRAM_ID);
+            ixs.push(instructions::collect_reward(
+                &self.program_id,
+                &owner,
+                &pool.whirlpool,
+                position,
+                index as u8,
+            ));
+        }
+        ixs
+    }
+
+    fn decrease_instruction(
+        &self,
+        pool: &Pool,
+        position: &Position,
+        liquidity: u128,
+        slippage_tolerance: f64,
+    ) -> Instruction {
+        let (amount_a, amount_b) = tick_math::amounts_for_liquidity(
+            pool.whirlpool.sqrt_price(),
+            tick_math::sqrt_price_at_tick(position.tick_lower),
+            tick_math::sqrt_price_at_tick(position.tick_upper),
+            liquidity,
+        );
+        let keep = (1.0 - slippage_tolerance).clamp(0.0, 1.0);
+        instructions::decrease_liquidity(
+            &self.program_id,
+            &self.wallet.pubkey(),
+            &pool.whirlpool,
+            position,
+            liquidity,
+            (amount_a as f64 * keep) as u64,
+            (amount_b as f64 * keep) as u64,
+        )
+    }
+
+    // Positions are found through the NFTs held by the wallet
+    pub(super) async fn whirlpool_positions(&self) -> Result<Vec<LpPosition>> {
+        let owner = self.wallet.pubkey();
+        let token_accounts = self
+            .rpc_client
+            .get_token_accounts_by_owner(&owner, TokenAccountsFilter::ProgramId(TOKEN_PROGRAM_ID))
+            .await?;
+
+        // Position NFTs are the only token accounts holding exactly one indivisible token
+        let mut position_addresses = Vec::new();
+        for keyed in token_accounts {
+            if let UiAccountData::Json(parsed) = keyed.account.data {
+                let info = &parsed.parsed["info"];
+                let amount = &info["tokenAmount"];
+                if amount["amount"].as_str() != Some("1") || amount["decimals"].as_u64() != Some(0)
+                {
+                    continue;
+                }
+                if let Some(mint) = info["mint"].as_str().and_then(|m| Pubkey::from_str(m).ok()) {
+                    position_addresses
+                        .push(instructions::position_address(&self.program_id, &mint));
+                }
+            }
+        }
+
+        let mut positions = Vec::new();
+        for chunk in position_addresses.chunks(100) {
+            let accounts = self.rpc_client.get_multiple_accounts(chunk).await?;
+            for account in accounts.into_iter().flatten() {
+                if account.owner != self.program_id {
+                    continue;
+                }
+                let position = Position::decode(&account.data)?;
+                let pool = self.load_pool(&position.whirlpool).await?;
+                positions.push(to_lp_position(&position, &pool)?);
+            }
+        }
+        Ok(positions)
+    }
+}
+
+pub fn to_lp_position(position: &Position, pool: &Pool) -> Result<LpPosition> {
+    let (amount_a, amount_b) = position.amounts(&pool.whirlpool);
+    let tick_price = |tick: i32| {
+        Price::from_f64(
+            tick_math::tick_to_price(tick, pool.decimals_a, pool.decimals_b),
+            Rounding::Nearest,
+        )
+    };
+    Ok(LpPosition {
+        id: position.position_mint.to_string(),
+        token_a: pool.whirlpool.token_mint_a,
+        token_b: pool.whirlpool.token_mint_b,
+        min_price: tick_price(position.tick_lower)?,
+        max_price: tick_price(position.tick_upper)?,
+        amount_a: TokenAmount::new(amount_a, pool.decimals_a),
+        amount_b: TokenAmount::new(amount_b, pool.decimals_b),
+    })
+}
+
+fn liquidity_for(
+    pool: &Pool,
+    tick_lower: i32,
+    tick_upper: i32,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<u128> {
+    let liquidity = tick_math::liquidity_for_amounts(
+        pool.whirlpool.sqrt_price(),
+        tick_math::sqrt_price_at_tick(tick_lower),
+        tick_math::sqrt_price_at_tick(tick_upper),
+        amount_a,
+        amount_b,
+    );
+    let liquidity = (liquidity as f64 * LIQUIDITY_SAFETY_FACTOR) as u128;
+    if liquidity == 0 {
+        return Err(anyhow!(
+            "amounts are too small to add liquidity in ticks {}..{}",
+            tick_lower,
+            tick_upper
+        ));
+    }
+    Ok(liquidity)
+}
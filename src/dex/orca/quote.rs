@@ -0,0 +1,37 @@
+use anyhow::Result;
+
+use super::state::{TickArray, Whirlpool};
+use crate::dex::quote::ConcentratedLiquidity;
+
+// Expected result of an exact-input swap, in base units
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapQuote {
+    pub a_to_b: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+    pub end_sqrt_price: f64,
+    // Shortfall against the spot price, fees included
+    pub price_impact: f64,
+}
+
+// Simulates a swap through the pool's liquidity, crossing the initialized
+// ticks of `tick_arrays` until the input is used up. Fails if the input
+// would move the price past the last loaded tick.
+pub fn quote_exact_in(
+    whirlpool: &Whirlpool,
+    tick_arrays: &[TickArray],
+    amount_in: u64,
+    a_to_b: bool,
+) -> Result<SwapQuote> {
+    let quote = ConcentratedLiquidity::from_whirlpool(whirlpool, tick_arrays)
+        .exact_in(amount_in, a_to_b)?;
+    Ok(SwapQuote {
+        a_to_b,
+        amount_in,
+        amount_out: quote.amount_out,
+        fee_amount: quote.fee_amount,
+        end_sqrt_price: quote.end_price.sqrt(),
+        price_impact: quote.price_impact,
+    })
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use tracing::info;
+
+use super::{OrcaClient, Pool};
+use crate::dex::rebalance::{rebalance_swap, swap_cost, RebalanceProgress, Stage};
+use crate::dex::{tick_math, RebalanceReceipt, SolBalance, SwapLeg};
+use crate::units::{Price, Rounding, TokenAmount};
+
+impl OrcaClient {
+    // Withdraws the old position and collects its fees and rewards in one
+    // transaction, swaps the proceeds to the new range's ratio, then opens
+    // the new position. Progress is journaled like the Raydium rebalance.
+    pub(super) async fn rebalance_whirlpool_position(
+        &self,
+        position_id: &str,
+        new_min_price: Price,
+        new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        let mut progress = match self.journal.get(position_id)? {
+            Some(progress) => {
+                info!(
+                    "Resuming rebalance of Orca position {} after {:?}",
+                    position_id, progress.stage
+                );
+                progress
+            }
+            None => self.withdraw_for_rebalance(position_id).await?,
+        };
+        let pool_id = Pubkey::from_str(&progress.pool_id)?;
+
+        let pool = self.load_pool(&pool_id).await?;
+        let (tick_lower, tick_upper) = tick_math::tick_range(
+            new_min_price.to_f64(),
+            new_max_price.to_f64(),
+            pool.decimals_a,
+            pool.decimals_b,
+            pool.whirlpool.tick_spacing,
+        )?;
+
+        if progress.stage == Stage::Withdrawn {
+            self.swap_to_range(&pool, tick_lower, tick_upper, &mut progress)
+                .await?;
+            progress.stage = Stage::Swapped;
+            self.journal.put(position_id, &progress)?;
+        }
+
+        let pool = self.load_pool(&pool_id).await?;
+        let mints = [pool.whirlpool.token_mint_a, pool.whirlpool.token_mint_b];
+        let before = self.token_balances(&mints).await?;
+        let (position_mint, signature) = self
+            .open_position_in_pool(
+                &pool,
+                tick_lower,
+                tick_upper,
+                progress.available_0,
+                progress.available_1,
+                SolBalance::Wrapped,
+            )
+            .await?;
+        let after = self.token_balances(&mints).await?;
+
+        let mut receipt = progress.receipt;
+        receipt.new_position_id = position_mint.to_string();
+        receipt.deposited_a = TokenAmount::new(before[0].saturating_sub(after[0]), pool.decimals_a);
+        receipt.deposited_b = TokenAmount::new(before[1].saturating_sub(after[1]), pool.decimals_b);
+        receipt.signatures.push(signature);
+        self.journal.remove(position_id)?;
+
+        info!(
+            "Rebalanced Orca position {} into {} (fees {} / {}, swap cost {})",
+            receipt.old_position_id,
+            receipt.new_position_id,
+            receipt.fees_a,
+            receipt.fees_b,
+            receipt.swap_cost
+        );
+        Ok(receipt)
+    }
+
+    async fn withdraw_for_rebalance(&self, position_id: &str) -> Result<RebalanceProgress> {
+        let (position, pool) = self.load_position(position_id).await?;
+        let (principal_a, principal_b) = position.amounts(&pool.whirlpool);
+
+        let mints = [pool.whirlpool.token_mint_a, pool.whirlpool.token_mint_b];
+        let before = self.token_balances(&mints).await?;
+        let ixs = self.withdraw_instructions(
+            &pool,
+            &position,
+            self.slippage_tolerance(),
+            SolBalance::Wrapped,
+        );
+        let signature = self.send_transaction(&ixs, &[]).await?;
+        let after = self.token_balances(&mints).await?;
+
+        // Whatever arrived beyond the principal is fees collected with it
+        let received_a = after[0].saturating_sub(before[0]);
+        let received_b = after[1].saturating_sub(before[1]);
+        let progress = RebalanceProgress {
+            pool_id: position.whirlpool.to_string(),
+            stage: Stage::Withdrawn,
+            available_0: received_a,
+            available_1: received_b,
+            sent_swap: None,
+            receipt: RebalanceReceipt {
+                old_position_id: position_id.to_string(),
+                withdrawn_a: TokenAmount::new(received_a.min(principal_a), pool.decimals_a),
+                withdrawn_b: TokenAmount::new(received_b.min(principal_b), pool.decimals_b),
+                fees_a: TokenAmount::new(received_a.saturating_sub(principal_a), pool.decimals_a),
+                fees_b: TokenAmount::new(received_b.saturating_sub(principal_b), pool.decimals_b),
+                swap: None,
+                swap_cost: TokenAmount::zero(pool.decimals_b),
+                deposited_a: TokenAmount::zero(pool.decimals_a),
+                deposited_b: TokenAmount::zero(pool.decimals_b),
+                signatures: vec![signature],
+            },
+        };
+        self.journal.put(position_id, &progress)?;
+        Ok(progress)
+    }
+
+    async fn swap_to_range(
+        &self,
+        pool: &Pool,
+        tick_lower: i32,
+        tick_upper: i32,
+        progress: &mut RebalanceProgress,
+    ) -> Result<()> {
+        let (a_to_b, amount_in) = match rebalance_swap(
+            progress.available_0,
+            progress.available_1,
+            pool.whirlpool.sqrt_price(),
+            tick_math::sqrt_price_at_tick(tick_lower),
+            tick_math::sqrt_price_at_tick(tick_upper),
+        ) {
+            Some(swap) => swap,
+            None => return Ok(()),
+        };
+
+        let raw_price = pool.whirlpool.sqrt_price() * pool.whirlpool.sqrt_price();
+        let expected_out = if a_to_b {
+            amount_in as f64 * raw_price
+        } else {
+            amount_in as f64 / raw_price
+        };
+        let min_amount_out = (expected_out * (1.0 - self.slippage_tolerance())).max(0.0) as u64;
+
+        let mints = [pool.whirlpool.token_mint_a, pool.whirlpool.token_mint_b];
+        let before = self.token_balances(&mints).await?;
+        let (_, signature) = self
+            .swap_in_pool(pool, a_to_b, amount_in, min_amount_out, SolBalance::Wrapped)
+            .await?;
+        let after = self.token_balances(&mints).await?;
+
+        let (spent, received) = if a_to_b {
+            let spent = before[0].saturating_sub(after[0]);
+            let received = after[1].saturating_sub(before[1]);
+            progress.available_0 = progress.available_0.saturating_sub(spent);
+            progress.available_1 += received;
+            (
+                TokenAmount::new(spent, pool.decimals_a),
+                TokenAmount::new(received, pool.decimals_b),
+            )
+        } else {
+            let spent = before[1].saturating_sub(after[1]);
+            let received = after[0].saturating_sub(before[0]);
+            progress.available_1 = progress.available_1.saturating_sub(spent);
+            progress.available_0 += received;
+            (
+                TokenAmount::new(spent, pool.decimals_b),
+                TokenAmount::new(received, pool.decimals_a),
+            )
+        };
+
+        let receipt = &mut progress.receipt;
+        receipt.swap_cost = swap_cost(
+            Price::from_f64(pool.price(), Rounding::Nearest)?,
+            a_to_b,
+            spent,
+            received,
+        )?;
+        receipt.swap = Some(SwapLeg {
+            a_to_b,
+            amount_in: spent,
+            amount_out: received,
+        });
+        receipt.signatures.push(signature);
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::dex::layout::{
+    check_anchor_account, read_i128, read_i32, read_pubkey, read_u128, read_u16, read_u64, read_u8,
+};
+use crate::dex::tick_math;
+
+pub const WHIRLPOOL_LEN: usize = 653;
+pub const POSITION_LEN: usize = 216;
+pub const TICK_ARRAY_SIZE: i32 = 88;
+pub const TICK_ARRAY_LEN: usize = 9988;
+pub const NUM_REWARDS: usize = 3;
+
+const WHIRLPOOL_REWARDS: usize = 269;
+const WHIRLPOOL_REWARD_LEN: usize = 128;
+const POSITION_REWARDS: usize = 144;
+const POSITION_REWARD_LEN: usize = 24;
+const TICK_ARRAY_TICKS: usize = 12;
+const TICK_LEN: usize = 113;
+
+// Fee rates are stored in hundredths of a basis point
+const FEE_RATE_DENOMINATOR: f64 = 1_000_000.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhirlpoolReward {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub growth_global_x64: u128,
+}
+
+// Offsets include the 8 byte Anchor discriminator
+#[derive(Debug, Clone, PartialEq)]
+pub struct Whirlpool {
+    pub whirlpools_config: Pubkey,
+    pub tick_spacing: u16,
+    pub fee_rate: u16,
+    pub liquidity: u128,
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    pub token_mint_a: Pubkey,
+    pub token_vault_a: Pubkey,
+    pub fee_growth_global_a_x64: u128,
+    pub token_mint_b: Pubkey,
+    pub token_vault_b: Pubkey,
+    pub fee_growth_global_b_x64: u128,
+    // Only rewards with a mint set are initialized
+    pub rewards: Vec<WhirlpoolReward>,
+}
+
+impl Whirlpool {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != WHIRLPOOL_LEN {
+            return Err(anyhow!(
+                "not a whirlpool account: expected {} bytes, got {}",
+                WHIRLPOOL_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "Whirlpool")?;
+
+        let tick_spacing = read_u16(data, 41)?;
+        if tick_spacing == 0 {
+            return Err(anyhow!("whirlpool has a tick spacing of 0"));
+        }
+        let mut rewards = Vec::new();
+        for i in 0..NUM_REWARDS {
+            let offset = WHIRLPOOL_REWARDS + i * WHIRLPOOL_REWARD_LEN;
+            let mint = read_pubkey(data, offset)?;
+            if mint != Pubkey::default() {
+                rewards.push(WhirlpoolReward {
+                    mint,
+                    vault: read_pubkey(data, offset + 32)?,
+                    growth_global_x64: read_u128(data, offset + 112)?,
+                });
+            }
+        }
+        Ok(Self {
+            whirlpools_config: read_pubkey(data, 8)?,
+            tick_spacing,
+            fee_rate: read_u16(data, 45)?,
+            liquidity: read_u128(data, 49)?,
+            sqrt_price_x64: read_u128(data, 65)?,
+            tick_current: read_i32(data, 81)?,
+            token_mint_a: read_pubkey(data, 101)?,
+            token_vault_a: read_pubkey(data, 133)?,
+            fee_growth_global_a_x64: read_u128(data, 165)?,
+            token_mint_b: read_pubkey(data, 181)?,
+            token_vault_b: read_pubkey(data, 213)?,
+            fee_growth_global_b_x64: read_u128(data, 245)?,
+            rewards,
+        })
+    }
+
+    pub fn sqrt_price(&self) -> f64 {
+        tick_math::sqrt_price_from_x64(self.sqrt_price_x64)
+    }
+
+    pub fn fee_rate(&self) -> f64 {
+        self.fee_rate as f64 / FEE_RATE_DENOMINATOR
+    }
+
+    pub fn tick_array_start_index(&self, tick: i32) -> i32 {
+        tick_math::tick_array_start_index(tick, self.tick_spacing, TICK_ARRAY_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub whirlpool: Pubkey,
+    pub position_mint: Pubkey,
+    pub liquidity: u128,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub fee_growth_checkpoint_a_x64: u128,
+    pub fee_owed_a: u64,
+    pub fee_growth_checkpoint_b_x64: u128,
+    pub fee_owed_b: u64,
+    pub reward_owed: [u64; NUM_REWARDS],
+}
+
+impl Position {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != POSITION_LEN {
+            return Err(anyhow!(
+                "not a whirlpool position account: expected {} bytes, got {}",
+                POSITION_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "Position")?;
+
+        let mut reward_owed = [0; NUM_REWARDS];
+        for (i, owed) in reward_owed.iter_mut().enumerate() {
+            *owed = read_u64(data, POSITION_REWARDS + i * POSITION_REWARD_LEN + 16)?;
+        }
+        Ok(Self {
+            whirlpool: read_pubkey(data, 8)?,
+            position_mint: read_pubkey(data, 40)?,
+            liquidity: read_u128(data, 72)?,
+            tick_lower: read_i32(data, 88)?,
+            tick_upper: read_i32(data, 92)?,
+            fee_growth_checkpoint_a_x64: read_u128(data, 96)?,
+            fee_owed_a: read_u64(data, 112)?,
+            fee_growth_checkpoint_b_x64: read_u128(data, 120)?,
+            fee_owed_b: read_u64(data, 136)?,
+            reward_owed,
+        })
+    }
+
+    // Base unit amounts of token a and token b the position holds at the pool price
+    pub fn amounts(&self, whirlpool: &Whirlpool) -> (u64, u64) {
+        tick_math::amounts_for_liquidity(
+            whirlpool.sqrt_price(),
+            tick_math::sqrt_price_at_tick(self.tick_lower),
+            tick_math::sqrt_price_at_tick(self.tick_upper),
+            self.liquidity,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tick {
+    pub index: i32,
+    pub liquidity_net: i128,
+    pub fee_growth_outside_a_x64: u128,
+    pub fee_growth_outside_b_x64: u128,
+}
+
+// The initialized ticks of one tick array account
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickArray {
+    pub start_tick_index: i32,
+    pub ticks: Vec<Tick>,
+}
+
+impl TickArray {
+    pub fn decode(data: &[u8], tick_spacing: u16) -> Result<Self> {
+        if data.len() != TICK_ARRAY_LEN {
+            return Err(anyhow!(
+                "not a whirlpool tick array: expected {} bytes, got {}",
+                TICK_ARRAY_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "TickArray")?;
+
+        let start_tick_index = read_i32(data, 8)?;
+        let mut ticks = Vec::new();
+        for i in 0..TICK_ARRAY_SIZE as usize {
+            let offset = TICK_ARRAY_TICKS + i * TICK_LEN;
+            if read_u8(data, offset)? == 0 {
+                continue;
+            }
+            ticks.push(Tick {
+                index: start_tick_index + i as i32 * tick_spacing as i32,
+                liquidity_net: read_i128(data, offset + 1)?,
+                fee_growth_outside_a_x64: read_u128(data, offset + 33)?,
+                fee_growth_outside_b_x64: read_u128(data, offset + 49)?,
+            });
+        }
+        Ok(Self {
+            start_tick_index,
+            ticks,
+        })
+    }
+
+    pub fn tick(&self, index: i32) -> Result<&Tick> {
+        self.ticks
+            .iter()
+            .find(|tick| tick.index == index)
+            .ok_or_else(|| anyhow!("tick {} is not initialized", index))
+    }
+}
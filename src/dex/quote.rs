@@ -0,0 +1,540 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::orca::{TickArray, Whirlpool};
+use super::raydium::clmm::{PoolState, TickArrayState};
+use super::tick_math;
+use super::PoolInfo;
+
+// Pool state older than this is not quoted from; the venue reads it again
+// the next time it prices or swaps through the pool
+pub const MAX_POOL_STATE_AGE: Duration = Duration::from_secs(30);
+
+// Result of a swap simulated from pool state, in base units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+    // Raw price (token_b base units per token_a base unit) after the swap
+    pub end_price: f64,
+    // Shortfall against the spot price, fees included
+    pub price_impact: f64,
+}
+
+impl Quote {
+    fn new(
+        amount_in: u64,
+        amount_out: u64,
+        fee_amount: u64,
+        spot_price: f64,
+        end_price: f64,
+        a_to_b: bool,
+    ) -> Self {
+        let spot_out = if a_to_b {
+            amount_in as f64 * spot_price
+        } else {
+            amount_in as f64 / spot_price
+        };
+        Self {
+            amount_in,
+            amount_out,
+            fee_amount,
+            end_price,
+            price_impact: if spot_out > 0.0 {
+                1.0 - amount_out as f64 / spot_out
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+// Fails when a swap moves the price further than `threshold` allows
+pub fn check_price_impact(price_impact: f64, threshold: f64) -> Result<()> {
+    if price_impact > threshold {
+        return Err(anyhow!(
+            "price impact {:.4}% exceeds the threshold of {:.4}%",
+            price_impact * 100.0,
+            threshold * 100.0
+        ));
+    }
+    Ok(())
+}
+
+// x * y = k pool with the fee taken from the input, as Raydium AMM v4
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantProduct {
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    pub fee_rate: f64,
+}
+
+impl ConstantProduct {
+    pub fn from_pool_info(info: &PoolInfo) -> Self {
+        Self {
+            reserve_a: info.reserve_a.base_units,
+            reserve_b: info.reserve_b.base_units,
+            fee_rate: info.fee_rate,
+        }
+    }
+
+    fn reserves(&self, a_to_b: bool) -> (u128, u128) {
+        if a_to_b {
+            (self.reserve_a as u128, self.reserve_b as u128)
+        } else {
+            (self.reserve_b as u128, self.reserve_a as u128)
+        }
+    }
+
+    // Fee charged on `amount_in`, rounded up as the program does
+    fn fee(&self, amount_in: u64) -> u64 {
+        ((amount_in as f64 * self.fee_rate).ceil() as u64).min(amount_in)
+    }
+
+    fn quote(&self, amount_in: u64, amount_out: u64, a_to_b: bool) -> Quote {
+        let (reserve_in, reserve_out) = self.reserves(a_to_b);
+        let fee = self.fee(amount_in);
+        let end_in = (reserve_in + (amount_in - fee) as u128) as f64;
+        let end_out = (reserve_out - amount_out as u128) as f64;
+        let spot_price = self.reserve_b as f64 / self.reserve_a as f64;
+        let end_price = if a_to_b {
+            end_out / end_in
+        } else {
+            end_in / end_out
+        };
+        Quote::new(amount_in, amount_out, fee, spot_price, end_price, a_to_b)
+    }
+
+    pub fn exact_in(&self, amount_in: u64, a_to_b: bool) -> Result<Quote> {
+        let (reserve_in, reserve_out) = self.reserves(a_to_b);
+        if reserve_in == 0 || reserve_out == 0 {
+            return Err(anyhow!("pool has no liquidity"));
+        }
+        let net = (amount_in - self.fee(amount_in)) as u128;
+        let amount_out = (reserve_out * net / (reserve_in + net)) as u64;
+        Ok(self.quote(amount_in, amount_out, a_to_b))
+    }
+
+    // Smallest input that receives at least `amount_out`
+    pub fn exact_out(&self, amount_out: u64, a_to_b: bool) -> Result<Quote> {
+        let (reserve_in, reserve_out) = self.reserves(a_to_b);
+        if reserve_in == 0 || amount_out as u128 >= reserve_out {
+            return Err(anyhow!(
+                "output of {} exceeds the pool's reserve of {}",
+                amount_out,
+                reserve_out
+            ));
+        }
+        let net = (reserve_in * amount_out as u128).div_ceil(reserve_out - amount_out as u128);
+        let net = u64::try_from(net).map_err(|_| anyhow!("input exceeds u64"))?;
+        let mut amount_in = (net as f64 / (1.0 - self.fee_rate)).ceil() as u64;
+        while amount_in - self.fee(amount_in) < net {
+            amount_in += 1;
+        }
+        Ok(self.quote(amount_in, amount_out, a_to_b))
+    }
+}
+
+// Concentrated liquidity pool between the initialized ticks that were loaded.
+// Swaps cross ticks until the amount is used up and fail once the price
+// would move past the last loaded tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcentratedLiquidity {
+    // Square root of the raw price
+    pub sqrt_price: f64,
+    pub tick_current: i32,
+    pub liquidity: u128,
+    pub fee_rate: f64,
+    // Index and liquidity_net of each loaded initialized tick, ascending
+    pub ticks: Vec<(i32, i128)>,
+}
+
+impl ConcentratedLiquidity {
+    pub fn new(
+        sqrt_price: f64,
+        tick_current: i32,
+        liquidity: u128,
+        fee_rate: f64,
+        ticks: impl IntoIterator<Item = (i32, i128)>,
+    ) -> Self {
+        let mut ticks: Vec<_> = ticks.into_iter().collect();
+        ticks.sort_by_key(|(index, _)| *index);
+        ticks.dedup_by_key(|(index, _)| *index);
+        Self {
+            sqrt_price,
+            tick_current,
+            liquidity,
+            fee_rate,
+            ticks,
+        }
+    }
+
+    pub fn from_whirlpool(whirlpool: &Whirlpool, tick_arrays: &[TickArray]) -> Self {
+        Self::new(
+            whirlpool.sqrt_price(),
+            whirlpool.tick_current,
+            whirlpool.liquidity,
+            whirlpool.fee_rate(),
+            tick_arrays
+                .iter()
+                .flat_map(|array| array.ticks.iter())
+                .map(|tick| (tick.index, tick.liquidity_net)),
+        )
+    }
+
+    // `fee_rate` comes from the pool's AMM config account
+    pub fn from_clmm(pool: &PoolState, fee_rate: f64, tick_arrays: &[TickArrayState]) -> Self {
+        Self::new(
+            pool.sqrt_price(),
+            pool.tick_current,
+            pool.liquidity,
+            fee_rate,
+            tick_arrays
+                .iter()
+                .flat_map(|array| array.ticks.iter().copied()),
+        )
+    }
+
+    // Initialized ticks in the order the price reaches them
+    fn crossings(&self, a_to_b: bool) -> Vec<(i32, i128)> {
+        if a_to_b {
+            self.ticks
+                .iter()
+                .rev()
+                .filter(|(index, _)| *index <= self.tick_current)
+                .copied()
+                .collect()
+        } else {
+            self.ticks
+                .iter()
+                .filter(|(index, _)| *index > self.tick_current)
+                .copied()
+                .collect()
+        }
+    }
+
+    pub fn exact_in(&self, amount_in: u64, a_to_b: bool) -> Result<Quote> {
+        let mut sqrt_price = self.sqrt_price;
+        let mut liquidity = self.liquidity as f64;
+        let mut remaining = amount_in as f64;
+        let mut amount_out = 0.0;
+        let mut fees = 0.0;
+
+        for (index, liquidity_net) in self.crossings(a_to_b) {
+            let target = tick_math::sqrt_price_at_tick(index);
+            if liquidity > 0.0 {
+                let net = remaining * (1.0 - self.fee_rate);
+                // Input needed to move the price all the way to the tick
+                let needed = input(liquidity, sqrt_price, target, a_to_b);
+                if net < needed {
+                    let next = if a_to_b {
+                        1.0 / (1.0 / sqrt_price + net / liquidity)
+                    } else {
+                        sqrt_price + net / liquidity
+                    };
+                    amount_out += output(liquidity, sqrt_price, next, a_to_b);
+                    fees += remaining - net;
+                    sqrt_price = next;
+                    remaining = 0.0;
+                    break;
+                }
+                let gross = needed / (1.0 - self.fee_rate);
+                amount_out += output(liquidity, sqrt_price, target, a_to_b);
+                fees += gross - needed;
+                remaining -= gross;
+            }
+            sqrt_price = target;
+            liquidity = cross(liquidity, liquidity_net, a_to_b);
+        }
+
+        // Less than a base unit left over is rounding
+        if remaining >= 1.0 {
+            return Err(anyhow!(
+                "swap of {} exceeds the liquidity in the loaded ticks",
+                amount_in
+            ));
+        }
+        Ok(Quote::new(
+            amount_in,
+            amount_out as u64,
+            fees.ceil() as u64,
+            self.sqrt_price * self.sqrt_price,
+            sqrt_price * sqrt_price,
+            a_to_b,
+        ))
+    }
+
+    // Input needed to receive `amount_out`, rounded up
+    pub fn exact_out(&self, amount_out: u64, a_to_b: bool) -> Result<Quote> {
+        let mut sqrt_price = self.sqrt_price;
+        let mut liquidity = self.liquidity as f64;
+        let mut remaining = amount_out as f64;
+        let mut amount_in = 0.0;
+        let mut fees = 0.0;
+
+        for (index, liquidity_net) in self.crossings(a_to_b) {
+            let target = tick_math::sqrt_price_at_tick(index);
+            if liquidity > 0.0 {
+                // Output released moving the price all the way to the tick
+                let available = output(liquidity, sqrt_price, target, a_to_b);
+                let next = if remaining < available {
+                    if a_to_b {
+                        sqrt_price - remaining / liquidity
+                    } else {
+                        1.0 / (1.0 / sqrt_price - remaining / liquidity)
+                    }
+                } else {
+                    target
+                };
+                let needed = input(liquidity, sqrt_price, next, a_to_b);
+                let gross = needed / (1.0 - self.fee_rate);
+                amount_in += gross;
+                fees += gross - needed;
+                if remaining < available {
+                    sqrt_price = next;
+                    remaining = 0.0;
+                    break;
+                }
+                remaining -= available;
+            }
+            sqrt_price = target;
+            liquidity = cross(liquidity, liquidity_net, a_to_b);
+        }
+
+        if remaining >= 1.0 {
+            return Err(anyhow!(
+                "output of {} exceeds the liquidity in the loaded ticks",
+                amount_out
+            ));
+        }
+        Ok(Quote::new(
+            amount_in.ceil() as u64,
+            amount_out,
+            fees.ceil() as u64,
+            self.sqrt_price * self.sqrt_price,
+            sqrt_price * sqrt_price,
+            a_to_b,
+        ))
+    }
+}
+
+// Input taken when the price moves from `from` to `to` at constant liquidity
+fn input(liquidity: f64, from: f64, to: f64, a_to_b: bool) -> f64 {
+    if a_to_b {
+        liquidity * (1.0 / to - 1.0 / from)
+    } else {
+        liquidity * (to - from)
+    }
+}
+
+// Output released when the price moves from `from` to `to` at constant liquidity
+fn output(liquidity: f64, from: f64, to: f64, a_to_b: bool) -> f64 {
+    if a_to_b {
+        liquidity * (from - to)
+    } else {
+        liquidity * (1.0 / from - 1.0 / to)
+    }
+}
+
+// Crossing downward leaves the tick's range, crossing upward enters it
+fn cross(liquidity: f64, liquidity_net: i128, a_to_b: bool) -> f64 {
+    let net = liquidity_net as f64;
+    (liquidity + if a_to_b { -net } else { net }).max(0.0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwapCurve {
+    ConstantProduct(ConstantProduct),
+    Concentrated(ConcentratedLiquidity),
+}
+
+impl SwapCurve {
+    pub fn exact_in(&self, amount_in: u64, a_to_b: bool) -> Result<Quote> {
+        match self {
+            SwapCurve::ConstantProduct(curve) => curve.exact_in(amount_in, a_to_b),
+            SwapCurve::Concentrated(curve) => curve.exact_in(amount_in, a_to_b),
+        }
+    }
+
+    pub fn exact_out(&self, amount_out: u64, a_to_b: bool) -> Result<Quote> {
+        match self {
+            SwapCurve::ConstantProduct(curve) => curve.exact_out(amount_out, a_to_b),
+            SwapCurve::Concentrated(curve) => curve.exact_out(amount_out, a_to_b),
+        }
+    }
+}
+
+// A pool's curve as last read from chain
+#[derive(Debug, Clone)]
+pub struct CachedPool {
+    pub venue: String,
+    pub address: Pubkey,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub curve: SwapCurve,
+    pub updated_at: Instant,
+}
+
+impl CachedPool {
+    fn is_fresh(&self) -> bool {
+        self.updated_at.elapsed() <= MAX_POOL_STATE_AGE
+    }
+
+    // Whether swapping `token_in` for `token_out` goes from token_a to
+    // token_b, None when the pool does not hold the pair
+    fn direction(&self, token_in: &Pubkey, token_out: &Pubkey) -> Option<bool> {
+        if (self.token_a, self.token_b) == (*token_in, *token_out) {
+            Some(true)
+        } else if (self.token_b, self.token_a) == (*token_in, *token_out) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+// Quote from one cached pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolQuote {
+    pub venue: String,
+    pub pool: Pubkey,
+    pub quote: Quote,
+}
+
+// Curves of the pools the venues have read, so trades can be quoted locally
+// without an RPC round-trip. Venues refresh a pool whenever they load it, and
+// check each swap's price impact against it before building the transaction.
+// Strategies reach it through their context to compare trades across pools.
+#[derive(Clone, Default)]
+pub struct PoolCache {
+    pools: Arc<Mutex<HashMap<Pubkey, CachedPool>>>,
+}
+
+impl PoolCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(
+        &self,
+        venue: &str,
+        address: Pubkey,
+        token_a: Pubkey,
+        token_b: Pubkey,
+        curve: SwapCurve,
+    ) {
+        self.pools.lock().unwrap().insert(
+            address,
+            CachedPool {
+                venue: venue.to_string(),
+                address,
+                token_a,
+                token_b,
+                curve,
+                updated_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn get(&self, address: &Pubkey) -> Option<CachedPool> {
+        self.pools.lock().unwrap().get(address).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Exact-in quote from one pool's cached curve. None when the pool is not
+    // cached, its state is stale or the swap does not fit in the loaded curve,
+    // in which case the venue loads the pool again.
+    pub fn quote_exact_in(
+        &self,
+        pool: &Pubkey,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: u64,
+    ) -> Option<Quote> {
+        let pools = self.pools.lock().unwrap();
+        let pool = pools.get(pool).filter(|pool| pool.is_fresh())?;
+        let a_to_b = pool.direction(token_in, token_out)?;
+        pool.curve.exact_in(amount_in, a_to_b).ok()
+    }
+
+    // Quotes from every fresh pool of the pair. Pools the swap does not fit
+    // in are left out.
+    fn quotes(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        quote: impl Fn(&SwapCurve, bool) -> Result<Quote>,
+    ) -> Vec<PoolQuote> {
+        let pools = self.pools.lock().unwrap();
+        pools
+            .values()
+            .filter(|pool| pool.is_fresh())
+            .filter_map(|pool| {
+                let a_to_b = pool.direction(token_in, token_out)?;
+                let quote = quote(&pool.curve, a_to_b).ok()?;
+                Some(PoolQuote {
+                    venue: pool.venue.clone(),
+                    pool: pool.address,
+                    quote,
+                })
+            })
+            .collect()
+    }
+
+    pub fn quotes_exact_in(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: u64,
+    ) -> Vec<PoolQuote> {
+        self.quotes(token_in, token_out, |curve, a_to_b| {
+            curve.exact_in(amount_in, a_to_b)
+        })
+    }
+
+    pub fn quotes_exact_out(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_out: u64,
+    ) -> Vec<PoolQuote> {
+        self.quotes(token_in, token_out, |curve, a_to_b| {
+            curve.exact_out(amount_out, a_to_b)
+        })
+    }
+
+    // The pool paying the most for `amount_in`
+    pub fn best_exact_in(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: u64,
+    ) -> Option<PoolQuote> {
+        self.quotes_exact_in(token_in, token_out, amount_in)
+            .into_iter()
+            .max_by_key(|quote| quote.quote.amount_out)
+    }
+
+    // The pool charging the least for `amount_out`
+    pub fn best_exact_out(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_out: u64,
+    ) -> Option<PoolQuote> {
+        self.quotes_exact_out(token_in, token_out, amount_out)
+            .into_iter()
+            .min_by_key(|quote| quote.quote.amount_in)
+    }
+}
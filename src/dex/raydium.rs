@@ -1,137 +0,0 @@
-use anyhow::Result;
-use async_trait::async_trait;
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::Keypair;
-use solana_sdk::transaction::Transaction;
-use std::str::FromStr;
-
-use super::DexClient;
-
-pub struct RaydiumClient {
-    rpc_client: RpcClient,
-    program_id: Pubkey,
-}
-
-impl RaydiumClient {
-    pub fn new(rpc_client: RpcClient, program_id: String) -> Result<Self> {
-        Ok(Self {
-            rpc_client,
-            program_id: Pubkey::from_str(&program_id)?,
-        })
-    }
-
-    async fn get_pool_info(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<PoolInfo> {
-        // Implement pool info retrieval logic
-        // This would typically involve querying the Raydium program for pool data
-        todo!("Implement pool info retrieval")
-    }
-
-    async fn create_swap_instruction(
-        &self,
-        token_in: &Pubkey,
-        token_out: &Pubkey,
-        amount_in: f64,
-        min_amount_out: f64,
-    ) -> Result<solana_sdk::instruction::Instruction> {
-        // Implement swap instruction creation
-        // This would create the necessary instruction to execute a swap on Raydium
-        todo!("Implement swap instruction creation")
-    }
-
-    async fn create_lp_instruction(
-        &self,
-        token_a: &Pubkey,
-        token_b: &Pubkey,
-        amount_a: f64,
-        amount_b: f64,
-        min_price: f64,
-        max_price: f64,
-    ) -> Result<solana_sdk::instruction::Instruction> {
-        // Implement LP position creation instruction
-        // This would create the necessary instruction to create an LP position on Raydium
-        todo!("Implement LP instruction creation")
-    }
-}
-
-#[async_trait]
-impl DexClient for RaydiumClient {
-    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<f64> {
-        let pool_info = self.get_pool_info(token_a, token_b).await?;
-        Ok(pool_info.price)
-    }
-
-    async fn create_lp_position(
-        &self,
-        token_a: &Pubkey,
-        token_b: &Pubkey,
-        amount_a: f64,
-        amount_b: f64,
-        min_price: f64,
-        max_price: f64,
-    ) -> Result<String> {
-        let instruction = self
-            .create_lp_instruction(token_a, token_b, amount_a, amount_b, min_price, max_price)
-            .await?;
-
-        // Create and sign transaction
-        let mut transaction = Transaction::new_with_payer(&[instruction], None);
-        // Add necessary signers and recent blockhash
-        // Send transaction
-        // Return position ID
-
-        todo!("Implement LP position creation")
-    }
-
-    async fn rebalance_position(
-        &self,
-        position_id: &str,
-        new_min_price: f64,
-        new_max_price: f64,
-    ) -> Result<()> {
-        // Implement position rebalancing logic
-        // This would involve:
-        // 1. Retrieving current position data
-        // 2. Calculating new token amounts
-        // 3. Creating and sending rebalance transaction
-        todo!("Implement position rebalancing")
-    }
-
-    async fn harvest_fees(&self, position_id: &str) -> Result<()> {
-        // Implement fee harvesting logic
-        // This would involve:
-        // 1. Retrieving accumulated fees
-        // 2. Creating and sending harvest transaction
-        todo!("Implement fee harvesting")
-    }
-
-    async fn execute_swap(
-        &self,
-        token_in: &Pubkey,
-        token_out: &Pubkey,
-        amount_in: f64,
-        min_amount_out: f64,
-    ) -> Result<String> {
-        let instruction = self
-            .create_swap_instruction(token_in, token_out, amount_in, min_amount_out)
-            .await?;
-
-        // Create and sign transaction
-        let mut transaction = Transaction::new_with_payer(&[instruction], None);
-        // Add necessary signers and recent blockhash
-        // Send transaction
-        // Return transaction signature
-
-        todo!("Implement swap execution")
-    }
-}
-
-#[derive(Debug)]
-struct PoolInfo {
-    pub token_a: Pubkey,
-    pub token_b: Pubkey,
-    pub reserve_a: f64,
-    pub reserve_b: f64,
-    pub price: f64,
-    pub fee_rate: f64,
-} 
\ No newline at end of file
@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+
+use super::PoolInfo;
+use crate::dex::layout::{read_pubkey, read_u64};
+use crate::dex::programs::TOKEN_PROGRAM_ID;
+use crate::units::{Price, Rounding, TokenAmount};
+
+// Raydium AMM v4 `AmmInfo` account. Every scalar field is a u64, followed by
+// the fee and state blocks and then the account keys.
+pub const AMM_V4_LEN: usize = 752;
+
+const STATUS: usize = 0;
+const BASE_DECIMALS: usize = 32;
+const QUOTE_DECIMALS: usize = 40;
+const TRADE_FEE_NUMERATOR: usize = 144;
+const TRADE_FEE_DENOMINATOR: usize = 152;
+const SWAP_FEE_NUMERATOR: usize = 176;
+const SWAP_FEE_DENOMINATOR: usize = 184;
+const NEED_TAKE_PNL_BASE: usize = 192;
+const NEED_TAKE_PNL_QUOTE: usize = 200;
+const BASE_VAULT: usize = 336;
+const QUOTE_VAULT: usize = 368;
+pub const BASE_MINT: usize = 400;
+pub const QUOTE_MINT: usize = 432;
+const LP_MINT: usize = 464;
+const OPEN_ORDERS: usize = 496;
+const MARKET_ID: usize = 528;
+
+// `SwapBaseInV2` settles against the pool vaults alone, without the OpenBook
+// market accounts the original swap instructions take
+const SWAP_BASE_IN_V2: u8 = 16;
+const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+// Only these pool states accept swaps. The others are uninitialized, disabled,
+// withdraw or deposit only, order book only, or waiting for the pool to open.
+const STATUS_INITIALIZED: u64 = 1;
+const STATUS_SWAP_ONLY: u64 = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmmInfo {
+    pub status: u64,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+    pub need_take_pnl_base: u64,
+    pub need_take_pnl_quote: u64,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub open_orders: Pubkey,
+    pub market_id: Pubkey,
+}
+
+impl AmmInfo {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != AMM_V4_LEN {
+            return Err(anyhow!(
+                "not an AMM v4 pool account: expected {} bytes, got {}",
+                AMM_V4_LEN,
+                data.len()
+            ));
+        }
+
+        Ok(Self {
+            status: read_u64(data, STATUS)?,
+            base_decimals: decimals(read_u64(data, BASE_DECIMALS)?)?,
+            quote_decimals: decimals(read_u64(data, QUOTE_DECIMALS)?)?,
+            trade_fee_numerator: read_u64(data, TRADE_FEE_NUMERATOR)?,
+            trade_fee_denominator: read_u64(data, TRADE_FEE_DENOMINATOR)?,
+            swap_fee_numerator: read_u64(data, SWAP_FEE_NUMERATOR)?,
+            swap_fee_denominator: read_u64(data, SWAP_FEE_DENOMINATOR)?,
+            need_take_pnl_base: read_u64(data, NEED_TAKE_PNL_BASE)?,
+            need_take_pnl_quote: read_u64(data, NEED_TAKE_PNL_QUOTE)?,
+            base_vault: read_pubkey(data, BASE_VAULT)?,
+            quote_vault: read_pubkey(data, QUOTE_VAULT)?,
+            base_mint: read_pubkey(data, BASE_MINT)?,
+            quote_mint: read_pubkey(data, QUOTE_MINT)?,
+            lp_mint: read_pubkey(data, LP_MINT)?,
+            open_orders: read_pubkey(data, OPEN_ORDERS)?,
+            market_id: read_pubkey(data, MARKET_ID)?,
+        })
+    }
+
+    pub fn is_tradable(&self) -> bool {
+        matches!(self.status, STATUS_INITIALIZED | STATUS_SWAP_ONLY)
+    }
+
+    // Fee charged on the input amount of a swap
+    pub fn fee_rate(&self) -> f64 {
+        if self.swap_fee_denominator == 0 {
+            return 0.0;
+        }
+        self.swap_fee_numerator as f64 / self.swap_fee_denominator as f64
+    }
+
+    // Builds the pool view from the vault balances. PnL owed to the protocol
+    // still sits in the vaults but is not available to swaps. Liquidity parked
+    // in the OpenBook open orders account is not counted.
+    pub fn pool_info(&self, base_vault_amount: u64, quote_vault_amount: u64) -> Result<PoolInfo> {
+        let base = base_vault_amount
+            .checked_sub(self.need_take_pnl_base)
+            .ok_or_else(|| anyhow!("base vault holds less than the pending pnl"))?;
+        let quote = quote_vault_amount
+            .checked_sub(self.need_take_pnl_quote)
+            .ok_or_else(|| anyhow!("quote vault holds less than the pending pnl"))?;
+        if base == 0 || quote == 0 {
+            return Err(anyhow!(
+                "pool {}/{} has no liquidity",
+                self.base_mint,
+                self.quote_mint
+            ));
+        }
+
+        let reserve_a = TokenAmount::new(base, self.base_decimals);
+        let reserve_b = TokenAmount::new(quote, self.quote_decimals);
+        Ok(PoolInfo {
+            token_a: self.base_mint,
+            token_b: self.quote_mint,
+            reserve_a,
+            reserve_b,
+            price: Price::from_amounts(reserve_a, reserve_b, Rounding::Nearest)?,
+            fee_rate: self.fee_rate(),
+        })
+    }
+}
+
+fn decimals(value: u64) -> Result<u8> {
+    u8::try_from(value)
+        .ok()
+        .filter(|d| *d <= 18)
+        .ok_or_else(|| anyhow!("invalid mint decimals {}", value))
+}
+
+pub fn amm_authority(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], program_id).0
+}
+
+// Exact-input swap from `source` into `destination`, both owned by `owner`
+#[allow(clippy::too_many_arguments)]
+pub fn swap_base_in(
+    program_id: &Pubkey,
+    pool_id: &Pubkey,
+    amm: &AmmInfo,
+    source: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    amount_in: u64,
+    min_amount_out: u64,
+) -> Instruction {
+    let mut data = vec![SWAP_BASE_IN_V2];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new(*pool_id, false),
+            AccountMeta::new_readonly(amm_authority(program_id), false),
+            AccountMeta::new(amm.base_vault, false),
+            AccountMeta::new(amm.quote_vault, false),
+            AccountMeta::new(*source, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data,
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::signature::Signer;
+
+use super::{instructions, PersonalPosition, PoolState, TickFeeGrowth};
+use crate::dex::raydium::RaydiumClient;
+use crate::dex::{tick_math, FeeAmounts, HarvestReceipt, SolBalance};
+use crate::units::TokenAmount;
+
+impl RaydiumClient {
+    // Fees owed on the position account plus what has accrued since it was
+    // last touched, read from the pool's fee growth accumulators
+    pub(in crate::dex::raydium) async fn clmm_unclaimed_fees(
+        &self,
+        position_id: &str,
+    ) -> Result<FeeAmounts> {
+        let (position, pool, _) = self.load_position(position_id).await?;
+        let (lower, upper) = self.position_tick_fee_growth(&position, &pool).await?;
+
+        let growth_0 = tick_math::fee_growth_inside(
+            pool.tick_current,
+            position.tick_lower,
+            position.tick_upper,
+            pool.fee_growth_global_0_x64,
+            lower.fee_growth_outside_0_x64,
+            upper.fee_growth_outside_0_x64,
+        );
+        let growth_1 = tick_math::fee_growth_inside(
+            pool.tick_current,
+            position.tick_lower,
+            position.tick_upper,
+            pool.fee_growth_global_1_x64,
+            lower.fee_growth_outside_1_x64,
+            upper.fee_growth_outside_1_x64,
+        );
+        let owed_0 = position
+            .token_fees_owed_0
+            .saturating_add(tick_math::fees_earned(
+                position.liquidity,
+                growth_0,
+                position.fee_growth_inside_0_last_x64,
+            ));
+        let owed_1 = position
+            .token_fees_owed_1
+            .saturating_add(tick_math::fees_earned(
+                position.liquidity,
+                growth_1,
+                position.fee_growth_inside_1_last_x64,
+            ));
+
+        Ok(FeeAmounts {
+            token_a: pool.token_mint_0,
+            token_b: pool.token_mint_1,
+            amount_a: TokenAmount::new(owed_0, pool.mint_decimals_0),
+            amount_b: TokenAmount::new(owed_1, pool.mint_decimals_1),
+        })
+    }
+
+    // Collects fees by withdrawing zero liquidity, which leaves the position
+    // untouched otherwise
+    pub(in crate::dex::raydium) async fn harvest_clmm_fees(
+        &self,
+        position_id: &str,
+    ) -> Result<HarvestReceipt> {
+        let (position, pool, nft_token_program) = self.load_position(position_id).await?;
+
+        let before = self.pool_token_balances(&pool).await?;
+        let instruction = instructions::decrease_liquidity(
+            &self.clmm_program_id,
+            &self.wallet.pubkey(),
+            &pool,
+            &position,
+            &nft_token_program,
+            0,
+            0,
+            0,
+        );
+        // Balances are compared before and after, so SOL stays wrapped
+        let accounts = self.withdraw_accounts(&pool, SolBalance::Wrapped);
+        let signature = self
+            .send_transaction(&accounts.build([instruction]), &[])
+            .await?;
+        let after = self.pool_token_balances(&pool).await?;
+
+        Ok(HarvestReceipt {
+            position_id: position_id.to_string(),
+            collected: FeeAmounts {
+                token_a: pool.token_mint_0,
+                token_b: pool.token_mint_1,
+                amount_a: TokenAmount::new(after.0.saturating_sub(before.0), pool.mint_decimals_0),
+                amount_b: TokenAmount::new(after.1.saturating_sub(before.1), pool.mint_decimals_1),
+            },
+            rewards: Vec::new(),
+            signature,
+        })
+    }
+
+    async fn position_tick_fee_growth(
+        &self,
+        position: &PersonalPosition,
+        pool: &PoolState,
+    ) -> Result<(TickFeeGrowth, TickFeeGrowth)> {
+        let addresses = [
+            instructions::tick_array_address(
+                &self.clmm_program_id,
+                &position.pool_id,
+                pool.tick_array_start_index(position.tick_lower),
+            ),
+            instructions::tick_array_address(
+                &self.clmm_program_id,
+                &position.pool_id,
+                pool.tick_array_start_index(position.tick_upper),
+            ),
+        ];
+        let accounts = self.rpc_client.get_multiple_accounts(&addresses).await?;
+        match accounts.as_slice() {
+            [Some(lower), Some(upper)] => Ok((
+                TickFeeGrowth::decode(&lower.data, position.tick_lower, pool.tick_spacing)?,
+                TickFeeGrowth::decode(&upper.data, position.tick_upper, pool.tick_spacing)?,
+            )),
+            _ => Err(anyhow!(
+                "tick arrays for position {} not found",
+                position.nft_mint
+            )),
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_program, sysvar};
+
+use super::state::{PersonalPosition, PoolState};
+use crate::dex::layout::anchor_discriminator;
+use crate::dex::programs::{
+    associated_token_address, ASSOCIATED_TOKEN_PROGRAM_ID, MEMO_PROGRAM_ID, METADATA_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+
+pub fn personal_position_address(program_id: &Pubkey, nft_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"position", nft_mint.as_ref()], program_id).0
+}
+
+pub fn protocol_position_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"position",
+            pool.as_ref(),
+            &tick_lower.to_be_bytes(),
+            &tick_upper.to_be_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn tick_array_address(program_id: &Pubkey, pool: &Pubkey, start_index: i32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"tick_array", pool.as_ref(), &start_index.to_be_bytes()],
+        program_id,
+    )
+    .0
+}
+
+pub fn tick_array_bitmap_extension_address(program_id: &Pubkey, pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"pool_tick_array_bitmap_extension", pool.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+fn metadata_address(nft_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", METADATA_PROGRAM_ID.as_ref(), nft_mint.as_ref()],
+        &METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+// Accounts shared by every instruction that touches a position's liquidity
+struct PositionKeys {
+    nft_account: Pubkey,
+    personal_position: Pubkey,
+    protocol_position: Pubkey,
+    tick_array_lower: Pubkey,
+    tick_array_upper: Pubkey,
+    token_account_0: Pubkey,
+    token_account_1: Pubkey,
+}
+
+impl PositionKeys {
+    // `nft_token_program` owns the position NFT: positions opened with a
+    // Token-2022 NFT hold it in a Token-2022 account
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        program_id: &Pubkey,
+        owner: &Pubkey,
+        pool_id: &Pubkey,
+        pool: &PoolState,
+        nft_mint: &Pubkey,
+        nft_token_program: &Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Self {
+        Self {
+            nft_account: associated_token_address(owner, nft_mint, nft_token_program),
+            personal_position: personal_position_address(program_id, nft_mint),
+            protocol_position: protocol_position_address(
+                program_id, pool_id, tick_lower, tick_upper,
+            ),
+            tick_array_lower: tick_array_address(
+                program_id,
+                pool_id,
+                pool.tick_array_start_index(tick_lower),
+            ),
+            tick_array_upper: tick_array_address(
+                program_id,
+                pool_id,
+                pool.tick_array_start_index(tick_upper),
+            ),
+            token_account_0: associated_token_address(owner, &pool.token_mint_0, &TOKEN_PROGRAM_ID),
+            token_account_1: associated_token_address(owner, &pool.token_mint_1, &TOKEN_PROGRAM_ID),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn open_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool_id: &Pubkey,
+    pool: &PoolState,
+    nft_mint: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u128,
+    amount_0_max: u64,
+    amount_1_max: u64,
+) -> Instruction {
+    let keys = PositionKeys::new(
+        program_id,
+        owner,
+        pool_id,
+        pool,
+        nft_mint,
+        &TOKEN_PROGRAM_ID,
+        tick_lower,
+        tick_upper,
+    );
+
+    let mut data = anchor_discriminator("global:open_position_v2").to_vec();
+    data.extend_from_slice(&tick_lower.to_le_bytes());
+    data.extend_from_slice(&tick_upper.to_le_bytes());
+    data.extend_from_slice(&pool.tick_array_start_index(tick_lower).to_le_bytes());
+    data.extend_from_slice(&pool.tick_array_start_index(tick_upper).to_le_bytes());
+    data.extend_from_slice(&liquidity.to_le_bytes());
+    data.extend_from_slice(&amount_0_max.to_le_bytes());
+    data.extend_from_slice(&amount_1_max.to_le_bytes());
+    // with_metadata = false, base_flag = None: liquidity is given explicitly
+    data.extend_from_slice(&[0, 0]);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new(*nft_mint, true),
+            AccountMeta::new(keys.nft_account, false),
+            AccountMeta::new(metadata_address(nft_mint), false),
+            AccountMeta::new(*pool_id, false),
+            AccountMeta::new(keys.protocol_position, false),
+            AccountMeta::new(keys.tick_array_lower, false),
+            AccountMeta::new(keys.tick_array_upper, false),
+            AccountMeta::new(keys.personal_position, false),
+            AccountMeta::new(keys.token_account_0, false),
+            AccountMeta::new(keys.token_account_1, false),
+            AccountMeta::new(pool.token_vault_0, false),
+            AccountMeta::new(pool.token_vault_1, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(METADATA_PROGRAM_ID, false),
+            AccountMeta::new_readonly(TOKEN_2022_PROGRAM_ID, false),
+            AccountMeta::new_readonly(pool.token_mint_0, false),
+            AccountMeta::new_readonly(pool.token_mint_1, false),
+            AccountMeta::new(
+                tick_array_bitmap_extension_address(program_id, pool_id),
+                false,
+            ),
+        ],
+        data,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn increase_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool: &PoolState,
+    position: &PersonalPosition,
+    nft_token_program: &Pubkey,
+    liquidity: u128,
+    amount_0_max: u64,
+    amount_1_max: u64,
+) -> Instruction {
+    let pool_id = &position.pool_id;
+    let keys = PositionKeys::new(
+        program_id,
+        owner,
+        pool_id,
+        pool,
+        &position.nft_mint,
+        nft_token_program,
+        position.tick_lower,
+        position.tick_upper,
+    );
+
+    let mut data = anchor_discriminator("global:increase_liquidity_v2").to_vec();
+    data.extend_from_slice(&liquidity.to_le_bytes());
+    data.extend_from_slice(&amount_0_max.to_le_bytes());
+    data.extend_from_slice(&amount_1_max.to_le_bytes());
+    // base_flag = None
+    data.push(0);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(keys.nft_account, false),
+            AccountMeta::new(*pool_id, false),
+            AccountMeta::new(keys.protocol_position, false),
+            AccountMeta::new(keys.personal_position, false),
+            AccountMeta::new(keys.tick_array_lower, false),
+            AccountMeta::new(keys.tick_array_upper, false),
+            AccountMeta::new(keys.token_account_0, false),
+            AccountMeta::new(keys.token_account_1, false),
+            AccountMeta::new(pool.token_vault_0, false),
+            AccountMeta::new(pool.token_vault_1, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(TOKEN_2022_PROGRAM_ID, false),
+            AccountMeta::new_readonly(pool.token_mint_0, false),
+            AccountMeta::new_readonly(pool.token_mint_1, false),
+            AccountMeta::new(
+                tick_array_bitmap_extension_address(program_id, pool_id),
+                false,
+            ),
+        ],
+        data,
+    }
+}
+
+// Also collects the fees owed to the position, even with `liquidity` = 0
+#[allow(clippy::too_many_arguments)]
+pub fn decrease_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool: &PoolState,
+    position: &PersonalPosition,
+    nft_token_program: &Pubkey,
+    liquidity: u128,
+    amount_0_min: u64,
+    amount_1_min: u64,
+) -> Instruction {
+    let pool_id = &position.pool_id;
+    let keys = PositionKeys::new(
+        program_id,
+        owner,
+        pool_id,
+        pool,
+        &position.nft_mint,
+        nft_token_program,
+        position.tick_lower,
+        position.tick_upper,
+    );
+
+    let mut data = anchor_discriminator("global:decrease_liquidity_v2").to_vec();
+    data.extend_from_slice(&liquidity.to_le_bytes());
+    data.extend_from_slice(&amount_0_min.to_le_bytes());
+    data.extend_from_slice(&amount_1_min.to_le_bytes());
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(keys.nft_account, false),
+            AccountMeta::new(keys.personal_position, false),
+            AccountMeta::new(*pool_id, false),
+            AccountMeta::new(keys.protocol_position, false),
+            AccountMeta::new(pool.token_vault_0, false),
+            AccountMeta::new(pool.token_vault_1, false),
+            AccountMeta::new(keys.tick_array_lower, false),
+            AccountMeta::new(keys.tick_array_upper, false),
+            AccountMeta::new(keys.token_account_0, false),
+            AccountMeta::new(keys.token_account_1, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(TOKEN_2022_PROGRAM_ID, false),
+            AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+            AccountMeta::new_readonly(pool.token_mint_0, false),
+            AccountMeta::new_readonly(pool.token_mint_1, false),
+            AccountMeta::new(
+                tick_array_bitmap_extension_address(program_id, pool_id),
+                false,
+            ),
+        ],
+        data,
+    }
+}
+
+// Burns the position NFT and closes the position account; liquidity and fees
+// must already have been withdrawn
+pub fn close_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    nft_mint: &Pubkey,
+    nft_token_program: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(*nft_mint, false),
+            AccountMeta::new(
+                associated_token_address(owner, nft_mint, nft_token_program),
+                false,
+            ),
+            AccountMeta::new(personal_position_address(program_id, nft_mint), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*nft_token_program, false),
+        ],
+        data: anchor_discriminator("global:close_position").to_vec(),
+    }
+}
+
+// Exact-input swap through the pool. `tick_arrays` are the initialized arrays
+// the price may cross, starting with the one holding the current tick.
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool_id: &Pubkey,
+    pool: &PoolState,
+    zero_for_one: bool,
+    amount_in: u64,
+    min_amount_out: u64,
+    tick_arrays: &[Pubkey],
+) -> Instruction {
+    let (input_mint, output_mint, input_vault, output_vault) = if zero_for_one {
+        (
+            pool.token_mint_0,
+            pool.token_mint_1,
+            pool.token_vault_0,
+            pool.token_vault_1,
+        )
+    } else {
+        (
+            pool.token_mint_1,
+            pool.token_mint_0,
+            pool.token_vault_1,
+            pool.token_vault_0,
+        )
+    };
+
+    let mut data = anchor_discriminator("global:swap_v2").to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    // A zero price limit lets the swap run to the edge of the supported range
+    data.extend_from_slice(&0u128.to_le_bytes());
+    // is_base_input
+    data.push(1);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(pool.amm_config, false),
+        AccountMeta::new(*pool_id, false),
+        AccountMeta::new(
+            associated_token_address(owner, &input_mint, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(
+            associated_token_address(owner, &output_mint, &TOKEN_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new(input_vault, false),
+        AccountMeta::new(output_vault, false),
+        AccountMeta::new(pool.observation_key, false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(TOKEN_2022_PROGRAM_ID, false),
+        AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+        AccountMeta::new_readonly(input_mint, false),
+        AccountMeta::new_readonly(output_mint, false),
+        AccountMeta::new(
+            tick_array_bitmap_extension_address(program_id, pool_id),
+            false,
+        ),
+    ];
+    accounts.extend(
+        tick_arrays
+            .iter()
+            .map(|address| AccountMeta::new(*address, false)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
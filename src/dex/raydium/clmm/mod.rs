@@ -0,0 +1,451 @@
+use anyhow::{anyhow, Result};
+use solana_account_decoder::{UiAccountData, UiAccountEncoding};
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::str::FromStr;
+use tracing::info;
+
+use super::RaydiumClient;
+use crate::dex::programs::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use crate::dex::{
+    invert_range, pair_key, tick_math, to_base_units, LpPosition, SolBalance, WalletAccounts,
+    RAYDIUM,
+};
+use crate::units::{Price, Rounding, TokenAmount};
+
+mod fees;
+pub mod instructions;
+mod rebalance;
+mod state;
+
+pub use state::{
+    AmmConfig, PersonalPosition, PoolState, TickArrayState, TickFeeGrowth, PERSONAL_POSITION_LEN,
+    POOL_STATE_LEN, TICK_ARRAY_LEN, TICK_ARRAY_SIZE,
+};
+
+// Opening a position creates the NFT mint, tick arrays and position accounts
+const OPEN_POSITION_COMPUTE_UNITS: u32 = 400_000;
+// Float liquidity math can overshoot by a few ulps; shave it so the amounts the
+// program pulls never exceed the maxima we pass
+const LIQUIDITY_SAFETY_FACTOR: f64 = 1.0 - 1e-9;
+
+impl RaydiumClient {
+    async fn load_clmm_pool(&self, pool_id: &Pubkey) -> Result<PoolState> {
+        let data = self.rpc_client.get_account_data(pool_id).await?;
+        PoolState::decode(&data)
+    }
+
+    // The CLMM pool with the most active liquidity for the pair
+    async fn find_clmm_pool(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+    ) -> Result<(Pubkey, PoolState)> {
+        let key = pair_key(token_a, token_b);
+        let cached = self.clmm_pools.lock().unwrap().get(&key).copied();
+        if let Some(pool_id) = cached {
+            return Ok((pool_id, self.load_clmm_pool(&pool_id).await?));
+        }
+
+        // CLMM pools always store the smaller mint as mint 0
+        let (mint_0, mint_1) = key;
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(POOL_STATE_LEN as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    state::POOL_MINT_0,
+                    mint_0.as_ref(),
+                )),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    state::POOL_MINT_1,
+                    mint_1.as_ref(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (pool_id, pool) = self
+            .rpc_client
+            .get_program_accounts_with_config(&self.clmm_program_id, config)
+            .await?
+            .into_iter()
+            .filter_map(|(address, account)| {
+                PoolState::decode(&account.data)
+                    .ok()
+                    .map(|pool| (address, pool))
+            })
+            .max_by_key(|(_, pool)| pool.liquidity)
+            .ok_or_else(|| anyhow!("no Raydium CLMM pool found for {}/{}", token_a, token_b))?;
+
+        self.clmm_pools.lock().unwrap().insert(key, pool_id);
+        Ok((pool_id, pool))
+    }
+
+    // Position instructions only move classic SPL tokens
+    async fn check_lp_supported(&self, pool: &PoolState) -> Result<()> {
+        self.mints
+            .check_lp_supported(
+                &self.rpc_client,
+                &[pool.token_mint_0, pool.token_mint_1],
+                RAYDIUM,
+            )
+            .await
+    }
+
+    // The position, its pool and the token program its NFT was minted under
+    async fn load_position(
+        &self,
+        position_id: &str,
+    ) -> Result<(PersonalPosition, PoolState, Pubkey)> {
+        let nft_mint = Pubkey::from_str(position_id)
+            .map_err(|_| anyhow!("invalid Raydium position id {}", position_id))?;
+        let address = instructions::personal_position_address(&self.clmm_program_id, &nft_mint);
+        let accounts = self
+            .rpc_client
+            .get_multiple_accounts(&[address, nft_mint])
+            .await?;
+        let (position, mint) = match (&accounts[0], &accounts[1]) {
+            (Some(position), Some(mint)) => (position, mint),
+            _ => return Err(anyhow!("Raydium position {} not found", position_id)),
+        };
+        let position = PersonalPosition::decode(&position.data)?;
+        let pool = self.load_clmm_pool(&position.pool_id).await?;
+        Ok((position, pool, mint.owner))
+    }
+
+    // Opens a ranged position funded with up to `amount_a`/`amount_b`. Prices
+    // are token_b per token_a. Returns the position NFT mint.
+    pub async fn open_clmm_position(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+        min_price: Price,
+        max_price: Price,
+    ) -> Result<String> {
+        let (pool_id, pool) = self.find_clmm_pool(token_a, token_b).await?;
+        self.check_lp_supported(&pool).await?;
+
+        // Express everything in the pool's token 0 / token 1 orientation
+        let (amount_0, amount_1, (min_price, max_price)) = if *token_a == pool.token_mint_0 {
+            (amount_a, amount_b, (min_price, max_price))
+        } else {
+            (amount_b, amount_a, invert_range(min_price, max_price)?)
+        };
+        let (tick_lower, tick_upper) = tick_math::tick_range(
+            min_price.to_f64(),
+            max_price.to_f64(),
+            pool.mint_decimals_0,
+            pool.mint_decimals_1,
+            pool.tick_spacing,
+        )?;
+
+        let amount_0_max = to_base_units(amount_0, pool.mint_decimals_0)?;
+        let amount_1_max = to_base_units(amount_1, pool.mint_decimals_1)?;
+        let (nft_mint, _) = self
+            .open_position_in_pool(
+                &pool_id,
+                &pool,
+                tick_lower,
+                tick_upper,
+                amount_0_max,
+                amount_1_max,
+                SolBalance::Native,
+            )
+            .await?;
+        Ok(nft_mint.to_string())
+    }
+
+    // Returns the new position's NFT mint and the transaction signature
+    async fn open_position_in_pool(
+        &self,
+        pool_id: &Pubkey,
+        pool: &PoolState,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount_0_max: u64,
+        amount_1_max: u64,
+        sol: SolBalance,
+    ) -> Result<(Pubkey, String)> {
+        let liquidity =
+            self.liquidity_for(pool, tick_lower, tick_upper, amount_0_max, amount_1_max)?;
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), sol);
+        accounts.spend(&pool.token_mint_0, &TOKEN_PROGRAM_ID, amount_0_max);
+        accounts.spend(&pool.token_mint_1, &TOKEN_PROGRAM_ID, amount_1_max);
+
+        let nft_mint = Keypair::new();
+        let instruction = instructions::open_position(
+            &self.clmm_program_id,
+            &self.wallet.pubkey(),
+            pool_id,
+            pool,
+            &nft_mint.pubkey(),
+            tick_lower,
+            tick_upper,
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+        );
+        let signature = self
+            .send_transaction(
+                &accounts.build([
+                    ComputeBudgetInstruction::set_compute_unit_limit(OPEN_POSITION_COMPUTE_UNITS),
+                    instruction,
+                ]),
+                &[&nft_mint],
+            )
+            .await?;
+
+        info!(
+            "Opened Raydium position {} in pool {} (ticks {}..{}, liquidity {}): {}",
+            nft_mint.pubkey(),
+            pool_id,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            signature
+        );
+        Ok((nft_mint.pubkey(), signature))
+    }
+
+    // Adds up to `amount_0`/`amount_1` of the pool's tokens to a position
+    pub async fn increase_clmm_liquidity(
+        &self,
+        position_id: &str,
+        amount_0: TokenAmount,
+        amount_1: TokenAmount,
+    ) -> Result<String> {
+        let (position, pool, nft_token_program) = self.load_position(position_id).await?;
+        self.check_lp_supported(&pool).await?;
+        let amount_0_max = to_base_units(amount_0, pool.mint_decimals_0)?;
+        let amount_1_max = to_base_units(amount_1, pool.mint_decimals_1)?;
+        let liquidity = self.liquidity_for(
+            &pool,
+            position.tick_lower,
+            position.tick_upper,
+            amount_0_max,
+            amount_1_max,
+        )?;
+
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), SolBalance::Native);
+        accounts.spend(&pool.token_mint_0, &TOKEN_PROGRAM_ID, amount_0_max);
+        accounts.spend(&pool.token_mint_1, &TOKEN_PROGRAM_ID, amount_1_max);
+        let instruction = instructions::increase_liquidity(
+            &self.clmm_program_id,
+            &self.wallet.pubkey(),
+            &pool,
+            &position,
+            &nft_token_program,
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+        );
+        self.send_transaction(&accounts.build([instruction]), &[])
+            .await
+    }
+
+    // Withdraws `fraction` (0..=1) of the position's liquidity, failing if the
+    // amounts received fall more than `slippage_tolerance` below the current quote
+    pub async fn decrease_liquidity(
+        &self,
+        position_id: &str,
+        fraction: f64,
+        slippage_tolerance: f64,
+    ) -> Result<String> {
+        if fraction.is_nan() || fraction <= 0.0 || fraction > 1.0 {
+            return Err(anyhow!("fraction must be in (0, 1], got {}", fraction));
+        }
+        let (position, pool, nft_token_program) = self.load_position(position_id).await?;
+        let liquidity = if fraction == 1.0 {
+            position.liquidity
+        } else {
+            (position.liquidity as f64 * fraction) as u128
+        };
+
+        let instruction = self.decrease_instruction(
+            &pool,
+            &position,
+            &nft_token_program,
+            liquidity,
+            slippage_tolerance,
+        );
+        let accounts = self.withdraw_accounts(&pool, SolBalance::Native);
+        self.send_transaction(&accounts.build([instruction]), &[])
+            .await
+    }
+
+    // Withdraws any remaining liquidity and fees, then burns the position NFT
+    pub async fn close_position(
+        &self,
+        position_id: &str,
+        slippage_tolerance: f64,
+    ) -> Result<String> {
+        let (position, pool, nft_token_program) = self.load_position(position_id).await?;
+
+        let mut ixs = Vec::new();
+        if position.liquidity > 0
+            || position.token_fees_owed_0 > 0
+            || position.token_fees_owed_1 > 0
+        {
+            ixs.push(self.decrease_instruction(
+                &pool,
+                &position,
+                &nft_token_program,
+                position.liquidity,
+                slippage_tolerance,
+            ));
+        }
+        ixs.push(instructions::close_position(
+            &self.clmm_program_id,
+            &self.wallet.pubkey(),
+            &position.nft_mint,
+            &nft_token_program,
+        ));
+        let accounts = self.withdraw_accounts(&pool, SolBalance::Native);
+        self.send_transaction(&accounts.build(ixs), &[]).await
+    }
+
+    // The wallet accounts a withdrawal pays the pool's tokens into, created
+    // if the wallet closed or never held them
+    pub(super) fn withdraw_accounts(&self, pool: &PoolState, sol: SolBalance) -> WalletAccounts {
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), sol);
+        accounts.receive(&pool.token_mint_0, &TOKEN_PROGRAM_ID);
+        accounts.receive(&pool.token_mint_1, &TOKEN_PROGRAM_ID);
+        accounts
+    }
+
+    fn decrease_instruction(
+        &self,
+        pool: &PoolState,
+        position: &PersonalPosition,
+        nft_token_program: &Pubkey,
+        liquidity: u128,
+        slippage_tolerance: f64,
+    ) -> solana_sdk::instruction::Instruction {
+        let (amount_0, amount_1) = tick_math::amounts_for_liquidity(
+            pool.sqrt_price(),
+            tick_math::sqrt_price_at_tick(position.tick_lower),
+            tick_math::sqrt_price_at_tick(position.tick_upper),
+            liquidity,
+        );
+        let keep = (1.0 - slippage_tolerance).clamp(0.0, 1.0);
+        instructions::decrease_liquidity(
+            &self.clmm_program_id,
+            &self.wallet.pubkey(),
+            pool,
+            position,
+            nft_token_program,
+            liquidity,
+            (amount_0 as f64 * keep) as u64,
+            (amount_1 as f64 * keep) as u64,
+        )
+    }
+
+    fn liquidity_for(
+        &self,
+        pool: &PoolState,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount_0: u64,
+        amount_1: u64,
+    ) -> Result<u128> {
+        let liquidity = tick_math::liquidity_for_amounts(
+            pool.sqrt_price(),
+            tick_math::sqrt_price_at_tick(tick_lower),
+            tick_math::sqrt_price_at_tick(tick_upper),
+            amount_0,
+            amount_1,
+        );
+        let liquidity = (liquidity as f64 * LIQUIDITY_SAFETY_FACTOR) as u128;
+        if liquidity == 0 {
+            return Err(anyhow!(
+                "amounts are too small to add liquidity in ticks {}..{}",
+                tick_lower,
+                tick_upper
+            ));
+        }
+        Ok(liquidity)
+    }
+
+    // Positions are found through the NFTs held by the wallet
+    pub(super) async fn clmm_positions(&self) -> Result<Vec<LpPosition>> {
+        let owner = self.wallet.pubkey();
+
+        // Position NFTs are the only token accounts holding exactly one
+        // indivisible token; newer positions mint theirs under Token-2022
+        let mut position_addresses = Vec::new();
+        for program in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+            let token_accounts = self
+                .rpc_client
+                .get_token_accounts_by_owner(&owner, TokenAccountsFilter::ProgramId(program))
+                .await?;
+            for keyed in token_accounts {
+                if let UiAccountData::Json(parsed) = keyed.account.data {
+                    let info = &parsed.parsed["info"];
+                    let amount = &info["tokenAmount"];
+                    if amount["amount"].as_str() != Some("1")
+                        || amount["decimals"].as_u64() != Some(0)
+                    {
+                        continue;
+                    }
+                    if let Some(mint) = info["mint"].as_str().and_then(|m| Pubkey::from_str(m).ok())
+                    {
+                        position_addresses.push(instructions::personal_position_address(
+                            &self.clmm_program_id,
+                            &mint,
+                        ));
+                    }
+                }
+            }
+        }
+
+        let mut positions = Vec::new();
+        for chunk in position_addresses.chunks(100) {
+            let accounts = self.rpc_client.get_multiple_accounts(chunk).await?;
+            for account in accounts.into_iter().flatten() {
+                if account.owner != self.clmm_program_id {
+                    continue;
+                }
+                let position = PersonalPosition::decode(&account.data)?;
+                let pool = self.load_clmm_pool(&position.pool_id).await?;
+                positions.push(to_lp_position(&position, &pool)?);
+            }
+        }
+        Ok(positions)
+    }
+}
+
+pub fn to_lp_position(position: &PersonalPosition, pool: &PoolState) -> Result<LpPosition> {
+    let (amount_0, amount_1) = position.amounts(pool);
+    Ok(LpPosition {
+        id: position.nft_mint.to_string(),
+        token_a: pool.token_mint_0,
+        token_b: pool.token_mint_1,
+        min_price: Price::from_f64(
+            tick_math::tick_to_price(
+                position.tick_lower,
+                pool.mint_decimals_0,
+                pool.mint_decimals_1,
+            ),
+            Rounding::Nearest,
+        )?,
+        max_price: Price::from_f64(
+            tick_math::tick_to_price(
+                position.tick_upper,
+                pool.mint_decimals_0,
+                pool.mint_decimals_1,
+            ),
+            Rounding::Nearest,
+        )?,
+        amount_a: TokenAmount::new(amount_0, pool.mint_decimals_0),
+        amount_b: TokenAmount::new(amount_1, pool.mint_decimals_1),
+    })
+}
@@ -0,0 +1,340 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use std::str::FromStr;
+use tracing::info;
+
+use super::{instructions, AmmConfig, PoolState, TickArrayState, TICK_ARRAY_SIZE};
+use crate::dex::layout::token_account_amount;
+use crate::dex::programs::{associated_token_address, TOKEN_PROGRAM_ID};
+use crate::dex::quote::ConcentratedLiquidity;
+use crate::dex::raydium::RaydiumClient;
+use crate::dex::rebalance::{rebalance_swap, swap_cost, RebalanceProgress, SentSwap, Stage};
+use crate::dex::{
+    tick_math, RebalanceReceipt, SolBalance, SwapCurve, SwapLeg, WalletAccounts, RAYDIUM,
+};
+use crate::units::{Price, Rounding, TokenAmount};
+
+impl RaydiumClient {
+    // Collects fees and withdraws the old position in one transaction, swaps
+    // the proceeds to the new range's ratio, then opens the new position.
+    // Position accounts, the swap and a fresh NFT mint do not fit in a single
+    // transaction, so the later steps are journaled and resumed on retry.
+    pub(in crate::dex::raydium) async fn rebalance_clmm_position(
+        &self,
+        position_id: &str,
+        new_min_price: Price,
+        new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        let mut progress = match self.journal.get(position_id)? {
+            Some(progress) => {
+                info!(
+                    "Resuming rebalance of Raydium position {} after {:?}",
+                    position_id, progress.stage
+                );
+                progress
+            }
+            None => self.withdraw_for_rebalance(position_id).await?,
+        };
+        let pool_id = Pubkey::from_str(&progress.pool_id)?;
+
+        let pool = self.load_clmm_pool(&pool_id).await?;
+        let (tick_lower, tick_upper) = tick_math::tick_range(
+            new_min_price.to_f64(),
+            new_max_price.to_f64(),
+            pool.mint_decimals_0,
+            pool.mint_decimals_1,
+            pool.tick_spacing,
+        )?;
+
+        if progress.stage == Stage::Withdrawn {
+            self.swap_to_range(&pool_id, &pool, tick_lower, tick_upper, &mut progress)
+                .await?;
+            progress.stage = match progress.sent_swap {
+                Some(_) => Stage::SwapSent,
+                None => Stage::Swapped,
+            };
+            self.journal.put(position_id, &progress)?;
+        }
+        if progress.stage == Stage::SwapSent {
+            self.record_swap(&pool, &mut progress).await?;
+            progress.stage = Stage::Swapped;
+            self.journal.put(position_id, &progress)?;
+        }
+
+        let pool = self.load_clmm_pool(&pool_id).await?;
+        let before = self.pool_token_balances(&pool).await?;
+        let (nft_mint, signature) = self
+            .open_position_in_pool(
+                &pool_id,
+                &pool,
+                tick_lower,
+                tick_upper,
+                progress.available_0,
+                progress.available_1,
+                SolBalance::Wrapped,
+            )
+            .await?;
+        let after = self.pool_token_balances(&pool).await?;
+
+        let mut receipt = progress.receipt;
+        receipt.new_position_id = nft_mint.to_string();
+        receipt.deposited_a =
+            TokenAmount::new(before.0.saturating_sub(after.0), pool.mint_decimals_0);
+        receipt.deposited_b =
+            TokenAmount::new(before.1.saturating_sub(after.1), pool.mint_decimals_1);
+        receipt.signatures.push(signature);
+        self.journal.remove(position_id)?;
+
+        info!(
+            "Rebalanced Raydium position {} into {} (fees {} / {}, swap cost {})",
+            receipt.old_position_id,
+            receipt.new_position_id,
+            receipt.fees_a,
+            receipt.fees_b,
+            receipt.swap_cost
+        );
+        Ok(receipt)
+    }
+
+    async fn withdraw_for_rebalance(&self, position_id: &str) -> Result<RebalanceProgress> {
+        let (position, pool, nft_token_program) = self.load_position(position_id).await?;
+        let (principal_0, principal_1) = position.amounts(&pool);
+
+        let before = self.pool_token_balances(&pool).await?;
+        let withdraw = self.decrease_instruction(
+            &pool,
+            &position,
+            &nft_token_program,
+            position.liquidity,
+            self.slippage_tolerance(),
+        );
+        let close = instructions::close_position(
+            &self.clmm_program_id,
+            &self.wallet.pubkey(),
+            &position.nft_mint,
+            &nft_token_program,
+        );
+        let accounts = self.withdraw_accounts(&pool, SolBalance::Wrapped);
+        let signature = self
+            .send_transaction(&accounts.build([withdraw, close]), &[])
+            .await?;
+        let after = self.pool_token_balances(&pool).await?;
+
+        // Whatever arrived beyond the principal is fees collected with it
+        let received_0 = after.0.saturating_sub(before.0);
+        let received_1 = after.1.saturating_sub(before.1);
+        let progress = RebalanceProgress {
+            pool_id: position.pool_id.to_string(),
+            stage: Stage::Withdrawn,
+            available_0: received_0,
+            available_1: received_1,
+            sent_swap: None,
+            receipt: RebalanceReceipt {
+                old_position_id: position_id.to_string(),
+                withdrawn_a: TokenAmount::new(received_0.min(principal_0), pool.mint_decimals_0),
+                withdrawn_b: TokenAmount::new(received_1.min(principal_1), pool.mint_decimals_1),
+                fees_a: TokenAmount::new(
+                    received_0.saturating_sub(principal_0),
+                    pool.mint_decimals_0,
+                ),
+                fees_b: TokenAmount::new(
+                    received_1.saturating_sub(principal_1),
+                    pool.mint_decimals_1,
+                ),
+                swap: None,
+                swap_cost: TokenAmount::zero(pool.mint_decimals_1),
+                deposited_a: TokenAmount::zero(pool.mint_decimals_0),
+                deposited_b: TokenAmount::zero(pool.mint_decimals_1),
+                signatures: vec![signature],
+            },
+        };
+        self.journal.put(position_id, &progress)?;
+        Ok(progress)
+    }
+
+    // Sends the swap to the new range's ratio, if one is needed, and notes it
+    // in `sent_swap` for `record_swap` to measure
+    async fn swap_to_range(
+        &self,
+        pool_id: &Pubkey,
+        pool: &PoolState,
+        tick_lower: i32,
+        tick_upper: i32,
+        progress: &mut RebalanceProgress,
+    ) -> Result<()> {
+        let (zero_for_one, amount_in) = match rebalance_swap(
+            progress.available_0,
+            progress.available_1,
+            pool.sqrt_price(),
+            tick_math::sqrt_price_at_tick(tick_lower),
+            tick_math::sqrt_price_at_tick(tick_upper),
+        ) {
+            Some(swap) => swap,
+            None => return Ok(()),
+        };
+
+        // The minimum is taken off a quote that crosses ticks and charges the
+        // pool's fee, so slippage only has to cover the price moving
+        let (tick_arrays, curve) = self.swap_tick_arrays(pool_id, pool, zero_for_one).await?;
+        let quote = curve.exact_in(amount_in, zero_for_one)?;
+        let min_amount_out =
+            (quote.amount_out as f64 * (1.0 - self.slippage_tolerance())).max(0.0) as u64;
+
+        let (mint_in, mint_out) = if zero_for_one {
+            (pool.token_mint_0, pool.token_mint_1)
+        } else {
+            (pool.token_mint_1, pool.token_mint_0)
+        };
+        let mut accounts = WalletAccounts::new(self.wallet.pubkey(), SolBalance::Wrapped);
+        accounts.spend(&mint_in, &TOKEN_PROGRAM_ID, amount_in);
+        accounts.receive(&mint_out, &TOKEN_PROGRAM_ID);
+
+        let price = Price::from_f64(pool.price(), Rounding::Nearest)?;
+        let before = self.pool_token_balances(pool).await?;
+        let instruction = instructions::swap(
+            &self.clmm_program_id,
+            &self.wallet.pubkey(),
+            pool_id,
+            pool,
+            zero_for_one,
+            amount_in,
+            min_amount_out,
+            &tick_arrays,
+        );
+        let signature = self
+            .send_transaction(&accounts.build([instruction]), &[])
+            .await?;
+        progress.sent_swap = Some(SentSwap {
+            signature,
+            zero_for_one,
+            price,
+            balances_before: before,
+        });
+        Ok(())
+    }
+
+    // Moves what the sent swap spent and received, measured from the wallet,
+    // into the progress and its receipt
+    async fn record_swap(&self, pool: &PoolState, progress: &mut RebalanceProgress) -> Result<()> {
+        let Some(SentSwap {
+            signature,
+            zero_for_one,
+            price,
+            balances_before: before,
+        }) = progress.sent_swap.take()
+        else {
+            return Ok(());
+        };
+        let after = self.pool_token_balances(pool).await?;
+
+        let (spent, received) = if zero_for_one {
+            let spent = before.0.saturating_sub(after.0);
+            let received = after.1.saturating_sub(before.1);
+            progress.available_0 = progress.available_0.saturating_sub(spent);
+            progress.available_1 += received;
+            (
+                TokenAmount::new(spent, pool.mint_decimals_0),
+                TokenAmount::new(received, pool.mint_decimals_1),
+            )
+        } else {
+            let spent = before.1.saturating_sub(after.1);
+            let received = after.0.saturating_sub(before.0);
+            progress.available_1 = progress.available_1.saturating_sub(spent);
+            progress.available_0 += received;
+            (
+                TokenAmount::new(spent, pool.mint_decimals_1),
+                TokenAmount::new(received, pool.mint_decimals_0),
+            )
+        };
+
+        let receipt = &mut progress.receipt;
+        receipt.swap_cost = swap_cost(price, zero_for_one, spent, received)?;
+        receipt.swap = Some(SwapLeg {
+            a_to_b: zero_for_one,
+            amount_in: spent,
+            amount_out: received,
+        });
+        receipt.signatures.push(signature);
+        Ok(())
+    }
+
+    // Initialized tick arrays the swap may walk through, nearest first, and
+    // the pool's curve over them, which is also kept in the pool cache
+    async fn swap_tick_arrays(
+        &self,
+        pool_id: &Pubkey,
+        pool: &PoolState,
+        zero_for_one: bool,
+    ) -> Result<(Vec<Pubkey>, ConcentratedLiquidity)> {
+        let span = pool.tick_spacing as i32 * TICK_ARRAY_SIZE;
+        let step = if zero_for_one { -span } else { span };
+        let start = pool.tick_array_start_index(pool.tick_current);
+        let candidates: Vec<Pubkey> = (0..3)
+            .map(|i| {
+                instructions::tick_array_address(&self.clmm_program_id, pool_id, start + i * step)
+            })
+            .collect();
+
+        // The fee tier is read with the tick arrays
+        let addresses: Vec<Pubkey> = std::iter::once(pool.amm_config)
+            .chain(candidates.iter().copied())
+            .collect();
+        let mut accounts = self
+            .rpc_client
+            .get_multiple_accounts(&addresses)
+            .await?
+            .into_iter();
+        let amm_config = accounts
+            .next()
+            .flatten()
+            .ok_or_else(|| anyhow!("AMM config {} not found", pool.amm_config))?;
+        let amm_config = AmmConfig::decode(&amm_config.data)?;
+
+        let mut keys = Vec::new();
+        let mut arrays = Vec::new();
+        for (address, account) in candidates.into_iter().zip(accounts) {
+            let account = match account {
+                Some(account) => account,
+                None => break,
+            };
+            arrays.push(TickArrayState::decode(&account.data)?);
+            keys.push(address);
+        }
+        if keys.is_empty() {
+            return Err(anyhow!(
+                "pool {} has no initialized tick array at the current price",
+                pool_id
+            ));
+        }
+
+        let curve = ConcentratedLiquidity::from_clmm(pool, amm_config.fee_rate(), &arrays);
+        self.pool_states.update(
+            RAYDIUM,
+            *pool_id,
+            pool.token_mint_0,
+            pool.token_mint_1,
+            SwapCurve::Concentrated(curve.clone()),
+        );
+        Ok((keys, curve))
+    }
+
+    // Wallet balances of the pool's two tokens, in base units
+    pub(super) async fn pool_token_balances(&self, pool: &PoolState) -> Result<(u64, u64)> {
+        let owner = self.wallet.pubkey();
+        let accounts = self
+            .rpc_client
+            .get_multiple_accounts(&[
+                associated_token_address(&owner, &pool.token_mint_0, &TOKEN_PROGRAM_ID),
+                associated_token_address(&owner, &pool.token_mint_1, &TOKEN_PROGRAM_ID),
+            ])
+            .await?;
+        let balance = |account: Option<&solana_sdk::account::Account>| {
+            account.map_or(Ok(0), |account| token_account_amount(&account.data))
+        };
+        Ok((
+            balance(accounts.first().and_then(Option::as_ref))?,
+            balance(accounts.get(1).and_then(Option::as_ref))?,
+        ))
+    }
+}
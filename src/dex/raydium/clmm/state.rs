@@ -0,0 +1,236 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::dex::layout::{
+    check_anchor_account, read_i128, read_i32, read_pubkey, read_u128, read_u16, read_u32,
+    read_u64, read_u8,
+};
+use crate::dex::tick_math;
+
+pub const POOL_STATE_LEN: usize = 1544;
+pub const PERSONAL_POSITION_LEN: usize = 281;
+pub const TICK_ARRAY_SIZE: i32 = 60;
+pub const TICK_ARRAY_LEN: usize = 10240;
+
+const TICK_ARRAY_TICKS: usize = 44;
+const TICK_STATE_LEN: usize = 168;
+// Trade fees are in hundredths of a basis point
+const FEE_RATE_DENOMINATOR: f64 = 1_000_000.0;
+
+// Offsets include the 8 byte Anchor discriminator
+pub const POOL_MINT_0: usize = 73;
+pub const POOL_MINT_1: usize = 105;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolState {
+    pub amm_config: Pubkey,
+    pub token_mint_0: Pubkey,
+    pub token_mint_1: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub observation_key: Pubkey,
+    pub mint_decimals_0: u8,
+    pub mint_decimals_1: u8,
+    pub tick_spacing: u16,
+    pub liquidity: u128,
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    pub fee_growth_global_0_x64: u128,
+    pub fee_growth_global_1_x64: u128,
+    pub status: u8,
+}
+
+impl PoolState {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != POOL_STATE_LEN {
+            return Err(anyhow!(
+                "not a CLMM pool account: expected {} bytes, got {}",
+                POOL_STATE_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "PoolState")?;
+
+        let tick_spacing = read_u16(data, 235)?;
+        if tick_spacing == 0 {
+            return Err(anyhow!("CLMM pool has a tick spacing of 0"));
+        }
+        Ok(Self {
+            amm_config: read_pubkey(data, 9)?,
+            token_mint_0: read_pubkey(data, POOL_MINT_0)?,
+            token_mint_1: read_pubkey(data, POOL_MINT_1)?,
+            token_vault_0: read_pubkey(data, 137)?,
+            token_vault_1: read_pubkey(data, 169)?,
+            observation_key: read_pubkey(data, 201)?,
+            mint_decimals_0: read_u8(data, 233)?,
+            mint_decimals_1: read_u8(data, 234)?,
+            tick_spacing,
+            liquidity: read_u128(data, 237)?,
+            sqrt_price_x64: read_u128(data, 253)?,
+            tick_current: read_i32(data, 269)?,
+            fee_growth_global_0_x64: read_u128(data, 277)?,
+            fee_growth_global_1_x64: read_u128(data, 293)?,
+            status: read_u8(data, 389)?,
+        })
+    }
+
+    // Token 1 per token 0 in UI units
+    pub fn price(&self) -> f64 {
+        tick_math::sqrt_price_x64_to_price(
+            self.sqrt_price_x64,
+            self.mint_decimals_0,
+            self.mint_decimals_1,
+        )
+    }
+
+    pub fn sqrt_price(&self) -> f64 {
+        tick_math::sqrt_price_from_x64(self.sqrt_price_x64)
+    }
+
+    pub fn tick_array_start_index(&self, tick: i32) -> i32 {
+        tick_math::tick_array_start_index(tick, self.tick_spacing, TICK_ARRAY_SIZE)
+    }
+}
+
+// The fee tier a pool was created under
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmmConfig {
+    pub trade_fee_rate: u32,
+}
+
+impl AmmConfig {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        check_anchor_account(data, "AmmConfig")?;
+        // bump, index, owner and protocol_fee_rate precede the trade fee
+        Ok(Self {
+            trade_fee_rate: read_u32(data, 47)?,
+        })
+    }
+
+    pub fn fee_rate(&self) -> f64 {
+        self.trade_fee_rate as f64 / FEE_RATE_DENOMINATOR
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersonalPosition {
+    pub nft_mint: Pubkey,
+    pub pool_id: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    pub fee_growth_inside_0_last_x64: u128,
+    pub fee_growth_inside_1_last_x64: u128,
+    pub token_fees_owed_0: u64,
+    pub token_fees_owed_1: u64,
+}
+
+impl PersonalPosition {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != PERSONAL_POSITION_LEN {
+            return Err(anyhow!(
+                "not a CLMM position account: expected {} bytes, got {}",
+                PERSONAL_POSITION_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "PersonalPositionState")?;
+
+        Ok(Self {
+            nft_mint: read_pubkey(data, 9)?,
+            pool_id: read_pubkey(data, 41)?,
+            tick_lower: read_i32(data, 73)?,
+            tick_upper: read_i32(data, 77)?,
+            liquidity: read_u128(data, 81)?,
+            fee_growth_inside_0_last_x64: read_u128(data, 97)?,
+            fee_growth_inside_1_last_x64: read_u128(data, 113)?,
+            token_fees_owed_0: read_u64(data, 129)?,
+            token_fees_owed_1: read_u64(data, 137)?,
+        })
+    }
+
+    // Base unit amounts of token 0 and token 1 the position holds at the pool price
+    pub fn amounts(&self, pool: &PoolState) -> (u64, u64) {
+        tick_math::amounts_for_liquidity(
+            pool.sqrt_price(),
+            tick_math::sqrt_price_at_tick(self.tick_lower),
+            tick_math::sqrt_price_at_tick(self.tick_upper),
+            self.liquidity,
+        )
+    }
+}
+
+// Fee growth recorded outside a single initialized tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickFeeGrowth {
+    pub fee_growth_outside_0_x64: u128,
+    pub fee_growth_outside_1_x64: u128,
+}
+
+impl TickFeeGrowth {
+    // Reads `tick` out of the tick array account that holds it
+    pub fn decode(data: &[u8], tick: i32, tick_spacing: u16) -> Result<Self> {
+        if data.len() != TICK_ARRAY_LEN {
+            return Err(anyhow!(
+                "not a CLMM tick array: expected {} bytes, got {}",
+                TICK_ARRAY_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "TickArrayState")?;
+
+        let start = read_i32(data, 40)?;
+        let index = (tick - start) / tick_spacing as i32;
+        if tick < start || index >= TICK_ARRAY_SIZE {
+            return Err(anyhow!(
+                "tick {} is not in the tick array starting at {}",
+                tick,
+                start
+            ));
+        }
+        let offset = TICK_ARRAY_TICKS + index as usize * TICK_STATE_LEN;
+        if read_i32(data, offset)? != tick {
+            return Err(anyhow!("tick {} is not initialized", tick));
+        }
+        // tick, liquidity_net and liquidity_gross precede the fee growth
+        Ok(Self {
+            fee_growth_outside_0_x64: read_u128(data, offset + 36)?,
+            fee_growth_outside_1_x64: read_u128(data, offset + 52)?,
+        })
+    }
+}
+
+// Index and liquidity_net of the initialized ticks in one tick array account
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickArrayState {
+    pub start_tick_index: i32,
+    pub ticks: Vec<(i32, i128)>,
+}
+
+impl TickArrayState {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != TICK_ARRAY_LEN {
+            return Err(anyhow!(
+                "not a CLMM tick array: expected {} bytes, got {}",
+                TICK_ARRAY_LEN,
+                data.len()
+            ));
+        }
+        check_anchor_account(data, "TickArrayState")?;
+
+        let start_tick_index = read_i32(data, 40)?;
+        let mut ticks = Vec::new();
+        for i in 0..TICK_ARRAY_SIZE as usize {
+            let offset = TICK_ARRAY_TICKS + i * TICK_STATE_LEN;
+            // Uninitialized ticks have no liquidity referencing them
+            if read_u128(data, offset + 20)? == 0 {
+                continue;
+            }
+            ticks.push((read_i32(data, offset)?, read_i128(data, offset + 4)?));
+        }
+        Ok(Self {
+            start_tick_index,
+            ticks,
+        })
+    }
+}
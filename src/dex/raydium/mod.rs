@@ -0,0 +1,384 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+use super::layout::token_account_amount;
+use super::mints::MintCache;
+use super::programs::TOKEN_PROGRAM_ID;
+use super::quote::ConstantProduct;
+use super::{
+    check_price_impact, check_trade_size, pair_key, send_transaction, to_base_units, DexClient,
+    FeeAmounts, HarvestReceipt, LpPosition, PoolCache, RebalanceJournal, RebalanceReceipt,
+    SolBalance, SwapCurve, SwapEstimate, WalletAccounts, RAYDIUM,
+};
+use crate::config::{RaydiumConfig, TradingParams};
+use crate::transactions::TransactionSender;
+use crate::units::{Price, Rounding, TokenAmount};
+
+mod amm;
+pub mod clmm;
+
+pub use amm::{AmmInfo, AMM_V4_LEN};
+
+// Swaps and prices go through the AMM v4 program; ranged LP positions live in
+// the concentrated liquidity (CLMM) program.
+pub struct RaydiumClient {
+    rpc_client: Arc<RpcClient>,
+    sender: Arc<TransactionSender>,
+    program_id: Pubkey,
+    clmm_program_id: Pubkey,
+    wallet: Arc<Keypair>,
+    // Pool address per mint pair, keyed with the smaller mint first. Finding a
+    // pool scans the program accounts, so it is only done once per pair.
+    pools: Mutex<HashMap<(Pubkey, Pubkey), Pubkey>>,
+    clmm_pools: Mutex<HashMap<(Pubkey, Pubkey), Pubkey>>,
+    // Token programs and extensions of CLMM position mints
+    mints: MintCache,
+    // Reserves of the AMM pools last loaded, for local quotes
+    pool_states: PoolCache,
+    trading: TradingParams,
+    journal: RebalanceJournal,
+}
+
+impl RaydiumClient {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        sender: Arc<TransactionSender>,
+        config: &RaydiumConfig,
+        trading: TradingParams,
+        wallet: Arc<Keypair>,
+        pool_states: PoolCache,
+    ) -> Result<Self> {
+        let journal = RebalanceJournal::new(&config.rebalance_journal_path);
+        let pending = journal.pending()?;
+        if !pending.is_empty() {
+            warn!(
+                "Unfinished Raydium rebalances for positions {}; they resume on the next rebalance of each",
+                pending.join(", ")
+            );
+        }
+
+        Ok(Self {
+            rpc_client,
+            sender,
+            program_id: Pubkey::from_str(&config.program_id)?,
+            clmm_program_id: Pubkey::from_str(&config.clmm_program_id)?,
+            wallet,
+            pools: Mutex::new(HashMap::new()),
+            clmm_pools: Mutex::new(HashMap::new()),
+            mints: MintCache::new(),
+            pool_states,
+            trading,
+            journal,
+        })
+    }
+
+    // Read as each operation starts, so reloaded values apply to the next one
+    fn slippage_tolerance(&self) -> f64 {
+        self.trading.borrow().slippage_tolerance
+    }
+
+    async fn send_transaction(
+        &self,
+        instructions: &[Instruction],
+        extra_signers: &[&Keypair],
+    ) -> Result<String> {
+        send_transaction(
+            &self.sender,
+            &self.wallet,
+            instructions,
+            extra_signers,
+            RAYDIUM,
+        )
+        .await
+    }
+
+    // Reserves and price for the pair, oriented so that `price` is the amount
+    // of `token_b` paid for one `token_a`
+    async fn get_pool_info(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<PoolInfo> {
+        let pool = self.find_pool(token_a, token_b).await?;
+        let (amm, info) = self.load_pool(&pool).await?;
+        if amm.base_mint == *token_a {
+            Ok(info)
+        } else {
+            info.inverted()
+        }
+    }
+
+    async fn load_pool(&self, pool: &Pubkey) -> Result<(AmmInfo, PoolInfo)> {
+        let data = self.rpc_client.get_account_data(pool).await?;
+        let amm = AmmInfo::decode(&data)?;
+        let (base_amount, quote_amount) = self.vault_amounts(&amm).await?;
+        let info = amm.pool_info(base_amount, quote_amount)?;
+        self.pool_states.update(
+            RAYDIUM,
+            *pool,
+            info.token_a,
+            info.token_b,
+            SwapCurve::ConstantProduct(ConstantProduct::from_pool_info(&info)),
+        );
+        Ok((amm, info))
+    }
+
+    async fn vault_amounts(&self, amm: &AmmInfo) -> Result<(u64, u64)> {
+        let accounts = self
+            .rpc_client
+            .get_multiple_accounts(&[amm.base_vault, amm.quote_vault])
+            .await?;
+        match accounts.as_slice() {
+            [Some(base), Some(quote)] => Ok((
+                token_account_amount(&base.data)?,
+                token_account_amount(&quote.data)?,
+            )),
+            _ => Err(anyhow!(
+                "vault accounts of pool {}/{} not found",
+                amm.base_mint,
+                amm.quote_mint
+            )),
+        }
+    }
+
+    async fn find_pool(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Pubkey> {
+        let key = pair_key(token_a, token_b);
+        if let Some(pool) = self.pools.lock().unwrap().get(&key) {
+            return Ok(*pool);
+        }
+
+        // A pair can be listed either way round and more than once; use the
+        // tradable pool holding the most of `token_a`
+        let mut candidates = Vec::new();
+        for (base, quote) in [(token_a, token_b), (token_b, token_a)] {
+            for (address, account) in self.pools_for_mints(base, quote).await? {
+                let amm = match AmmInfo::decode(&account.data) {
+                    Ok(amm) if amm.is_tradable() => amm,
+                    _ => continue,
+                };
+                let (base_amount, quote_amount) = match self.vault_amounts(&amm).await {
+                    Ok(amounts) => amounts,
+                    Err(e) => {
+                        debug!("Skipping Raydium pool {}: {:#}", address, e);
+                        continue;
+                    }
+                };
+                let depth = if amm.base_mint == *token_a {
+                    base_amount
+                } else {
+                    quote_amount
+                };
+                candidates.push((address, depth));
+            }
+        }
+
+        let (pool, _) = candidates
+            .into_iter()
+            .max_by_key(|(_, depth)| *depth)
+            .ok_or_else(|| anyhow!("no Raydium pool found for {}/{}", token_a, token_b))?;
+        self.pools.lock().unwrap().insert(key, pool);
+        Ok(pool)
+    }
+
+    async fn pools_for_mints(
+        &self,
+        base_mint: &Pubkey,
+        quote_mint: &Pubkey,
+    ) -> Result<Vec<(Pubkey, solana_sdk::account::Account)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(AMM_V4_LEN as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    amm::BASE_MINT,
+                    base_mint.as_ref(),
+                )),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    amm::QUOTE_MINT,
+                    quote_mint.as_ref(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Ok(self
+            .rpc_client
+            .get_program_accounts_with_config(&self.program_id, config)
+            .await?)
+    }
+
+    // Exact-input swap through the deepest pool, paying from and to the
+    // wallet's native SOL balance when either side is SOL
+    async fn swap_instructions(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+        min_amount_out: TokenAmount,
+    ) -> Result<Vec<Instruction>> {
+        let pool = self.find_pool(token_in, token_out).await?;
+        let amm = AmmInfo::decode(&self.rpc_client.get_account_data(&pool).await?)?;
+        let (decimals_in, decimals_out) = if amm.base_mint == *token_in {
+            (amm.base_decimals, amm.quote_decimals)
+        } else {
+            (amm.quote_decimals, amm.base_decimals)
+        };
+        let amount_in = to_base_units(amount_in, decimals_in)?;
+
+        // Price impact comes from the cached reserves, read again once stale
+        if self
+            .pool_states
+            .quote_exact_in(&pool, token_in, token_out, amount_in)
+            .is_none()
+        {
+            self.load_pool(&pool).await?;
+        }
+        let quote = self
+            .pool_states
+            .quote_exact_in(&pool, token_in, token_out, amount_in)
+            .ok_or_else(|| {
+                anyhow!(
+                    "swap of {} exceeds the reserves of Raydium pool {}",
+                    amount_in,
+                    pool
+                )
+            })?;
+        check_price_impact(
+            quote.price_impact,
+            self.trading.borrow().price_impact_threshold,
+        )?;
+
+        let owner = self.wallet.pubkey();
+        let mut accounts = WalletAccounts::new(owner, SolBalance::Native);
+        let source = accounts.spend(token_in, &TOKEN_PROGRAM_ID, amount_in);
+        let destination = accounts.receive(token_out, &TOKEN_PROGRAM_ID);
+        let swap = amm::swap_base_in(
+            &self.program_id,
+            &pool,
+            &amm,
+            &source,
+            &destination,
+            &owner,
+            amount_in,
+            min_amount_out.base_units_at(decimals_out, Rounding::Up)?,
+        );
+        Ok(accounts.build([swap]))
+    }
+}
+
+#[async_trait]
+impl DexClient for RaydiumClient {
+    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Price> {
+        let pool_info = self.get_pool_info(token_a, token_b).await?;
+        Ok(pool_info.price)
+    }
+
+    async fn create_lp_position(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+        min_price: Price,
+        max_price: Price,
+    ) -> Result<String> {
+        self.open_clmm_position(token_a, token_b, amount_a, amount_b, min_price, max_price)
+            .await
+    }
+
+    async fn rebalance_position(
+        &self,
+        position_id: &str,
+        new_min_price: Price,
+        new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        self.rebalance_clmm_position(position_id, new_min_price, new_max_price)
+            .await
+    }
+
+    async fn harvest_fees(&self, position_id: &str) -> Result<HarvestReceipt> {
+        self.harvest_clmm_fees(position_id).await
+    }
+
+    async fn get_unclaimed_fees(&self, position_id: &str) -> Result<FeeAmounts> {
+        self.clmm_unclaimed_fees(position_id).await
+    }
+
+    async fn increase_liquidity(
+        &self,
+        position_id: &str,
+        amount_a: TokenAmount,
+        amount_b: TokenAmount,
+    ) -> Result<String> {
+        self.increase_clmm_liquidity(position_id, amount_a, amount_b)
+            .await
+    }
+
+    async fn get_positions(&self) -> Result<Vec<LpPosition>> {
+        self.clmm_positions().await
+    }
+
+    // Priced locally from the pool's reserves
+    async fn quote_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+    ) -> Result<SwapEstimate> {
+        let info = self.get_pool_info(token_in, token_out).await?;
+        let (decimals_in, decimals_out) = (info.reserve_a.decimals, info.reserve_b.decimals);
+        let quote = ConstantProduct::from_pool_info(&info)
+            .exact_in(to_base_units(amount_in, decimals_in)?, true)?;
+        Ok(SwapEstimate {
+            amount_in: TokenAmount::new(quote.amount_in, decimals_in),
+            amount_out: TokenAmount::new(quote.amount_out, decimals_out),
+            price_impact: quote.price_impact,
+        })
+    }
+
+    async fn execute_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+        min_amount_out: TokenAmount,
+    ) -> Result<String> {
+        check_trade_size(&self.trading.borrow(), amount_in)?;
+        let instructions = self
+            .swap_instructions(token_in, token_out, amount_in, min_amount_out)
+            .await?;
+        self.send_transaction(&instructions, &[]).await
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolInfo {
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub reserve_a: TokenAmount,
+    pub reserve_b: TokenAmount,
+    pub price: Price,
+    pub fee_rate: f64,
+}
+
+impl PoolInfo {
+    pub fn inverted(&self) -> Result<Self> {
+        Ok(Self {
+            token_a: self.token_b,
+            token_b: self.token_a,
+            reserve_a: self.reserve_b,
+            reserve_b: self.reserve_a,
+            price: Price::from_amounts(self.reserve_b, self.reserve_a, Rounding::Nearest)?,
+            fee_rate: self.fee_rate,
+        })
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::{tick_math, RebalanceReceipt};
+use crate::units::{Price, Rounding, TokenAmount};
+
+// Rebalance bookkeeping shared by the concentrated liquidity venues
+
+// Imbalances smaller than this share of the holdings are left unswapped
+const MIN_SWAP_FRACTION: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Stage {
+    Withdrawn,
+    // The swap landed but its amounts are not yet in the progress
+    SwapSent,
+    Swapped,
+}
+
+// A rebalance that has closed the old position but not yet opened the new one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RebalanceProgress {
+    pub pool_id: String,
+    pub stage: Stage,
+    // Base units set aside for the new position
+    pub available_0: u64,
+    pub available_1: u64,
+    #[serde(default)]
+    pub sent_swap: Option<SentSwap>,
+    pub receipt: RebalanceReceipt,
+}
+
+// A swap journaled as soon as it lands, so a rebalance resumed before its
+// amounts were recorded measures them from the wallet instead of swapping twice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SentSwap {
+    pub signature: String,
+    pub zero_for_one: bool,
+    // Pool price and wallet balances of token 0 / token 1 before the swap
+    pub price: Price,
+    pub balances_before: (u64, u64),
+}
+
+// Unfinished rebalances keyed by the old position id, persisted after every
+// step so a rebalance interrupted by an error or restart resumes instead of
+// leaving the withdrawn funds idle
+pub struct RebalanceJournal {
+    path: PathBuf,
+}
+
+impl RebalanceJournal {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, RebalanceProgress>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("corrupt rebalance journal {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, entries: &BTreeMap<String, RebalanceProgress>) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(entries)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, position_id: &str) -> Result<Option<RebalanceProgress>> {
+        Ok(self.load()?.remove(position_id))
+    }
+
+    pub(crate) fn put(&self, position_id: &str, progress: &RebalanceProgress) -> Result<()> {
+        let mut entries = self.load()?;
+        entries.insert(position_id.to_string(), progress.clone());
+        self.save(&entries)
+    }
+
+    pub(crate) fn remove(&self, position_id: &str) -> Result<()> {
+        let mut entries = self.load()?;
+        if entries.remove(position_id).is_some() {
+            self.save(&entries)?;
+        }
+        Ok(())
+    }
+
+    pub fn pending(&self) -> Result<Vec<String>> {
+        Ok(self.load()?.into_keys().collect())
+    }
+}
+
+// Value lost to a rebalance swap in token_b: what was spent less what arrived,
+// the token_a side valued at `price` from before the swap. A swap that came
+// out ahead costs nothing.
+pub fn swap_cost(
+    price: Price,
+    a_to_b: bool,
+    spent: TokenAmount,
+    received: TokenAmount,
+) -> Result<TokenAmount> {
+    let (spent_b, received_b) = if a_to_b {
+        (
+            price.quote_for(spent, received.decimals, Rounding::Nearest)?,
+            received,
+        )
+    } else {
+        (
+            spent,
+            price.quote_for(received, spent.decimals, Rounding::Nearest)?,
+        )
+    };
+    Ok(TokenAmount::new(
+        spent_b.base_units.saturating_sub(received_b.base_units),
+        spent_b.decimals,
+    ))
+}
+
+// Side and exact input amount of the swap that brings the holdings to the
+// token ratio the range needs at the current price, or None when close enough
+pub fn rebalance_swap(
+    available_0: u64,
+    available_1: u64,
+    sqrt_price: f64,
+    sqrt_lower: f64,
+    sqrt_upper: f64,
+) -> Option<(bool, u64)> {
+    let (per_0, per_1) = tick_math::amounts_per_liquidity(sqrt_price, sqrt_lower, sqrt_upper);
+    // Token 1 base units per token 0 base unit
+    let price = sqrt_price * sqrt_price;
+    let share_0 = per_0 * price / (per_0 * price + per_1);
+    swap_to_value_share(available_0, available_1, price, share_0)
+}
+
+// Side and exact input amount of the swap that leaves `share_0` of the
+// holdings' value in token 0, or None when close enough. `price` is token 1
+// base units per token 0 base unit.
+pub fn swap_to_value_share(
+    available_0: u64,
+    available_1: u64,
+    price: f64,
+    share_0: f64,
+) -> Option<(bool, u64)> {
+    let value = available_0 as f64 * price + available_1 as f64;
+    if value <= 0.0 {
+        return None;
+    }
+
+    let target_0 = value * share_0 / price;
+    let (zero_for_one, excess_value, amount_in) = if available_0 as f64 > target_0 {
+        let excess = available_0 as f64 - target_0;
+        (true, excess * price, excess)
+    } else {
+        let excess = available_1 as f64 - value * (1.0 - share_0);
+        (false, excess, excess)
+    };
+
+    if excess_value / value < MIN_SWAP_FRACTION {
+        return None;
+    }
+    Some((zero_for_one, amount_in as u64))
+}
@@ -0,0 +1,380 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use super::{
+    check_price_impact, check_trade_size, DexClient, DexClients, FeeAmounts, HarvestReceipt,
+    LpPosition, RebalanceReceipt, SwapEstimate, JUPITER,
+};
+use crate::config::{RouterConfig, TradingParams};
+use crate::units::{Price, Rounding, TokenAmount};
+
+// One venue's share of a routed swap
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteLeg {
+    pub venue: String,
+    // Parts of the order swapped on this venue
+    pub parts: u32,
+    pub estimate: SwapEstimate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePlan {
+    pub split_parts: u32,
+    pub legs: Vec<RouteLeg>,
+}
+
+impl RoutePlan {
+    pub fn amount_out(&self) -> u64 {
+        self.legs
+            .iter()
+            .map(|leg| leg.estimate.amount_out.base_units)
+            .sum()
+    }
+
+    // Price impact of the legs weighted by their input
+    pub fn price_impact(&self) -> f64 {
+        let total: u64 = self
+            .legs
+            .iter()
+            .map(|leg| leg.estimate.amount_in.base_units)
+            .sum();
+        if total == 0 {
+            return 0.0;
+        }
+        self.legs
+            .iter()
+            .map(|leg| leg.estimate.price_impact * leg.estimate.amount_in.base_units as f64)
+            .sum::<f64>()
+            / total as f64
+    }
+
+    fn describe(&self) -> String {
+        self.legs
+            .iter()
+            .map(|leg| format!("{} {}/{}", leg.venue, leg.parts, self.split_parts))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// Input of `parts` out of `split_parts` equal parts of `amount_in`
+pub fn split_amount(amount_in: u64, parts: u32, split_parts: u32) -> u64 {
+    (amount_in as u128 * parts as u128 / split_parts as u128) as u64
+}
+
+// Picks how many of the `split_parts` parts each venue swaps so that the total
+// output is highest. `quotes[v][k - 1]` is venue v's quote for k parts, None
+// where it could not quote. Every part must be placed.
+pub fn plan_route(
+    split_parts: u32,
+    quotes: &[(String, Vec<Option<SwapEstimate>>)],
+) -> Option<RoutePlan> {
+    let parts = split_parts as usize;
+    // Best output and allocation for each number of parts placed so far
+    let mut best: Vec<Option<(u64, Vec<(usize, usize)>)>> = vec![None; parts + 1];
+    best[0] = Some((0, Vec::new()));
+    for (venue, (_, estimates)) in quotes.iter().enumerate() {
+        let mut next = best.clone();
+        for placed in 1..=parts {
+            for k in 1..=placed {
+                let (Some((out, allocation)), Some(Some(estimate))) =
+                    (&best[placed - k], estimates.get(k - 1))
+                else {
+                    continue;
+                };
+                let total = out + estimate.amount_out.base_units;
+                // Ties keep the allocation with fewer legs
+                let better = match &next[placed] {
+                    Some((best_out, best_allocation)) => {
+                        total > *best_out
+                            || (total == *best_out && allocation.len() + 1 < best_allocation.len())
+                    }
+                    None => true,
+                };
+                if better {
+                    let mut allocation = allocation.clone();
+                    allocation.push((venue, k));
+                    next[placed] = Some((total, allocation));
+                }
+            }
+        }
+        best = next;
+    }
+
+    let (_, allocation) = best[parts].take()?;
+    let mut legs: Vec<RouteLeg> = allocation
+        .into_iter()
+        .map(|(venue, k)| RouteLeg {
+            venue: quotes[venue].0.clone(),
+            parts: k as u32,
+            estimate: quotes[venue].1[k - 1].unwrap(),
+        })
+        .collect();
+    legs.sort_by_key(|leg| std::cmp::Reverse(leg.parts));
+    Some(RoutePlan { split_parts, legs })
+}
+
+// Sends each swap through whichever venues pay the most for it, splitting the
+// order when spreading it lowers the total price impact. Every decision is
+// logged with the quotes it was chosen from, including Jupiter's for the full
+// size when Jupiter is one of the venues.
+pub struct SmartRouter {
+    venues: DexClients,
+    split_parts: u32,
+    trading: TradingParams,
+}
+
+impl SmartRouter {
+    pub fn new(venues: DexClients, config: &RouterConfig, trading: TradingParams) -> Self {
+        Self {
+            venues,
+            split_parts: config.split_parts.max(1),
+            trading,
+        }
+    }
+
+    // Quotes every venue for each multiple of one part in parallel and picks
+    // the best allocation. Parts of the order below the minimum trade size
+    // are not quoted, since the venue would refuse to swap them.
+    pub async fn plan(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+    ) -> Result<RoutePlan> {
+        let parts = self.split_parts;
+        let min_trade_size = self.trading.borrow().min_trade_size;
+        let requests = self.venues.iter().flat_map(|(venue, client)| {
+            (1..=parts).map(move |k| {
+                let amount = TokenAmount::new(
+                    split_amount(amount_in.base_units, k, parts),
+                    amount_in.decimals,
+                );
+                async move {
+                    let estimate = if amount.is_zero() {
+                        Err(anyhow!("part is below one base unit"))
+                    } else if k < parts && amount.to_ui() < min_trade_size {
+                        Err(anyhow!("part is below the minimum trade size"))
+                    } else {
+                        client.quote_swap(token_in, token_out, amount).await
+                    };
+                    (venue, k, estimate)
+                }
+            })
+        });
+
+        let mut quotes: Vec<(String, Vec<Option<SwapEstimate>>)> = self
+            .venues
+            .names()
+            .map(|venue| (venue.to_string(), vec![None; parts as usize]))
+            .collect();
+        let mut full_size = Vec::new();
+        for (venue, k, estimate) in join_all(requests).await {
+            if k == parts {
+                full_size.push((venue, estimate.as_ref().ok().copied()));
+                if let Err(e) = &estimate {
+                    warn!("Router: {} could not quote {}: {:#}", venue, amount_in, e);
+                }
+            }
+            if let (Ok(estimate), Some((_, estimates))) =
+                (estimate, quotes.iter_mut().find(|(name, _)| name == venue))
+            {
+                estimates[k as usize - 1] = Some(estimate);
+            }
+        }
+
+        let plan = plan_route(parts, &quotes).ok_or_else(|| {
+            anyhow!(
+                "no venue could quote {} of {} for {}",
+                amount_in,
+                token_in,
+                token_out
+            )
+        })?;
+
+        for (venue, estimate) in &full_size {
+            if let Some(estimate) = estimate {
+                info!(
+                    "Router: {} quotes {} -> {} ({:.4}% impact)",
+                    venue,
+                    estimate.amount_in,
+                    estimate.amount_out,
+                    estimate.price_impact * 100.0
+                );
+            }
+        }
+        let amount_out = plan.amount_out();
+        let versus_jupiter = full_size
+            .iter()
+            .find(|(venue, _)| *venue == JUPITER)
+            .and_then(|(_, estimate)| *estimate)
+            .filter(|estimate| !estimate.amount_out.is_zero())
+            .map(|estimate| {
+                let jupiter = estimate.amount_out.base_units as f64;
+                format!(
+                    ", {:+.1} bps vs Jupiter",
+                    (amount_out as f64 - jupiter) / jupiter * 10_000.0
+                )
+            })
+            .unwrap_or_default();
+        info!(
+            "Router: {} {} -> {} {} via {} ({:.4}% impact{})",
+            amount_in,
+            token_in,
+            amount_out,
+            token_out,
+            plan.describe(),
+            plan.price_impact() * 100.0,
+            versus_jupiter
+        );
+        Ok(plan)
+    }
+}
+
+#[async_trait]
+impl DexClient for SmartRouter {
+    // The best price any venue offers
+    async fn get_price(&self, token_a: &Pubkey, token_b: &Pubkey) -> Result<Price> {
+        join_all(
+            self.venues
+                .iter()
+                .map(|(_, client)| client.get_price(token_a, token_b)),
+        )
+        .await
+        .into_iter()
+        .filter_map(|price| price.ok())
+        .max()
+        .ok_or_else(|| anyhow!("no venue could price {}/{}", token_a, token_b))
+    }
+
+    async fn create_lp_position(
+        &self,
+        _token_a: &Pubkey,
+        _token_b: &Pubkey,
+        _amount_a: TokenAmount,
+        _amount_b: TokenAmount,
+        _min_price: Price,
+        _max_price: Price,
+    ) -> Result<String> {
+        Err(anyhow!("the router only swaps; open positions on a venue"))
+    }
+
+    async fn rebalance_position(
+        &self,
+        _position_id: &str,
+        _new_min_price: Price,
+        _new_max_price: Price,
+    ) -> Result<RebalanceReceipt> {
+        Err(anyhow!("the router does not hold LP positions"))
+    }
+
+    async fn harvest_fees(&self, _position_id: &str) -> Result<HarvestReceipt> {
+        Err(anyhow!("the router does not hold LP positions"))
+    }
+
+    async fn get_unclaimed_fees(&self, _position_id: &str) -> Result<FeeAmounts> {
+        Err(anyhow!("the router does not hold LP positions"))
+    }
+
+    async fn increase_liquidity(
+        &self,
+        _position_id: &str,
+        _amount_a: TokenAmount,
+        _amount_b: TokenAmount,
+    ) -> Result<String> {
+        Err(anyhow!("the router does not hold LP positions"))
+    }
+
+    // Positions are listed by the venues that hold them
+    async fn get_positions(&self) -> Result<Vec<LpPosition>> {
+        Ok(Vec::new())
+    }
+
+    async fn quote_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+    ) -> Result<SwapEstimate> {
+        let plan = self.plan(token_in, token_out, amount_in).await?;
+        Ok(SwapEstimate {
+            amount_in,
+            amount_out: TokenAmount::new(
+                plan.amount_out(),
+                plan.legs[0].estimate.amount_out.decimals,
+            ),
+            price_impact: plan.price_impact(),
+        })
+    }
+
+    // Legs are sent largest first, each with its share of `min_amount_out`.
+    // A leg that fails stops the rest; the signatures of legs that already
+    // landed are part of the error. Returns the signatures comma separated.
+    async fn execute_swap(
+        &self,
+        token_in: &Pubkey,
+        token_out: &Pubkey,
+        amount_in: TokenAmount,
+        min_amount_out: TokenAmount,
+    ) -> Result<String> {
+        check_trade_size(&self.trading.borrow(), amount_in)?;
+        let plan = self.plan(token_in, token_out, amount_in).await?;
+        // Read once the route is known, so a reloaded threshold applies
+        check_price_impact(
+            plan.price_impact(),
+            self.trading.borrow().price_impact_threshold,
+        )?;
+        let decimals_out = plan.legs[0].estimate.amount_out.decimals;
+        let min_total = min_amount_out.base_units_at(decimals_out, Rounding::Up)?;
+        let expected = plan.amount_out();
+        if expected < min_total {
+            return Err(anyhow!(
+                "best route returns {} base units, below the minimum output {}",
+                expected,
+                min_total
+            ));
+        }
+
+        // Parts are rounded down, so the largest leg also takes the remainder
+        let leg_amounts: Vec<u64> = plan
+            .legs
+            .iter()
+            .map(|leg| split_amount(amount_in.base_units, leg.parts, plan.split_parts))
+            .collect();
+        let remainder = amount_in.base_units - leg_amounts.iter().sum::<u64>();
+
+        let mut signatures = Vec::new();
+        for (i, leg) in plan.legs.iter().enumerate() {
+            let leg_in = TokenAmount::new(
+                leg_amounts[i] + if i == 0 { remainder } else { 0 },
+                amount_in.decimals,
+            );
+            let leg_min = (min_total as u128 * leg.estimate.amount_out.base_units as u128)
+                .div_ceil(expected.max(1) as u128) as u64;
+            let client = self.venues.require(&leg.venue)?;
+            match client
+                .execute_swap(
+                    token_in,
+                    token_out,
+                    leg_in,
+                    TokenAmount::new(leg_min, decimals_out),
+                )
+                .await
+            {
+                Ok(signature) => signatures.push(signature),
+                Err(e) if signatures.is_empty() => return Err(e),
+                Err(e) => {
+                    return Err(e.context(format!(
+                        "routed swap stopped at {} after legs {} landed",
+                        leg.venue,
+                        signatures.join(", ")
+                    )))
+                }
+            }
+        }
+        Ok(signatures.join(","))
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Result};
+
+// Concentrated liquidity math shared by the CLMM venues. On chain prices are
+// Q64.64 square roots of the raw (base unit) price; off-chain estimates here
+// use f64, which is precise to well within a tick.
+
+pub const MIN_TICK: i32 = -443636;
+pub const MAX_TICK: i32 = 443636;
+
+const TICK_BASE: f64 = 1.0001;
+const Q64: f64 = 18_446_744_073_709_551_616.0;
+
+// Converts a UI price (token_b per token_a) to the tick at or just below it
+pub fn price_to_tick(price: f64, decimals_a: u8, decimals_b: u8) -> Result<i32> {
+    if !price.is_finite() || price <= 0.0 {
+        return Err(anyhow!("price must be positive, got {}", price));
+    }
+    let raw = price * decimal_factor(decimals_a, decimals_b);
+    // Nudge up so prices that sit exactly on a tick are not floored to the one below
+    let tick = (raw.ln() / TICK_BASE.ln() + 1e-9).floor();
+    if tick < MIN_TICK as f64 || tick > MAX_TICK as f64 {
+        return Err(anyhow!(
+            "price {} is outside the supported tick range",
+            price
+        ));
+    }
+    Ok(tick as i32)
+}
+
+pub fn tick_to_price(tick: i32, decimals_a: u8, decimals_b: u8) -> f64 {
+    TICK_BASE.powi(tick) / decimal_factor(decimals_a, decimals_b)
+}
+
+pub fn round_tick_down(tick: i32, tick_spacing: u16) -> i32 {
+    let spacing = tick_spacing as i32;
+    tick.div_euclid(spacing) * spacing
+}
+
+pub fn round_tick_up(tick: i32, tick_spacing: u16) -> i32 {
+    let down = round_tick_down(tick, tick_spacing);
+    if down == tick {
+        tick
+    } else {
+        down + tick_spacing as i32
+    }
+}
+
+// Initializable tick range covering at least `min_price..max_price`
+pub fn tick_range(
+    min_price: f64,
+    max_price: f64,
+    decimals_a: u8,
+    decimals_b: u8,
+    tick_spacing: u16,
+) -> Result<(i32, i32)> {
+    if min_price >= max_price {
+        return Err(anyhow!("min_price must be below max_price"));
+    }
+    let lower = round_tick_down(
+        price_to_tick(min_price, decimals_a, decimals_b)?,
+        tick_spacing,
+    );
+    let mut upper = round_tick_up(
+        price_to_tick(max_price, decimals_a, decimals_b)?,
+        tick_spacing,
+    );
+    if upper <= lower {
+        upper = lower + tick_spacing as i32;
+    }
+    if lower < round_tick_up(MIN_TICK, tick_spacing)
+        || upper > round_tick_down(MAX_TICK, tick_spacing)
+    {
+        return Err(anyhow!(
+            "range {}..{} is outside the supported tick range",
+            min_price,
+            max_price
+        ));
+    }
+    Ok((lower, upper))
+}
+
+// First tick of the tick array holding `tick`
+pub fn tick_array_start_index(tick: i32, tick_spacing: u16, ticks_per_array: i32) -> i32 {
+    let span = tick_spacing as i32 * ticks_per_array;
+    tick.div_euclid(span) * span
+}
+
+pub fn sqrt_price_at_tick(tick: i32) -> f64 {
+    TICK_BASE.powf(tick as f64 / 2.0)
+}
+
+pub fn sqrt_price_from_x64(sqrt_price_x64: u128) -> f64 {
+    sqrt_price_x64 as f64 / Q64
+}
+
+pub fn sqrt_price_to_x64(sqrt_price: f64) -> u128 {
+    (sqrt_price * Q64) as u128
+}
+
+// UI price (token_b per token_a) from an on-chain sqrt price
+pub fn sqrt_price_x64_to_price(sqrt_price_x64: u128, decimals_a: u8, decimals_b: u8) -> f64 {
+    let sqrt_price = sqrt_price_from_x64(sqrt_price_x64);
+    sqrt_price * sqrt_price / decimal_factor(decimals_a, decimals_b)
+}
+
+// Largest liquidity the given base unit amounts can fund in `sqrt_lower..sqrt_upper`
+pub fn liquidity_for_amounts(
+    sqrt_price: f64,
+    sqrt_lower: f64,
+    sqrt_upper: f64,
+    amount_a: u64,
+    amount_b: u64,
+) -> u128 {
+    let from_a = |lower: f64, upper: f64| amount_a as f64 * lower * upper / (upper - lower);
+    let from_b = |lower: f64, upper: f64| amount_b as f64 / (upper - lower);
+
+    let liquidity = if sqrt_price <= sqrt_lower {
+        from_a(sqrt_lower, sqrt_upper)
+    } else if sqrt_price >= sqrt_upper {
+        from_b(sqrt_lower, sqrt_upper)
+    } else {
+        from_a(sqrt_price, sqrt_upper).min(from_b(sqrt_lower, sqrt_price))
+    };
+    liquidity as u128
+}
+
+// Base unit amounts of token a and token b backing one unit of liquidity at
+// the current price; their ratio is what a deposit into the range must match
+pub fn amounts_per_liquidity(sqrt_price: f64, sqrt_lower: f64, sqrt_upper: f64) -> (f64, f64) {
+    let price = sqrt_price.clamp(sqrt_lower, sqrt_upper);
+    (
+        (sqrt_upper - price) / (price * sqrt_upper),
+        price - sqrt_lower,
+    )
+}
+
+// Base unit amounts backing `liquidity` at the current price, rounded down
+pub fn amounts_for_liquidity(
+    sqrt_price: f64,
+    sqrt_lower: f64,
+    sqrt_upper: f64,
+    liquidity: u128,
+) -> (u64, u64) {
+    let (per_a, per_b) = amounts_per_liquidity(sqrt_price, sqrt_lower, sqrt_upper);
+    let liquidity = liquidity as f64;
+    ((liquidity * per_a) as u64, (liquidity * per_b) as u64)
+}
+
+// Fee growth per unit of liquidity accrued inside `tick_lower..tick_upper`.
+// Growth values are Q64.64 accumulators that are allowed to wrap.
+pub fn fee_growth_inside(
+    tick_current: i32,
+    tick_lower: i32,
+    tick_upper: i32,
+    fee_growth_global: u128,
+    lower_outside: u128,
+    upper_outside: u128,
+) -> u128 {
+    let below = if tick_current >= tick_lower {
+        lower_outside
+    } else {
+        fee_growth_global.wrapping_sub(lower_outside)
+    };
+    let above = if tick_current < tick_upper {
+        upper_outside
+    } else {
+        fee_growth_global.wrapping_sub(upper_outside)
+    };
+    fee_growth_global.wrapping_sub(below).wrapping_sub(above)
+}
+
+// Base units earned by `liquidity` since the growth was last checkpointed
+pub fn fees_earned(liquidity: u128, growth_inside: u128, growth_inside_last: u128) -> u64 {
+    let growth = growth_inside.wrapping_sub(growth_inside_last);
+    (liquidity as f64 * growth as f64 / Q64) as u64
+}
+
+fn decimal_factor(decimals_a: u8, decimals_b: u8) -> f64 {
+    10f64.powi(decimals_b as i32 - decimals_a as i32)
+}
@@ -0,0 +1,399 @@
+use anyhow::{anyhow, Result};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+
+use super::layout::{mint_decimals, read_pubkey, read_u16, read_u32, read_u64, read_u8};
+use crate::tokens::TokenProgram;
+
+// Token-2022 accounts with extensions are padded to the size of a token
+// account, followed by an account type byte and then the extensions as
+// type-length-value entries
+const ACCOUNT_LEN: usize = 165;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+
+// Mint extension types
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const NON_TRANSFERABLE: u16 = 9;
+const INTEREST_BEARING_CONFIG: u16 = 10;
+const TRANSFER_HOOK: u16 = 14;
+
+// TransferFeeConfig: two authorities and the withheld amount, then the older
+// and newer fee schedules
+const OLDER_TRANSFER_FEE: usize = 72;
+const NEWER_TRANSFER_FEE: usize = 90;
+// TransferHook: authority, then the hook program
+const TRANSFER_HOOK_PROGRAM: usize = 32;
+
+const MAX_FEE_BASIS_POINTS: u128 = 10_000;
+
+const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+// Accounts every hook `Execute` instruction starts with: source, mint,
+// destination, authority and the validation account
+const EXECUTE_ACCOUNTS: usize = 5;
+const EXTRA_ACCOUNT_META_LEN: usize = 35;
+
+// A transfer fee schedule, in effect from `epoch` on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFee {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub basis_points: u16,
+}
+
+impl TransferFee {
+    fn decode(data: &[u8], offset: usize) -> Result<Self> {
+        Ok(Self {
+            epoch: read_u64(data, offset)?,
+            maximum_fee: read_u64(data, offset + 8)?,
+            basis_points: read_u16(data, offset + 16)?,
+        })
+    }
+
+    // Withheld from a transfer of `amount`, rounded up and capped
+    pub fn fee(&self, amount: u64) -> u64 {
+        if self.basis_points == 0 || amount == 0 {
+            return 0;
+        }
+        let fee = (amount as u128 * self.basis_points as u128).div_ceil(MAX_FEE_BASIS_POINTS);
+        fee.min(self.maximum_fee as u128) as u64
+    }
+
+    // What arrives of a transfer of `amount`
+    pub fn net(&self, amount: u64) -> u64 {
+        amount - self.fee(amount)
+    }
+
+    // The smallest transfer that still delivers `net`
+    pub fn gross(&self, net: u64) -> Result<u64> {
+        if self.basis_points == 0 || net == 0 {
+            return Ok(net);
+        }
+        let net = net as u128;
+        let gross = if self.basis_points as u128 >= MAX_FEE_BASIS_POINTS {
+            net + self.maximum_fee as u128
+        } else {
+            let gross = (net * MAX_FEE_BASIS_POINTS)
+                .div_ceil(MAX_FEE_BASIS_POINTS - self.basis_points as u128);
+            gross.min(net + self.maximum_fee as u128)
+        };
+        u64::try_from(gross).map_err(|_| anyhow!("transfer of {} plus its fee overflows", net))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFeeConfig {
+    pub older: TransferFee,
+    pub newer: TransferFee,
+}
+
+impl TransferFeeConfig {
+    pub fn at_epoch(&self, epoch: u64) -> TransferFee {
+        if epoch >= self.newer.epoch {
+            self.newer
+        } else {
+            self.older
+        }
+    }
+}
+
+// A mint account with the Token-2022 extensions that change what a transfer
+// delivers or whether it can happen at all
+#[derive(Debug, Clone, PartialEq)]
+pub struct MintInfo {
+    pub mint: Pubkey,
+    pub program: TokenProgram,
+    pub decimals: u8,
+    pub transfer_fee: Option<TransferFeeConfig>,
+    // Program every transfer invokes, which may require extra accounts
+    pub transfer_hook: Option<Pubkey>,
+    // UI amounts accrue interest, so balances and quotes drift from base units
+    pub interest_bearing: bool,
+    pub non_transferable: bool,
+}
+
+impl MintInfo {
+    pub fn parse(mint: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<Self> {
+        let program = TokenProgram::from_owner(owner)
+            .ok_or_else(|| anyhow!("{} is not a token mint (owned by {})", mint, owner))?;
+        let mut info = Self {
+            mint: *mint,
+            program,
+            decimals: mint_decimals(data)?,
+            transfer_fee: None,
+            transfer_hook: None,
+            interest_bearing: false,
+            non_transferable: false,
+        };
+        if program != TokenProgram::Token2022 || data.len() <= ACCOUNT_LEN {
+            return Ok(info);
+        }
+        if read_u8(data, ACCOUNT_LEN)? != ACCOUNT_TYPE_MINT {
+            return Err(anyhow!("{} is not a mint account", mint));
+        }
+
+        let mut offset = ACCOUNT_LEN + 1;
+        while offset + 4 <= data.len() {
+            let kind = read_u16(data, offset)?;
+            let length = read_u16(data, offset + 2)? as usize;
+            let value = data
+                .get(offset + 4..offset + 4 + length)
+                .ok_or_else(|| anyhow!("mint {} has a truncated extension {}", mint, kind))?;
+            match kind {
+                0 => break,
+                TRANSFER_FEE_CONFIG => {
+                    info.transfer_fee = Some(TransferFeeConfig {
+                        older: TransferFee::decode(value, OLDER_TRANSFER_FEE)?,
+                        newer: TransferFee::decode(value, NEWER_TRANSFER_FEE)?,
+                    })
+                }
+                TRANSFER_HOOK => {
+                    let program = read_pubkey(value, TRANSFER_HOOK_PROGRAM)?;
+                    info.transfer_hook = (program != Pubkey::default()).then_some(program);
+                }
+                INTEREST_BEARING_CONFIG => info.interest_bearing = true,
+                NON_TRANSFERABLE => info.non_transferable = true,
+                _ => {}
+            }
+            offset += 4 + length;
+        }
+        Ok(info)
+    }
+
+    // Extensions a quote or transfer has to account for, for logging
+    pub fn extensions(&self) -> Vec<&'static str> {
+        let mut extensions = Vec::new();
+        if self.transfer_fee.is_some() {
+            extensions.push("transfer fee");
+        }
+        if self.transfer_hook.is_some() {
+            extensions.push("transfer hook");
+        }
+        if self.interest_bearing {
+            extensions.push("interest bearing");
+        }
+        if self.non_transferable {
+            extensions.push("non-transferable");
+        }
+        extensions
+    }
+
+    // Non-transferable tokens cannot be swapped at all, and interest-bearing
+    // ones quote in amounts that no longer match what the pools hold
+    pub fn check_tradable(&self) -> Result<()> {
+        if self.non_transferable {
+            return Err(anyhow!("token {} is non-transferable", self.mint));
+        }
+        if self.interest_bearing {
+            return Err(anyhow!(
+                "token {} is interest-bearing, so its quotes are not reliable",
+                self.mint
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Transfer fees on both legs of a swap: the pool receives the input net of
+// its fee, and the wallet receives the output net of its fee
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwapTransferFees {
+    pub input: Option<TransferFee>,
+    pub output: Option<TransferFee>,
+}
+
+impl SwapTransferFees {
+    // Fails for tokens that are unsafe to trade. The epoch is only fetched
+    // when either mint charges a fee.
+    pub async fn load(
+        rpc_client: &RpcClient,
+        mint_in: &MintInfo,
+        mint_out: &MintInfo,
+    ) -> Result<Self> {
+        mint_in.check_tradable()?;
+        mint_out.check_tradable()?;
+        if mint_in.transfer_fee.is_none() && mint_out.transfer_fee.is_none() {
+            return Ok(Self::default());
+        }
+        let epoch = rpc_client.get_epoch_info().await?.epoch;
+        Ok(Self {
+            input: mint_in.transfer_fee.map(|config| config.at_epoch(epoch)),
+            output: mint_out.transfer_fee.map(|config| config.at_epoch(epoch)),
+        })
+    }
+
+    // What reaches the pool of `amount_in` sent from the wallet
+    pub fn pool_input(&self, amount_in: u64) -> u64 {
+        self.input.map_or(amount_in, |fee| fee.net(amount_in))
+    }
+
+    // What reaches the wallet of `amount_out` sent by the pool
+    pub fn received(&self, amount_out: u64) -> u64 {
+        self.output.map_or(amount_out, |fee| fee.net(amount_out))
+    }
+
+    // Pool output needed for the wallet to receive at least `min_received`.
+    // Pools check their minimum before the output fee is withheld.
+    pub fn pool_min_output(&self, min_received: u64) -> Result<u64> {
+        self.output
+            .map_or(Ok(min_received), |fee| fee.gross(min_received))
+    }
+}
+
+pub fn extra_account_metas_address(mint: &Pubkey, hook_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[EXTRA_ACCOUNT_METAS_SEED, mint.as_ref()], hook_program).0
+}
+
+// `Execute` instruction discriminator, which also tags its account list in
+// the validation account
+fn execute_discriminator() -> [u8; 8] {
+    let hash = solana_sdk::hash::hash(b"spl-transfer-hook-interface:execute");
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+// Accounts a transfer of `mint` passes on to its hook: the extra accounts the
+// hook declares, then the hook program and its validation account. Empty for
+// mints without a hook.
+pub async fn transfer_hook_accounts(
+    rpc_client: &RpcClient,
+    mint: &MintInfo,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Result<Vec<AccountMeta>> {
+    let program = match mint.transfer_hook {
+        Some(program) => program,
+        None => return Ok(Vec::new()),
+    };
+    let validation = extra_account_metas_address(&mint.mint, &program);
+    let data = rpc_client
+        .get_account_data(&validation)
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "transfer hook accounts of {} not found at {}: {}",
+                mint.mint,
+                validation,
+                e
+            )
+        })?;
+
+    let mut execute_data = execute_discriminator().to_vec();
+    execute_data.extend_from_slice(&amount.to_le_bytes());
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*source, false),
+        AccountMeta::new_readonly(mint.mint, false),
+        AccountMeta::new_readonly(*destination, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(validation, false),
+    ];
+    for meta in extra_account_metas(&data)? {
+        let pubkey = resolve_extra_account(meta, &program, &accounts, &execute_data)?;
+        accounts.push(AccountMeta {
+            pubkey,
+            is_signer: meta[33] != 0,
+            is_writable: meta[34] != 0,
+        });
+    }
+
+    let mut extra = accounts.split_off(EXECUTE_ACCOUNTS);
+    extra.push(AccountMeta::new_readonly(program, false));
+    extra.push(AccountMeta::new_readonly(validation, false));
+    Ok(extra)
+}
+
+// The packed `ExtraAccountMeta` entries listed for `Execute`
+fn extra_account_metas(data: &[u8]) -> Result<Vec<&[u8]>> {
+    let discriminator = execute_discriminator();
+    let mut offset = 0;
+    while offset + 12 <= data.len() {
+        let length = read_u32(data, offset + 8)? as usize;
+        if data[offset..offset + 8] != discriminator {
+            offset += 12 + length;
+            continue;
+        }
+        let count = read_u32(data, offset + 12)? as usize;
+        let start = offset + 16;
+        let entries = data
+            .get(start..start + count * EXTRA_ACCOUNT_META_LEN)
+            .ok_or_else(|| anyhow!("truncated transfer hook account list"))?;
+        return Ok(entries.chunks(EXTRA_ACCOUNT_META_LEN).collect());
+    }
+    Err(anyhow!("validation account lists no accounts for Execute"))
+}
+
+// An entry is either a fixed address or a PDA of the hook program (or of
+// an earlier account) derived from packed seeds
+fn resolve_extra_account(
+    meta: &[u8],
+    hook_program: &Pubkey,
+    accounts: &[AccountMeta],
+    instruction_data: &[u8],
+) -> Result<Pubkey> {
+    let config = &meta[1..33];
+    let program = match meta[0] {
+        0 => return read_pubkey(config, 0),
+        1 => *hook_program,
+        index @ 128.. => {
+            accounts
+                .get((index - 128) as usize)
+                .ok_or_else(|| anyhow!("transfer hook seed program {} out of range", index))?
+                .pubkey
+        }
+        other => return Err(anyhow!("unknown transfer hook account type {}", other)),
+    };
+    let seeds = resolve_seeds(config, accounts, instruction_data)?;
+    let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+    Ok(Pubkey::find_program_address(&seeds, &program).0)
+}
+
+fn resolve_seeds(
+    config: &[u8],
+    accounts: &[AccountMeta],
+    instruction_data: &[u8],
+) -> Result<Vec<Vec<u8>>> {
+    let out_of_range = || anyhow!("transfer hook seed out of range");
+    let mut seeds = Vec::new();
+    let mut offset = 0;
+    while offset < config.len() {
+        match config[offset] {
+            0 => break,
+            // Literal bytes
+            1 => {
+                let length = read_u8(config, offset + 1)? as usize;
+                let bytes = config
+                    .get(offset + 2..offset + 2 + length)
+                    .ok_or_else(out_of_range)?;
+                seeds.push(bytes.to_vec());
+                offset += 2 + length;
+            }
+            // A slice of the Execute instruction data
+            2 => {
+                let start = read_u8(config, offset + 1)? as usize;
+                let length = read_u8(config, offset + 2)? as usize;
+                let bytes = instruction_data
+                    .get(start..start + length)
+                    .ok_or_else(out_of_range)?;
+                seeds.push(bytes.to_vec());
+                offset += 3;
+            }
+            // The key of an earlier account
+            3 => {
+                let index = read_u8(config, offset + 1)? as usize;
+                let account = accounts.get(index).ok_or_else(out_of_range)?;
+                seeds.push(account.pubkey.to_bytes().to_vec());
+                offset += 2;
+            }
+            4 => {
+                return Err(anyhow!(
+                    "transfer hook seeds from account data are not supported"
+                ))
+            }
+            other => return Err(anyhow!("unknown transfer hook seed type {}", other)),
+        }
+    }
+    Ok(seeds)
+}
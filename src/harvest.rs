@@ -0,0 +1,141 @@
+use anyhow::Result;
+use solana_sdk::native_token::lamports_to_sol;
+use std::time::Duration;
+use tracing::{debug, info, warn, Instrument};
+
+use crate::config::HarvestConfig;
+use crate::dex::programs::NATIVE_MINT;
+use crate::dex::{DexClient, DexClients, FeeAmounts};
+use crate::logging;
+use crate::units::Rounding;
+
+const SOL_DECIMALS: u8 = 9;
+
+// Periodically checks every LP position and harvests the ones whose unclaimed
+// fees are worth enough, in SOL, to pay for the transactions many times over
+pub struct HarvestScheduler {
+    dex_clients: DexClients,
+    config: HarvestConfig,
+}
+
+impl HarvestScheduler {
+    pub fn new(dex_clients: DexClients, config: HarvestConfig) -> Self {
+        Self {
+            dex_clients,
+            config,
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            interval.tick().await;
+            for (venue, client) in self.dex_clients.iter() {
+                if !self.config.venues.is_empty() && !self.config.venues.iter().any(|v| v == venue)
+                {
+                    continue;
+                }
+                // A venue failing to list its positions should not stop the others
+                let positions = match client.get_positions().await {
+                    Ok(positions) => positions,
+                    Err(e) => {
+                        warn!("Harvest: failed to list {} positions: {:#}", venue, e);
+                        continue;
+                    }
+                };
+                for position in positions {
+                    if let Err(e) = self
+                        .check_position(venue, client.as_ref(), &position.id)
+                        .await
+                    {
+                        warn!(
+                            "Harvest: position {} on {} failed: {:#}",
+                            position.id, venue, e
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    async fn check_position(
+        &self,
+        venue: &str,
+        client: &dyn DexClient,
+        position_id: &str,
+    ) -> Result<()> {
+        let fees = client.get_unclaimed_fees(position_id).await?;
+        let fee_value = fees_value_in_sol(client, &fees).await?;
+        let transactions = if self.config.auto_compound { 2 } else { 1 };
+        let cost = lamports_to_sol(self.config.transaction_cost_lamports * transactions);
+
+        if !should_harvest(fee_value, cost, self.config.min_fee_to_cost_ratio) {
+            debug!(
+                "Harvest: position {} on {} has {} SOL of fees, below {}x the {} SOL cost",
+                position_id, venue, fee_value, self.config.min_fee_to_cost_ratio, cost
+            );
+            return Ok(());
+        }
+
+        let span = logging::trade_span(
+            "harvest",
+            venue,
+            &format!("{}/{}", fees.token_a, fees.token_b),
+        );
+        span.record("position", position_id);
+        async {
+            let receipt = client.harvest_fees(position_id).await?;
+            info!(
+                "Harvested {} / {} from position {} ({} SOL of fees)",
+                receipt.collected.amount_a, receipt.collected.amount_b, position_id, fee_value
+            );
+
+            if self.config.auto_compound {
+                let signature = client
+                    .increase_liquidity(
+                        position_id,
+                        receipt.collected.amount_a,
+                        receipt.collected.amount_b,
+                    )
+                    .await?;
+                info!(
+                    "Compounded fees back into position {}: {}",
+                    position_id, signature
+                );
+            }
+            Ok(())
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+pub fn should_harvest(
+    fee_value_sol: f64,
+    transaction_cost_sol: f64,
+    min_fee_to_cost_ratio: f64,
+) -> bool {
+    fee_value_sol > 0.0 && fee_value_sol >= transaction_cost_sol * min_fee_to_cost_ratio
+}
+
+async fn fees_value_in_sol(client: &dyn DexClient, fees: &FeeAmounts) -> Result<f64> {
+    let mut lamports: u64 = 0;
+    for (mint, amount) in [(fees.token_a, fees.amount_a), (fees.token_b, fees.amount_b)] {
+        if amount.is_zero() {
+            continue;
+        }
+        let value = if mint == NATIVE_MINT {
+            amount.with_decimals(SOL_DECIMALS, Rounding::Down)?
+        } else {
+            // Undervalue rather than overvalue, so a harvest is never paid for
+            // with fees that are not there
+            client.get_price(&mint, &NATIVE_MINT).await?.quote_for(
+                amount,
+                SOL_DECIMALS,
+                Rounding::Down,
+            )?
+        };
+        lamports = lamports.saturating_add(value.base_units);
+    }
+    Ok(lamports_to_sol(lamports))
+}
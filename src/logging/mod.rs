@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::Write;
+use std::path::Path;
+use tracing::{field, Span};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{self, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::config::{LogRotation, LoggingConfig};
+
+mod rotation;
+
+pub use rotation::SizeRotatingWriter;
+
+// Flushes buffered file output when dropped, so it has to live until main returns
+pub struct LogGuard {
+    _file: WorkerGuard,
+}
+
+// Human readable output on stdout plus JSON lines in the rotating log file.
+// Every file event carries the fields of the spans it happened in, so grepping
+// for a signature or position id returns that action's whole history.
+pub fn init(config: &LoggingConfig) -> Result<LogGuard> {
+    let (writer, guard) = tracing_appender::non_blocking(file_writer(config)?);
+
+    tracing_subscriber::registry()
+        .with(build_filter(config)?)
+        .with(console_layer())
+        .with(
+            fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_ansi(false)
+                .with_writer(writer),
+        )
+        .try_init()
+        .map_err(|e| anyhow!("failed to install log subscriber: {}", e))?;
+
+    Ok(LogGuard { _file: guard })
+}
+
+// Stdout only, for commands that do not connect to anything
+pub fn init_console(config: &LoggingConfig) -> Result<()> {
+    tracing_subscriber::registry()
+        .with(build_filter(config)?)
+        .with(console_layer())
+        .try_init()
+        .map_err(|e| anyhow!("failed to install log subscriber: {}", e))
+}
+
+pub fn build_filter(config: &LoggingConfig) -> Result<EnvFilter> {
+    let mut filter = EnvFilter::try_new(&config.level)
+        .with_context(|| format!("invalid log level {:?}", config.level))?;
+    for directive in &config.filters {
+        filter = filter.add_directive(
+            directive
+                .parse()
+                .with_context(|| format!("invalid log filter {:?}", directive))?,
+        );
+    }
+    Ok(filter)
+}
+
+fn console_layer<S>() -> impl tracing_subscriber::Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fmt::layer()
+        .with_target(false)
+        .with_thread_ids(true)
+        .with_file(true)
+        .with_line_number(true)
+        .with_thread_names(true)
+        .with_ansi(true)
+        .pretty()
+}
+
+fn file_writer(config: &LoggingConfig) -> Result<Box<dyn Write + Send>> {
+    let path = &config.file_path;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create log directory {}", dir.display()))?;
+
+    match config.rotation {
+        LogRotation::Daily => {
+            let file_name = path
+                .file_name()
+                .ok_or_else(|| anyhow!("log file path {} has no file name", path.display()))?;
+            let appender = rolling::Builder::new()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(file_name.to_string_lossy())
+                .max_log_files(config.max_files + 1)
+                .build(dir)
+                .with_context(|| format!("failed to open log file {}", path.display()))?;
+            Ok(Box::new(appender))
+        }
+        LogRotation::Size => {
+            let writer = SizeRotatingWriter::new(
+                path,
+                config.max_file_size_mb * 1024 * 1024,
+                config.max_files,
+            )
+            .with_context(|| format!("failed to open log file {}", path.display()))?;
+            Ok(Box::new(writer))
+        }
+    }
+}
+
+// Span wrapping a single trade or position action. The signature is filled in
+// with `record_signature` once the transaction has been sent; position actions
+// also record the `position` they act on.
+pub fn trade_span(action: &str, venue: &str, pair: &str) -> Span {
+    tracing::info_span!(
+        "trade",
+        action,
+        venue,
+        pair,
+        position = field::Empty,
+        signature = field::Empty
+    )
+}
+
+pub fn record_signature(signature: &str) {
+    Span::current().record("signature", signature);
+}
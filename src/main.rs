@@ -1,68 +1,231 @@
 use anyhow::Result;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+use clap::Parser;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
+mod app;
+mod cli;
 mod config;
 mod dex;
+mod harvest;
+mod logging;
 mod oracles;
 mod cex;
 mod models;
+mod registry;
+mod secrets;
+mod shutdown;
 mod utils;
 mod metrics;
 mod simulation;
+mod strategy;
+mod supervisor;
+mod tokens;
+mod transactions;
+mod units;
+
+use cli::{Command, ConfigCommand};
+
+// How often open CEX orders are checked for fills to deliver to strategies
+const ORDER_FILL_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_file(true)
-        .with_line_number(true)
-        .with_thread_names(true)
-        .with_ansi(true)
-        .pretty()
-        .init();
+    let cli = cli::Cli::parse();
+    let sources = cli.config_sources();
+
+    match cli.command.unwrap_or(Command::Run) {
+        // These only need the configuration, not any connections
+        Command::Config {
+            action: ConfigCommand::Check,
+        } => {
+            logging::init_console(&config::LoggingConfig::default())?;
+            cli::commands::config_check(&sources)
+        }
+        Command::Keystore { action } => {
+            let config = config::Config::build(&sources)?;
+            logging::init_console(&config.logging)?;
+            cli::keystore::run(&config, &action)
+        }
+        command => {
+            // Load configuration
+            let config = config::Config::load_from(&sources)?;
+
+            // Initialize logging; the guard flushes the log file on exit
+            let _log_guard = logging::init(&config.logging)?;
+            info!("Configuration loaded successfully");
+
+            if let Command::Run = command {
+                return run(config, sources).await;
+            }
+
+            let trading_params = config::fixed_trading_params(&config);
+            let app = app::App::init(config, trading_params).await?;
+            cli::commands::execute(&app, command).await
+        }
+    }
+}
 
+async fn run(config: config::Config, sources: config::ConfigSources) -> Result<()> {
     info!("Starting Solana DEX Bot...");
 
-    // Load configuration
-    let config = config::Config::load()?;
-    info!("Configuration loaded successfully");
+    // Every spawned task watches this token and stops when it is cancelled
+    let shutdown = CancellationToken::new();
+    shutdown::spawn_signal_handler(shutdown.clone());
+
+    // Watch the config file for trading parameter changes
+    let (trading_params, _config_watcher) =
+        config::watch_trading_config(sources, &config, shutdown.clone())?;
 
     // Initialize metrics
     metrics::init(&config)?;
     info!("Metrics initialized");
 
-    // Initialize DEX clients
-    let dex_clients = dex::init_clients(&config).await?;
-    info!("DEX clients initialized");
-
-    // Initialize price feeds
-    let price_feeds = oracles::init_price_feeds(&config).await?;
-    info!("Price feeds initialized");
+    // Initialize wallet and venue clients
+    let app = app::App::init(config, trading_params.clone()).await?;
 
-    // Initialize CEX clients
-    let cex_clients = cex::init_clients(&config).await?;
-    info!("CEX clients initialized");
+    // Start background monitors under supervision
+    let supervisor = supervisor::Supervisor::new(shutdown.clone());
+    spawn_monitors(&app, &supervisor)?;
 
     // Start the main trading loop
-    run_trading_loop(config, dex_clients, price_feeds, cex_clients).await?;
+    let result = run_trading_loop(&app, &trading_params, shutdown.clone()).await;
+    if let Err(e) = &result {
+        error!("Trading loop failed: {:#}", e);
+    }
+    shutdown.cancel();
+    supervisor.join().await;
+
+    let trading = trading_params.borrow().clone();
+    shutdown::wind_down(&app, &trading).await?;
+    info!("Shutdown complete");
+
+    result
+}
 
+fn spawn_monitors(app: &app::App, supervisor: &supervisor::Supervisor) -> Result<()> {
+    let monitoring = &app.config.monitoring;
+    let policy = monitoring.restart.policy();
+
+    for monitor in &monitoring.price_changes {
+        let feed = app.price_feeds.require(&monitor.feed)?;
+        let symbol = monitor.symbol.clone();
+        let threshold = monitor.threshold;
+        let name = format!("price_changes:{}:{}", monitor.feed, symbol);
+        let callback: Arc<dyn Fn(f64) + Send + Sync> = {
+            let name = name.clone();
+            Arc::new(move |price| info!("{}: price moved to {}", name, price))
+        };
+        supervisor.spawn(&name, policy.clone(), move || {
+            let feed = feed.clone();
+            let symbol = symbol.clone();
+            let callback = callback.clone();
+            async move { oracles::monitor_price_changes(feed, &symbol, threshold, callback).await }
+        });
+    }
+
+    for monitor in &monitoring.cex_spreads {
+        let clients = app.cex_clients.clone();
+        let symbol = monitor.symbol.clone();
+        let threshold = monitor.threshold;
+        supervisor.spawn(
+            &format!("cex_spreads:{}", symbol),
+            policy.clone(),
+            move || {
+                let clients = clients.clone();
+                let symbol = symbol.clone();
+                async move { cex::monitor_price_differences(&clients, &symbol, threshold).await }
+            },
+        );
+    }
+
+    if app.config.harvest.enabled {
+        let scheduler = Arc::new(harvest::HarvestScheduler::new(
+            app.dex_clients.clone(),
+            app.config.harvest.clone(),
+        ));
+        supervisor.spawn("harvest", policy.clone(), move || {
+            let scheduler = scheduler.clone();
+            async move { scheduler.run().await }
+        });
+    }
+
+    if app.config.sweep.enabled {
+        let keep = app
+            .config
+            .sweep
+            .keep
+            .iter()
+            .map(|token| app.tokens.resolve_mint(token))
+            .collect::<Result<_>>()?;
+        let sweeper = Arc::new(dex::AccountSweeper::new(
+            app.rpc_client.clone(),
+            app.sender.clone(),
+            app.wallet.clone(),
+            app.dex_clients.clone(),
+            app.config.sweep.clone(),
+            keep,
+        ));
+        supervisor.spawn("sweep", policy.clone(), move || {
+            let sweeper = sweeper.clone();
+            async move { sweeper.run().await }
+        });
+    }
+
+    supervisor.spawn_health_reporter(Duration::from_secs(monitoring.health_log_interval_secs));
     Ok(())
 }
 
 async fn run_trading_loop(
-    config: config::Config,
-    dex_clients: dex::DexClients,
-    price_feeds: oracles::PriceFeeds,
-    cex_clients: cex::CexClients,
+    app: &app::App,
+    trading_params: &config::TradingParams,
+    shutdown: CancellationToken,
 ) -> Result<()> {
     info!("Starting trading loop...");
-    
-    // Main trading loop implementation will go here
-    // This is where we'll implement the core trading logic
-    
-    Ok(())
-} 
\ No newline at end of file
+
+    let registry = strategy::StrategyRegistry::with_builtin();
+    let engine = strategy::StrategyEngine::new(
+        &registry,
+        &app.config.strategies,
+        &app.dex_clients,
+        &app.price_feeds,
+        &app.cex_clients,
+        &app.orders,
+        trading_params,
+        &app.pool_states,
+    )?;
+
+    let fills = route_order_fills(engine.fill_router(), app, shutdown.clone());
+    let (result, ()) = tokio::join!(engine.run(shutdown), fills);
+    result
+}
+
+// Delivers fills of the CEX orders strategies placed back to the strategies
+async fn route_order_fills(
+    router: strategy::FillRouter,
+    app: &app::App,
+    shutdown: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(ORDER_FILL_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+        for fill in app.orders.poll_fills(&app.cex_clients).await {
+            let fill = strategy::Fill {
+                strategy: fill.strategy,
+                venue: fill.venue,
+                order_id: fill.order_id,
+                side: fill.side,
+                price: fill.price,
+                quantity: fill.quantity,
+            };
+            if let Err(e) = router.route(fill).await {
+                warn!("Order fill not delivered: {:#}", e);
+            }
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use solana_client::rpc_client::RpcClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
+use tracing::warn;
+
+use crate::registry::VenueRegistry;
+use crate::units::{Price, Rounding};
 
 mod pyth;
 mod switchboard;
@@ -12,52 +16,64 @@ mod switchboard;
 pub use pyth::PythClient;
 pub use switchboard::SwitchboardClient;
 
-#[derive(Clone)]
-pub struct PriceFeeds {
-    pub pyth: Arc<PythClient>,
-    pub switchboard: Arc<SwitchboardClient>,
-}
+pub const PYTH: &str = "pyth";
+pub const SWITCHBOARD: &str = "switchboard";
+
+pub type PriceFeeds = VenueRegistry<dyn PriceFeed>;
 
 #[async_trait]
-pub trait PriceFeed {
-    async fn get_price(&self, symbol: &str) -> Result<f64>;
-    async fn get_price_with_confidence(&self, symbol: &str) -> Result<(f64, f64)>;
+pub trait PriceFeed: Send + Sync {
+    async fn get_price(&self, symbol: &str) -> Result<Price>;
+    // The price and the width of its confidence interval
+    async fn get_price_with_confidence(&self, symbol: &str) -> Result<(Price, Price)>;
     async fn subscribe_price_updates(
         &self,
         symbol: &str,
-        callback: Box<dyn Fn(f64) + Send + Sync>,
+        callback: Box<dyn Fn(Price) + Send + Sync>,
     ) -> Result<()>;
 }
 
 pub async fn init_price_feeds(config: &crate::config::Config) -> Result<PriceFeeds> {
-    let rpc_client = RpcClient::new(config.solana_rpc_url.clone());
+    let rpc_client = Arc::new(RpcClient::new(config.solana.rpc_url.clone()));
+    let mut feeds = PriceFeeds::new();
+
+    if let Some(pyth) = config.oracles.pyth.as_ref().filter(|c| c.enabled) {
+        feeds.register(
+            PYTH,
+            Arc::new(PythClient::new(
+                rpc_client.clone(),
+                pyth.program_id.clone(),
+            )?),
+        )?;
+    }
 
-    let pyth_client = Arc::new(PythClient::new(
-        rpc_client.clone(),
-        config.pyth_network_program_id.clone(),
-    )?);
+    if let Some(switchboard) = config.oracles.switchboard.as_ref().filter(|c| c.enabled) {
+        feeds.register(
+            SWITCHBOARD,
+            Arc::new(SwitchboardClient::new(
+                rpc_client.clone(),
+                switchboard.program_id.clone(),
+            )?),
+        )?;
+    }
 
-    let switchboard_client = Arc::new(SwitchboardClient::new(
-        rpc_client,
-        config.switchboard_program_id.clone(),
-    )?);
+    if feeds.is_empty() {
+        warn!("No price feeds enabled");
+    }
 
-    Ok(PriceFeeds {
-        pyth: pyth_client,
-        switchboard: switchboard_client,
-    })
+    Ok(feeds)
 }
 
 // Helper functions for price feed management
 pub async fn get_best_price(
-    pyth_price: Result<f64>,
-    switchboard_price: Result<f64>,
-) -> Result<f64> {
+    pyth_price: Result<Price>,
+    switchboard_price: Result<Price>,
+) -> Result<Price> {
     match (pyth_price, switchboard_price) {
         (Ok(pyth), Ok(switchboard)) => {
             // Compare prices and return the one with better confidence
             // This is a simple implementation - you might want to add more sophisticated logic
-            Ok((pyth + switchboard) / 2.0)
+            Ok(pyth.midpoint(switchboard, Rounding::Nearest))
         }
         (Ok(price), Err(_)) => Ok(price),
         (Err(_), Ok(price)) => Ok(price),
@@ -70,10 +86,10 @@ pub async fn get_best_price(
 }
 
 pub async fn monitor_price_changes(
-    price_feed: Arc<dyn PriceFeed + Send + Sync>,
+    price_feed: Arc<dyn PriceFeed>,
     symbol: &str,
     threshold: f64,
-    callback: Box<dyn Fn(f64) + Send + Sync>,
+    callback: Arc<dyn Fn(Price) + Send + Sync>,
 ) -> Result<()> {
     let mut last_price = price_feed.get_price(symbol).await?;
     let mut interval = time::interval(Duration::from_secs(1));
@@ -81,11 +97,12 @@ pub async fn monitor_price_changes(
     loop {
         interval.tick().await;
         let current_price = price_feed.get_price(symbol).await?;
-        let price_change = (current_price - last_price).abs() / last_price;
+        let price_change =
+            (current_price.to_f64() - last_price.to_f64()).abs() / last_price.to_f64();
 
         if price_change >= threshold {
             callback(current_price);
             last_price = current_price;
         }
     }
-} 
\ No newline at end of file
+}
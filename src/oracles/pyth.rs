@@ -1,22 +1,23 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use solana_client::rpc_client::RpcClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use std::collections::HashMap;
 
 use super::PriceFeed;
+use crate::units::Price;
 
 pub struct PythClient {
-    rpc_client: RpcClient,
+    rpc_client: Arc<RpcClient>,
     program_id: Pubkey,
     price_accounts: Arc<RwLock<HashMap<String, Pubkey>>>,
 }
 
 impl PythClient {
-    pub fn new(rpc_client: RpcClient, program_id: String) -> Result<Self> {
+    pub fn new(rpc_client: Arc<RpcClient>, program_id: String) -> Result<Self> {
         Ok(Self {
             rpc_client,
             program_id: Pubkey::from_str(&program_id)?,
@@ -32,7 +33,7 @@ impl PythClient {
         todo!("Implement price account lookup")
     }
 
-    async fn parse_price_data(&self, price_account: &Pubkey) -> Result<(f64, f64)> {
+    async fn parse_price_data(&self, price_account: &Pubkey) -> Result<(Price, Price)> {
         // In a real implementation, you would:
         // 1. Fetch the account data from the price account
         // 2. Parse the Pyth price data structure
@@ -43,13 +44,13 @@ impl PythClient {
 
 #[async_trait]
 impl PriceFeed for PythClient {
-    async fn get_price(&self, symbol: &str) -> Result<f64> {
+    async fn get_price(&self, symbol: &str) -> Result<Price> {
         let price_account = self.get_price_account(symbol).await?;
         let (price, _) = self.parse_price_data(&price_account).await?;
         Ok(price)
     }
 
-    async fn get_price_with_confidence(&self, symbol: &str) -> Result<(f64, f64)> {
+    async fn get_price_with_confidence(&self, symbol: &str) -> Result<(Price, Price)> {
         let price_account = self.get_price_account(symbol).await?;
         self.parse_price_data(&price_account).await
     }
@@ -57,10 +58,10 @@ impl PriceFeed for PythClient {
     async fn subscribe_price_updates(
         &self,
         symbol: &str,
-        callback: Box<dyn Fn(f64) + Send + Sync>,
+        callback: Box<dyn Fn(Price) + Send + Sync>,
     ) -> Result<()> {
         let price_account = self.get_price_account(symbol).await?;
-        
+
         // In a real implementation, you would:
         // 1. Subscribe to account changes for the price account
         // 2. Parse price updates
@@ -77,11 +78,16 @@ pub async fn get_pyth_symbols() -> Result<Vec<String>> {
     todo!("Implement symbol list retrieval")
 }
 
-pub async fn validate_pyth_price(price: f64, confidence: f64) -> bool {
+pub async fn validate_pyth_price(price: Price, confidence: Price) -> bool {
     // Implement price validation logic
     // This could include checks for:
     // - Price is within reasonable bounds
     // - Confidence interval is not too wide
     // - Price has not changed too dramatically
-    price > 0.0 && confidence > 0.0 && confidence < price * 0.1
-} 
\ No newline at end of file
+    !price.is_zero()
+        && !confidence.is_zero()
+        && confidence
+            .raw()
+            .checked_mul(10)
+            .is_some_and(|confidence| confidence < price.raw())
+}
@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// Venue clients keyed by name ("raydium", "binance", "pyth", ...). Only venues
+// enabled in the configuration are registered, so callers iterate over what is
+// actually available instead of assuming a fixed set of fields.
+pub struct VenueRegistry<T: ?Sized> {
+    venues: BTreeMap<String, Arc<T>>,
+}
+
+impl<T: ?Sized> VenueRegistry<T> {
+    pub fn new() -> Self {
+        Self {
+            venues: BTreeMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, name: impl Into<String>, venue: Arc<T>) -> Result<()> {
+        let name = name.into();
+        if self.venues.contains_key(&name) {
+            return Err(anyhow!("venue {} is already registered", name));
+        }
+        self.venues.insert(name, venue);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<T>> {
+        self.venues.get(name).cloned()
+    }
+
+    pub fn require(&self, name: &str) -> Result<Arc<T>> {
+        self.get(name)
+            .ok_or_else(|| anyhow!("venue {} is not enabled in the configuration", name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.venues.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.venues.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<T>)> {
+        self.venues
+            .iter()
+            .map(|(name, venue)| (name.as_str(), venue))
+    }
+
+    pub fn len(&self) -> usize {
+        self.venues.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.venues.is_empty()
+    }
+}
+
+impl<T: ?Sized> Default for VenueRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Clone for VenueRegistry<T> {
+    fn clone(&self) -> Self {
+        Self {
+            venues: self.venues.clone(),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Keypair;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::Secret;
+
+const KEYSTORE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+// Argon2id parameters: 64 MiB, 3 passes, single lane
+const KDF_MEMORY_KIB: u32 = 64 * 1024;
+const KDF_ITERATIONS: u32 = 3;
+const KDF_PARALLELISM: u32 = 1;
+
+// The most a keystore file may ask for, so a tampered file cannot make
+// opening it exhaust memory or run for hours before the passphrase is checked
+const MAX_KDF_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_KDF_ITERATIONS: u32 = 32;
+const MAX_KDF_PARALLELISM: u32 = 16;
+
+// On-disk layout. Only the KDF parameters, salt and nonce are stored in the
+// clear; the secrets and keypair are sealed with XChaCha20-Poly1305 under a
+// key derived from the passphrase.
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KdfParams {
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl KdfParams {
+    fn check_bounds(&self) -> Result<()> {
+        for (name, value, max) in [
+            ("memory_kib", self.memory_kib, MAX_KDF_MEMORY_KIB),
+            ("iterations", self.iterations, MAX_KDF_ITERATIONS),
+            ("parallelism", self.parallelism, MAX_KDF_PARALLELISM),
+        ] {
+            if value > max {
+                return Err(anyhow!(
+                    "keystore key derivation {} of {} exceeds the maximum of {}",
+                    name,
+                    value,
+                    max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct KeystoreContents {
+    secrets: BTreeMap<String, String>,
+    keypair: Option<Vec<u8>>,
+}
+
+pub struct Keystore {
+    path: PathBuf,
+    passphrase: Secret<String>,
+    contents: KeystoreContents,
+}
+
+impl Keystore {
+    pub fn create(path: &Path, passphrase: &Secret<String>) -> Result<Self> {
+        if path.exists() {
+            return Err(anyhow!("keystore {} already exists", path.display()));
+        }
+        let keystore = Self {
+            path: path.to_path_buf(),
+            passphrase: passphrase.clone(),
+            contents: KeystoreContents::default(),
+        };
+        keystore.save()?;
+        Ok(keystore)
+    }
+
+    pub fn open(path: &Path, passphrase: &Secret<String>) -> Result<Self> {
+        let raw = fs::read_to_string(path)?;
+        let file: KeystoreFile = serde_json::from_str(&raw).context("malformed keystore file")?;
+        if file.version != KEYSTORE_VERSION {
+            return Err(anyhow!("unsupported keystore version {}", file.version));
+        }
+
+        file.kdf.check_bounds()?;
+        let salt = BASE64.decode(&file.kdf.salt)?;
+        let key = derive_key(passphrase, &salt, &file.kdf)?;
+        let nonce = BASE64.decode(&file.nonce)?;
+        if nonce.len() != 24 {
+            return Err(anyhow!("malformed keystore nonce"));
+        }
+        let ciphertext = BASE64.decode(&file.ciphertext)?;
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+        let plaintext = cipher
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| anyhow!("wrong passphrase or corrupted keystore"))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            passphrase: passphrase.clone(),
+            contents: serde_json::from_slice(&plaintext)?,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kdf = KdfParams {
+            salt: BASE64.encode(salt),
+            memory_kib: KDF_MEMORY_KIB,
+            iterations: KDF_ITERATIONS,
+            parallelism: KDF_PARALLELISM,
+        };
+        let key = derive_key(&self.passphrase, &salt, &kdf)?;
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(&self.contents)?;
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|_| anyhow!("failed to encrypt keystore"))?;
+
+        let file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            kdf,
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+
+        // Write to a temporary file first so a crash never leaves a truncated keystore
+        let tmp = self.path.with_extension("tmp");
+        let mut out = create_private(&tmp)?;
+        out.write_all(&serde_json::to_vec_pretty(&file)?)?;
+        out.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn secret(&self, name: &str) -> Option<Secret<String>> {
+        self.contents.secrets.get(name).cloned().map(Secret::new)
+    }
+
+    pub fn secret_names(&self) -> impl Iterator<Item = &str> {
+        self.contents.secrets.keys().map(String::as_str)
+    }
+
+    pub fn set_secret(&mut self, name: &str, value: Secret<String>) {
+        self.contents
+            .secrets
+            .insert(name.to_string(), value.expose().clone());
+    }
+
+    pub fn remove_secret(&mut self, name: &str) -> bool {
+        self.contents.secrets.remove(name).is_some()
+    }
+
+    pub fn keypair(&self) -> Result<Option<Keypair>> {
+        self.contents
+            .keypair
+            .as_ref()
+            .map(|bytes| Keypair::from_bytes(bytes).map_err(|e| anyhow!("invalid keypair: {}", e)))
+            .transpose()
+    }
+
+    pub fn set_keypair(&mut self, keypair: &Keypair) {
+        self.contents.keypair = Some(keypair.to_bytes().to_vec());
+    }
+}
+
+fn derive_key(passphrase: &Secret<String>, salt: &[u8], kdf: &KdfParams) -> Result<[u8; KEY_LEN]> {
+    let params = Params::new(
+        kdf.memory_kib,
+        kdf.iterations,
+        kdf.parallelism,
+        Some(KEY_LEN),
+    )
+    .map_err(|e| anyhow!("invalid key derivation parameters: {}", e))?;
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.expose().as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+// Creates the file readable by its owner only from the start, rather than
+// restricting it once the contents are already on disk. A file left over from
+// an interrupted save is replaced, since it may carry other permissions.
+fn create_private(path: &Path) -> Result<File> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .with_context(|| format!("failed to create {}", path.display()))
+}
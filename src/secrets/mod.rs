@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use std::fmt;
+use tracing::{info, warn};
+
+use crate::config::{CexVenueConfig, Config, SolanaConfig};
+
+mod keystore;
+
+pub use keystore::Keystore;
+
+pub const PASSPHRASE_ENV: &str = "BOT_KEYSTORE_PASSPHRASE";
+
+const REDACTED: &str = "[REDACTED]";
+
+// Wrapper for sensitive values. Debug, Display and Serialize never print the
+// inner value; call `expose` at the point where the secret is actually used.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+// Resolves secrets at startup: values from the unlocked keystore take
+// precedence over anything supplied through the config file or environment.
+pub struct SecretStore {
+    keystore: Option<Keystore>,
+}
+
+impl SecretStore {
+    pub fn unlock(config: &Config) -> Result<Self> {
+        let keystore = match &config.secrets.keystore_path {
+            Some(path) => {
+                let passphrase = read_passphrase()?;
+                let keystore = Keystore::open(path, &passphrase)
+                    .with_context(|| format!("failed to unlock keystore {}", path.display()))?;
+                info!("Keystore {} unlocked", path.display());
+                Some(keystore)
+            }
+            None => None,
+        };
+
+        Ok(Self { keystore })
+    }
+
+    pub fn without_keystore() -> Self {
+        Self { keystore: None }
+    }
+
+    pub fn cex_secret(&self, venue: &str, config: &CexVenueConfig) -> Result<Secret<String>> {
+        let name = cex_secret_name(venue);
+        if let Some(secret) = self.keystore.as_ref().and_then(|k| k.secret(&name)) {
+            return Ok(secret);
+        }
+        config
+            .api_secret
+            .clone()
+            .ok_or_else(|| anyhow!("no API secret for {}: add {} to the keystore", venue, name))
+    }
+
+    pub fn keypair(&self, config: &SolanaConfig) -> Result<Keypair> {
+        if let Some(keystore) = &self.keystore {
+            if let Some(keypair) = keystore.keypair()? {
+                return Ok(keypair);
+            }
+        }
+
+        warn!(
+            "Loading unencrypted keypair from {}; import it into the keystore instead",
+            config.keypair_path.display()
+        );
+        read_keypair_file(&config.keypair_path).map_err(|e| {
+            anyhow!(
+                "failed to read keypair {}: {}",
+                config.keypair_path.display(),
+                e
+            )
+        })
+    }
+}
+
+pub fn cex_secret_name(venue: &str) -> String {
+    format!("cex.{}.api_secret", venue)
+}
+
+pub fn read_passphrase() -> Result<Secret<String>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Secret::new(passphrase));
+    }
+    let passphrase = rpassword::prompt_password("Keystore passphrase: ")?;
+    Ok(Secret::new(passphrase))
+}
@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use solana_sdk::signature::Signer;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::app::App;
+use crate::cex::OpenOrder;
+use crate::config::TradingConfig;
+use crate::transactions::{PendingOutcome, PendingTransaction};
+
+// Cancels `shutdown` on Ctrl-C or SIGTERM. A second signal exits immediately.
+pub fn spawn_signal_handler(shutdown: CancellationToken) {
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Shutdown requested, winding down (signal again to force exit)");
+        shutdown.cancel();
+
+        wait_for_signal().await;
+        warn!("Second shutdown signal received, exiting without cleanup");
+        std::process::exit(130);
+    });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        Err(e) => {
+            error!("Failed to install SIGTERM handler: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[derive(Debug, Serialize)]
+pub struct StateSnapshot {
+    pub written_at: u64,
+    pub wallet: String,
+    pub trading: TradingConfig,
+    pub strategies: Vec<String>,
+    // Orders that could not be cancelled and may still be live
+    pub open_orders: Vec<OpenOrder>,
+    pub transactions: Vec<TransactionRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionRecord {
+    #[serde(flatten)]
+    pub transaction: PendingTransaction,
+    pub outcome: PendingOutcome,
+}
+
+// Runs after every task has observed the cancellation: cancel open CEX
+// orders, wait for in-flight Solana transactions, then persist what is left.
+pub async fn wind_down(app: &App, trading: &TradingConfig) -> Result<()> {
+    let open = app.orders.open_orders().len();
+    if open > 0 {
+        info!("Cancelling {} open CEX order(s)", open);
+        let cancelled = app.orders.cancel_all(&app.cex_clients).await;
+        info!("Cancelled {}/{} open order(s)", cancelled, open);
+    }
+
+    let outcomes = if app.pending_transactions.is_empty() {
+        Vec::new()
+    } else {
+        info!("Waiting for pending Solana transactions");
+        match app
+            .pending_transactions
+            .wait_all(&app.rpc_client, app.config.shutdown.transaction_timeout())
+            .await
+        {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                error!("Failed to track pending transactions: {:#}", e);
+                app.pending_transactions
+                    .snapshot()
+                    .into_iter()
+                    .map(|tx| (tx, PendingOutcome::TimedOut))
+                    .collect()
+            }
+        }
+    };
+
+    let snapshot = StateSnapshot {
+        written_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        wallet: app.wallet.pubkey().to_string(),
+        trading: trading.clone(),
+        strategies: app
+            .config
+            .strategies
+            .iter()
+            .map(|s| s.name.clone())
+            .collect(),
+        open_orders: app.orders.open_orders(),
+        transactions: outcomes
+            .into_iter()
+            .map(|(transaction, outcome)| TransactionRecord {
+                transaction,
+                outcome,
+            })
+            .collect(),
+    };
+    write_snapshot(&app.config.shutdown.snapshot_path, &snapshot)?;
+    info!(
+        "State snapshot written to {}",
+        app.config.shutdown.snapshot_path.display()
+    );
+
+    Ok(())
+}
+
+fn write_snapshot(path: &std::path::Path, snapshot: &StateSnapshot) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(snapshot)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
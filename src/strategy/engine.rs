@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::{self, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use super::{Fill, PriceUpdate, Strategy, StrategyContext, StrategyRegistry};
+use crate::{
+    cex::{self, CexClients, OrderTracker},
+    config::{StrategyConfig, TradingParams},
+    dex::{DexClients, PoolCache},
+    oracles::{PriceFeed, PriceFeeds},
+};
+
+const FILL_CHANNEL_CAPACITY: usize = 64;
+
+// Delivers order fills to the strategy that placed the order
+#[derive(Clone, Default)]
+pub struct FillRouter {
+    senders: HashMap<String, mpsc::Sender<Fill>>,
+}
+
+impl FillRouter {
+    pub async fn route(&self, fill: Fill) -> Result<()> {
+        let sender = self
+            .senders
+            .get(&fill.strategy)
+            .ok_or_else(|| anyhow!("no running strategy named {}", fill.strategy))?;
+        sender
+            .send(fill)
+            .await
+            .map_err(|_| anyhow!("strategy stopped before the fill was delivered"))
+    }
+}
+
+struct StrategyTask {
+    config: StrategyConfig,
+    strategy: Box<dyn Strategy>,
+    ctx: StrategyContext,
+    feed_name: String,
+    feed: Arc<dyn PriceFeed>,
+    fills: mpsc::Receiver<Fill>,
+}
+
+// Drives each configured strategy from price feed polling, its timer and
+// routed fills. Every strategy runs in its own task so a slow one cannot
+// hold up the others.
+pub struct StrategyEngine {
+    tasks: Vec<StrategyTask>,
+    fill_router: FillRouter,
+}
+
+impl StrategyEngine {
+    pub fn new(
+        registry: &StrategyRegistry,
+        configs: &[StrategyConfig],
+        dex_clients: &DexClients,
+        price_feeds: &PriceFeeds,
+        cex_clients: &CexClients,
+        orders: &OrderTracker,
+        trading_params: &TradingParams,
+        pool_states: &PoolCache,
+    ) -> Result<Self> {
+        let mut tasks = Vec::new();
+        let mut fill_router = FillRouter::default();
+
+        for config in configs {
+            let strategy = registry.build(config)?;
+            let (feed_name, feed) = select_price_feed(price_feeds, config)?;
+            let (fill_tx, fill_rx) = mpsc::channel(FILL_CHANNEL_CAPACITY);
+            fill_router.senders.insert(config.name.clone(), fill_tx);
+
+            tasks.push(StrategyTask {
+                config: config.clone(),
+                strategy,
+                ctx: StrategyContext {
+                    name: config.name.clone(),
+                    pair: config.token_pair()?,
+                    dex_clients: dex_clients.clone(),
+                    price_feeds: price_feeds.clone(),
+                    // Orders are labelled with the strategy so fills find their way back
+                    cex_clients: cex::attribute_orders(cex_clients, orders, &config.name)?,
+                    trading_params: trading_params.clone(),
+                    pool_states: pool_states.clone(),
+                },
+                feed_name,
+                feed,
+                fills: fill_rx,
+            });
+        }
+
+        Ok(Self { tasks, fill_router })
+    }
+
+    pub fn fill_router(&self) -> FillRouter {
+        self.fill_router.clone()
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
+        if self.tasks.is_empty() {
+            warn!("No strategies configured, waiting for shutdown");
+            shutdown.cancelled().await;
+            return Ok(());
+        }
+
+        let mut running = JoinSet::new();
+        for task in self.tasks {
+            running.spawn(run_strategy(task, shutdown.clone()));
+        }
+
+        while let Some(result) = running.join_next().await {
+            if let Err(e) = result {
+                error!("Strategy task panicked: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn select_price_feed(
+    price_feeds: &PriceFeeds,
+    config: &StrategyConfig,
+) -> Result<(String, Arc<dyn PriceFeed>)> {
+    match &config.price_feed {
+        Some(name) => Ok((name.clone(), price_feeds.require(name)?)),
+        None => price_feeds
+            .iter()
+            .next()
+            .map(|(name, feed)| (name.to_string(), feed.clone()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "strategy {} needs a price feed but none are enabled",
+                    config.name
+                )
+            }),
+    }
+}
+
+async fn run_strategy(mut task: StrategyTask, shutdown: CancellationToken) {
+    let name = task.config.name.clone();
+
+    if let Err(e) = task.strategy.on_start(&task.ctx).await {
+        error!("Strategy {} failed to start: {:#}", name, e);
+        return;
+    }
+    info!(
+        "Strategy {} ({}) started on {}",
+        name, task.config.kind, task.ctx.pair.symbol
+    );
+
+    let mut price_interval = time::interval(task.config.price_poll_interval());
+    price_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut timer = time::interval(task.config.timer_interval());
+    timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    // The first tick of an interval completes immediately; timers fire after a full period
+    timer.tick().await;
+
+    loop {
+        let result = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = price_interval.tick() => {
+                match task.feed.get_price(&task.ctx.pair.symbol).await {
+                    Ok(price) => {
+                        let update = PriceUpdate {
+                            source: task.feed_name.clone(),
+                            symbol: task.ctx.pair.symbol.clone(),
+                            price,
+                            received_at: Instant::now(),
+                        };
+                        task.strategy.on_price(&task.ctx, &update).await
+                    }
+                    Err(e) => {
+                        warn!("Strategy {}: price feed {} failed: {:#}", name, task.feed_name, e);
+                        Ok(())
+                    }
+                }
+            }
+            _ = timer.tick() => task.strategy.on_timer(&task.ctx).await,
+            Some(fill) = task.fills.recv() => task.strategy.on_fill(&task.ctx, &fill).await,
+        };
+
+        if let Err(e) = result {
+            error!("Strategy {} hook failed: {:#}", name, e);
+        }
+    }
+
+    if let Err(e) = task.strategy.on_shutdown(&task.ctx).await {
+        error!("Strategy {} failed to shut down cleanly: {:#}", name, e);
+    }
+    info!("Strategy {} stopped", name);
+}
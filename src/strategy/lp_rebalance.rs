@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tracing::{info, warn, Instrument};
+
+use super::{PriceUpdate, Strategy, StrategyContext};
+use crate::config::StrategyConfig;
+use crate::dex;
+use crate::logging;
+use crate::units::{Price, Rounding};
+
+#[derive(Debug, Clone, Deserialize)]
+struct LpRebalanceParams {
+    venue: String,
+    position_id: String,
+    min_price: f64,
+    max_price: f64,
+    // Width of the new range as a fraction of the current price
+    range_width: f64,
+    #[serde(default = "default_cooldown_secs")]
+    cooldown_secs: u64,
+}
+
+fn default_cooldown_secs() -> u64 {
+    300
+}
+
+// Re-centres a concentrated liquidity position around the current price once
+// the price drifts further than `trading.rebalance_threshold` from the middle
+// of the range.
+pub struct LpRebalanceStrategy {
+    params: LpRebalanceParams,
+    // Range of the current position, starting from the configured one
+    min_price: Price,
+    max_price: Price,
+    last_rebalance: Option<Instant>,
+}
+
+impl LpRebalanceStrategy {
+    pub const KIND: &'static str = "lp_rebalance";
+
+    pub fn from_config(config: &StrategyConfig) -> Result<Self> {
+        let params: LpRebalanceParams = serde_json::from_value(config.params.clone())
+            .map_err(|e| anyhow!("invalid params for strategy {}: {}", config.name, e))?;
+        if params.range_width.is_nan() || params.range_width <= 0.0 || params.range_width >= 2.0 {
+            return Err(anyhow!(
+                "strategy {}: range_width must be between 0 and 2",
+                config.name
+            ));
+        }
+        if params.min_price >= params.max_price {
+            return Err(anyhow!(
+                "strategy {}: min_price must be below max_price",
+                config.name
+            ));
+        }
+
+        Ok(Self {
+            min_price: Price::from_f64(params.min_price, Rounding::Down)?,
+            max_price: Price::from_f64(params.max_price, Rounding::Up)?,
+            params,
+            last_rebalance: None,
+        })
+    }
+
+    fn in_cooldown(&self) -> bool {
+        self.last_rebalance
+            .map(|at| at.elapsed() < Duration::from_secs(self.params.cooldown_secs))
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl Strategy for LpRebalanceStrategy {
+    // A rebalance replaces the position, and the configured one is closed by
+    // the time the bot restarts, so the live position of the pair is looked
+    // up and its range taken from chain
+    async fn on_start(&mut self, ctx: &StrategyContext) -> Result<()> {
+        let client = ctx.dex_clients.require(&self.params.venue)?;
+        let positions = client.get_positions().await?;
+        let position = match positions
+            .iter()
+            .find(|position| position.id == self.params.position_id)
+        {
+            Some(position) => position,
+            None => {
+                let pair: Vec<_> = positions
+                    .iter()
+                    .filter(|position| {
+                        let tokens = (position.token_a, position.token_b);
+                        tokens == (ctx.pair.base, ctx.pair.quote)
+                            || tokens == (ctx.pair.quote, ctx.pair.base)
+                    })
+                    .collect();
+                match pair.as_slice() {
+                    [position] => {
+                        info!(
+                            "Strategy {}: position {} is closed, managing its replacement {}",
+                            ctx.name, self.params.position_id, position.id
+                        );
+                        position
+                    }
+                    // An unfinished rebalance of it resumes on the next one
+                    [] => {
+                        warn!(
+                            "Strategy {}: no open {} position for {}, keeping {}",
+                            ctx.name, self.params.venue, ctx.pair.symbol, self.params.position_id
+                        );
+                        return Ok(());
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "strategy {}: position {} is closed and {} holds {} positions for {}; set position_id to the one to manage",
+                            ctx.name,
+                            self.params.position_id,
+                            self.params.venue,
+                            pair.len(),
+                            ctx.pair.symbol
+                        ))
+                    }
+                }
+            }
+        };
+
+        // Ranges are quote per base, positions token_b per token_a
+        let (min_price, max_price) = if position.token_a == ctx.pair.base {
+            (position.min_price, position.max_price)
+        } else {
+            dex::invert_range(position.min_price, position.max_price)?
+        };
+        self.params.position_id = position.id.clone();
+        self.min_price = min_price;
+        self.max_price = max_price;
+        Ok(())
+    }
+
+    async fn on_price(&mut self, ctx: &StrategyContext, update: &PriceUpdate) -> Result<()> {
+        if self.in_cooldown() {
+            return Ok(());
+        }
+
+        let drift = dex::calculate_rebalance_threshold(
+            update.price.to_f64(),
+            self.min_price.to_f64(),
+            self.max_price.to_f64(),
+        )
+        .await;
+        let threshold = ctx.trading_params.borrow().rebalance_threshold;
+        if drift < threshold {
+            return Ok(());
+        }
+
+        let half_width = self.params.range_width / 2.0;
+        // Rounded outwards so the new range is never narrower than asked
+        let new_min = update.price.scaled_by(1.0 - half_width, Rounding::Down)?;
+        let new_max = update.price.scaled_by(1.0 + half_width, Rounding::Up)?;
+        info!(
+            "Strategy {}: price {} drifted {:.2}% from range {}..{}, rebalancing to {}..{}",
+            ctx.name,
+            update.price,
+            drift * 100.0,
+            self.min_price,
+            self.max_price,
+            new_min,
+            new_max
+        );
+
+        let client = ctx.dex_clients.require(&self.params.venue)?;
+        self.last_rebalance = Some(Instant::now());
+        let span = logging::trade_span("rebalance", &self.params.venue, &ctx.pair.symbol);
+        span.record("position", self.params.position_id.as_str());
+        let receipt = client
+            .rebalance_position(&self.params.position_id, new_min, new_max)
+            .instrument(span)
+            .await?;
+        info!(
+            "Strategy {}: position {} replaced by {}, fees collected {} / {}, swap cost {}",
+            ctx.name,
+            receipt.old_position_id,
+            receipt.new_position_id,
+            receipt.fees_a,
+            receipt.fees_b,
+            receipt.swap_cost
+        );
+
+        // Later rebalances act on the position that was just opened
+        self.params.position_id = receipt.new_position_id;
+        self.min_price = new_min;
+        self.max_price = new_max;
+        Ok(())
+    }
+}
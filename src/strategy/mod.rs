@@ -0,0 +1,148 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    cex::CexClients,
+    config::{StrategyConfig, TradingParams},
+    dex::{DexClients, PoolCache},
+    oracles::PriceFeeds,
+    units::{Price, TokenAmount},
+};
+
+mod engine;
+mod lp_rebalance;
+
+pub use engine::{FillRouter, StrategyEngine};
+pub use lp_rebalance::LpRebalanceStrategy;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPair {
+    pub base: Pubkey,
+    pub quote: Pubkey,
+    // Symbol used to look the pair up on price feeds, e.g. "SOL/USD"
+    pub symbol: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PriceUpdate {
+    pub source: String,
+    pub symbol: String,
+    pub price: Price,
+    pub received_at: Instant,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub strategy: String,
+    pub venue: String,
+    pub order_id: String,
+    pub side: String,
+    pub price: Price,
+    pub quantity: TokenAmount,
+}
+
+// Shared handles a strategy can act through. Trading parameters are read
+// through the watch channel so hot-reloaded values apply immediately.
+pub struct StrategyContext {
+    pub name: String,
+    pub pair: TokenPair,
+    pub dex_clients: DexClients,
+    pub price_feeds: PriceFeeds,
+    pub cex_clients: CexClients,
+    pub trading_params: TradingParams,
+    // Curves of the pools the venues have loaded, for quoting candidate
+    // trades without RPC calls
+    pub pool_states: PoolCache,
+}
+
+#[async_trait]
+pub trait Strategy: Send {
+    async fn on_start(&mut self, _ctx: &StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_price(&mut self, ctx: &StrategyContext, update: &PriceUpdate) -> Result<()>;
+
+    async fn on_fill(&mut self, _ctx: &StrategyContext, _fill: &Fill) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_timer(&mut self, _ctx: &StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_shutdown(&mut self, _ctx: &StrategyContext) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub type StrategyFactory = Arc<dyn Fn(&StrategyConfig) -> Result<Box<dyn Strategy>> + Send + Sync>;
+
+// Maps a strategy `kind` from the config to the factory that builds it.
+// Plugins register their own kinds before the engine is started.
+#[derive(Clone, Default)]
+pub struct StrategyRegistry {
+    factories: HashMap<String, StrategyFactory>,
+}
+
+impl StrategyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register(LpRebalanceStrategy::KIND, |config| {
+                Ok(Box::new(LpRebalanceStrategy::from_config(config)?))
+            })
+            .expect("builtin strategy kinds are unique");
+        registry
+    }
+
+    pub fn register<F>(&mut self, kind: &str, factory: F) -> Result<()>
+    where
+        F: Fn(&StrategyConfig) -> Result<Box<dyn Strategy>> + Send + Sync + 'static,
+    {
+        if self.factories.contains_key(kind) {
+            return Err(anyhow!("strategy kind {} is already registered", kind));
+        }
+        self.factories.insert(kind.to_string(), Arc::new(factory));
+        Ok(())
+    }
+
+    pub fn build(&self, config: &StrategyConfig) -> Result<Box<dyn Strategy>> {
+        let factory = self
+            .factories
+            .get(&config.kind)
+            .ok_or_else(|| anyhow!("unknown strategy kind {}", config.kind))?;
+        factory(config)
+    }
+
+    pub fn kinds(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+}
+
+impl StrategyConfig {
+    pub fn token_pair(&self) -> Result<TokenPair> {
+        Ok(TokenPair {
+            base: Pubkey::from_str(&self.base_mint)?,
+            quote: Pubkey::from_str(&self.quote_mint)?,
+            symbol: self.price_symbol.clone(),
+        })
+    }
+
+    pub fn timer_interval(&self) -> Duration {
+        Duration::from_secs(self.timer_interval_secs)
+    }
+
+    pub fn price_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.price_poll_interval_ms)
+    }
+}
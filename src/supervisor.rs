@@ -0,0 +1,244 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // None restarts forever
+    pub max_restarts: Option<u32>,
+    // A task that stays up this long has its restart count reset
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_restarts: Some(10),
+            reset_after: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RestartPolicy {
+    pub fn backoff(&self, restarts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(restarts.saturating_sub(1));
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    Backoff,
+    Stopped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub name: String,
+    pub state: TaskState,
+    pub restarts: u32,
+    pub total_restarts: u32,
+    pub last_error: Option<String>,
+    pub last_started_at: Option<u64>,
+}
+
+// Runs named background tasks, restarting them with exponential backoff when
+// they fail or panic, and keeps a health record for each.
+#[derive(Clone)]
+pub struct Supervisor {
+    health: Arc<Mutex<BTreeMap<String, TaskHealth>>>,
+    tasks: Arc<Mutex<JoinSet<()>>>,
+    shutdown: CancellationToken,
+}
+
+impl Supervisor {
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self {
+            health: Arc::new(Mutex::new(BTreeMap::new())),
+            tasks: Arc::new(Mutex::new(JoinSet::new())),
+            shutdown,
+        }
+    }
+
+    pub fn spawn<F, Fut>(&self, name: &str, policy: RestartPolicy, factory: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.health.lock().unwrap().insert(
+            name.to_string(),
+            TaskHealth {
+                name: name.to_string(),
+                state: TaskState::Running,
+                restarts: 0,
+                total_restarts: 0,
+                last_error: None,
+                last_started_at: None,
+            },
+        );
+
+        let supervisor = self.clone();
+        let name = name.to_string();
+        self.tasks
+            .lock()
+            .unwrap()
+            .spawn(async move { supervisor.supervise(name, policy, factory).await });
+    }
+
+    async fn supervise<F, Fut>(self, name: String, policy: RestartPolicy, factory: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        loop {
+            self.update(&name, |h| {
+                h.state = TaskState::Running;
+                h.last_started_at = Some(unix_now());
+            });
+            let started = Instant::now();
+
+            // Run in a separate task so a panic is reported as a failure instead
+            // of taking the supervisor down with it
+            let mut handle = tokio::spawn(factory());
+            let outcome = tokio::select! {
+                _ = self.shutdown.cancelled() => {
+                    handle.abort();
+                    let _ = handle.await;
+                    self.update(&name, |h| h.state = TaskState::Stopped);
+                    return;
+                }
+                joined = &mut handle => joined,
+            };
+
+            let error = match outcome {
+                Ok(Ok(())) => {
+                    info!("Task {} finished", name);
+                    self.update(&name, |h| h.state = TaskState::Stopped);
+                    return;
+                }
+                Ok(Err(e)) => format!("{:#}", e),
+                Err(e) if e.is_panic() => format!("panicked: {}", panic_message(e.into_panic())),
+                Err(e) => e.to_string(),
+            };
+
+            if started.elapsed() >= policy.reset_after {
+                self.update(&name, |h| h.restarts = 0);
+            }
+            let restarts = self.update(&name, |h| {
+                h.restarts += 1;
+                h.total_restarts += 1;
+                h.last_error = Some(error.clone());
+                h.restarts
+            });
+
+            if policy.max_restarts.is_some_and(|max| restarts > max) {
+                error!(
+                    "Task {} failed {} times in a row, giving up: {}",
+                    name, restarts, error
+                );
+                self.update(&name, |h| h.state = TaskState::Failed);
+                return;
+            }
+
+            let backoff = policy.backoff(restarts);
+            warn!(
+                "Task {} failed: {}; restart {} in {:?}",
+                name, error, restarts, backoff
+            );
+            self.update(&name, |h| h.state = TaskState::Backoff);
+
+            tokio::select! {
+                _ = self.shutdown.cancelled() => {
+                    self.update(&name, |h| h.state = TaskState::Stopped);
+                    return;
+                }
+                _ = tokio::time::sleep(backoff) => {}
+            }
+        }
+    }
+
+    fn update<R>(&self, name: &str, f: impl FnOnce(&mut TaskHealth) -> R) -> R {
+        let mut health = self.health.lock().unwrap();
+        let entry = health
+            .get_mut(name)
+            .expect("health entry is created before the task is spawned");
+        f(entry)
+    }
+
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.health.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health
+            .lock()
+            .unwrap()
+            .values()
+            .all(|h| h.state != TaskState::Failed)
+    }
+
+    // Logs a one-line health summary on every interval until shutdown
+    pub fn spawn_health_reporter(&self, interval: Duration) {
+        let supervisor = self.clone();
+        self.tasks.lock().unwrap().spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = supervisor.shutdown.cancelled() => return,
+                    _ = ticker.tick() => {}
+                }
+                let summary = supervisor
+                    .health()
+                    .iter()
+                    .map(|h| format!("{}={:?}/{}", h.name, h.state, h.total_restarts))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if supervisor.is_healthy() {
+                    info!("Task health: {}", summary);
+                } else {
+                    error!("Task health: {}", summary);
+                }
+            }
+        });
+    }
+
+    // Waits for every supervised task to stop after shutdown is requested
+    pub async fn join(&self) {
+        let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        while tasks.join_next().await.is_some() {}
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
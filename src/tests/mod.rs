@@ -1,28 +1,76 @@
 use anyhow::Result;
+use serde_json::json;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
+use wiremock::matchers::{body_partial_json, body_string_contains, method, path, query_param};
+use wiremock::{Mock, MockServer, Respond, ResponseTemplate};
 
 use crate::{
-    config::Config,
-    dex::{DexClient, DexClients},
-    oracles::{PriceFeed, PriceFeeds},
-    cex::{CexClient, CexClients},
-    simulation::{SimulationConfig, VolumeSimulator},
+    cex::{self, CexClient, CexClients, OrderBook, OrderTracker, Trade},
+    config::{
+        reload, Config, ConfigSources, LiquidityShape, LoggingConfig, PriorityFeeConfig,
+        PriorityFeePolicy, TransactionConfig, Urgency,
+    },
+    dex::{
+        self, clmm, meteora, orca, quote, tick_math,
+        token_2022::{MintInfo, SwapTransferFees, TransferFee},
+        AmmInfo, DexClient,
+    },
+    harvest,
+    logging::{self, SizeRotatingWriter},
     metrics::MetricsManager,
+    oracles::{self, PriceFeed},
+    registry::VenueRegistry,
+    secrets::{Keystore, Secret, SecretStore},
+    simulation::{SimulationConfig, VolumeSimulator},
+    strategy::{
+        Fill, LpRebalanceStrategy, PriceUpdate, Strategy, StrategyContext, StrategyEngine,
+        StrategyRegistry,
+    },
+    supervisor::{RestartPolicy, Supervisor, TaskState},
+    tokens::{TokenProgram, TokenRegistry},
+    transactions::{
+        decode_error, parse_logs, writable_accounts, PendingTransactions, PriorityFeeEstimator,
+        ProgramLog, SwapPrograms, TransactionFailure, TransactionOutcome, TransactionSender,
+    },
+    units::{Price, Rounding, TokenAmount},
 };
 
 #[tokio::test]
 async fn test_dex_integration() -> Result<()> {
     let config = Config::load()?;
-    let dex_clients = DexClients::init_clients(&config).await?;
+    let rpc_client = Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+        config.solana.rpc_url.clone(),
+    ));
+    let sender = Arc::new(TransactionSender::new(
+        rpc_client.clone(),
+        &config.transactions,
+        SwapPrograms::from_config(&config.dex)?,
+        PendingTransactions::new(),
+    ));
+    let dex_clients = dex::init_clients(
+        &config,
+        rpc_client,
+        sender,
+        Arc::new(Keypair::new()),
+        &crate::config::fixed_trading_params(&config),
+        &dex::PoolCache::new(),
+    )
+    .await?;
 
     // Test price fetching
-    let token_a = Pubkey::from_str("So11111111111111111111111111111111111111112")?; // SOL
-    let token_b = Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")?; // USDC
+    let tokens = TokenRegistry::bundled()?;
+    let token_a = tokens.mint("SOL")?;
+    let token_b = tokens.mint("USDC")?;
 
-    let price = dex_clients.jupiter.get_price(&token_a, &token_b).await?;
-    assert!(price > 0.0);
+    let price = dex_clients
+        .require(dex::JUPITER)?
+        .get_price(&token_a, &token_b)
+        .await?;
+    assert!(!price.is_zero());
 
     Ok(())
 }
@@ -30,15 +78,21 @@ async fn test_dex_integration() -> Result<()> {
 #[tokio::test]
 async fn test_oracle_integration() -> Result<()> {
     let config = Config::load()?;
-    let price_feeds = PriceFeeds::init_price_feeds(&config).await?;
+    let price_feeds = oracles::init_price_feeds(&config).await?;
 
     // Test price fetching
-    let price = price_feeds.pyth.get_price("SOL/USD").await?;
-    assert!(price > 0.0);
+    let price = price_feeds
+        .require(oracles::PYTH)?
+        .get_price("SOL/USD")
+        .await?;
+    assert!(!price.is_zero());
 
-    let (price, confidence) = price_feeds.pyth.get_price_with_confidence("SOL/USD").await?;
-    assert!(price > 0.0);
-    assert!(confidence > 0.0);
+    let (price, confidence) = price_feeds
+        .require(oracles::PYTH)?
+        .get_price_with_confidence("SOL/USD")
+        .await?;
+    assert!(!price.is_zero());
+    assert!(!confidence.is_zero());
 
     Ok(())
 }
@@ -46,16 +100,22 @@ async fn test_oracle_integration() -> Result<()> {
 #[tokio::test]
 async fn test_cex_integration() -> Result<()> {
     let config = Config::load()?;
-    let cex_clients = CexClients::init_clients(&config).await?;
+    let cex_clients = cex::init_clients(&config, &SecretStore::unlock(&config)?).await?;
 
     // Test order book fetching
-    let order_book = cex_clients.binance.get_order_book("BTCUSDT").await?;
+    let order_book = cex_clients
+        .require(cex::BINANCE)?
+        .get_order_book("BTCUSDT")
+        .await?;
     assert!(!order_book.bids.is_empty());
     assert!(!order_book.asks.is_empty());
 
     // Test ticker fetching
-    let price = cex_clients.binance.get_ticker("BTCUSDT").await?;
-    assert!(price > 0.0);
+    let price = cex_clients
+        .require(cex::BINANCE)?
+        .get_ticker("BTCUSDT")
+        .await?;
+    assert!(!price.is_zero());
 
     Ok(())
 }
@@ -116,17 +176,17 @@ async fn test_metrics() -> Result<()> {
 #[tokio::test]
 async fn test_price_monitoring() -> Result<()> {
     let config = Config::load()?;
-    let price_feeds = PriceFeeds::init_price_feeds(&config).await?;
+    let price_feeds = oracles::init_price_feeds(&config).await?;
 
     let mut price_updates = Vec::new();
-    let callback = Box::new(|price: f64| {
+    let callback = Box::new(|price: Price| {
         price_updates.push(price);
     });
 
     // Start price monitoring
     let monitor_handle = tokio::spawn(async move {
         price_feeds
-            .pyth
+            .require(oracles::PYTH)?
             .subscribe_price_updates("SOL/USD", callback)
             .await
     });
@@ -143,12 +203,2508 @@ async fn test_price_monitoring() -> Result<()> {
 #[tokio::test]
 async fn test_arbitrage_detection() -> Result<()> {
     let config = Config::load()?;
-    let cex_clients = CexClients::init_clients(&config).await?;
+    let cex_clients = cex::init_clients(&config, &SecretStore::unlock(&config)?).await?;
 
     // Test arbitrage detection
     let (price, exchange) = cex::get_best_price_across_exchanges(&cex_clients, "BTCUSDT").await?;
-    assert!(price > 0.0);
+    assert!(!price.is_zero());
     assert!(!exchange.is_empty());
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+const TEST_CONFIG: &str = r#"
+[solana]
+rpc_url = "http://127.0.0.1:8899"
+ws_url = "ws://127.0.0.1:8900"
+keypair_path = "test-keypair.json"
+
+[dex.raydium]
+program_id = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"
+
+[database]
+url = "postgres://localhost/test"
+
+[trading]
+min_trade_size = 0.1
+max_trade_size = 10.0
+price_impact_threshold = 0.01
+slippage_tolerance = 0.005
+rebalance_threshold = 0.05
+"#;
+
+fn write_test_config(name: &str, contents: &str) -> Result<std::path::PathBuf> {
+    let path = std::env::temp_dir().join(format!("solana_dex_bot_{}.toml", name));
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+#[test]
+fn test_config_layering() -> Result<()> {
+    let path = write_test_config("layering", TEST_CONFIG)?;
+    let config = Config::load_from(&ConfigSources {
+        file: Some(path),
+        overrides: vec![("trading.slippage_tolerance".to_string(), "0.01".to_string())],
+    })?;
+
+    assert_eq!(config.trading.slippage_tolerance, 0.01);
+    assert!(config.dex.raydium.is_some());
+    assert!(config.dex.jupiter.is_none());
+    assert!(config.cex.binance.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_config_validation_reports_all_errors() -> Result<()> {
+    let path = write_test_config("invalid", TEST_CONFIG)?;
+    let config = Config::build(&ConfigSources {
+        file: Some(path),
+        overrides: vec![
+            ("trading.min_trade_size".to_string(), "20.0".to_string()),
+            ("trading.slippage_tolerance".to_string(), "1.5".to_string()),
+            ("solana.rpc_url".to_string(), "localhost".to_string()),
+        ],
+    })?;
+
+    let errors = config.validate().unwrap_err().0;
+    let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+    assert!(fields.contains(&"trading.max_trade_size"));
+    assert!(fields.contains(&"trading.slippage_tolerance"));
+    assert!(fields.contains(&"solana.rpc_url"));
+
+    let mut trading = test_trading_params().borrow().clone();
+    for max_trade_size in [f64::INFINITY, f64::NAN] {
+        trading.max_trade_size = max_trade_size;
+        let mut errors = Vec::new();
+        trading.validate(&mut errors);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "trading.max_trade_size");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_venue_registry() -> Result<()> {
+    let mut registry: VenueRegistry<str> = VenueRegistry::new();
+    registry.register("raydium", Arc::from("raydium client"))?;
+    registry.register("pyth", Arc::from("pyth client"))?;
+
+    assert!(registry
+        .register("raydium", Arc::from("duplicate"))
+        .is_err());
+    assert_eq!(
+        registry.names().collect::<Vec<_>>(),
+        vec!["pyth", "raydium"]
+    );
+    assert!(registry.require("binance").is_err());
+    assert_eq!(&*registry.require("raydium")?, "raydium client");
+
+    Ok(())
+}
+
+#[test]
+fn test_config_reload_rejects_non_reloadable_changes() -> Result<()> {
+    let path = write_test_config("reload", TEST_CONFIG)?;
+    let current = Config::load_from(&ConfigSources {
+        file: Some(path),
+        overrides: vec![],
+    })?;
+
+    let mut candidate = current.clone();
+    candidate.trading.slippage_tolerance = 0.02;
+    let trading = reload::check_reload(&current, &candidate).unwrap();
+    assert_eq!(trading.map(|t| t.slippage_tolerance), Some(0.02));
+
+    candidate.solana.rpc_url = "http://other-rpc:8899".to_string();
+    let problems = reload::check_reload(&current, &candidate).unwrap_err();
+    assert!(problems.iter().any(|p| p.contains("solana")));
+
+    let mut invalid = current.clone();
+    invalid.trading.slippage_tolerance = 2.0;
+    assert!(reload::check_reload(&current, &invalid).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_secret_is_redacted() -> Result<()> {
+    let secret = Secret::new("hunter2".to_string());
+
+    assert_eq!(format!("{:?}", secret), "[REDACTED]");
+    assert_eq!(format!("{}", secret), "[REDACTED]");
+    assert_eq!(serde_json::to_string(&secret)?, "\"[REDACTED]\"");
+    assert_eq!(secret.expose(), "hunter2");
+
+    Ok(())
+}
+
+#[test]
+fn test_keystore_roundtrip() -> Result<()> {
+    let path = std::env::temp_dir().join(format!(
+        "solana_dex_bot_keystore_{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let passphrase = Secret::new("correct horse battery staple".to_string());
+    let keypair = Keypair::new();
+
+    let mut keystore = Keystore::create(&path, &passphrase)?;
+    keystore.set_secret("cex.binance.api_secret", Secret::new("s3cret".to_string()));
+    keystore.set_keypair(&keypair);
+    keystore.save()?;
+
+    let raw = std::fs::read_to_string(&path)?;
+    assert!(!raw.contains("s3cret"));
+
+    let reopened = Keystore::open(&path, &passphrase)?;
+    assert_eq!(
+        reopened
+            .secret("cex.binance.api_secret")
+            .map(|s| s.expose().clone()),
+        Some("s3cret".to_string())
+    );
+    assert_eq!(
+        reopened.keypair()?.map(|k| k.pubkey()),
+        Some(keypair.pubkey())
+    );
+
+    assert!(Keystore::open(&path, &Secret::new("wrong".to_string())).is_err());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // Key derivation parameters from the file are bounded before they are used
+    let mut tampered: serde_json::Value = serde_json::from_str(&raw)?;
+    tampered["kdf"]["memory_kib"] = json!(u32::MAX);
+    std::fs::write(&path, tampered.to_string())?;
+    let err = Keystore::open(&path, &passphrase).err().unwrap();
+    assert!(err.to_string().contains("memory_kib"));
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn test_strategy_registry() -> Result<()> {
+    let path = write_test_config(
+        "strategies",
+        &format!(
+            "{}{}",
+            TEST_CONFIG,
+            r#"
+[[strategies]]
+name = "sol-usdc-lp"
+kind = "lp_rebalance"
+base_mint = "So11111111111111111111111111111111111111112"
+quote_mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"
+price_symbol = "SOL/USD"
+
+[strategies.params]
+venue = "raydium"
+position_id = "position1"
+min_price = 140.0
+max_price = 160.0
+range_width = 0.1
+"#
+        ),
+    )?;
+    let config = Config::load_from(&ConfigSources {
+        file: Some(path),
+        overrides: vec![],
+    })?;
+    let strategy_config = &config.strategies[0];
+
+    let registry = StrategyRegistry::with_builtin();
+    assert!(registry
+        .kinds()
+        .any(|kind| kind == LpRebalanceStrategy::KIND));
+    assert!(registry.build(strategy_config).is_ok());
+    assert_eq!(strategy_config.token_pair()?.symbol, "SOL/USD");
+
+    let mut unknown = strategy_config.clone();
+    unknown.kind = "does_not_exist".to_string();
+    assert!(registry.build(&unknown).is_err());
+
+    let mut bad_params = strategy_config.clone();
+    bad_params.params["range_width"] = serde_json::json!(-1.0);
+    assert!(registry.build(&bad_params).is_err());
+
+    Ok(())
+}
+
+// Holds fixed positions and records which ones it is asked to rebalance
+struct StubDex {
+    positions: Vec<dex::LpPosition>,
+    rebalanced: std::sync::Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl DexClient for StubDex {
+    async fn get_price(&self, _token_a: &Pubkey, _token_b: &Pubkey) -> Result<Price> {
+        Price::parse("150", Rounding::Nearest)
+    }
+
+    async fn create_lp_position(
+        &self,
+        _token_a: &Pubkey,
+        _token_b: &Pubkey,
+        _amount_a: TokenAmount,
+        _amount_b: TokenAmount,
+        _min_price: Price,
+        _max_price: Price,
+    ) -> Result<String> {
+        Err(anyhow::anyhow!("not stubbed"))
+    }
+
+    async fn rebalance_position(
+        &self,
+        position_id: &str,
+        _new_min_price: Price,
+        _new_max_price: Price,
+    ) -> Result<dex::RebalanceReceipt> {
+        self.rebalanced
+            .lock()
+            .unwrap()
+            .push(position_id.to_string());
+        Err(anyhow::anyhow!("not stubbed"))
+    }
+
+    async fn harvest_fees(&self, _position_id: &str) -> Result<dex::HarvestReceipt> {
+        Err(anyhow::anyhow!("not stubbed"))
+    }
+
+    async fn get_unclaimed_fees(&self, _position_id: &str) -> Result<dex::FeeAmounts> {
+        Err(anyhow::anyhow!("not stubbed"))
+    }
+
+    async fn increase_liquidity(
+        &self,
+        _position_id: &str,
+        _amount_a: TokenAmount,
+        _amount_b: TokenAmount,
+    ) -> Result<String> {
+        Err(anyhow::anyhow!("not stubbed"))
+    }
+
+    async fn get_positions(&self) -> Result<Vec<dex::LpPosition>> {
+        Ok(self.positions.clone())
+    }
+
+    async fn quote_swap(
+        &self,
+        _token_in: &Pubkey,
+        _token_out: &Pubkey,
+        _amount_in: TokenAmount,
+    ) -> Result<dex::SwapEstimate> {
+        Err(anyhow::anyhow!("not stubbed"))
+    }
+
+    async fn execute_swap(
+        &self,
+        _token_in: &Pubkey,
+        _token_out: &Pubkey,
+        _amount_in: TokenAmount,
+        _min_amount_out: TokenAmount,
+    ) -> Result<String> {
+        Err(anyhow::anyhow!("not stubbed"))
+    }
+}
+
+#[tokio::test]
+async fn test_lp_rebalance_resumes_replacement_position() -> Result<()> {
+    let path = write_test_config(
+        "lp_restart",
+        &format!(
+            "{}{}",
+            TEST_CONFIG,
+            r#"
+[[strategies]]
+name = "sol-usdc-lp"
+kind = "lp_rebalance"
+base_mint = "So11111111111111111111111111111111111111112"
+quote_mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"
+price_symbol = "SOL/USD"
+
+[strategies.params]
+venue = "raydium"
+position_id = "position1"
+min_price = 140.0
+max_price = 160.0
+range_width = 0.1
+"#
+        ),
+    )?;
+    let config = Config::load_from(&ConfigSources {
+        file: Some(path),
+        overrides: vec![],
+    })?;
+    let strategy_config = &config.strategies[0];
+    let pair = strategy_config.token_pair()?;
+
+    // position1 was rebalanced into position2 before the restart, and
+    // position2's range no longer holds the price
+    let stub = Arc::new(StubDex {
+        positions: vec![dex::LpPosition {
+            id: "position2".to_string(),
+            token_a: pair.base,
+            token_b: pair.quote,
+            min_price: Price::parse("100", Rounding::Nearest)?,
+            max_price: Price::parse("120", Rounding::Nearest)?,
+            amount_a: TokenAmount::zero(9),
+            amount_b: TokenAmount::zero(6),
+        }],
+        rebalanced: std::sync::Mutex::new(Vec::new()),
+    });
+    let mut dex_clients = dex::DexClients::new();
+    dex_clients.register(dex::RAYDIUM, stub.clone())?;
+    let ctx = StrategyContext {
+        name: strategy_config.name.clone(),
+        pair,
+        dex_clients,
+        price_feeds: oracles::PriceFeeds::new(),
+        cex_clients: CexClients::new(),
+        trading_params: test_trading_params(),
+        pool_states: dex::PoolCache::new(),
+    };
+
+    let mut strategy = LpRebalanceStrategy::from_config(strategy_config)?;
+    strategy.on_start(&ctx).await?;
+    let update = PriceUpdate {
+        source: "stub_feed".to_string(),
+        symbol: "SOL/USD".to_string(),
+        price: Price::parse("150", Rounding::Nearest)?,
+        received_at: std::time::Instant::now(),
+    };
+    assert!(strategy.on_price(&ctx, &update).await.is_err());
+    assert_eq!(*stub.rebalanced.lock().unwrap(), vec!["position2"]);
+    Ok(())
+}
+
+struct StubFeed;
+
+#[async_trait::async_trait]
+impl PriceFeed for StubFeed {
+    async fn get_price(&self, _symbol: &str) -> Result<Price> {
+        Price::parse("150", Rounding::Nearest)
+    }
+
+    async fn get_price_with_confidence(&self, symbol: &str) -> Result<(Price, Price)> {
+        Ok((self.get_price(symbol).await?, Price::default()))
+    }
+
+    async fn subscribe_price_updates(
+        &self,
+        _symbol: &str,
+        _callback: Box<dyn Fn(Price) + Send + Sync>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Records every hook the engine calls
+struct StubStrategy {
+    events: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[async_trait::async_trait]
+impl Strategy for StubStrategy {
+    async fn on_start(&mut self, ctx: &StrategyContext) -> Result<()> {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("start {}", ctx.name));
+        Ok(())
+    }
+
+    async fn on_price(&mut self, _ctx: &StrategyContext, update: &PriceUpdate) -> Result<()> {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("price {} {}", update.source, update.price));
+        Ok(())
+    }
+
+    async fn on_fill(&mut self, _ctx: &StrategyContext, fill: &Fill) -> Result<()> {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("fill {} {}", fill.order_id, fill.quantity));
+        Ok(())
+    }
+
+    async fn on_timer(&mut self, _ctx: &StrategyContext) -> Result<()> {
+        self.events.lock().unwrap().push("timer".to_string());
+        Ok(())
+    }
+
+    async fn on_shutdown(&mut self, _ctx: &StrategyContext) -> Result<()> {
+        self.events.lock().unwrap().push("shutdown".to_string());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_strategy_engine_drives_hooks() -> Result<()> {
+    let path = write_test_config(
+        "engine",
+        &format!(
+            "{}{}",
+            TEST_CONFIG,
+            r#"
+[[strategies]]
+name = "stub-1"
+kind = "stub"
+base_mint = "So11111111111111111111111111111111111111112"
+quote_mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"
+price_symbol = "SOL/USD"
+timer_interval_secs = 1
+price_poll_interval_ms = 100
+"#
+        ),
+    )?;
+    let config = Config::load_from(&ConfigSources {
+        file: Some(path),
+        overrides: vec![],
+    })?;
+
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut registry = StrategyRegistry::new();
+    let recorded = events.clone();
+    registry.register("stub", move |_| {
+        Ok(Box::new(StubStrategy {
+            events: recorded.clone(),
+        }))
+    })?;
+    let mut price_feeds = oracles::PriceFeeds::new();
+    price_feeds.register("stub_feed", Arc::new(StubFeed))?;
+    let (_trading_tx, trading_params) = tokio::sync::watch::channel(config.trading.clone());
+
+    let engine = StrategyEngine::new(
+        &registry,
+        &config.strategies,
+        &dex::DexClients::new(),
+        &price_feeds,
+        &CexClients::new(),
+        &OrderTracker::new(),
+        &trading_params,
+        &dex::PoolCache::new(),
+    )?;
+    let fills = engine.fill_router();
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let running = tokio::spawn(engine.run(shutdown.clone()));
+
+    let fill = Fill {
+        strategy: "stub-1".to_string(),
+        venue: "mock".to_string(),
+        order_id: "order-1".to_string(),
+        side: "BUY".to_string(),
+        price: Price::parse("150", Rounding::Nearest)?,
+        quantity: TokenAmount::new(100_000_000, 8),
+    };
+    fills.route(fill.clone()).await?;
+    let unknown = Fill {
+        strategy: "not-running".to_string(),
+        ..fill
+    };
+    assert!(fills.route(unknown).await.is_err());
+
+    // Long enough for the one second timer to fire once
+    tokio::time::sleep(Duration::from_millis(1_300)).await;
+    shutdown.cancel();
+    running.await??;
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.first().map(String::as_str), Some("start stub-1"));
+    assert!(events.iter().any(|event| event == "price stub_feed 150"));
+    assert!(events.iter().any(|event| event == "fill order-1 1"));
+    assert_eq!(events.iter().filter(|event| *event == "timer").count(), 1);
+    assert_eq!(events.last().map(String::as_str), Some("shutdown"));
+    Ok(())
+}
+
+#[derive(Default)]
+struct MockExchange {
+    cancelled: std::sync::Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl CexClient for MockExchange {
+    async fn get_order_book(&self, _symbol: &str) -> Result<OrderBook> {
+        Ok(OrderBook {
+            bids: vec![],
+            asks: vec![],
+            timestamp: 0,
+        })
+    }
+
+    async fn get_ticker(&self, _symbol: &str) -> Result<Price> {
+        Price::parse("100", Rounding::Nearest)
+    }
+
+    async fn place_order(
+        &self,
+        _symbol: &str,
+        _side: &str,
+        _price: Price,
+        _quantity: TokenAmount,
+    ) -> Result<String> {
+        Ok("order-1".to_string())
+    }
+
+    async fn cancel_order(&self, _symbol: &str, order_id: &str) -> Result<()> {
+        self.cancelled.lock().unwrap().push(order_id.to_string());
+        Ok(())
+    }
+
+    async fn get_balance(&self, _asset: &str) -> Result<TokenAmount> {
+        Ok(TokenAmount::zero(8))
+    }
+
+    async fn get_recent_trades(&self, _symbol: &str) -> Result<Vec<Trade>> {
+        Ok(vec![])
+    }
+}
+
+#[tokio::test]
+async fn test_open_orders_cancelled_on_shutdown() -> Result<()> {
+    let exchange = Arc::new(MockExchange::default());
+    let mut clients = CexClients::new();
+    clients.register("mock", exchange.clone())?;
+
+    let orders = OrderTracker::new();
+    let tracked = cex::track_orders(&clients, &orders)?;
+    let order_id = tracked
+        .require("mock")?
+        .place_order(
+            "SOLUSDT",
+            "BUY",
+            Price::parse("100", Rounding::Nearest)?,
+            TokenAmount::new(100_000_000, 8),
+        )
+        .await?;
+    assert_eq!(orders.open_orders().len(), 1);
+
+    assert_eq!(orders.cancel_all(&tracked).await, 1);
+    assert!(orders.open_orders().is_empty());
+    assert_eq!(*exchange.cancelled.lock().unwrap(), vec![order_id]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_supervisor_restarts_failing_task() -> Result<()> {
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let supervisor = Supervisor::new(shutdown.clone());
+    let policy = RestartPolicy {
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+        max_restarts: Some(3),
+        ..Default::default()
+    };
+
+    let attempts = Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let counter = attempts.clone();
+    supervisor.spawn("flaky", policy.clone(), move || {
+        let counter = counter.clone();
+        async move {
+            let attempt = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if attempt == 0 {
+                panic!("first run panics");
+            }
+            Err(anyhow::anyhow!("rpc unavailable"))
+        }
+    });
+    supervisor.join().await;
+
+    // One initial run plus three restarts before giving up
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 4);
+    let health = supervisor.health();
+    assert_eq!(health[0].state, TaskState::Failed);
+    assert_eq!(health[0].total_restarts, 4);
+    assert_eq!(health[0].last_error.as_deref(), Some("rpc unavailable"));
+    assert!(!supervisor.is_healthy());
+
+    assert_eq!(policy.backoff(1), Duration::from_millis(1));
+    assert_eq!(policy.backoff(3), Duration::from_millis(4));
+    assert_eq!(policy.backoff(10), Duration::from_millis(5));
+
+    Ok(())
+}
+
+#[test]
+fn test_size_rotating_log_writer() -> Result<()> {
+    use std::io::Write;
+
+    let dir = std::env::temp_dir().join(format!("bot-log-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("bot.log");
+
+    let mut writer = SizeRotatingWriter::new(&path, 16, 2)?;
+    for line in [
+        "first line\n",
+        "second line\n",
+        "third line\n",
+        "fourth line\n",
+    ] {
+        writer.write_all(line.as_bytes())?;
+    }
+    writer.flush()?;
+
+    // Each line pushes the file past 16 bytes, so only the newest two rotations survive
+    assert_eq!(std::fs::read_to_string(&path)?, "fourth line\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.join("bot.log.1"))?,
+        "third line\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("bot.log.2"))?,
+        "second line\n"
+    );
+    assert!(!dir.join("bot.log.3").exists());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_log_filter_from_config() -> Result<()> {
+    let mut config = LoggingConfig {
+        filters: vec!["solana_dex_bot::dex=debug".to_string()],
+        ..Default::default()
+    };
+    let filter = logging::build_filter(&config)?;
+    assert!(filter.to_string().contains("solana_dex_bot::dex=debug"));
+
+    config.filters = vec!["solana_dex_bot::dex=loud".to_string()];
+    assert!(logging::build_filter(&config).is_err());
+
+    Ok(())
+}
+
+// Account snapshots in `solana account --output json` form, keyed by role
+const RAYDIUM_SOL_USDC: &str = include_str!("fixtures/raydium_amm_v4_sol_usdc.json");
+
+fn fixture_account(fixture: &str, name: &str) -> Result<Vec<u8>> {
+    use base64::Engine;
+
+    let accounts: serde_json::Value = serde_json::from_str(fixture)?;
+    let data = accounts[name]["account"]["data"][0]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("fixture has no account {}", name))?;
+    Ok(base64::engine::general_purpose::STANDARD.decode(data)?)
+}
+
+#[test]
+fn test_raydium_amm_v4_pool_decoding() -> Result<()> {
+    let amm = AmmInfo::decode(&fixture_account(RAYDIUM_SOL_USDC, "pool")?)?;
+    let tokens = TokenRegistry::bundled()?;
+    let (sol, usdc) = (tokens.mint("SOL")?, tokens.mint("USDC")?);
+    assert_eq!(amm.base_mint, sol);
+    assert_eq!(amm.quote_mint, usdc);
+    assert_eq!((amm.base_decimals, amm.quote_decimals), (9, 6));
+    assert_eq!(
+        amm.base_vault,
+        Pubkey::from_str("DQyrAcCrDXQ7NeoqGgDCZwBvWDcYmFCjSb9JtteuvPpz")?
+    );
+    assert!(amm.is_tradable());
+    // Liquidity only (4) and waiting to open (7) pools take no swaps
+    for (status, tradable) in [(1, true), (4, false), (7, false)] {
+        let mut data = fixture_account(RAYDIUM_SOL_USDC, "pool")?;
+        data[..8].copy_from_slice(&u64::to_le_bytes(status));
+        assert_eq!(AmmInfo::decode(&data)?.is_tradable(), tradable);
+    }
+
+    let base_amount =
+        dex::layout::token_account_amount(&fixture_account(RAYDIUM_SOL_USDC, "base_vault")?)?;
+    let quote_amount =
+        dex::layout::token_account_amount(&fixture_account(RAYDIUM_SOL_USDC, "quote_vault")?)?;
+    let info = amm.pool_info(base_amount, quote_amount)?;
+
+    // Pending pnl is excluded from the reserves
+    assert_eq!(info.reserve_a.to_string(), "250000");
+    assert_eq!(info.reserve_b.to_string(), "37500000");
+    assert_eq!(info.price, Price::parse("150", Rounding::Nearest)?);
+    assert_eq!(info.fee_rate, 0.0025);
+
+    let inverted = info.inverted()?;
+    assert_eq!(inverted.token_a, usdc);
+    assert_eq!(inverted.price.to_string(), "0.006666666666666667");
+
+    assert!(AmmInfo::decode(&[0u8; 100]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_tick_math() -> Result<()> {
+    // SOL (9 decimals) priced in USDC (6 decimals)
+    let tick = tick_math::price_to_tick(150.0, 9, 6)?;
+    assert_eq!(tick, -18973);
+    let price = tick_math::tick_to_price(tick, 9, 6);
+    assert!(price <= 150.0 && price > 150.0 / 1.0001);
+    assert_eq!(
+        tick_math::price_to_tick(tick_math::tick_to_price(-18970, 9, 6), 9, 6)?,
+        -18970
+    );
+
+    assert_eq!(tick_math::round_tick_down(-18973, 10), -18980);
+    assert_eq!(tick_math::round_tick_up(-18973, 10), -18970);
+    assert_eq!(tick_math::round_tick_up(-18970, 10), -18970);
+    assert_eq!(
+        tick_math::tick_range(120.0, 180.0, 9, 6, 10)?,
+        (-21210, -17140)
+    );
+    assert!(tick_math::tick_range(180.0, 120.0, 9, 6, 10).is_err());
+    assert!(tick_math::price_to_tick(0.0, 9, 6).is_err());
+
+    assert_eq!(tick_math::tick_array_start_index(-18973, 10, 60), -19200);
+    assert_eq!(tick_math::tick_array_start_index(599, 10, 60), 0);
+
+    // Liquidity funded by a pair of amounts never needs more than was offered
+    let sqrt_price = tick_math::sqrt_price_at_tick(-18973);
+    let sqrt_lower = tick_math::sqrt_price_at_tick(-21210);
+    let sqrt_upper = tick_math::sqrt_price_at_tick(-17140);
+    let liquidity = tick_math::liquidity_for_amounts(
+        sqrt_price,
+        sqrt_lower,
+        sqrt_upper,
+        1_000_000_000,
+        150_000_000,
+    );
+    let (amount_a, amount_b) =
+        tick_math::amounts_for_liquidity(sqrt_price, sqrt_lower, sqrt_upper, liquidity);
+    assert!(amount_a <= 1_000_000_000 && amount_b <= 150_000_000);
+    assert!(amount_a > 999_000_000 || amount_b > 149_000_000);
+
+    // Below the range the position is entirely token a
+    let (amount_a, amount_b) =
+        tick_math::amounts_for_liquidity(sqrt_lower / 2.0, sqrt_lower, sqrt_upper, liquidity);
+    assert!(amount_a > 0);
+    assert_eq!(amount_b, 0);
+
+    Ok(())
+}
+
+const RAYDIUM_CLMM_SOL_USDC: &str = include_str!("fixtures/raydium_clmm_sol_usdc.json");
+
+#[test]
+fn test_raydium_clmm_decoding() -> Result<()> {
+    let pool = clmm::PoolState::decode(&fixture_account(RAYDIUM_CLMM_SOL_USDC, "pool")?)?;
+    assert_eq!(pool.token_mint_0, TokenRegistry::bundled()?.mint("SOL")?);
+    assert_eq!((pool.mint_decimals_0, pool.mint_decimals_1), (9, 6));
+    assert_eq!(pool.tick_spacing, 10);
+    assert_eq!(pool.tick_current, -18973);
+    assert!((pool.price() - 150.0).abs() < 1e-6);
+
+    let position =
+        clmm::PersonalPosition::decode(&fixture_account(RAYDIUM_CLMM_SOL_USDC, "position")?)?;
+    assert_eq!(
+        position.pool_id,
+        Pubkey::from_str("2QdhepnKRTLjjSqPL1PtKNwqrUkoLee5Gqs8bvZhRdMv")?
+    );
+    assert_eq!((position.tick_lower, position.tick_upper), (-21210, -17140));
+    assert_eq!(
+        (position.token_fees_owed_0, position.token_fees_owed_1),
+        (1_500_000, 2_250_000)
+    );
+
+    let lp = clmm::to_lp_position(&position, &pool)?;
+    assert!(lp.min_price.to_f64() <= 120.0 && lp.min_price.to_f64() > 119.8);
+    assert!(lp.max_price.to_f64() >= 180.0 && lp.max_price.to_f64() < 180.2);
+    assert!(!lp.amount_a.is_zero() && !lp.amount_b.is_zero());
+    assert_eq!((lp.amount_a.decimals, lp.amount_b.decimals), (9, 6));
+
+    // A pool account is not a position
+    assert!(
+        clmm::PersonalPosition::decode(&fixture_account(RAYDIUM_CLMM_SOL_USDC, "pool")?).is_err()
+    );
+
+    let program_id = Pubkey::from_str("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK")?;
+    let owner = Pubkey::new_unique();
+    let nft_mint = Pubkey::new_unique();
+    let instruction = clmm::instructions::open_position(
+        &program_id,
+        &owner,
+        &position.pool_id,
+        &pool,
+        &nft_mint,
+        -21210,
+        -17140,
+        1_000,
+        10,
+        20,
+    );
+    assert_eq!(
+        instruction.data[..8],
+        dex::layout::anchor_discriminator("global:open_position_v2")
+    );
+    assert_eq!(instruction.data.len(), 8 + 4 * 4 + 16 + 8 + 8 + 2);
+    assert_eq!(
+        i32::from_le_bytes(instruction.data[16..20].try_into()?),
+        -21600
+    );
+    assert!(instruction.accounts[2].is_signer && instruction.accounts[2].pubkey == nft_mint);
+
+    // A position minted under Token-2022 is closed through its Token-2022 NFT account
+    let token_2022 = dex::programs::TOKEN_2022_PROGRAM_ID;
+    let close = clmm::instructions::close_position(&program_id, &owner, &nft_mint, &token_2022);
+    assert_eq!(
+        close.accounts[2].pubkey,
+        dex::programs::associated_token_address(&owner, &nft_mint, &token_2022)
+    );
+    assert_eq!(close.accounts[5].pubkey, token_2022);
+
+    Ok(())
+}
+
+#[test]
+fn test_rebalance_swap_matches_range_ratio() {
+    let sqrt_price = tick_math::sqrt_price_at_tick(-18973);
+    let sqrt_lower = tick_math::sqrt_price_at_tick(-21210);
+    let sqrt_upper = tick_math::sqrt_price_at_tick(-17140);
+
+    // All token 0 after withdrawing: sell part of it for token 1
+    let (zero_for_one, amount_in) =
+        dex::rebalance_swap(2_000_000_000, 0, sqrt_price, sqrt_lower, sqrt_upper).unwrap();
+    assert!(zero_for_one);
+    assert!(amount_in > 0 && amount_in < 2_000_000_000);
+
+    // After the swap the holdings fund the range with almost nothing left over
+    let price = sqrt_price * sqrt_price;
+    let remaining_0 = 2_000_000_000 - amount_in;
+    let received_1 = (amount_in as f64 * price) as u64;
+    assert!(
+        dex::rebalance_swap(remaining_0, received_1, sqrt_price, sqrt_lower, sqrt_upper).is_none()
+    );
+
+    // Above the range only token 1 is needed
+    let (zero_for_one, amount_in) =
+        dex::rebalance_swap(1_000_000_000, 0, sqrt_upper * 1.01, sqrt_lower, sqrt_upper).unwrap();
+    assert!(zero_for_one);
+    assert_eq!(amount_in, 1_000_000_000);
+
+    assert!(dex::rebalance_swap(0, 0, sqrt_price, sqrt_lower, sqrt_upper).is_none());
+}
+
+#[test]
+fn test_rebalance_swap_cost() -> Result<()> {
+    let price = Price::parse("150", Rounding::Nearest)?;
+
+    // Selling 1 SOL for 149.5 USDC at 150 costs 0.5 USDC
+    let cost = dex::swap_cost(
+        price,
+        true,
+        TokenAmount::new(1_000_000_000, 9),
+        TokenAmount::new(149_500_000, 6),
+    )?;
+    assert_eq!(cost, TokenAmount::new(500_000, 6));
+
+    // Buying with USDC values what arrived at the same price
+    let cost = dex::swap_cost(
+        price,
+        false,
+        TokenAmount::new(150_000_000, 6),
+        TokenAmount::new(990_000_000, 9),
+    )?;
+    assert_eq!(cost, TokenAmount::new(1_500_000, 6));
+
+    // A swap that came out ahead costs nothing
+    let cost = dex::swap_cost(
+        price,
+        true,
+        TokenAmount::new(1_000_000_000, 9),
+        TokenAmount::new(151_000_000, 6),
+    )?;
+    assert!(cost.is_zero());
+    Ok(())
+}
+
+#[test]
+fn test_fee_growth_and_harvest_threshold() {
+    let q64 = 1u128 << 64;
+
+    // In range: growth inside is global minus what accrued outside each bound
+    let inside = tick_math::fee_growth_inside(0, -10, 10, 10 * q64, 2 * q64, 3 * q64);
+    assert_eq!(inside, 5 * q64);
+    assert_eq!(tick_math::fees_earned(1_000, inside, 4 * q64), 1_000);
+
+    // Below the range nothing accrues inside even as global growth increases
+    let below = tick_math::fee_growth_inside(-20, -10, 10, 10 * q64, 4 * q64, 3 * q64);
+    assert_eq!(below, 4 * q64 - 3 * q64);
+
+    // Checkpoints may wrap around; the difference is still what was earned
+    let last = u128::MAX - q64 + 1;
+    assert_eq!(tick_math::fees_earned(500, q64, last), 1_000);
+
+    assert!(harvest::should_harvest(0.0006, 0.0001, 5.0));
+    assert!(!harvest::should_harvest(0.0004, 0.0001, 5.0));
+    assert!(!harvest::should_harvest(0.0, 0.0, 5.0));
+}
+
+const ORCA_WHIRLPOOL_SOL_USDC: &str = include_str!("fixtures/orca_whirlpool_sol_usdc.json");
+
+#[test]
+fn test_orca_whirlpool_decoding_and_quote() -> Result<()> {
+    let whirlpool =
+        orca::Whirlpool::decode(&fixture_account(ORCA_WHIRLPOOL_SOL_USDC, "whirlpool")?)?;
+    let sol = TokenRegistry::bundled()?.mint("SOL")?;
+    assert_eq!(whirlpool.token_mint_a, sol);
+    assert_eq!((whirlpool.tick_spacing, whirlpool.fee_rate), (64, 3000));
+    assert_eq!(whirlpool.tick_current, -18973);
+    assert_eq!(whirlpool.rewards.len(), 1);
+
+    let pool = orca::Pool {
+        address: Pubkey::from_str("2roUsCshVDvDqfq5XriyRR5oqy3a1akvxpPYeEsqE1Wu")?,
+        whirlpool,
+        decimals_a: 9,
+        decimals_b: 6,
+    };
+    assert!((pool.price() - 150.0).abs() < 1e-6);
+
+    let tick_array = orca::TickArray::decode(
+        &fixture_account(ORCA_WHIRLPOOL_SOL_USDC, "tick_array")?,
+        pool.whirlpool.tick_spacing,
+    )?;
+    assert_eq!(tick_array.start_tick_index, -22528);
+    assert_eq!(tick_array.ticks.len(), 2);
+    assert!(tick_array.tick(-20032)?.liquidity_net > 0);
+    assert!(tick_array.tick(-20000).is_err());
+
+    let position = orca::Position::decode(&fixture_account(ORCA_WHIRLPOOL_SOL_USDC, "position")?)?;
+    assert_eq!(position.whirlpool, pool.address);
+    assert_eq!((position.tick_lower, position.tick_upper), (-20032, -17984));
+    assert_eq!(position.reward_owed, [5_000_000, 0, 0]);
+    let lp = orca::to_lp_position(&position, &pool)?;
+    assert!(lp.min_price.to_f64() < 150.0 && lp.max_price.to_f64() > 150.0);
+    assert!(lp.amount_a.to_ui() > 12.0 && lp.amount_b.to_ui() > 1900.0);
+
+    // Fees accrued since the checkpoint come from growth inside the range
+    let (lower, upper) = (tick_array.tick(-20032)?, tick_array.tick(-17984)?);
+    let inside = tick_math::fee_growth_inside(
+        pool.whirlpool.tick_current,
+        position.tick_lower,
+        position.tick_upper,
+        pool.whirlpool.fee_growth_global_a_x64,
+        lower.fee_growth_outside_a_x64,
+        upper.fee_growth_outside_a_x64,
+    );
+    let earned = tick_math::fees_earned(
+        position.liquidity,
+        inside,
+        position.fee_growth_checkpoint_a_x64,
+    );
+    assert!((3_999_000..=4_000_000).contains(&earned));
+
+    // One SOL moves the price well within the range: ~150 USDC less fee and impact
+    let tick_arrays = [tick_array];
+    let quote = orca::quote_exact_in(&pool.whirlpool, &tick_arrays, 1_000_000_000, true)?;
+    assert!(quote.amount_out > 145_000_000 && quote.amount_out < 150_000_000);
+    assert_eq!(quote.fee_amount, 3_000_000);
+    assert!(quote.price_impact > 0.003 && quote.price_impact < 0.01);
+
+    let quote = orca::quote_exact_in(&pool.whirlpool, &tick_arrays, 150_000_000, false)?;
+    assert!(quote.amount_out > 970_000_000 && quote.amount_out < 1_000_000_000);
+
+    // Past the position's lower tick there is no liquidity left to swap against
+    assert!(orca::quote_exact_in(&pool.whirlpool, &tick_arrays, 100_000_000_000, true).is_err());
+
+    let program_id = Pubkey::from_str("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc")?;
+    let instruction = orca::instructions::swap(
+        &program_id,
+        &Pubkey::new_unique(),
+        &pool.address,
+        &pool.whirlpool,
+        true,
+        10,
+        1,
+        [pool.address; 3],
+    );
+    assert_eq!(
+        instruction.data[..8],
+        dex::layout::anchor_discriminator("global:swap")
+    );
+    assert_eq!(instruction.data.len(), 8 + 8 + 8 + 16 + 2);
+    assert_eq!(instruction.accounts.len(), 11);
+
+    Ok(())
+}
+
+const METEORA_DLMM_SOL_USDC: &str = include_str!("fixtures/meteora_dlmm_sol_usdc.json");
+
+#[test]
+fn test_meteora_dlmm_bins_and_quote() -> Result<()> {
+    let lb_pair = meteora::LbPair::decode(&fixture_account(METEORA_DLMM_SOL_USDC, "lb_pair")?)?;
+    let sol = TokenRegistry::bundled()?.mint("SOL")?;
+    assert_eq!(lb_pair.token_x_mint, sol);
+    assert_eq!((lb_pair.active_id, lb_pair.bin_step), (-1899, 10));
+    assert!(lb_pair.is_enabled());
+    assert!((lb_pair.fee_rate() - 0.001).abs() < 1e-12);
+
+    let pair = meteora::Pair {
+        address: Pubkey::from_str("HdzaqzgLQ3go71hv4pQMQiuy91RDztLseDviEwA1PnxN")?,
+        lb_pair,
+        decimals_x: 9,
+        decimals_y: 6,
+    };
+    assert!(pair.price() > 149.8 && pair.price() < 150.0);
+    let bin_step = pair.lb_pair.bin_step;
+    assert_eq!(
+        meteora::bins::price_to_bin_id(150.0, bin_step, 9, 6)?,
+        -1899
+    );
+    let (lower, upper) = meteora::bins::bin_range(145.0, 155.0, bin_step, 9, 6)?;
+    assert!(lower < -1899 && upper > -1899);
+    // A position holds at most 70 bins
+    assert!(meteora::bins::bin_range(100.0, 200.0, bin_step, 9, 6).is_err());
+
+    let bin_array =
+        meteora::BinArray::decode(&fixture_account(METEORA_DLMM_SOL_USDC, "bin_array")?)?;
+    assert_eq!(meteora::bin_array_index(-1899), bin_array.index);
+    assert_eq!(
+        bin_array.bin(-1899).map(|bin| bin.amount_y),
+        Some(75_000_000)
+    );
+    assert!(bin_array.bin(-1890).is_none());
+
+    let position = meteora::Position::decode(&fixture_account(METEORA_DLMM_SOL_USDC, "position")?)?;
+    let bin_arrays = [bin_array];
+    let address = Pubkey::new_unique();
+    let lp = meteora::to_lp_position(&address, &position, &pair, &bin_arrays)?;
+    assert_eq!(lp.id, address.to_string());
+    assert!(lp.min_price.to_f64() < 150.0 && lp.max_price.to_f64() > 150.0);
+    assert!((lp.amount_a.to_ui() - 2.75).abs() < 1e-6);
+    assert!((lp.amount_b.to_ui() - 412.5).abs() < 1e-3);
+    let (fee_x, fee_y) = meteora::bins::position_fees(&position, &bin_arrays)?;
+    assert!((10_331_000..=10_332_000).contains(&fee_x));
+    assert!((1_651_000..=1_652_000).contains(&fee_y));
+
+    // Every shape puts all of each token somewhere, x at and above the active
+    // bin and y at and below it
+    for shape in [
+        LiquidityShape::Spot,
+        LiquidityShape::Curve,
+        LiquidityShape::BidAsk,
+    ] {
+        let bins = meteora::distribution(shape, -1905, -1890, -1899);
+        assert_eq!(bins.iter().map(|b| b.x_bps as u32).sum::<u32>(), 10_000);
+        assert_eq!(bins.iter().map(|b| b.y_bps as u32).sum::<u32>(), 10_000);
+        assert!(bins.iter().all(|b| b.bin_id >= -1899 || b.x_bps == 0));
+        assert!(bins.iter().all(|b| b.bin_id <= -1899 || b.y_bps == 0));
+    }
+    let curve = meteora::distribution(LiquidityShape::Curve, -1905, -1893, -1899);
+    let bid_ask = meteora::distribution(LiquidityShape::BidAsk, -1905, -1893, -1899);
+    assert!(curve[6].y_bps > curve[0].y_bps);
+    assert!(bid_ask[6].y_bps < bid_ask[0].y_bps);
+    // Above the price a range only takes token x
+    let above = meteora::distribution(LiquidityShape::Spot, -1890, -1880, -1899);
+    assert!(above.iter().all(|b| b.y_bps == 0));
+    assert_eq!(
+        meteora::bins::value_share_x(LiquidityShape::Spot, -1890, -1880, -1899),
+        1.0
+    );
+
+    // One SOL empties the active bin's USDC and moves one bin down
+    let quote = meteora::quote_exact_in(&pair.lb_pair, &bin_arrays, 1_000_000_000, true)?;
+    assert!(quote.amount_out > 149_000_000 && quote.amount_out < 150_000_000);
+    assert!((999_999..=1_000_001).contains(&quote.fee_amount));
+    assert_eq!(quote.end_bin_id, -1900);
+    assert!(quote.price_impact > 0.001 && quote.price_impact < 0.002);
+
+    let quote = meteora::quote_exact_in(&pair.lb_pair, &bin_arrays, 150_000_000, false)?;
+    assert!(quote.amount_out > 990_000_000 && quote.amount_out < 1_000_000_000);
+
+    // Past the last loaded bin array the swap cannot be filled
+    assert!(meteora::quote_exact_in(&pair.lb_pair, &bin_arrays, 10_000_000_000, true).is_err());
+
+    let program_id = Pubkey::from_str("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo")?;
+    let instruction = meteora::instructions::add_liquidity(
+        &program_id,
+        &Pubkey::new_unique(),
+        &pair.address,
+        &pair.lb_pair,
+        &address,
+        -1905,
+        -1893,
+        1,
+        1,
+        &[meteora::instructions::BinLiquidityDistribution {
+            bin_id: -1899,
+            distribution_x: 10_000,
+            distribution_y: 10_000,
+        }],
+    );
+    assert_eq!(
+        instruction.data[..8],
+        dex::layout::anchor_discriminator("global:add_liquidity")
+    );
+    assert_eq!(instruction.data.len(), 8 + 8 + 8 + 4 + 8);
+    assert_eq!(instruction.accounts.len(), 16);
+
+    Ok(())
+}
+
+// JSON-RPC envelope the mock RPC node answers with
+fn rpc_result(result: serde_json::Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+}
+
+fn rpc_account(owner: &Pubkey, data: &[u8]) -> serde_json::Value {
+    use base64::Engine;
+
+    json!({
+        "lamports": 1_000_000,
+        "data": [base64::engine::general_purpose::STANDARD.encode(data), "base64"],
+        "owner": owner.to_string(),
+        "executable": false,
+        "rentEpoch": 0,
+        "space": data.len(),
+    })
+}
+
+// A simulateTransaction result
+fn simulated(err: Option<serde_json::Value>, units_consumed: u64) -> serde_json::Value {
+    json!({
+        "err": err,
+        "logs": [],
+        "accounts": null,
+        "unitsConsumed": units_consumed,
+        "returnData": null,
+    })
+}
+
+// Answers sendTransaction with the signature of the transaction it was sent
+struct EchoSignature;
+
+impl Respond for EchoSignature {
+    fn respond(&self, request: &wiremock::Request) -> ResponseTemplate {
+        use base64::Engine;
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let wire = base64::engine::general_purpose::STANDARD
+            .decode(body["params"][0].as_str().unwrap())
+            .unwrap();
+        // A single signer: one byte of signature count, then the signature
+        let signature = Signature::try_from(&wire[1..65]).unwrap();
+        rpc_result(json!(signature.to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_jupiter_v6_quote_and_swap() -> Result<()> {
+    let server = MockServer::start().await;
+    let tokens = TokenRegistry::bundled()?;
+    let (sol, usdc) = (tokens.mint("SOL")?, tokens.mint("USDC")?);
+    let jupiter_program = Pubkey::from_str("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4")?;
+    let lookup_table = Pubkey::new_unique();
+    let (route_a, route_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let wallet = Arc::new(Keypair::new());
+
+    // Mints: decimals at byte 44
+    let mut sol_mint = vec![0u8; 82];
+    sol_mint[44] = 9;
+    let mut usdc_mint = vec![0u8; 82];
+    usdc_mint[44] = 6;
+    let token_program = dex::programs::TOKEN_PROGRAM_ID;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "getMultipleAccounts" })))
+        .and(body_string_contains(sol.to_string()))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 1 },
+            "value": [rpc_account(&token_program, &sol_mint), rpc_account(&token_program, &usdc_mint)],
+        })))
+        .mount(&server)
+        .await;
+
+    // Lookup table: 56 byte header (type 1, never deactivated) then addresses
+    let mut table = vec![0u8; 56];
+    table[0..4].copy_from_slice(&1u32.to_le_bytes());
+    table[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+    table.extend_from_slice(route_a.as_ref());
+    table.extend_from_slice(route_b.as_ref());
+    let lookup_table_program = Pubkey::from_str("AddressLookupTab1e1111111111111111111111111")?;
+    Mock::given(method("POST"))
+        .and(body_partial_json(
+            json!({ "method": "getMultipleAccounts" }),
+        ))
+        .and(body_string_contains(lookup_table.to_string()))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 1 },
+            "value": [rpc_account(&lookup_table_program, &table)],
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "getLatestBlockhash" })))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 1 },
+            "value": {
+                "blockhash": solana_sdk::hash::Hash::new_unique().to_string(),
+                "lastValidBlockHeight": 1_000,
+            },
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "getVersion" })))
+        .respond_with(rpc_result(
+            json!({ "solana-core": "1.17.0", "feature-set": 1 }),
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(
+            json!({ "method": "simulateTransaction" }),
+        ))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 1 },
+            "value": simulated(None, 120_000),
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "sendTransaction" })))
+        .respond_with(EchoSignature)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(
+            json!({ "method": "getSignatureStatuses" }),
+        ))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 2 },
+            "value": [{
+                "slot": 2,
+                "confirmations": null,
+                "err": null,
+                "status": { "Ok": null },
+                "confirmationStatus": "finalized",
+            }],
+        })))
+        .mount(&server)
+        .await;
+
+    // Amounts go out as integer base units in the query string
+    let quote = |amount: &str, out_amount: &str| {
+        json!({
+            "inputMint": sol.to_string(),
+            "inAmount": amount,
+            "outputMint": usdc.to_string(),
+            "outAmount": out_amount,
+            "otherAmountThreshold": "149250000",
+            "swapMode": "ExactIn",
+            "slippageBps": 50,
+            "priceImpactPct": "0.0001",
+            "routePlan": [{
+                "swapInfo": {
+                    "ammKey": route_a.to_string(),
+                    "label": "Whirlpool",
+                    "inputMint": sol.to_string(),
+                    "outputMint": usdc.to_string(),
+                    "inAmount": amount,
+                    "outAmount": out_amount,
+                    "feeAmount": "300000",
+                    "feeMint": sol.to_string(),
+                },
+                "percent": 100,
+            }],
+            "contextSlot": 1,
+            "timeTaken": 0.01,
+        })
+    };
+    Mock::given(method("GET"))
+        .and(path("/quote"))
+        .and(query_param("inputMint", sol.to_string()))
+        .and(query_param("outputMint", usdc.to_string()))
+        .and(query_param("amount", "1000000000"))
+        .and(query_param("slippageBps", "50"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(quote("1000000000", "150000000")))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/quote"))
+        .and(query_param("amount", "2000000000"))
+        .respond_with(
+            ResponseTemplate::new(400).set_body_json(json!({ "error": "No routes found" })),
+        )
+        .mount(&server)
+        .await;
+
+    // The swap instruction addresses the route accounts through the lookup table
+    let wallet_key = wallet.pubkey().to_string();
+    Mock::given(method("POST"))
+        .and(path("/swap-instructions"))
+        .and(body_partial_json(
+            json!({ "userPublicKey": wallet_key, "quoteResponse": { "inAmount": "1000000000" } }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "computeBudgetInstructions": [{
+                "programId": "ComputeBudget111111111111111111111111111111",
+                "accounts": [],
+                "data": "AsBcFQA=",
+            }],
+            "setupInstructions": [],
+            "swapInstruction": {
+                "programId": jupiter_program.to_string(),
+                "accounts": [
+                    { "pubkey": wallet_key, "isSigner": true, "isWritable": true },
+                    { "pubkey": route_a.to_string(), "isSigner": false, "isWritable": true },
+                    { "pubkey": route_b.to_string(), "isSigner": false, "isWritable": false },
+                ],
+                "data": "5RfLl3rjrSoBAAAAJmQAAQ==",
+            },
+            "cleanupInstruction": null,
+            "addressLookupTableAddresses": [lookup_table.to_string()],
+        })))
+        .mount(&server)
+        .await;
+
+    let rpc_client = Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+        server.uri(),
+    ));
+    let config = crate::config::JupiterConfig {
+        enabled: true,
+        api_url: format!("{}/", server.uri()),
+    };
+    let sender = Arc::new(TransactionSender::new(
+        rpc_client.clone(),
+        &TransactionConfig::default(),
+        SwapPrograms::default(),
+        PendingTransactions::new(),
+    ));
+    let jupiter = dex::JupiterClient::new(
+        rpc_client,
+        sender,
+        &config,
+        test_trading_params(),
+        wallet.clone(),
+    )?;
+
+    // One whole SOL is quoted to price it
+    assert_eq!(jupiter.get_price(&sol, &usdc).await?.to_string(), "150");
+
+    let quote = jupiter.get_quote(&sol, &usdc, 1_000_000_000, 50).await?;
+    assert_eq!(quote.out_amount, 150_000_000);
+    assert_eq!(quote.route_plan[0].swap_info.label, "Whirlpool");
+    let err = jupiter
+        .get_quote(&sol, &usdc, 2_000_000_000, 50)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("No routes found"));
+
+    // A stricter minimum tightens the slippage the program enforces
+    let tightened = quote.clone().with_min_amount_out(149_850_000)?;
+    assert_eq!(tightened.slippage_bps, 10);
+    assert_eq!(tightened.other_amount_threshold, 149_850_000);
+    assert!(quote.clone().with_min_amount_out(150_000_001).is_err());
+
+    let transaction = jupiter.swap_transaction(&quote).await?.transaction;
+    let message = match &transaction.message {
+        solana_sdk::message::VersionedMessage::V0(message) => message,
+        _ => panic!("expected a v0 message"),
+    };
+    assert_eq!(message.account_keys[0], wallet.pubkey());
+    assert_eq!(message.instructions.len(), 2);
+    // Jupiter's compute budget is replaced by the limit sized from simulation
+    assert_eq!(
+        message.instructions[0].data,
+        solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(132_000).data
+    );
+    assert_eq!(message.address_table_lookups.len(), 1);
+    assert_eq!(message.address_table_lookups[0].writable_indexes, vec![0]);
+    assert_eq!(message.address_table_lookups[0].readonly_indexes, vec![1]);
+    assert!(transaction.verify_with_results().into_iter().all(|ok| ok));
+
+    let signature = jupiter
+        .execute_swap(
+            &sol,
+            &usdc,
+            TokenAmount::new(1_000_000_000, 9),
+            TokenAmount::new(149_000_000, 6),
+        )
+        .await?;
+    assert_eq!(Signature::from_str(&signature)?.as_ref().len(), 64);
+
+    Ok(())
+}
+
+// An RPC node that simulates any transaction as `simulation`, accepts it and
+// reports `status` for it
+async fn transaction_rpc(
+    simulation: serde_json::Value,
+    status: serde_json::Value,
+    block_height: u64,
+) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(
+            json!({ "method": "simulateTransaction" }),
+        ))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 1 },
+            "value": simulation,
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "getLatestBlockhash" })))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 1 },
+            "value": {
+                "blockhash": solana_sdk::hash::Hash::new_unique().to_string(),
+                "lastValidBlockHeight": 1_000,
+            },
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "getVersion" })))
+        .respond_with(rpc_result(
+            json!({ "solana-core": "1.17.0", "feature-set": 1 }),
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "sendTransaction" })))
+        .respond_with(EchoSignature)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(
+            json!({ "method": "getSignatureStatuses" }),
+        ))
+        .respond_with(rpc_result(json!({
+            "context": { "slot": 5 },
+            "value": [status],
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "getBlockHeight" })))
+        .respond_with(rpc_result(json!(block_height)))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn test_transaction_sender_outcomes() -> Result<()> {
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use solana_sdk::instruction::InstructionError;
+    use solana_sdk::transaction::TransactionError;
+
+    let wallet = Keypair::new();
+    let transfer =
+        solana_sdk::system_instruction::transfer(&wallet.pubkey(), &Pubkey::new_unique(), 1);
+    let config = TransactionConfig {
+        priority_fee: PriorityFeeConfig {
+            micro_lamports: 5_000,
+            ..Default::default()
+        },
+        compute_unit_limit: Some(300_000),
+        resend_interval_ms: 100,
+        ..Default::default()
+    };
+    let sender_for = |server: &MockServer, pending: &PendingTransactions| {
+        TransactionSender::new(
+            Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+                server.uri(),
+            )),
+            &config,
+            SwapPrograms::default(),
+            pending.clone(),
+        )
+    };
+
+    let landed = transaction_rpc(
+        simulated(None, 50_000),
+        json!({
+            "slot": 5,
+            "confirmations": 1,
+            "err": null,
+            "status": { "Ok": null },
+            "confirmationStatus": "confirmed",
+        }),
+        900,
+    )
+    .await;
+    let pending = PendingTransactions::new();
+    let sender = sender_for(&landed, &pending);
+
+    // Both compute budget instructions go first, unless one is already set
+    let prepared = sender
+        .prepare(&wallet, &[transfer.clone()], &[], &[])
+        .await?;
+    assert_eq!(prepared.last_valid_block_height, 1_000);
+    assert_eq!(prepared.transaction.message.instructions().len(), 3);
+    let budgeted = sender
+        .with_compute_budget(&[
+            ComputeBudgetInstruction::set_compute_unit_limit(1_000_000),
+            transfer.clone(),
+        ])
+        .await?;
+    assert_eq!(budgeted.len(), 3);
+    assert_eq!(
+        budgeted[0],
+        ComputeBudgetInstruction::set_compute_unit_price(5_000)
+    );
+
+    // The cached blockhash signs the second transaction too
+    let outcome = sender
+        .send(&wallet, &[transfer.clone()], &[], "transfer")
+        .await?;
+    assert!(matches!(
+        outcome,
+        TransactionOutcome::Landed { slot: 5, .. }
+    ));
+    assert!(outcome.into_result().is_ok());
+    assert!(pending.is_empty());
+
+    let failed = transaction_rpc(
+        simulated(None, 50_000),
+        json!({
+            "slot": 5,
+            "confirmations": 0,
+            "err": { "InstructionError": [0, { "Custom": 6001 }] },
+            "status": { "Err": { "InstructionError": [0, { "Custom": 6001 }] } },
+            "confirmationStatus": "processed",
+        }),
+        900,
+    )
+    .await;
+    let outcome = sender_for(&failed, &pending)
+        .send(&wallet, &[transfer.clone()], &[], "transfer")
+        .await?;
+    assert_eq!(
+        outcome,
+        TransactionOutcome::Failed {
+            signature: *outcome.signature(),
+            error: TransactionError::InstructionError(0, InstructionError::Custom(6001)),
+        }
+    );
+
+    // Never seen and past its last valid block height
+    let expired = transaction_rpc(simulated(None, 50_000), serde_json::Value::Null, 1_001).await;
+    let outcome = sender_for(&expired, &pending)
+        .send(&wallet, &[transfer], &[], "transfer")
+        .await?;
+    assert!(matches!(outcome, TransactionOutcome::Expired { .. }));
+    assert!(outcome.into_result().is_err());
+    assert!(pending.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_preflight_simulation_decodes_errors() -> Result<()> {
+    use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+    use solana_sdk::instruction::InstructionError;
+    use solana_sdk::transaction::TransactionError;
+
+    let whirlpool = Pubkey::from_str("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc")?;
+    let jupiter = dex::programs::JUPITER_PROGRAM_ID;
+    let token = dex::programs::TOKEN_PROGRAM_ID;
+    let swap_programs = SwapPrograms::from_config(&serde_json::from_value(json!({
+        "orca": {},
+        "jupiter": { "api_url": "https://quote-api.jup.ag/v6/" },
+    }))?)?;
+
+    // A whirlpool swap whose output falls short after its token transfer ran
+    let logs = parse_logs(&[
+        format!("Program {} invoke [1]", compute_budget::id()),
+        format!("Program {} success", compute_budget::id()),
+        format!("Program {} invoke [1]", whirlpool),
+        "Program log: Instruction: Swap".to_string(),
+        format!("Program {} invoke [2]", token),
+        "Program log: Instruction: Transfer".to_string(),
+        format!("Program {} consumed 4645 of 1381000 compute units", token),
+        format!("Program {} success", token),
+        "Program log: AnchorError thrown in programs/whirlpool/src/util/swap_utils.rs:94. \
+         Error Code: AmountOutBelowMinimum. Error Number: 6036. \
+         Error Message: Amount out below minimum threshold."
+            .to_string(),
+        format!(
+            "Program {} consumed 40000 of 1400000 compute units",
+            whirlpool
+        ),
+        format!("Program {} failed: custom program error: 0x1794", whirlpool),
+    ]);
+    assert_eq!(
+        logs[4],
+        ProgramLog::Invoke {
+            program_id: token,
+            depth: 2
+        }
+    );
+    assert_eq!(
+        logs[6],
+        ProgramLog::Consumed {
+            program_id: token,
+            units: 4645,
+            limit: 1_381_000
+        }
+    );
+    assert!(matches!(&logs[8], ProgramLog::Log { program_id, .. } if *program_id == whirlpool));
+    let error = TransactionError::InstructionError(1, InstructionError::Custom(6036));
+    assert_eq!(
+        decode_error(
+            &error,
+            &[compute_budget::id(), whirlpool],
+            &logs,
+            &swap_programs
+        ),
+        TransactionFailure::SlippageExceeded {
+            program_id: whirlpool,
+            code: 6036
+        }
+    );
+
+    // Slippage codes belong to the programs configured for a venue, so a
+    // deployment elsewhere is recognised and one nobody configured is not
+    let program = Pubkey::new_unique();
+    let redeployed = SwapPrograms::from_config(&serde_json::from_value(json!({
+        "orca": { "program_id": program.to_string() },
+    }))?)?;
+    let error = TransactionError::InstructionError(0, InstructionError::Custom(6018));
+    assert_eq!(
+        decode_error(&error, &[program], &[], &redeployed),
+        TransactionFailure::SlippageExceeded {
+            program_id: program,
+            code: 6018
+        }
+    );
+    assert_eq!(
+        decode_error(&error, &[whirlpool], &[], &redeployed),
+        TransactionFailure::Program {
+            program_id: whirlpool,
+            code: 6018,
+            name: None
+        }
+    );
+
+    // Custom errors belong to the innermost program that failed
+    let logs = parse_logs(&[
+        format!("Program {} invoke [1]", jupiter),
+        format!("Program {} invoke [2]", token),
+        "Program log: Error: insufficient funds".to_string(),
+        format!("Program {} failed: custom program error: 0x1", token),
+        format!("Program {} failed: custom program error: 0x1", jupiter),
+    ]);
+    let error = TransactionError::InstructionError(0, InstructionError::Custom(1));
+    assert_eq!(
+        decode_error(&error, &[jupiter], &logs, &swap_programs),
+        TransactionFailure::InsufficientFunds
+    );
+
+    // Anchor's own errors apply to any program; unknown ones keep their name
+    let program = Pubkey::new_unique();
+    let logs = parse_logs(&[
+        format!("Program {} invoke [1]", program),
+        "Program log: AnchorError occurred. Error Code: StaleOracle. Error Number: 6010. \
+         Error Message: Oracle is stale."
+            .to_string(),
+        format!("Program {} failed: custom program error: 0x177a", program),
+    ]);
+    let not_initialized = TransactionError::InstructionError(0, InstructionError::Custom(3012));
+    assert_eq!(
+        decode_error(&not_initialized, &[program], &[], &swap_programs),
+        TransactionFailure::AccountNotInitialized
+    );
+    let stale = TransactionError::InstructionError(0, InstructionError::Custom(6010));
+    assert_eq!(
+        decode_error(&stale, &[program], &logs, &swap_programs),
+        TransactionFailure::Program {
+            program_id: program,
+            code: 6010,
+            name: Some("StaleOracle".to_string())
+        }
+    );
+    assert_eq!(
+        decode_error(
+            &TransactionError::InsufficientFundsForFee,
+            &[],
+            &[],
+            &swap_programs
+        ),
+        TransactionFailure::InsufficientFunds
+    );
+
+    let wallet = Keypair::new();
+    let transfer =
+        solana_sdk::system_instruction::transfer(&wallet.pubkey(), &Pubkey::new_unique(), 1);
+    let config = TransactionConfig::default();
+    let status = json!({
+        "slot": 5,
+        "confirmations": 1,
+        "err": null,
+        "status": { "Ok": null },
+        "confirmationStatus": "confirmed",
+    });
+
+    // The limit requested is what the simulation used plus the margin
+    let server = transaction_rpc(simulated(None, 50_000), status.clone(), 900).await;
+    let sender = TransactionSender::new(
+        Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+            server.uri(),
+        )),
+        &config,
+        swap_programs.clone(),
+        PendingTransactions::new(),
+    );
+    let prepared = sender
+        .prepare(&wallet, &[transfer.clone()], &[], &[])
+        .await?;
+    let limit = ComputeBudgetInstruction::set_compute_unit_limit(55_000);
+    let message = &prepared.transaction.message;
+    assert_eq!(message.instructions().len(), 2);
+    assert_eq!(message.instructions()[0].data, limit.data);
+
+    // A doomed transaction is rejected before it is sent
+    let server = transaction_rpc(
+        json!({
+            "err": { "InstructionError": [0, { "Custom": 6001 }] },
+            "logs": [
+                format!("Program {} invoke [1]", jupiter),
+                "Program log: AnchorError occurred. Error Code: SlippageToleranceExceeded.",
+                format!("Program {} failed: custom program error: 0x1771", jupiter),
+            ],
+            "accounts": null,
+            "unitsConsumed": 30_000,
+            "returnData": null,
+        }),
+        status,
+        900,
+    )
+    .await;
+    let sender = TransactionSender::new(
+        Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+            server.uri(),
+        )),
+        &config,
+        swap_programs.clone(),
+        PendingTransactions::new(),
+    );
+    let err = sender
+        .send(&wallet, &[transfer], &[], "swap")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<TransactionFailure>(),
+        Some(&TransactionFailure::SlippageExceeded {
+            program_id: jupiter,
+            code: 6001
+        })
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_priority_fee_policies() -> Result<()> {
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+
+    let pool = Pubkey::new_unique();
+    let vault = Pubkey::new_unique();
+    let swap = Instruction::new_with_bytes(
+        Pubkey::new_unique(),
+        &[],
+        vec![
+            AccountMeta::new(pool, false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(pool, false),
+        ],
+    );
+    let accounts = writable_accounts(&[swap]);
+    assert_eq!(accounts, vec![pool, vault]);
+
+    // Ten recent slots, two of them without any fee
+    let server = MockServer::start().await;
+    let fees: Vec<serde_json::Value> = [0, 0, 1000, 2000, 3000, 4000, 5000, 6000, 7000, 8000]
+        .iter()
+        .enumerate()
+        .map(|(i, fee)| json!({ "slot": 100 + i as u64, "prioritizationFee": fee }))
+        .collect();
+    Mock::given(method("POST"))
+        .and(body_partial_json(
+            json!({ "method": "getRecentPrioritizationFees" }),
+        ))
+        .and(body_string_contains(pool.to_string()))
+        .respond_with(rpc_result(json!(fees)))
+        .mount(&server)
+        .await;
+    let rpc_client = Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+        server.uri(),
+    ));
+    let estimator =
+        |config: PriorityFeeConfig| PriorityFeeEstimator::new(rpc_client.clone(), &config);
+    let config = PriorityFeeConfig {
+        policy: PriorityFeePolicy::Percentile,
+        micro_lamports: 500,
+        percentile: 75,
+        max_micro_lamports: 20_000,
+        ..Default::default()
+    };
+
+    let fixed = estimator(PriorityFeeConfig {
+        policy: PriorityFeePolicy::Fixed,
+        ..config.clone()
+    });
+    assert_eq!(fixed.estimate(&accounts).await?, 500);
+
+    // Nearest rank: the 8th of 10 fees
+    let percentile = estimator(config.clone());
+    assert_eq!(percentile.estimate(&accounts).await?, 6000);
+
+    let urgency = estimator(PriorityFeeConfig {
+        policy: PriorityFeePolicy::Urgency,
+        urgency: Urgency::High,
+        ..config.clone()
+    });
+    assert_eq!(urgency.estimate(&accounts).await?, 12_000);
+    assert_eq!(
+        urgency
+            .estimate_with_urgency(&accounts, Urgency::Low)
+            .await?,
+        3000
+    );
+    // Capped at max_micro_lamports
+    assert_eq!(
+        urgency
+            .estimate_with_urgency(&accounts, Urgency::Critical)
+            .await?,
+        20_000
+    );
+
+    // Only the newest `window_slots` slots count
+    let windowed = estimator(PriorityFeeConfig {
+        window_slots: 5,
+        ..config.clone()
+    });
+    assert_eq!(windowed.estimate(&accounts).await?, 7000);
+    windowed.record(200, 50);
+    assert_eq!(windowed.percentile(75), Some(50));
+    // The current answer for the accounts is priced, not the rolling window,
+    // which is only used when the node cannot answer
+    assert_eq!(windowed.estimate(&accounts).await?, 7000);
+    assert_eq!(windowed.estimate(&[Pubkey::new_unique()]).await?, 50);
+
+    // Without any known fees the configured fee is the fallback
+    let unknown = estimator(config);
+    assert_eq!(unknown.estimate(&[Pubkey::new_unique()]).await?, 500);
+    assert_eq!(unknown.percentile(75), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_token_amount_and_price_rounding() -> Result<()> {
+    // Decimal strings convert exactly; extra digits round as asked
+    let amount = TokenAmount::parse("1.2345678915", 9, Rounding::Down)?;
+    assert_eq!(amount.base_units, 1_234_567_891);
+    assert_eq!(
+        TokenAmount::parse("1.2345678911", 9, Rounding::Up)?.base_units,
+        1_234_567_892
+    );
+    assert_eq!(
+        TokenAmount::parse("1.2345678915", 9, Rounding::Nearest)?.base_units,
+        1_234_567_892
+    );
+    assert_eq!(
+        TokenAmount::from_ui(0.1, 6, Rounding::Down)?.base_units,
+        100_000
+    );
+    assert_eq!(amount.to_string(), "1.234567891");
+    assert!(TokenAmount::parse("-1", 9, Rounding::Down).is_err());
+    assert!(TokenAmount::parse("1e5", 9, Rounding::Down).is_err());
+    assert!(TokenAmount::parse("18446744073.709551616", 9, Rounding::Down).is_err());
+
+    // Changing decimals only rounds when precision is lost
+    assert_eq!(amount.base_units_at(6, Rounding::Down)?, 1_234_567);
+    assert_eq!(amount.base_units_at(6, Rounding::Up)?, 1_234_568);
+    assert_eq!(amount.base_units_at(12, Rounding::Down)?, 1_234_567_891_000);
+
+    // 150 USDC per SOL, from a quote of 2 SOL for 300 USDC
+    let sol = TokenAmount::new(2_000_000_000, 9);
+    let usdc = TokenAmount::new(300_000_000, 6);
+    let price = Price::from_amounts(sol, usdc, Rounding::Nearest)?;
+    assert_eq!(price, Price::parse("150", Rounding::Nearest)?);
+    assert_eq!(price.quote_for(sol, 6, Rounding::Down)?, usdc);
+    assert_eq!(price.base_for(usdc, 9, Rounding::Down)?, sol);
+
+    // A third of a lamport's worth rounds either way
+    let third = Price::from_amounts(
+        TokenAmount::new(3, 0),
+        TokenAmount::new(1, 0),
+        Rounding::Down,
+    )?;
+    assert_eq!(third.to_string(), "0.333333333333333333");
+    let one = TokenAmount::new(1, 0);
+    assert_eq!(
+        third.quote_for(one, 9, Rounding::Down)?.base_units,
+        333_333_333
+    );
+    assert_eq!(
+        third.quote_for(one, 9, Rounding::Up)?.base_units,
+        333_333_334
+    );
+    assert_eq!(
+        price.invert(Rounding::Up)?.to_string(),
+        "0.006666666666666667"
+    );
+    assert!(price.invert(Rounding::Down)? < price.invert(Rounding::Up)?);
+
+    // Ranges are widened outwards, and large products do not overflow
+    assert_eq!(
+        price.scaled_by(0.9, Rounding::Down)?,
+        Price::parse("135", Rounding::Nearest)?
+    );
+    let large = Price::from_raw(u128::MAX / 4);
+    assert_eq!(
+        large.scaled_by(2.0, Rounding::Down)?.raw(),
+        u128::MAX / 4 * 2
+    );
+    assert!(large.scaled_by(8.0, Rounding::Down).is_err());
+    assert_eq!(
+        Price::from_raw(3).midpoint(Price::from_raw(4), Rounding::Down),
+        Price::from_raw(3)
+    );
+
+    // Prices round-trip through JSON as strings
+    let json = serde_json::to_string(&price)?;
+    assert_eq!(json, "\"150\"");
+    assert_eq!(serde_json::from_str::<Price>(&json)?, price);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_registry() -> Result<()> {
+    let server = MockServer::start().await;
+    let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    let (wif, copycat) = (Pubkey::new_unique(), Pubkey::new_unique());
+    Mock::given(method("GET"))
+        .and(path("/tokens/v2/tag"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            // Already curated: the bundled entry wins
+            { "id": usdc, "symbol": "USDC", "name": "USD Coin", "decimals": 9,
+              "tokenProgram": dex::programs::TOKEN_PROGRAM_ID.to_string() },
+            { "id": wif.to_string(), "symbol": "WIF", "name": "dogwifhat", "decimals": 6,
+              "tokenProgram": dex::programs::TOKEN_2022_PROGRAM_ID.to_string() },
+            // Reuses a curated symbol, so only resolves by mint
+            { "id": copycat.to_string(), "symbol": "sol", "decimals": 9 },
+            { "id": "not-a-mint", "symbol": "BAD", "decimals": 6 },
+        ])))
+        .mount(&server)
+        .await;
+
+    let config = crate::config::TokensConfig {
+        path: None,
+        jupiter_token_list: true,
+        jupiter_token_list_url: format!("{}/tokens/v2/tag?query=verified", server.uri()),
+    };
+    let tokens = TokenRegistry::load(&config).await?;
+
+    let sol = tokens.require("sol")?;
+    assert_eq!(sol.mint, spl_token::native_mint::id());
+    assert_eq!((sol.decimals, sol.program), (9, TokenProgram::Spl));
+    assert_eq!(sol.oracle_symbol(), "SOL/USD");
+    assert_eq!(
+        tokens.by_mint(&copycat).map(|t| t.symbol.as_str()),
+        Some("SOL")
+    );
+    assert_eq!(tokens.resolve_mint("SOL")?, sol.mint);
+    assert_eq!(tokens.label(&sol.mint), "SOL");
+    assert_eq!(tokens.label(&copycat), copycat.to_string());
+
+    assert_eq!(tokens.require("USDC")?.decimals, 6);
+    let listed = tokens.require("WIF")?;
+    assert_eq!(
+        (listed.mint, listed.program),
+        (wif, TokenProgram::Token2022)
+    );
+    assert!(tokens.get("BAD").is_none());
+    assert_eq!(tokens.resolve_mint(&wif.to_string())?, wif);
+
+    // Each CEX spells the same pair its own way
+    assert_eq!(
+        tokens.cex_instrument(cex::BINANCE, "SOL", "USDT")?,
+        "SOLUSDT"
+    );
+    assert_eq!(tokens.cex_instrument(cex::OKX, "sol", "usdt")?, "SOL-USDT");
+    assert!(tokens.cex_instrument(cex::BINANCE, "WIF", "USDT").is_err());
+    Ok(())
+}
+
+// A Token-2022 mint with 6 decimals and the given (type, value) extensions
+fn token_2022_mint(extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut data = vec![0u8; 165];
+    data[44] = 6;
+    data[45] = 1;
+    data.push(1);
+    for (kind, value) in extensions {
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        data.extend_from_slice(value);
+    }
+    data
+}
+
+#[test]
+fn test_token_2022_mint_extensions() -> Result<()> {
+    let mint = Pubkey::new_unique();
+    let spl = MintInfo::parse(&mint, &dex::programs::TOKEN_PROGRAM_ID, &[6u8; 82])?;
+    assert_eq!(spl.program, TokenProgram::Spl);
+    assert!(spl.extensions().is_empty());
+
+    let schedule = |epoch: u64, maximum_fee: u64, basis_points: u16| {
+        let mut bytes = epoch.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&maximum_fee.to_le_bytes());
+        bytes.extend_from_slice(&basis_points.to_le_bytes());
+        bytes
+    };
+    let mut fee_config = vec![0u8; 72];
+    fee_config.extend(schedule(0, 5_000, 100));
+    fee_config.extend(schedule(10, 1_000_000, 50));
+    let hook_program = Pubkey::new_unique();
+    let mut hook = vec![0u8; 32];
+    hook.extend_from_slice(hook_program.as_ref());
+    let data = token_2022_mint(&[(1, fee_config), (14, hook)]);
+
+    let info = MintInfo::parse(&mint, &dex::programs::TOKEN_2022_PROGRAM_ID, &data)?;
+    assert_eq!((info.program, info.decimals), (TokenProgram::Token2022, 6));
+    assert_eq!(info.transfer_hook, Some(hook_program));
+    assert_eq!(info.extensions(), vec!["transfer fee", "transfer hook"]);
+    info.check_tradable()?;
+
+    let config = info.transfer_fee.unwrap();
+    let fee = config.at_epoch(5);
+    assert_eq!(
+        fee,
+        TransferFee {
+            epoch: 0,
+            maximum_fee: 5_000,
+            basis_points: 100
+        }
+    );
+    assert_eq!(config.at_epoch(10).basis_points, 50);
+    assert_eq!((fee.fee(1_000), fee.net(1_000)), (10, 990));
+    // Rounded up, and capped at the maximum fee
+    assert_eq!(fee.fee(1_001), 11);
+    assert_eq!(fee.fee(10_000_000), 5_000);
+    assert_eq!(fee.gross(990)?, 1_000);
+    assert_eq!(fee.gross(9_995_000)?, 10_000_000);
+    for net in [1, 99, 990, 123_456] {
+        assert!(fee.net(fee.gross(net)?) >= net);
+    }
+
+    let fees = SwapTransferFees {
+        input: Some(fee),
+        output: Some(fee),
+    };
+    assert_eq!(fees.pool_input(1_000), 990);
+    assert_eq!(fees.received(1_000), 990);
+    assert_eq!(fees.pool_min_output(990)?, 1_000);
+    assert_eq!(SwapTransferFees::default().pool_min_output(990)?, 990);
+
+    let interest_bearing = token_2022_mint(&[(10, vec![0u8; 52])]);
+    let info = MintInfo::parse(
+        &mint,
+        &dex::programs::TOKEN_2022_PROGRAM_ID,
+        &interest_bearing,
+    )?;
+    assert!(info.interest_bearing);
+    assert!(info.check_tradable().is_err());
+    let soulbound = token_2022_mint(&[(9, Vec::new())]);
+    let info = MintInfo::parse(&mint, &dex::programs::TOKEN_2022_PROGRAM_ID, &soulbound)?;
+    assert!(info.check_tradable().is_err());
+
+    assert!(MintInfo::parse(&mint, &Pubkey::new_unique(), &data).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_wallet_accounts_wrap_and_sweep_filter() -> Result<()> {
+    use dex::programs::{ASSOCIATED_TOKEN_PROGRAM_ID, NATIVE_MINT, TOKEN_PROGRAM_ID};
+    use solana_sdk::instruction::Instruction;
+
+    let owner = Pubkey::new_unique();
+    let usdc = TokenRegistry::bundled()?.mint("USDC")?;
+    let swap = Instruction::new_with_bytes(Pubkey::new_unique(), &[], Vec::new());
+    let programs = |instructions: &[Instruction]| {
+        instructions
+            .iter()
+            .map(|instruction| instruction.program_id)
+            .collect::<Vec<_>>()
+    };
+
+    // Native SOL is wrapped before the swap and unwrapped after it
+    let mut accounts = dex::WalletAccounts::new(owner, dex::SolBalance::Native);
+    let source = accounts.spend(&NATIVE_MINT, &TOKEN_PROGRAM_ID, 5_000);
+    accounts.receive(&usdc, &TOKEN_PROGRAM_ID);
+    accounts.receive(&usdc, &TOKEN_PROGRAM_ID);
+    let instructions = accounts.build([swap.clone()]);
+    assert_eq!(
+        programs(&instructions),
+        vec![
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+            solana_sdk::system_program::id(),
+            TOKEN_PROGRAM_ID,
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+            swap.program_id,
+            TOKEN_PROGRAM_ID,
+        ]
+    );
+    assert_eq!(instructions[2].data, vec![17]);
+    let close = instructions.last().unwrap();
+    assert_eq!(close.data, vec![9]);
+    assert_eq!(close.accounts[0].pubkey, source);
+    assert_eq!(close.accounts[1].pubkey, owner);
+
+    // Between rebalance steps wrapped SOL stays wrapped
+    let mut accounts = dex::WalletAccounts::new(owner, dex::SolBalance::Wrapped);
+    accounts.spend(&NATIVE_MINT, &TOKEN_PROGRAM_ID, 5_000);
+    accounts.receive(&usdc, &TOKEN_PROGRAM_ID);
+    assert_eq!(
+        programs(&accounts.build([swap.clone()])),
+        vec![
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+            swap.program_id,
+        ]
+    );
+
+    let empty = json!({
+        "mint": usdc.to_string(),
+        "owner": owner.to_string(),
+        "state": "initialized",
+        "tokenAmount": { "amount": "0", "decimals": 6 },
+    });
+    assert!(dex::accounts::is_closable(&empty, &owner));
+    let mut funded = empty.clone();
+    funded["tokenAmount"]["amount"] = json!("1");
+    assert!(!dex::accounts::is_closable(&funded, &owner));
+    let mut frozen = empty.clone();
+    frozen["state"] = json!("frozen");
+    assert!(!dex::accounts::is_closable(&frozen, &owner));
+    let mut delegated_close = empty.clone();
+    delegated_close["closeAuthority"] = json!(Pubkey::new_unique().to_string());
+    assert!(!dex::accounts::is_closable(&delegated_close, &owner));
+    let mut withheld = empty;
+    withheld["extensions"] = json!([
+        { "extension": "transferFeeAmount", "state": { "withheldAmount": 12 } }
+    ]);
+    assert!(!dex::accounts::is_closable(&withheld, &owner));
+    Ok(())
+}
+
+fn test_trading_params() -> crate::config::TradingParams {
+    tokio::sync::watch::channel(crate::config::TradingConfig {
+        min_trade_size: 0.1,
+        max_trade_size: 10.0,
+        price_impact_threshold: 0.01,
+        slippage_tolerance: 0.005,
+        rebalance_threshold: 0.05,
+    })
+    .1
+}
+
+#[test]
+fn test_withdraw_creates_wallet_accounts() -> Result<()> {
+    use dex::programs::{ASSOCIATED_TOKEN_PROGRAM_ID, NATIVE_MINT, TOKEN_PROGRAM_ID};
+
+    let whirlpool =
+        orca::Whirlpool::decode(&fixture_account(ORCA_WHIRLPOOL_SOL_USDC, "whirlpool")?)?;
+    let position = orca::Position::decode(&fixture_account(ORCA_WHIRLPOOL_SOL_USDC, "position")?)?;
+    let pool = orca::Pool {
+        address: position.whirlpool,
+        whirlpool,
+        decimals_a: 9,
+        decimals_b: 6,
+    };
+
+    let rpc_client = Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
+        "http://127.0.0.1:8899".to_string(),
+    ));
+    let sender = Arc::new(TransactionSender::new(
+        rpc_client.clone(),
+        &TransactionConfig::default(),
+        SwapPrograms::default(),
+        PendingTransactions::new(),
+    ));
+    let config: crate::config::OrcaConfig = serde_json::from_value(json!({
+        "rebalance_journal_path": std::env::temp_dir().join("solana_dex_bot_withdraw_journal.json"),
+    }))?;
+    let wallet = Arc::new(Keypair::new());
+    let owner = wallet.pubkey();
+    let client = dex::OrcaClient::new(
+        rpc_client,
+        sender,
+        &config,
+        test_trading_params(),
+        wallet,
+        quote::PoolCache::new(),
+    )?;
+
+    // The wallet may have closed its wSOL and token accounts since the
+    // position was opened, so the withdrawal recreates them first
+    let created = |instructions: &[solana_sdk::instruction::Instruction]| {
+        instructions
+            .iter()
+            .filter(|instruction| instruction.program_id == ASSOCIATED_TOKEN_PROGRAM_ID)
+            .map(|instruction| instruction.accounts[3].pubkey)
+            .collect::<Vec<_>>()
+    };
+    let wsol_account =
+        dex::programs::associated_token_address(&owner, &NATIVE_MINT, &TOKEN_PROGRAM_ID);
+    let instructions =
+        client.withdraw_instructions(&pool, &position, 0.01, dex::SolBalance::Native);
+    let mints = created(&instructions);
+    assert!(mints.contains(&pool.whirlpool.token_mint_a));
+    assert!(mints.contains(&pool.whirlpool.token_mint_b));
+    assert!(mints.contains(&pool.whirlpool.rewards[0].mint));
+    assert_eq!(instructions[0].program_id, ASSOCIATED_TOKEN_PROGRAM_ID);
+    let close = instructions.last().unwrap();
+    assert_eq!(
+        (close.program_id, close.data.clone()),
+        (TOKEN_PROGRAM_ID, vec![9])
+    );
+    assert_eq!(close.accounts[0].pubkey, wsol_account);
+
+    // Rebalances measure what arrived, so SOL is left wrapped
+    let instructions =
+        client.withdraw_instructions(&pool, &position, 0.01, dex::SolBalance::Wrapped);
+    assert!(created(&instructions).contains(&NATIVE_MINT));
+    assert_ne!(instructions.last().unwrap().program_id, TOKEN_PROGRAM_ID);
+    Ok(())
+}
+
+#[test]
+fn test_offline_quote_engine() -> Result<()> {
+    let tokens = TokenRegistry::bundled()?;
+    let (sol, usdc) = (tokens.mint("SOL")?, tokens.mint("USDC")?);
+
+    // 250k SOL against 37.5M USDC with a 0.25% fee
+    let amm = AmmInfo::decode(&fixture_account(RAYDIUM_SOL_USDC, "pool")?)?;
+    let info = amm.pool_info(
+        dex::layout::token_account_amount(&fixture_account(RAYDIUM_SOL_USDC, "base_vault")?)?,
+        dex::layout::token_account_amount(&fixture_account(RAYDIUM_SOL_USDC, "quote_vault")?)?,
+    )?;
+    let constant_product = quote::ConstantProduct::from_pool_info(&info);
+    let sell = constant_product.exact_in(1_000_000_000, true)?;
+    assert_eq!(sell.fee_amount, 2_500_000);
+    assert!(sell.amount_out > 149_600_000 && sell.amount_out < 149_625_000);
+    assert!(sell.price_impact > 0.0025 && sell.price_impact < 0.0026);
+    // Exact-out inverts exact-in to within rounding
+    let buy = constant_product.exact_out(sell.amount_out, true)?;
+    assert!(buy.amount_in <= 1_000_000_000 && buy.amount_in > 999_999_990);
+    assert!(constant_product.exact_in(buy.amount_in, true)?.amount_out >= sell.amount_out);
+    assert!(constant_product
+        .exact_out(info.reserve_b.base_units, true)
+        .is_err());
+
+    // The whirlpool's liquidity runs out at the position's lower tick
+    let whirlpool =
+        orca::Whirlpool::decode(&fixture_account(ORCA_WHIRLPOOL_SOL_USDC, "whirlpool")?)?;
+    let tick_array = orca::TickArray::decode(
+        &fixture_account(ORCA_WHIRLPOOL_SOL_USDC, "tick_array")?,
+        whirlpool.tick_spacing,
+    )?;
+    let concentrated = quote::ConcentratedLiquidity::from_whirlpool(&whirlpool, &[tick_array]);
+    let sell = concentrated.exact_in(1_000_000_000, true)?;
+    let buy = concentrated.exact_out(sell.amount_out, true)?;
+    assert!(buy.amount_in.abs_diff(1_000_000_000) < 1_000);
+    assert!(buy.end_price < concentrated.sqrt_price * concentrated.sqrt_price);
+    assert!(concentrated.exact_out(100_000_000_000_000, true).is_err());
+
+    // A Raydium CLMM pool quoted from its tick arrays and fee tier, with all
+    // of its liquidity in the fixture position's range
+    let clmm_pool = clmm::PoolState::decode(&fixture_account(RAYDIUM_CLMM_SOL_USDC, "pool")?)?;
+    let liquidity = clmm_pool.liquidity as i128;
+    let tick_array = |start: i32, ticks: &[(i32, i128)]| {
+        let mut data = vec![0u8; clmm::TICK_ARRAY_LEN];
+        data[..8].copy_from_slice(&dex::layout::anchor_discriminator("account:TickArrayState"));
+        data[40..44].copy_from_slice(&start.to_le_bytes());
+        for (tick, liquidity_net) in ticks {
+            let offset = 44 + ((tick - start) / 10) as usize * 168;
+            data[offset..offset + 4].copy_from_slice(&tick.to_le_bytes());
+            data[offset + 4..offset + 20].copy_from_slice(&liquidity_net.to_le_bytes());
+            data[offset + 20..offset + 36]
+                .copy_from_slice(&liquidity_net.unsigned_abs().to_le_bytes());
+        }
+        clmm::TickArrayState::decode(&data)
+    };
+    let tick_arrays = [
+        tick_array(-21600, &[(-21210, liquidity)])?,
+        tick_array(-17400, &[(-17140, -liquidity)])?,
+    ];
+    assert_eq!(tick_arrays[0].ticks, vec![(-21210, liquidity)]);
+    let mut amm_config = vec![0u8; 117];
+    amm_config[..8].copy_from_slice(&dex::layout::anchor_discriminator("account:AmmConfig"));
+    amm_config[47..51].copy_from_slice(&2_500u32.to_le_bytes());
+    let fee_rate = clmm::AmmConfig::decode(&amm_config)?.fee_rate();
+    assert_eq!(fee_rate, 0.0025);
+    let clmm_curve = quote::ConcentratedLiquidity::from_clmm(&clmm_pool, fee_rate, &tick_arrays);
+    let clmm_sell = clmm_curve.exact_in(1_000_000_000, true)?;
+    assert_eq!(clmm_sell.fee_amount, 2_500_000);
+    assert!(clmm_sell.amount_out > 149_500_000 && clmm_sell.amount_out < 149_625_000);
+    assert!(clmm_sell.price_impact > 0.0025 && clmm_sell.price_impact < 0.003);
+    assert!(clmm_curve.exact_in(10_000_000_000_000, true).is_err());
+
+    // The deeper constant-product pool wins, and a tight threshold rejects it
+    let pools = dex::PoolCache::new();
+    let (raydium_pool, orca_pool) = (Pubkey::new_unique(), Pubkey::new_unique());
+    pools.update(
+        dex::RAYDIUM,
+        raydium_pool,
+        sol,
+        usdc,
+        dex::SwapCurve::ConstantProduct(constant_product),
+    );
+    pools.update(
+        dex::ORCA,
+        orca_pool,
+        sol,
+        usdc,
+        dex::SwapCurve::Concentrated(concentrated),
+    );
+    assert_eq!(pools.quotes_exact_in(&sol, &usdc, 1_000_000_000).len(), 2);
+    let best = pools.best_exact_in(&sol, &usdc, 1_000_000_000).unwrap();
+    assert_eq!(
+        (best.venue.as_str(), best.pool),
+        (dex::RAYDIUM, raydium_pool)
+    );
+    assert!(dex::check_price_impact(best.quote.price_impact, 0.01).is_ok());
+    assert!(dex::check_price_impact(best.quote.price_impact, 0.001).is_err());
+    let best = pools.best_exact_out(&usdc, &sol, 1_000_000_000).unwrap();
+    assert!(best.quote.amount_in > 150_000_000);
+    assert!(pools
+        .best_exact_in(&sol, &Pubkey::new_unique(), 1)
+        .is_none());
+
+    // Venues quote the pool they swap through, in either direction
+    let clmm_address = Pubkey::new_unique();
+    pools.update(
+        dex::RAYDIUM,
+        clmm_address,
+        sol,
+        usdc,
+        dex::SwapCurve::Concentrated(clmm_curve),
+    );
+    assert_eq!(
+        pools.quote_exact_in(&clmm_address, &sol, &usdc, 1_000_000_000),
+        Some(clmm_sell)
+    );
+    assert!(pools
+        .quote_exact_in(&clmm_address, &usdc, &sol, 150_000_000)
+        .is_some_and(|quote| quote.amount_out < 1_000_000_000));
+    assert!(pools
+        .quote_exact_in(&clmm_address, &sol, &usdc, 10_000_000_000_000)
+        .is_none());
+    assert!(pools
+        .quote_exact_in(&Pubkey::new_unique(), &sol, &usdc, 1_000_000_000)
+        .is_none());
+    Ok(())
+}
+
+#[test]
+fn test_router_splits_to_maximise_output() -> Result<()> {
+    let amount_in = 1_000_000_000_000;
+    let quotes = |venue: &str, pool: &quote::ConstantProduct, split_parts: u32| {
+        let estimates = (1..=split_parts)
+            .map(|k| {
+                let quote = pool
+                    .exact_in(dex::split_amount(amount_in, k, split_parts), true)
+                    .ok()?;
+                Some(dex::SwapEstimate {
+                    amount_in: TokenAmount::new(quote.amount_in, 9),
+                    amount_out: TokenAmount::new(quote.amount_out, 6),
+                    price_impact: quote.price_impact,
+                })
+            })
+            .collect::<Vec<_>>();
+        (venue.to_string(), estimates)
+    };
+    let pool = |reserve_a: u64, fee_rate: f64| quote::ConstantProduct {
+        reserve_a,
+        reserve_b: reserve_a / 1_000 * 150,
+        fee_rate,
+    };
+
+    // Two equally deep pools share the order evenly
+    let (raydium, orca) = (
+        pool(10_000_000_000_000, 0.0025),
+        pool(10_000_000_000_000, 0.0025),
+    );
+    let plan = dex::plan_route(
+        4,
+        &[quotes("raydium", &raydium, 4), quotes("orca", &orca, 4)],
+    )
+    .unwrap();
+    assert_eq!(
+        plan.legs
+            .iter()
+            .map(|leg| (leg.venue.as_str(), leg.parts))
+            .collect::<Vec<_>>(),
+        vec![("raydium", 2), ("orca", 2)]
+    );
+    let single = raydium.exact_in(amount_in, true)?;
+    assert!(plan.amount_out() > single.amount_out);
+    assert!(plan.price_impact() < single.price_impact);
+
+    // A much deeper pool takes the whole order, as does any pool when splitting is off
+    let deep = pool(1_000_000_000_000_000, 0.0025);
+    let plan = dex::plan_route(
+        4,
+        &[quotes("raydium", &raydium, 4), quotes("orca", &deep, 4)],
+    )
+    .unwrap();
+    assert_eq!(plan.legs.len(), 1);
+    assert_eq!(
+        (plan.legs[0].venue.as_str(), plan.legs[0].parts),
+        ("orca", 4)
+    );
+    let plan = dex::plan_route(
+        1,
+        &[quotes("raydium", &raydium, 1), quotes("orca", &orca, 1)],
+    )
+    .unwrap();
+    assert_eq!(plan.legs.len(), 1);
+
+    // Venues that cannot quote are skipped; with none left there is no route
+    let failed = ("jupiter".to_string(), vec![None; 4]);
+    let plan = dex::plan_route(4, &[failed.clone(), quotes("orca", &orca, 4)]).unwrap();
+    assert_eq!(plan.legs[0].venue, "orca");
+    assert!(dex::plan_route(4, &[failed]).is_none());
+
+    // An output matched by fewer legs is routed through fewer venues
+    let flat = |venue: &str, quoted: u32| {
+        let estimates = (1..=4)
+            .map(|k| {
+                (k <= quoted).then(|| dex::SwapEstimate {
+                    amount_in: TokenAmount::new(dex::split_amount(amount_in, k, 4), 9),
+                    amount_out: TokenAmount::new(k as u64 * 1_000_000, 6),
+                    price_impact: 0.0,
+                })
+            })
+            .collect::<Vec<_>>();
+        (venue.to_string(), estimates)
+    };
+    let plan = dex::plan_route(
+        4,
+        &[flat("raydium", 2), flat("orca", 2), flat("meteora", 4)],
+    )
+    .unwrap();
+    assert_eq!(
+        plan.legs
+            .iter()
+            .map(|leg| (leg.venue.as_str(), leg.parts))
+            .collect::<Vec<_>>(),
+        vec![("meteora", 4)]
+    );
+    Ok(())
+}
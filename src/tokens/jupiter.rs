@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tracing::debug;
+
+use super::{Token, TokenProgram};
+
+// An entry of the Jupiter token list. The v2 API calls the mint `id` and
+// names the token program; the older list calls it `address` and does not.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListedToken {
+    #[serde(alias = "address")]
+    id: String,
+    symbol: String,
+    #[serde(default)]
+    name: String,
+    decimals: u8,
+    #[serde(default)]
+    token_program: Option<String>,
+}
+
+pub async fn fetch_token_list(url: &str) -> Result<Vec<Token>> {
+    let response = reqwest::get(url).await?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "token list request failed with status {}",
+            response.status()
+        ));
+    }
+    let listed: Vec<ListedToken> = response.json().await?;
+    Ok(listed.into_iter().filter_map(to_token).collect())
+}
+
+// Entries with a malformed mint or an unknown token program are skipped
+fn to_token(listed: ListedToken) -> Option<Token> {
+    let mint = Pubkey::from_str(&listed.id).ok()?;
+    let program = match listed.token_program.as_deref() {
+        None => TokenProgram::Spl,
+        Some(program) => match Pubkey::from_str(program)
+            .ok()
+            .and_then(|id| TokenProgram::from_owner(&id))
+        {
+            Some(program) => program,
+            None => {
+                debug!("Skipping token {} owned by {}", listed.id, program);
+                return None;
+            }
+        },
+    };
+    Some(Token {
+        symbol: listed.symbol.to_uppercase(),
+        name: listed.name,
+        mint,
+        decimals: listed.decimals,
+        program,
+        pyth_feed_id: None,
+        switchboard_feed: None,
+        cex_symbols: BTreeMap::new(),
+    })
+}
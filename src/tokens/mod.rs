@@ -0,0 +1,286 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::cex;
+use crate::config::TokensConfig;
+use crate::dex::programs::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+mod jupiter;
+
+const BUNDLED_TOKENS: &str = include_str!("tokens.json");
+
+// Quote asset oracle symbols are priced in, as in "SOL/USD"
+pub const ORACLE_QUOTE: &str = "USD";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenProgram {
+    #[default]
+    Spl,
+    Token2022,
+}
+
+impl TokenProgram {
+    pub fn id(&self) -> Pubkey {
+        match self {
+            TokenProgram::Spl => TOKEN_PROGRAM_ID,
+            TokenProgram::Token2022 => TOKEN_2022_PROGRAM_ID,
+        }
+    }
+
+    // The program owning a mint account
+    pub fn from_owner(owner: &Pubkey) -> Option<Self> {
+        if *owner == TOKEN_PROGRAM_ID {
+            Some(TokenProgram::Spl)
+        } else if *owner == TOKEN_2022_PROGRAM_ID {
+            Some(TokenProgram::Token2022)
+        } else {
+            None
+        }
+    }
+}
+
+// One canonical asset and how each venue refers to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub symbol: String,
+    pub name: String,
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub program: TokenProgram,
+    // Hex id of the Pyth <symbol>/USD price feed
+    pub pyth_feed_id: Option<String>,
+    // Switchboard <symbol>/USD aggregator account
+    pub switchboard_feed: Option<Pubkey>,
+    // Asset code on each CEX, keyed by venue; venues left out do not list it
+    pub cex_symbols: BTreeMap<String, String>,
+}
+
+impl Token {
+    pub fn cex_symbol(&self, venue: &str) -> Option<&str> {
+        self.cex_symbols.get(venue).map(String::as_str)
+    }
+
+    // Symbol of the USD price on the oracles, e.g. "SOL/USD"
+    pub fn oracle_symbol(&self) -> String {
+        format!("{}/{}", self.symbol, ORACLE_QUOTE)
+    }
+}
+
+// Format of the bundled and configured token files
+#[derive(Debug, Clone, Deserialize)]
+struct TokenEntry {
+    symbol: String,
+    #[serde(default)]
+    name: String,
+    mint: String,
+    decimals: u8,
+    #[serde(default)]
+    program: TokenProgram,
+    #[serde(default)]
+    pyth_feed_id: Option<String>,
+    #[serde(default)]
+    switchboard_feed: Option<String>,
+    #[serde(default)]
+    cex_symbols: BTreeMap<String, String>,
+}
+
+impl TokenEntry {
+    fn into_token(self) -> Result<Token> {
+        let mint = Pubkey::from_str(&self.mint)
+            .with_context(|| format!("token {}: invalid mint {:?}", self.symbol, self.mint))?;
+        let switchboard_feed = self
+            .switchboard_feed
+            .map(|feed| {
+                Pubkey::from_str(&feed).with_context(|| {
+                    format!("token {}: invalid switchboard feed {:?}", self.symbol, feed)
+                })
+            })
+            .transpose()?;
+        Ok(Token {
+            symbol: self.symbol.to_uppercase(),
+            name: self.name,
+            mint,
+            decimals: self.decimals,
+            program: self.program,
+            pyth_feed_id: self
+                .pyth_feed_id
+                .map(|id| id.trim_start_matches("0x").to_lowercase()),
+            switchboard_feed,
+            cex_symbols: self.cex_symbols,
+        })
+    }
+}
+
+// Maps canonical symbols ("SOL", "USDC") to mints, decimals, oracle feeds and
+// CEX symbols, so DEX, CEX and oracle code all refer to assets the same way.
+// Curated entries come first and win over the Jupiter token list, which only
+// fills in mints they do not cover.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: Vec<Token>,
+    by_symbol: HashMap<String, usize>,
+    by_mint: HashMap<Pubkey, usize>,
+}
+
+impl TokenRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Only the tokens shipped with the bot
+    pub fn bundled() -> Result<Self> {
+        let mut registry = Self::new();
+        registry.extend_from_json(BUNDLED_TOKENS)?;
+        Ok(registry)
+    }
+
+    // The bundled tokens, then `tokens.path`, then the Jupiter token list.
+    // A token list that cannot be fetched is logged and skipped.
+    pub async fn load(config: &TokensConfig) -> Result<Self> {
+        let mut registry = Self::bundled()?;
+        if let Some(path) = &config.path {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read token file {}", path.display()))?;
+            registry
+                .extend_from_json(&json)
+                .with_context(|| format!("invalid token file {}", path.display()))?;
+        }
+        if config.jupiter_token_list {
+            match jupiter::fetch_token_list(&config.jupiter_token_list_url).await {
+                Ok(tokens) => {
+                    let added = registry.extend_listed(tokens);
+                    info!("Added {} tokens from the Jupiter token list", added);
+                }
+                Err(e) => warn!("Failed to fetch the Jupiter token list: {:#}", e),
+            }
+        }
+        Ok(registry)
+    }
+
+    // Curated entries replace any earlier token with the same mint or symbol
+    pub fn extend_from_json(&mut self, json: &str) -> Result<()> {
+        let entries: Vec<TokenEntry> = serde_json::from_str(json)?;
+        for entry in entries {
+            self.upsert(entry.into_token()?);
+        }
+        Ok(())
+    }
+
+    // Adds tokens whose mints are not known yet. A listed symbol already
+    // taken by another mint only resolves by mint, so a copycat token can
+    // never shadow a curated one.
+    pub fn extend_listed(&mut self, tokens: impl IntoIterator<Item = Token>) -> usize {
+        let mut added = 0;
+        for token in tokens {
+            if self.by_mint.contains_key(&token.mint) {
+                continue;
+            }
+            let index = self.tokens.len();
+            self.by_mint.insert(token.mint, index);
+            self.by_symbol
+                .entry(token.symbol.to_uppercase())
+                .or_insert(index);
+            self.tokens.push(token);
+            added += 1;
+        }
+        added
+    }
+
+    fn upsert(&mut self, token: Token) {
+        let symbol = token.symbol.to_uppercase();
+        let existing = self
+            .by_mint
+            .get(&token.mint)
+            .or_else(|| self.by_symbol.get(&symbol))
+            .copied();
+        match existing {
+            Some(index) => {
+                let old = std::mem::replace(&mut self.tokens[index], token);
+                self.by_mint.remove(&old.mint);
+                let old_symbol = old.symbol.to_uppercase();
+                if self.by_symbol.get(&old_symbol) == Some(&index) {
+                    self.by_symbol.remove(&old_symbol);
+                }
+                self.by_mint.insert(self.tokens[index].mint, index);
+                self.by_symbol.insert(symbol, index);
+            }
+            None => {
+                let index = self.tokens.len();
+                self.by_mint.insert(token.mint, index);
+                self.by_symbol.insert(symbol, index);
+                self.tokens.push(token);
+            }
+        }
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Token> {
+        self.by_symbol
+            .get(&symbol.to_uppercase())
+            .map(|&index| &self.tokens[index])
+    }
+
+    pub fn require(&self, symbol: &str) -> Result<&Token> {
+        self.get(symbol)
+            .ok_or_else(|| anyhow!("unknown token {}", symbol))
+    }
+
+    pub fn by_mint(&self, mint: &Pubkey) -> Option<&Token> {
+        self.by_mint.get(mint).map(|&index| &self.tokens[index])
+    }
+
+    pub fn mint(&self, symbol: &str) -> Result<Pubkey> {
+        Ok(self.require(symbol)?.mint)
+    }
+
+    // Accepts either a mint address or a known symbol
+    pub fn resolve_mint(&self, token: &str) -> Result<Pubkey> {
+        match Pubkey::from_str(token) {
+            Ok(mint) => Ok(mint),
+            Err(_) => self.mint(token),
+        }
+    }
+
+    // Symbol for display, or the mint address unless the symbol resolves
+    // back to this mint
+    pub fn label(&self, mint: &Pubkey) -> String {
+        match self.by_mint(mint) {
+            Some(token) if self.get(&token.symbol).map(|t| t.mint) == Some(*mint) => {
+                token.symbol.clone()
+            }
+            _ => mint.to_string(),
+        }
+    }
+
+    // Trading pair symbol on a CEX, e.g. "SOLUSDT" on Binance or "SOL-USDT"
+    // on OKX
+    pub fn cex_instrument(&self, venue: &str, base: &str, quote: &str) -> Result<String> {
+        let code = |symbol: &str| {
+            self.require(symbol)?
+                .cex_symbol(venue)
+                .ok_or_else(|| anyhow!("token {} is not listed on {}", symbol, venue))
+        };
+        let (base, quote) = (code(base)?, code(quote)?);
+        match venue {
+            cex::OKX => Ok(format!("{}-{}", base, quote)),
+            cex::BINANCE | cex::BYBIT => Ok(format!("{}{}", base, quote)),
+            _ => Err(anyhow!("unknown CEX venue {}", venue)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Token> {
+        self.tokens.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
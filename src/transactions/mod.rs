@@ -0,0 +1,140 @@
+use anyhow::Result;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+mod preflight;
+mod priority_fee;
+mod sender;
+
+pub use preflight::{
+    decode_error, parse_logs, ProgramLog, Simulation, SwapPrograms, TransactionFailure,
+};
+pub use priority_fee::{writable_accounts, PriorityFeeEstimator};
+pub use sender::{PreparedTransaction, TransactionOutcome, TransactionSender};
+
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingTransaction {
+    pub signature: String,
+    pub description: String,
+    // The transaction can no longer land once the chain passes this height
+    pub last_valid_block_height: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingOutcome {
+    Confirmed,
+    Failed,
+    Expired,
+    TimedOut,
+}
+
+// Transactions that have been sent but not confirmed yet
+#[derive(Clone, Default)]
+pub struct PendingTransactions {
+    pending: Arc<Mutex<HashMap<Signature, PendingTransaction>>>,
+}
+
+impl PendingTransactions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(&self, signature: Signature, description: &str, last_valid_block_height: u64) {
+        self.pending.lock().unwrap().insert(
+            signature,
+            PendingTransaction {
+                signature: signature.to_string(),
+                description: description.to_string(),
+                last_valid_block_height,
+            },
+        );
+    }
+
+    pub fn complete(&self, signature: &Signature) {
+        self.pending.lock().unwrap().remove(signature);
+    }
+
+    pub fn snapshot(&self) -> Vec<PendingTransaction> {
+        self.pending.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.lock().unwrap().is_empty()
+    }
+
+    // Waits until every pending transaction is confirmed, failed or past its
+    // last valid block height, or until `timeout` elapses.
+    pub async fn wait_all(
+        &self,
+        rpc_client: &RpcClient,
+        timeout: Duration,
+    ) -> Result<Vec<(PendingTransaction, PendingOutcome)>> {
+        let deadline = Instant::now() + timeout;
+        let mut outcomes = Vec::new();
+
+        while !self.is_empty() {
+            let pending: Vec<(Signature, PendingTransaction)> = self
+                .pending
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(signature, tx)| (*signature, tx.clone()))
+                .collect();
+
+            if Instant::now() >= deadline {
+                for (signature, tx) in pending {
+                    warn!(
+                        "Gave up waiting for transaction {} ({})",
+                        tx.signature, tx.description
+                    );
+                    self.complete(&signature);
+                    outcomes.push((tx, PendingOutcome::TimedOut));
+                }
+                break;
+            }
+
+            let signatures: Vec<Signature> =
+                pending.iter().map(|(signature, _)| *signature).collect();
+            let statuses = rpc_client.get_signature_statuses(&signatures).await?.value;
+            let block_height = rpc_client
+                .get_block_height_with_commitment(CommitmentConfig::confirmed())
+                .await?;
+
+            for ((signature, tx), status) in pending.into_iter().zip(statuses) {
+                let outcome = match status {
+                    Some(status) if status.err.is_some() => Some(PendingOutcome::Failed),
+                    Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => {
+                        Some(PendingOutcome::Confirmed)
+                    }
+                    None if block_height > tx.last_valid_block_height => {
+                        Some(PendingOutcome::Expired)
+                    }
+                    _ => None,
+                };
+                if let Some(outcome) = outcome {
+                    info!(
+                        "Transaction {} ({}): {:?}",
+                        tx.signature, tx.description, outcome
+                    );
+                    self.complete(&signature);
+                    outcomes.push((tx, outcome));
+                }
+            }
+
+            if !self.is_empty() {
+                tokio::time::sleep(STATUS_POLL_INTERVAL).await;
+            }
+        }
+
+        Ok(outcomes)
+    }
+}
@@ -0,0 +1,316 @@
+use anyhow::Result;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+use solana_sdk::transaction::TransactionError;
+use std::collections::HashMap;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::config::DexConfig;
+use crate::dex::programs::{JUPITER_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+// Anchor framework errors, shared by every Anchor program
+const ANCHOR_ACCOUNT_DISCRIMINATOR_NOT_FOUND: u32 = 3001;
+const ANCHOR_ACCOUNT_NOT_INITIALIZED: u32 = 3012;
+
+// SPL token errors
+const TOKEN_INSUFFICIENT_FUNDS: u32 = 1;
+const TOKEN_UNINITIALIZED_STATE: u32 = 9;
+
+// System program ResultWithNegativeLamports
+const SYSTEM_INSUFFICIENT_LAMPORTS: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SwapProgram {
+    RaydiumAmm,
+    RaydiumClmm,
+    Whirlpool,
+    Dlmm,
+    Jupiter,
+}
+
+impl SwapProgram {
+    fn is_slippage_error(self, code: u32) -> bool {
+        match self {
+            // ExceededSlippage
+            Self::RaydiumAmm => code == 30,
+            // PriceSlippageCheck, TooLittleOutputReceived, TooMuchInputPaid
+            Self::RaydiumClmm => (6021..=6023).contains(&code),
+            // TokenMaxExceeded, TokenMinSubceeded, AmountOutBelowMinimum, AmountInAboveMaximum
+            Self::Whirlpool => matches!(code, 6017 | 6018 | 6036 | 6037),
+            // ExceededAmountSlippageTolerance, ExceededBinSlippageTolerance
+            Self::Dlmm => matches!(code, 6003 | 6004),
+            // SlippageToleranceExceeded
+            Self::Jupiter => code == 6001,
+        }
+    }
+}
+
+// The swap programs of the configured DEX venues by program id, so their
+// slippage errors are recognised whichever deployment a venue points at
+#[derive(Debug, Clone, Default)]
+pub struct SwapPrograms(HashMap<Pubkey, SwapProgram>);
+
+impl SwapPrograms {
+    pub fn from_config(config: &DexConfig) -> Result<Self> {
+        let mut programs = HashMap::new();
+        if let Some(raydium) = &config.raydium {
+            programs.insert(
+                Pubkey::from_str(&raydium.program_id)?,
+                SwapProgram::RaydiumAmm,
+            );
+            programs.insert(
+                Pubkey::from_str(&raydium.clmm_program_id)?,
+                SwapProgram::RaydiumClmm,
+            );
+        }
+        if let Some(orca) = &config.orca {
+            programs.insert(Pubkey::from_str(&orca.program_id)?, SwapProgram::Whirlpool);
+        }
+        if let Some(meteora) = &config.meteora {
+            programs.insert(Pubkey::from_str(&meteora.program_id)?, SwapProgram::Dlmm);
+        }
+        if config.jupiter.is_some() {
+            programs.insert(JUPITER_PROGRAM_ID, SwapProgram::Jupiter);
+        }
+        Ok(Self(programs))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TransactionFailure {
+    #[error("slippage exceeded in program {program_id} (error {code})")]
+    SlippageExceeded { program_id: Pubkey, code: u32 },
+    #[error("insufficient funds")]
+    InsufficientFunds,
+    #[error("account not initialized")]
+    AccountNotInitialized,
+    #[error("compute budget exceeded")]
+    ComputeBudgetExceeded,
+    #[error("program {program_id} failed with error {code} ({})", .name.as_deref().unwrap_or("unknown"))]
+    Program {
+        program_id: Pubkey,
+        code: u32,
+        name: Option<String>,
+    },
+    #[error("{0}")]
+    Other(TransactionError),
+}
+
+// One line of program log output, attributed to the program that wrote it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramLog {
+    Invoke {
+        program_id: Pubkey,
+        depth: usize,
+    },
+    Log {
+        program_id: Pubkey,
+        message: String,
+    },
+    Data {
+        program_id: Pubkey,
+        data: String,
+    },
+    Consumed {
+        program_id: Pubkey,
+        units: u64,
+        limit: u64,
+    },
+    Success {
+        program_id: Pubkey,
+    },
+    Failed {
+        program_id: Pubkey,
+        reason: String,
+    },
+    Other(String),
+}
+
+// What `simulateTransaction` says the transaction would do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<ProgramLog>,
+    pub error: Option<TransactionFailure>,
+}
+
+impl Simulation {
+    pub fn from_result(
+        result: RpcSimulateTransactionResult,
+        message: &VersionedMessage,
+        swap_programs: &SwapPrograms,
+    ) -> Self {
+        let logs = parse_logs(&result.logs.unwrap_or_default());
+        let program_ids: Vec<Pubkey> = message
+            .instructions()
+            .iter()
+            .map(|ix| *ix.program_id(message.static_account_keys()))
+            .collect();
+        Self {
+            units_consumed: result.units_consumed,
+            error: result
+                .err
+                .map(|error| decode_error(&error, &program_ids, &logs, swap_programs)),
+            logs,
+        }
+    }
+}
+
+pub fn parse_logs(logs: &[String]) -> Vec<ProgramLog> {
+    // Programs currently executing, innermost last
+    let mut stack: Vec<Pubkey> = Vec::new();
+    logs.iter()
+        .map(|line| parse_line(line, &mut stack).unwrap_or_else(|| ProgramLog::Other(line.clone())))
+        .collect()
+}
+
+fn parse_line(line: &str, stack: &mut Vec<Pubkey>) -> Option<ProgramLog> {
+    if let Some(message) = line.strip_prefix("Program log: ") {
+        return Some(ProgramLog::Log {
+            program_id: *stack.last()?,
+            message: message.to_string(),
+        });
+    }
+    if let Some(data) = line.strip_prefix("Program data: ") {
+        return Some(ProgramLog::Data {
+            program_id: *stack.last()?,
+            data: data.to_string(),
+        });
+    }
+
+    let (program_id, rest) = line.strip_prefix("Program ")?.split_once(' ')?;
+    let program_id = Pubkey::from_str(program_id).ok()?;
+    if let Some(depth) = rest.strip_prefix("invoke [") {
+        stack.push(program_id);
+        return Some(ProgramLog::Invoke {
+            program_id,
+            depth: depth.trim_end_matches(']').parse().ok()?,
+        });
+    }
+    if rest == "success" {
+        stack.pop();
+        return Some(ProgramLog::Success { program_id });
+    }
+    if let Some(reason) = rest.strip_prefix("failed: ") {
+        stack.pop();
+        return Some(ProgramLog::Failed {
+            program_id,
+            reason: reason.to_string(),
+        });
+    }
+    // "consumed 12345 of 200000 compute units"
+    let mut words = rest.strip_prefix("consumed ")?.split(' ');
+    let units = words.next()?.parse().ok()?;
+    let limit = words.nth(1)?.parse().ok()?;
+    Some(ProgramLog::Consumed {
+        program_id,
+        units,
+        limit,
+    })
+}
+
+// Maps a transaction error to what went wrong. Custom errors are attributed to
+// the innermost program that failed, since a swap's token transfer fails
+// inside the swap instruction.
+pub fn decode_error(
+    error: &TransactionError,
+    program_ids: &[Pubkey],
+    logs: &[ProgramLog],
+    swap_programs: &SwapPrograms,
+) -> TransactionFailure {
+    let (index, instruction_error) = match error {
+        TransactionError::InsufficientFundsForFee
+        | TransactionError::InsufficientFundsForRent { .. }
+        | TransactionError::AccountNotFound => return TransactionFailure::InsufficientFunds,
+        TransactionError::InstructionError(index, instruction_error) => {
+            (*index as usize, instruction_error)
+        }
+        _ => return TransactionFailure::Other(error.clone()),
+    };
+
+    match instruction_error {
+        InstructionError::Custom(code) => {
+            let program_id = logs
+                .iter()
+                .find_map(|log| match log {
+                    ProgramLog::Failed { program_id, .. } => Some(*program_id),
+                    _ => None,
+                })
+                .or_else(|| program_ids.get(index).copied());
+            match program_id {
+                Some(program_id) => decode_custom(program_id, *code, logs, swap_programs),
+                None => TransactionFailure::Other(error.clone()),
+            }
+        }
+        InstructionError::InsufficientFunds => TransactionFailure::InsufficientFunds,
+        InstructionError::UninitializedAccount => TransactionFailure::AccountNotInitialized,
+        InstructionError::ComputationalBudgetExceeded => TransactionFailure::ComputeBudgetExceeded,
+        InstructionError::ProgramFailedToComplete if exceeded_compute_units(logs) => {
+            TransactionFailure::ComputeBudgetExceeded
+        }
+        _ => TransactionFailure::Other(error.clone()),
+    }
+}
+
+fn decode_custom(
+    program_id: Pubkey,
+    code: u32,
+    logs: &[ProgramLog],
+    swap_programs: &SwapPrograms,
+) -> TransactionFailure {
+    let name = anchor_error_name(&program_id, logs);
+    if program_id == TOKEN_PROGRAM_ID || program_id == TOKEN_2022_PROGRAM_ID {
+        match code {
+            TOKEN_INSUFFICIENT_FUNDS => return TransactionFailure::InsufficientFunds,
+            TOKEN_UNINITIALIZED_STATE => return TransactionFailure::AccountNotInitialized,
+            _ => {}
+        }
+    } else if program_id == system_program::id() && code == SYSTEM_INSUFFICIENT_LAMPORTS {
+        return TransactionFailure::InsufficientFunds;
+    } else if matches!(
+        code,
+        ANCHOR_ACCOUNT_DISCRIMINATOR_NOT_FOUND | ANCHOR_ACCOUNT_NOT_INITIALIZED
+    ) {
+        return TransactionFailure::AccountNotInitialized;
+    }
+
+    let slippage = match swap_programs.0.get(&program_id) {
+        Some(program) => program.is_slippage_error(code),
+        None => name
+            .as_deref()
+            .is_some_and(|name| name.to_lowercase().contains("slippage")),
+    };
+    if slippage {
+        return TransactionFailure::SlippageExceeded { program_id, code };
+    }
+    TransactionFailure::Program {
+        program_id,
+        code,
+        name,
+    }
+}
+
+// Anchor logs "AnchorError ... Error Code: <name>. Error Number: ..." from the
+// failing program
+fn anchor_error_name(program_id: &Pubkey, logs: &[ProgramLog]) -> Option<String> {
+    logs.iter().find_map(|log| match log {
+        ProgramLog::Log {
+            program_id: author,
+            message,
+        } if author == program_id => {
+            let (_, rest) = message.split_once("Error Code: ")?;
+            Some(rest.split('.').next()?.to_string())
+        }
+        _ => None,
+    })
+}
+
+fn exceeded_compute_units(logs: &[ProgramLog]) -> bool {
+    logs.iter().any(|log| {
+        matches!(log, ProgramLog::Failed { reason, .. } if reason.contains("exceeded CUs meter"))
+    })
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+use crate::config::{PriorityFeeConfig, PriorityFeePolicy, Urgency};
+
+// getRecentPrioritizationFees takes at most this many accounts
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+// Prices compute units from recent prioritization fees. Each estimate asks the
+// node for the fees paid to write the transaction's accounts and prices from
+// the newest `window_slots` slots of that answer. Every answer is also folded
+// into a rolling model of the last `window_slots` slots, keeping the highest
+// fee seen for each slot, which stands in when the node has no fees for the
+// accounts or cannot be reached.
+pub struct PriorityFeeEstimator {
+    rpc_client: Arc<RpcClient>,
+    config: PriorityFeeConfig,
+    // Fee per slot
+    samples: Mutex<BTreeMap<u64, u64>>,
+}
+
+impl PriorityFeeEstimator {
+    pub fn new(rpc_client: Arc<RpcClient>, config: &PriorityFeeConfig) -> Self {
+        Self {
+            rpc_client,
+            config: config.clone(),
+            samples: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    // Micro-lamports per compute unit for a transaction writing `accounts`,
+    // at the configured urgency
+    pub async fn estimate(&self, accounts: &[Pubkey]) -> Result<u64> {
+        self.estimate_with_urgency(accounts, self.config.urgency)
+            .await
+    }
+
+    pub async fn estimate_with_urgency(
+        &self,
+        accounts: &[Pubkey],
+        urgency: Urgency,
+    ) -> Result<u64> {
+        if self.config.policy == PriorityFeePolicy::Fixed {
+            return Ok(self.config.micro_lamports);
+        }
+
+        let current = match self.refresh(accounts).await {
+            Ok(fees) => nearest_rank(fees, self.config.percentile),
+            Err(e) => {
+                warn!("Failed to fetch recent prioritization fees: {:#}", e);
+                None
+            }
+        };
+        let recent = match current.or_else(|| self.percentile(self.config.percentile)) {
+            Some(fee) => fee,
+            None => return Ok(self.config.micro_lamports),
+        };
+
+        let fee = match self.config.policy {
+            PriorityFeePolicy::Urgency => (recent as f64 * urgency.multiplier()).ceil() as u64,
+            _ => recent,
+        };
+        let fee = fee.min(self.config.max_micro_lamports);
+        debug!(
+            "Priority fee {} micro-lamports (p{} {}, {:?})",
+            fee, self.config.percentile, recent, urgency
+        );
+        Ok(fee)
+    }
+
+    // Fees paid in the newest `window_slots` slots the node reports for
+    // `accounts`, recorded into the rolling model as well
+    pub async fn refresh(&self, accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        let accounts = &accounts[..accounts.len().min(MAX_FEE_ACCOUNTS)];
+        let mut fees = self
+            .rpc_client
+            .get_recent_prioritization_fees(accounts)
+            .await?;
+        for fee in &fees {
+            self.record(fee.slot, fee.prioritization_fee);
+        }
+        fees.sort_unstable_by_key(|fee| fee.slot);
+        let newest = fees.len().saturating_sub(self.config.window_slots as usize);
+        Ok(fees[newest..]
+            .iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect())
+    }
+
+    pub fn record(&self, slot: u64, micro_lamports: u64) {
+        let mut samples = self.samples.lock().unwrap();
+        let fee = samples.entry(slot).or_insert(0);
+        *fee = (*fee).max(micro_lamports);
+
+        // Forget slots that fell out of the window
+        let newest = *samples.keys().next_back().unwrap();
+        let oldest = newest.saturating_sub(self.config.window_slots.saturating_sub(1));
+        *samples = samples.split_off(&oldest);
+    }
+
+    // Nearest-rank percentile of the fees in the rolling window
+    pub fn percentile(&self, percentile: u8) -> Option<u64> {
+        let fees = self.samples.lock().unwrap().values().copied().collect();
+        nearest_rank(fees, percentile)
+    }
+}
+
+fn nearest_rank(mut fees: Vec<u64>, percentile: u8) -> Option<u64> {
+    if fees.is_empty() {
+        return None;
+    }
+    fees.sort_unstable();
+    let rank = (percentile.min(100) as f64 / 100.0 * fees.len() as f64).ceil() as usize;
+    Some(fees[rank.saturating_sub(1)])
+}
+
+// The accounts a transaction locks for writing, which are what it competes
+// for with other transactions
+pub fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts = Vec::new();
+    for meta in instructions.iter().flat_map(|ix| &ix.accounts) {
+        if meta.is_writable && !accounts.contains(&meta.pubkey) {
+            accounts.push(meta.pubkey);
+        }
+    }
+    accounts
+}
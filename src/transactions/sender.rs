@@ -0,0 +1,373 @@
+use anyhow::{anyhow, Result};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig};
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, Message, VersionedMessage};
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+use super::priority_fee::{writable_accounts, PriorityFeeEstimator};
+use super::{PendingTransactions, Simulation, SwapPrograms, STATUS_POLL_INTERVAL};
+use crate::config::TransactionConfig;
+
+// Compute budget instruction tags
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+// A signed transaction and the block height after which it can no longer land
+#[derive(Debug, Clone)]
+pub struct PreparedTransaction {
+    pub transaction: VersionedTransaction,
+    pub last_valid_block_height: u64,
+}
+
+impl PreparedTransaction {
+    pub fn signature(&self) -> Signature {
+        self.transaction.signatures[0]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionOutcome {
+    Landed {
+        signature: Signature,
+        slot: u64,
+    },
+    Failed {
+        signature: Signature,
+        error: TransactionError,
+    },
+    Expired {
+        signature: Signature,
+    },
+}
+
+impl TransactionOutcome {
+    pub fn signature(&self) -> &Signature {
+        match self {
+            Self::Landed { signature, .. }
+            | Self::Failed { signature, .. }
+            | Self::Expired { signature } => signature,
+        }
+    }
+
+    // The signature of a landed transaction, anything else as an error
+    pub fn into_result(self) -> Result<Signature> {
+        match self {
+            Self::Landed { signature, .. } => Ok(signature),
+            Self::Failed { signature, error } => {
+                Err(anyhow!("transaction {} failed: {}", signature, error))
+            }
+            Self::Expired { signature } => Err(anyhow!(
+                "transaction {} expired before it landed",
+                signature
+            )),
+        }
+    }
+}
+
+struct CachedBlockhash {
+    blockhash: Hash,
+    last_valid_block_height: u64,
+    fetched_at: Instant,
+}
+
+// Shared by every DEX client: adds compute budget instructions, signs with a
+// cached blockhash, simulates, then resends until the transaction reaches the
+// configured commitment, fails or its blockhash expires. Transactions in
+// flight are tracked so shutdown can wait for them.
+pub struct TransactionSender {
+    rpc_client: Arc<RpcClient>,
+    config: TransactionConfig,
+    fees: PriorityFeeEstimator,
+    swap_programs: SwapPrograms,
+    pending: PendingTransactions,
+    blockhash: Mutex<Option<CachedBlockhash>>,
+}
+
+impl TransactionSender {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        config: &TransactionConfig,
+        swap_programs: SwapPrograms,
+        pending: PendingTransactions,
+    ) -> Self {
+        Self {
+            fees: PriorityFeeEstimator::new(rpc_client.clone(), &config.priority_fee),
+            rpc_client,
+            config: config.clone(),
+            swap_programs,
+            pending,
+            blockhash: Mutex::new(None),
+        }
+    }
+
+    pub async fn latest_blockhash(&self) -> Result<(Hash, u64)> {
+        let cached = self
+            .blockhash
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|cached| cached.fetched_at.elapsed() < self.config.blockhash_ttl())
+            .map(|cached| (cached.blockhash, cached.last_valid_block_height));
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+
+        let (blockhash, last_valid_block_height) = self
+            .rpc_client
+            .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+            .await?;
+        *self.blockhash.lock().unwrap() = Some(CachedBlockhash {
+            blockhash,
+            last_valid_block_height,
+            fetched_at: Instant::now(),
+        });
+        Ok((blockhash, last_valid_block_height))
+    }
+
+    // Prepends the configured compute unit limit and the estimated priority
+    // fee, leaving alone whichever of them the instructions already set
+    pub async fn with_compute_budget(
+        &self,
+        instructions: &[Instruction],
+    ) -> Result<Vec<Instruction>> {
+        let compute_unit_price = self.fees.estimate(&writable_accounts(instructions)).await?;
+        Ok(budgeted(
+            instructions,
+            self.config.compute_unit_limit,
+            compute_unit_price,
+        ))
+    }
+
+    // Signs a legacy transaction, or a v0 one when lookup tables are given,
+    // with the payer and any new account keypairs. With simulation on, a
+    // transaction that would fail is rejected with its `TransactionFailure`
+    // before any fee is paid, and the compute unit limit is sized from the
+    // units the simulation consumed.
+    pub async fn prepare(
+        &self,
+        payer: &Keypair,
+        instructions: &[Instruction],
+        lookup_tables: &[AddressLookupTableAccount],
+        extra_signers: &[&Keypair],
+    ) -> Result<PreparedTransaction> {
+        let (blockhash, last_valid_block_height) = self.latest_blockhash().await?;
+        let compute_unit_price = self.fees.estimate(&writable_accounts(instructions)).await?;
+        let sign = |compute_unit_limit: Option<u32>| {
+            sign_transaction(
+                payer,
+                &budgeted(instructions, compute_unit_limit, compute_unit_price),
+                lookup_tables,
+                extra_signers,
+                blockhash,
+            )
+        };
+
+        let mut compute_unit_limit = self.config.compute_unit_limit;
+        if self.config.simulate {
+            let transaction = sign(Some(MAX_COMPUTE_UNIT_LIMIT))?;
+            let simulation = self.simulate(&transaction).await?;
+            if let Some(error) = simulation.error {
+                debug!("Simulation failed with logs {:?}", simulation.logs);
+                return Err(error.into());
+            }
+            if let Some(units) = simulation.units_consumed {
+                let limit = (units as f64 * (1.0 + self.config.compute_unit_margin)).ceil();
+                compute_unit_limit = Some((limit as u32).min(MAX_COMPUTE_UNIT_LIMIT));
+            }
+        }
+
+        Ok(PreparedTransaction {
+            transaction: sign(compute_unit_limit)?,
+            last_valid_block_height,
+        })
+    }
+
+    pub async fn simulate(&self, transaction: &VersionedTransaction) -> Result<Simulation> {
+        let result = self
+            .rpc_client
+            .simulate_transaction_with_config(
+                transaction,
+                RpcSimulateTransactionConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .value;
+        Ok(Simulation::from_result(
+            result,
+            &transaction.message,
+            &self.swap_programs,
+        ))
+    }
+
+    pub async fn send(
+        &self,
+        payer: &Keypair,
+        instructions: &[Instruction],
+        extra_signers: &[&Keypair],
+        description: &str,
+    ) -> Result<TransactionOutcome> {
+        let prepared = self
+            .prepare(payer, instructions, &[], extra_signers)
+            .await?;
+        self.submit(&prepared, description).await
+    }
+
+    // Sends and resends the same signed transaction until it settles. It
+    // stays tracked as pending if confirmation tracking itself errors.
+    pub async fn submit(
+        &self,
+        prepared: &PreparedTransaction,
+        description: &str,
+    ) -> Result<TransactionOutcome> {
+        let signature = prepared.signature();
+        // Only the first send runs the node's preflight, unless the
+        // transaction was already simulated; resends go straight to the leader
+        self.rpc_client
+            .send_transaction_with_config(
+                &prepared.transaction,
+                RpcSendTransactionConfig {
+                    skip_preflight: self.config.simulate,
+                    preflight_commitment: Some(CommitmentLevel::Confirmed),
+                    max_retries: Some(0),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        self.pending
+            .track(signature, description, prepared.last_valid_block_height);
+
+        let outcome = self.confirm(prepared).await?;
+        self.pending.complete(&signature);
+        info!("Transaction {} ({}): {:?}", signature, description, outcome);
+        Ok(outcome)
+    }
+
+    async fn confirm(&self, prepared: &PreparedTransaction) -> Result<TransactionOutcome> {
+        let signature = prepared.signature();
+        let commitment = CommitmentConfig {
+            commitment: self.config.commitment,
+        };
+        let mut last_sent = Instant::now();
+
+        loop {
+            tokio::time::sleep(STATUS_POLL_INTERVAL).await;
+
+            let status = self
+                .rpc_client
+                .get_signature_statuses(&[signature])
+                .await?
+                .value
+                .pop()
+                .flatten();
+            match status {
+                Some(status) => {
+                    if let Some(error) = status.err {
+                        return Ok(TransactionOutcome::Failed { signature, error });
+                    }
+                    if status.satisfies_commitment(commitment) {
+                        return Ok(TransactionOutcome::Landed {
+                            signature,
+                            slot: status.slot,
+                        });
+                    }
+                }
+                None => {
+                    let block_height = self
+                        .rpc_client
+                        .get_block_height_with_commitment(CommitmentConfig::confirmed())
+                        .await?;
+                    if block_height > prepared.last_valid_block_height {
+                        return Ok(TransactionOutcome::Expired { signature });
+                    }
+                }
+            }
+
+            if last_sent.elapsed() >= self.config.resend_interval() {
+                debug!("Resending transaction {}", signature);
+                if let Err(e) = self
+                    .rpc_client
+                    .send_transaction_with_config(
+                        &prepared.transaction,
+                        RpcSendTransactionConfig {
+                            skip_preflight: true,
+                            max_retries: Some(0),
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                {
+                    warn!("Failed to resend transaction {}: {}", signature, e);
+                }
+                last_sent = Instant::now();
+            }
+        }
+    }
+}
+
+fn sign_transaction(
+    payer: &Keypair,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    extra_signers: &[&Keypair],
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let message = if lookup_tables.is_empty() {
+        VersionedMessage::Legacy(Message::new_with_blockhash(
+            instructions,
+            Some(&payer.pubkey()),
+            &blockhash,
+        ))
+    } else {
+        VersionedMessage::V0(v0::Message::try_compile(
+            &payer.pubkey(),
+            instructions,
+            lookup_tables,
+            blockhash,
+        )?)
+    };
+
+    let mut signers = vec![payer];
+    signers.extend_from_slice(extra_signers);
+    Ok(VersionedTransaction::try_new(message, &signers)?)
+}
+
+// Compute budget instructions go first; any the instructions already contain
+// are kept instead
+fn budgeted(
+    instructions: &[Instruction],
+    compute_unit_limit: Option<u32>,
+    compute_unit_price: u64,
+) -> Vec<Instruction> {
+    let sets = |tag: u8| {
+        instructions
+            .iter()
+            .any(|ix| ix.program_id == compute_budget::id() && ix.data.first() == Some(&tag))
+    };
+
+    let mut budget = Vec::new();
+    if let Some(limit) = compute_unit_limit {
+        if !sets(SET_COMPUTE_UNIT_LIMIT) {
+            budget.push(ComputeBudgetInstruction::set_compute_unit_limit(limit));
+        }
+    }
+    if compute_unit_price > 0 && !sets(SET_COMPUTE_UNIT_PRICE) {
+        budget.push(ComputeBudgetInstruction::set_compute_unit_price(
+            compute_unit_price,
+        ));
+    }
+    budget.extend_from_slice(instructions);
+    budget
+}
@@ -0,0 +1,349 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// Which way a conversion that cannot be exact goes. Amounts we pay or deposit
+// round down and minimums we accept round up, so a rounding error never
+// spends more than asked or accepts less than intended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+    Nearest,
+}
+
+impl Rounding {
+    // Adjusts the quotient of a division given its remainder
+    fn adjust(self, quotient: u128, remainder: u128, divisor: u128) -> Option<u128> {
+        let round_up = match self {
+            Rounding::Down => false,
+            Rounding::Up => remainder > 0,
+            Rounding::Nearest => remainder >= divisor - remainder,
+        };
+        if round_up {
+            quotient.checked_add(1)
+        } else {
+            Some(quotient)
+        }
+    }
+}
+
+// An amount of a token in its base units, together with the mint's decimals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TokenAmount {
+    pub base_units: u64,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(base_units: u64, decimals: u8) -> Self {
+        Self {
+            base_units,
+            decimals,
+        }
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Self::new(0, decimals)
+    }
+
+    // Converts through the shortest decimal representation of `amount`, so
+    // 0.1 is exactly 0.1 rather than the nearest binary fraction
+    pub fn from_ui(amount: f64, decimals: u8, rounding: Rounding) -> Result<Self> {
+        if !amount.is_finite() || amount < 0.0 {
+            return Err(anyhow!("invalid token amount {}", amount));
+        }
+        Self::parse(&amount.to_string(), decimals, rounding)
+    }
+
+    pub fn parse(amount: &str, decimals: u8, rounding: Rounding) -> Result<Self> {
+        let base_units = parse_fixed(amount, decimals as u32, rounding)?;
+        Ok(Self::new(
+            u64::try_from(base_units).map_err(|_| anyhow!("token amount {} overflows", amount))?,
+            decimals,
+        ))
+    }
+
+    // Lossy, for display and analytics only
+    pub fn to_ui(&self) -> f64 {
+        self.base_units as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.base_units == 0
+    }
+
+    // The same amount in base units of a mint with `decimals` decimals
+    pub fn base_units_at(&self, decimals: u8, rounding: Rounding) -> Result<u64> {
+        let units = if decimals >= self.decimals {
+            pow10(decimals - self.decimals)
+                .and_then(|scale| (self.base_units as u128).checked_mul(scale))
+        } else {
+            pow10(self.decimals - decimals).and_then(|scale| {
+                let units = self.base_units as u128;
+                rounding.adjust(units / scale, units % scale, scale)
+            })
+        };
+        units
+            .and_then(|units| u64::try_from(units).ok())
+            .ok_or_else(|| anyhow!("{} does not fit {} decimals", self, decimals))
+    }
+
+    pub fn with_decimals(&self, decimals: u8, rounding: Rounding) -> Result<Self> {
+        Ok(Self::new(self.base_units_at(decimals, rounding)?, decimals))
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_fixed(self.base_units as u128, self.decimals as u32))
+    }
+}
+
+// Units of the quote token paid for one whole base token, as a fixed-point
+// number with 18 decimals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price(u128);
+
+impl Price {
+    pub const DECIMALS: u32 = 18;
+    const SCALE: u128 = 1_000_000_000_000_000_000;
+
+    pub fn from_raw(raw: u128) -> Self {
+        Self(raw)
+    }
+
+    pub fn raw(&self) -> u128 {
+        self.0
+    }
+
+    pub fn from_f64(price: f64, rounding: Rounding) -> Result<Self> {
+        if !price.is_finite() || price < 0.0 {
+            return Err(anyhow!("invalid price {}", price));
+        }
+        Self::parse(&price.to_string(), rounding)
+    }
+
+    pub fn parse(price: &str, rounding: Rounding) -> Result<Self> {
+        Ok(Self(parse_fixed(price, Self::DECIMALS, rounding)?))
+    }
+
+    // Price of `base` in terms of `quote` when one is traded for the other
+    pub fn from_amounts(base: TokenAmount, quote: TokenAmount, rounding: Rounding) -> Result<Self> {
+        let numerator = scaled(quote.base_units, base.decimals)?;
+        let denominator = scaled(base.base_units, quote.decimals)?;
+        if denominator == 0 {
+            return Err(anyhow!("cannot price a zero base amount"));
+        }
+        mul_div(numerator, Self::SCALE, denominator, rounding)
+            .map(Self)
+            .ok_or_else(|| anyhow!("price of {} for {} overflows", quote, base))
+    }
+
+    // Lossy, for display and analytics only
+    pub fn to_f64(&self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    // The price multiplied by `factor`, e.g. 1.05 for five percent higher
+    pub fn scaled_by(&self, factor: f64, rounding: Rounding) -> Result<Self> {
+        let factor = Self::from_f64(factor, rounding)?;
+        mul_div(self.0, factor.0, Self::SCALE, rounding)
+            .map(Self)
+            .ok_or_else(|| anyhow!("price {} scaled by {} overflows", self, factor))
+    }
+
+    // Halfway between two prices, without overflowing
+    pub fn midpoint(&self, other: Price, rounding: Rounding) -> Self {
+        let (a, b) = (self.0, other.0);
+        let half = (a >> 1) + (b >> 1) + (a & b & 1);
+        Self(rounding.adjust(half, (a ^ b) & 1, 2).unwrap_or(half))
+    }
+
+    // Base per quote instead of quote per base
+    pub fn invert(&self, rounding: Rounding) -> Result<Self> {
+        if self.0 == 0 {
+            return Err(anyhow!("cannot invert a zero price"));
+        }
+        mul_div(Self::SCALE, Self::SCALE, self.0, rounding)
+            .map(Self)
+            .ok_or_else(|| anyhow!("inverse of price {} overflows", self))
+    }
+
+    // What `base` is worth in the quote token
+    pub fn quote_for(
+        &self,
+        base: TokenAmount,
+        quote_decimals: u8,
+        rounding: Rounding,
+    ) -> Result<TokenAmount> {
+        let units = mul_div(
+            scaled(base.base_units, quote_decimals)?,
+            self.0,
+            scaled(1, base.decimals)?
+                .checked_mul(Self::SCALE)
+                .ok_or_else(|| anyhow!("{} decimals overflow", base.decimals))?,
+            rounding,
+        );
+        to_amount(units, quote_decimals)
+    }
+
+    // How much of the base token `quote` buys
+    pub fn base_for(
+        &self,
+        quote: TokenAmount,
+        base_decimals: u8,
+        rounding: Rounding,
+    ) -> Result<TokenAmount> {
+        if self.0 == 0 {
+            return Err(anyhow!("cannot convert at a zero price"));
+        }
+        let units = mul_div(
+            scaled(quote.base_units, base_decimals)?,
+            Self::SCALE,
+            self.0
+                .checked_mul(scaled(1, quote.decimals)?)
+                .ok_or_else(|| anyhow!("price {} overflows", self))?,
+            rounding,
+        );
+        to_amount(units, base_decimals)
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_fixed(self.0, Self::DECIMALS))
+    }
+}
+
+// Prices are written as decimal strings so they survive JSON unchanged
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let price = String::deserialize(deserializer)?;
+        Price::parse(&price, Rounding::Nearest).map_err(serde::de::Error::custom)
+    }
+}
+
+fn pow10(exponent: u8) -> Option<u128> {
+    10u128.checked_pow(exponent as u32)
+}
+
+fn scaled(units: u64, decimals: u8) -> Result<u128> {
+    pow10(decimals)
+        .and_then(|scale| (units as u128).checked_mul(scale))
+        .ok_or_else(|| anyhow!("{} scaled by {} decimals overflows", units, decimals))
+}
+
+fn to_amount(units: Option<u128>, decimals: u8) -> Result<TokenAmount> {
+    units
+        .and_then(|units| u64::try_from(units).ok())
+        .map(|units| TokenAmount::new(units, decimals))
+        .ok_or_else(|| anyhow!("token amount overflows"))
+}
+
+// Parses a plain decimal string into an integer with `decimals` implied
+// decimal places, rounding any digits beyond them
+fn parse_fixed(value: &str, decimals: u32, rounding: Rounding) -> Result<u128> {
+    let invalid = || anyhow!("invalid decimal number {:?}", value);
+    let (whole, fraction) = value.trim().split_once('.').unwrap_or((value.trim(), ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let mut result: u128 = 0;
+    let digits = whole.bytes().chain(
+        fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(decimals as usize),
+    );
+    for digit in digits {
+        result = result
+            .checked_mul(10)
+            .and_then(|r| r.checked_add((digit - b'0') as u128))
+            .ok_or_else(|| anyhow!("decimal number {:?} overflows", value))?;
+    }
+
+    let dropped = fraction.get(decimals as usize..).unwrap_or("");
+    let round_up = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => dropped.bytes().any(|b| b != b'0'),
+        Rounding::Nearest => dropped.bytes().next().is_some_and(|b| b >= b'5'),
+    };
+    if round_up {
+        result = result
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("decimal number {:?} overflows", value))?;
+    }
+    Ok(result)
+}
+
+fn format_fixed(value: u128, decimals: u32) -> String {
+    let scale = 10u128.pow(decimals);
+    let (whole, fraction) = (value / scale, value % scale);
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+// a * b / c through a 256-bit intermediate; None if c is zero or the result
+// does not fit in a u128
+fn mul_div(a: u128, b: u128, c: u128, rounding: Rounding) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+    let (high, low) = mul_wide(a, b);
+    if high == 0 {
+        return rounding.adjust(low / c, low % c, c);
+    }
+    if high >= c {
+        return None;
+    }
+
+    // Long division of the 256-bit product, one bit at a time
+    let mut remainder = high;
+    let mut quotient: u128 = 0;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    rounding.adjust(quotient, remainder, c)
+}
+
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    let mask = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & mask);
+    let (b_high, b_low) = (b >> 64, b & mask);
+
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (high_low & mask) + (low_high & mask);
+    let low = (low_low & mask) | (middle << 64);
+    let high = high_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+    (high, low)
+}